@@ -1,4 +1,4 @@
-use crate::chess::square::*;
+use crate::chess::{piece::*, square::*};
 use crate::transmute_enum;
 use std::fmt;
 
@@ -14,6 +14,24 @@ pub const EMPTY_BB: BitBoard = BitBoard(0);
 pub const FULL_BB: BitBoard = BitBoard(0xFFFFFFFFFFFFFFFF);
 pub const EMPTY_BB64: BB64 = [EMPTY_BB; SQUARE_COUNT];
 
+pub const FILE_A_BB: BitBoard = BitBoard(0x0101010101010101);
+pub const FILE_H_BB: BitBoard = BitBoard(0x8080808080808080);
+pub const LIGHT_SQUARES_BB: BitBoard = BitBoard(0xAA55AA55AA55AA55);
+pub const DARK_SQUARES_BB: BitBoard = BitBoard(!LIGHT_SQUARES_BB.0);
+
+/// All squares on the given file
+pub const fn file_bb(file: File) -> BitBoard {
+    BitBoard(FILE_A_BB.0 << file as usize)
+}
+
+/// All squares on the given rank
+pub const fn rank_bb(rank: Rank) -> BitBoard {
+    BitBoard(0xFF << (8 * rank as usize))
+}
+
+/// Squares of each color complex, indexed by Square::color()
+pub const SQUARE_COLOR_BB: [BitBoard; 2] = [LIGHT_SQUARES_BB, DARK_SQUARES_BB];
+
 /// Idea for ops implementation is from https://github.com/analog-hors/tantabus
 /// Implement math standard operations
 macro_rules! impl_math_ops {
@@ -123,3 +141,127 @@ impl BitBoard {
         transmute_enum!(self.0.trailing_zeros() as u8, 63)
     }
 }
+
+/// Shifts, fills and spans (squares are indexed from A8, so north is a right shift)
+impl BitBoard {
+    /// Shift all squares one rank up, towards the eighth rank
+    pub const fn north(self) -> BitBoard {
+        BitBoard(self.0 >> 8)
+    }
+
+    /// Shift all squares one rank down, towards the first rank
+    pub const fn south(self) -> BitBoard {
+        BitBoard(self.0 << 8)
+    }
+
+    /// Shift all squares one file right, towards the H file
+    pub const fn east(self) -> BitBoard {
+        BitBoard(self.0 << 1 & !FILE_A_BB.0)
+    }
+
+    /// Shift all squares one file left, towards the A file
+    pub const fn west(self) -> BitBoard {
+        BitBoard(self.0 >> 1 & !FILE_H_BB.0)
+    }
+
+    /// Shift all squares one rank forward from the point of view of the given side
+    pub const fn forward(self, side: Color) -> BitBoard {
+        match side {
+            Color::White => self.north(),
+            Color::Black => self.south(),
+        }
+    }
+
+    /// Extend all squares up to the eighth rank
+    pub const fn north_fill(self) -> BitBoard {
+        let mut bb = self.0;
+        bb |= bb >> 8;
+        bb |= bb >> 16;
+        bb |= bb >> 32;
+
+        BitBoard(bb)
+    }
+
+    /// Extend all squares down to the first rank
+    pub const fn south_fill(self) -> BitBoard {
+        let mut bb = self.0;
+        bb |= bb << 8;
+        bb |= bb << 16;
+        bb |= bb << 32;
+
+        BitBoard(bb)
+    }
+
+    /// Extend all squares forward from the point of view of the given side
+    pub const fn fill(self, side: Color) -> BitBoard {
+        match side {
+            Color::White => self.north_fill(),
+            Color::Black => self.south_fill(),
+        }
+    }
+
+    /// All files containing at least one square
+    pub const fn file_fill(self) -> BitBoard {
+        BitBoard(self.north_fill().0 | self.south_fill().0)
+    }
+
+    /// Squares in front of the set squares (excluded) from the point of view of the given side
+    pub const fn front_span(self, side: Color) -> BitBoard {
+        self.forward(side).fill(side)
+    }
+
+    /// Squares behind the set squares (excluded) from the point of view of the given side
+    pub const fn rear_span(self, side: Color) -> BitBoard {
+        match side {
+            Color::White => self.front_span(Color::Black),
+            Color::Black => self.front_span(Color::White),
+        }
+    }
+
+    /// All files adjacent to the files containing the set squares
+    pub const fn adjacent_files(self) -> BitBoard {
+        BitBoard(self.east().0 | self.west().0).file_fill()
+    }
+
+    /// Squares attacked by pawns of the given side placed on the set squares
+    pub const fn pawn_attacks(self, side: Color) -> BitBoard {
+        let forward = self.forward(side);
+
+        BitBoard(forward.east().0 | forward.west().0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fills_and_spans() {
+        let bb = Square::E4.to_board() | Square::B7.to_board();
+
+        assert_eq!(bb.north(), Square::E5.to_board() | Square::B8.to_board());
+        assert_eq!(Square::A4.to_board().west(), EMPTY_BB);
+        assert_eq!(Square::H4.to_board().east(), EMPTY_BB);
+        assert_eq!(bb.file_fill().count_bits(), 16);
+        assert_eq!(bb.front_span(Color::White).count_bits(), 5);
+        assert_eq!(bb.rear_span(Color::White).count_bits(), 9);
+        assert_eq!(bb.adjacent_files().count_bits(), 32);
+        assert_eq!(
+            Square::A2.to_board().pawn_attacks(Color::White),
+            Square::B3.to_board()
+        );
+        assert_eq!(
+            Square::E5.to_board().pawn_attacks(Color::Black),
+            Square::D4.to_board() | Square::F4.to_board()
+        );
+
+        for square in ALL_SQUARES {
+            assert!(file_bb(square.file()).get_bit(square));
+            assert!(rank_bb(square.rank()).get_bit(square));
+            assert_eq!(file_bb(square.file()), square.to_board().file_fill());
+            assert!(SQUARE_COLOR_BB[square.color() as usize].get_bit(square));
+        }
+        assert_eq!(Square::A1.color(), Color::Black);
+        assert_eq!(Square::H1.color(), Color::White);
+    }
+}
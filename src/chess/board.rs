@@ -1,15 +1,22 @@
+use std::mem::size_of;
+use std::thread;
 use std::time::Instant;
 use std::{fmt, str::FromStr};
 
 use crate::chess::{
-    bitboard::*, castle::*, move_list::*, moves::*, piece::*, square::*, tables::*, zobrist::*,
+    bitboard::*, builder::*, castle::*, move_list::*, moves::*, piece::*, square::*, tables::*,
+    zobrist::*,
 };
 
-use crate::engine::{nnue::*, search_params::*};
+use crate::engine::{
+    hce::{psqt_from_scratch, Score, MAX_PHASE, PSQT},
+    nnue::*,
+    search_params::*,
+};
 
 /// Piece-centric board representation
-/// Any board without a king for each player (and with more than one for either) is UB!
-#[derive(Clone, Debug)]
+/// Any board failing Board::validate (e.g. without a king for each player) is UB!
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Board {
     // Main bitboards
     pub piece_bb: [BitBoard; PIECE_COUNT],
@@ -24,12 +31,32 @@ pub struct Board {
     pub castling_rights: CastlingRights,
     pub en_passant: Option<Square>,
     pub halfmoves: usize,
+    pub fullmoves: usize,
     pub hash: ZHash,
+    pub pawn_hash: ZHash,
+
+    // Chess960 castling, where castling moves are encoded (and printed) as king takes rook
+    pub chess960: bool,
+
+    // Material and piece-square score from White's point of view, kept incrementally
+    pub psqt_score: Score,
 
     // Checkers kept for in_check() within search
     pub checkers: BitBoard,
 }
 
+/// State lost when making a move in place, which cannot be recovered from the move itself
+#[derive(Clone, Copy, Debug)]
+pub struct UndoInfo {
+    captured: Option<Piece>,
+    castling_rights: CastlingRights,
+    en_passant: Option<Square>,
+    halfmoves: usize,
+    hash: ZHash,
+    pawn_hash: ZHash,
+    checkers: BitBoard,
+}
+
 /// Pretty print board state
 impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -43,8 +70,9 @@ impl fmt::Display for Board {
 
             for file in ALL_FILES {
                 let square = Square::from_coords(file, rank);
-                let piece_str =
-                    self.piece[square as usize].map_or(String::from(" "), |p| p.to_string());
+                let piece_str = self
+                    .piece_on(square)
+                    .map_or(String::from(" "), |p| p.to_string());
 
                 board_str.push_str(&piece_str);
                 board_str.push_str(" ┃ ");
@@ -68,25 +96,29 @@ impl fmt::Display for Board {
  Castling Rights   : {}
  En Passant Square : {en_passant_str}
  Halfmoves         : {}
+ Fullmoves         : {}
  ",
-            self.side, self.castling_rights, self.halfmoves,
+            self.side, self.castling_rights, self.halfmoves, self.fullmoves,
         )
     }
 }
 
-/// Init board state from FEN string with complete error handling (no legality check)
+/// Init board state from FEN string with complete error handling. The position is then checked
+/// with Board::validate, so any board parsed from a FEN is safe to search. Missing move counters
+/// default to 0 and 1, and anything after them is ignored, which also accepts EPD lines.
 impl FromStr for Board {
     type Err = &'static str;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let fen: Vec<&str> = s.split_whitespace().take(6).collect();
-        if fen.len() != 6 {
+        if fen.len() < 4 {
             return Err("Invalid fen!");
         }
 
-        let mut board = Board::new();
+        let mut builder = BoardBuilder::new();
         let board_str = fen[0];
         let mut token_count = 0; // used for checking that number of tokens is correct
+        let mut rank_count = 1;
 
         let (mut file, mut rank) = (File::A, Rank::Eight);
         for token in board_str.chars() {
@@ -98,6 +130,7 @@ impl FromStr for Board {
 
                     rank = rank.down();
                     token_count = 0;
+                    rank_count += 1;
                 }
                 '1'..='8' => {
                     for _ in '1'..=token {
@@ -106,48 +139,100 @@ impl FromStr for Board {
                     }
                 }
                 _ => {
-                    board.set_piece(Piece::try_from(token)?, Square::from_coords(file, rank));
+                    builder.piece(Piece::try_from(token)?, Square::from_coords(file, rank));
                     file = file.right();
                     token_count += 1;
                 }
             }
         }
 
-        if token_count != 8 {
+        if token_count != 8 || rank_count != 8 {
             return Err("Invalid fen!");
         }
 
         match fen[1] {
-            "w" => {
-                board.side = Color::White;
-                board.hash.toggle_side();
-            }
-            "b" => board.side = Color::Black,
+            "w" => builder.side(Color::White),
+            "b" => builder.side(Color::Black),
             _ => return Err("Invalid fen!"),
-        }
+        };
 
-        let rights: CastlingRights = fen[2].parse()?;
-        board.castling_rights = rights;
-        board.hash.toggle_castle(rights);
+        builder.castling_fen(fen[2])?;
 
         match fen[3] {
-            "-" => board.en_passant = None,
-            _ => {
-                let ep_square: Square = fen[3].parse()?;
+            "-" => builder.en_passant(None),
+            _ => builder.en_passant(Some(fen[3].parse()?)),
+        };
 
-                board.en_passant = Some(ep_square);
-                board.hash.toggle_ep(ep_square);
-            }
+        // EPD lines may have no move counters, with operations such as "bm e4;" instead
+        let has_counters = fen
+            .get(4)
+            .is_some_and(|hm| hm.starts_with(|c: char| c.is_ascii_digit()));
+        if has_counters {
+            match fen[4].parse::<usize>() {
+                Ok(hm) => builder.halfmoves(hm),
+                Err(_) => return Err("Invalid halfmove count!"),
+            };
+
+            match fen.get(5).map(|fm| fm.parse::<usize>()) {
+                Some(Ok(fm)) => builder.fullmoves(fm),
+                None => &mut builder,
+                _ => return Err("Invalid fullmove count!"),
+            };
+        }
+
+        builder.build()
+    }
+}
+
+/// Legality checks for boards coming from outside the engine
+impl Board {
+    /// Check that the position could occur in a game, as far as movegen and eval care: one king
+    /// per side, no pawns on the back ranks, at most 16 pieces per side, the side not to move not
+    /// in check, castling rights matching the king and rook squares and an en passant square
+    /// right behind a pawn which just double pushed.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.piece_bb[Piece::WK as usize].count_bits() != 1
+            || self.piece_bb[Piece::BK as usize].count_bits() != 1
+        {
+            return Err("Invalid number of kings!");
+        }
+        if self.pawns() & (rank_bb(Rank::First) | rank_bb(Rank::Eight)) != EMPTY_BB {
+            return Err("Pawns on the back ranks!");
+        }
+        if self.side_occupancy.iter().any(|bb| bb.count_bits() > 16) {
+            return Err("Too many pieces!");
         }
 
-        match fen[4].parse::<usize>() {
-            Ok(hm) => board.halfmoves = hm,
-            Err(_) => return Err("Invalid halfmove count!"),
+        if self.is_square_attacked(self.opp_king().lsb(), self.side) {
+            return Err("Side not to move is in check!");
+        }
+
+        let rights = self.castling_rights;
+        for side in [Color::White, Color::Black] {
+            for kingside in [true, false] {
+                if rights.has_right(side, kingside)
+                    && (self.piece_on(rights.king_square(side)) != Some(side.king())
+                        || self.piece_on(rights.rook_square(side, kingside)) != Some(side.rook()))
+                {
+                    return Err("Invalid Castling Rights!");
+                }
+            }
         }
 
-        board.map_checkers();
+        if let Some(ep) = self.en_passant {
+            let pushed = ep.forward(!self.side);
+            let origin = ep.forward(self.side);
+
+            if ep.relative_rank(self.side) != 5
+                || !self.opp_pawns().get_bit(pushed)
+                || self.occupancy.get_bit(ep)
+                || self.occupancy.get_bit(origin)
+            {
+                return Err("Invalid en passant square!");
+            }
+        }
 
-        Ok(board)
+        Ok(())
     }
 }
 
@@ -162,7 +247,7 @@ impl Board {
             for file in ALL_FILES {
                 let square = Square::from_coords(file, rank);
 
-                if let Some(p) = self.piece[square as usize] {
+                if let Some(p) = self.piece_on(square) {
                     if empty > 0 {
                         fen.push_str(&empty.to_string());
                         empty = 0;
@@ -194,7 +279,7 @@ impl Board {
             fen.push_str(" -");
         }
 
-        fen.push_str(&format!(" {} 1", self.halfmoves));
+        fen.push_str(&format!(" {} {}", self.halfmoves, self.fullmoves));
 
         fen
     }
@@ -241,6 +326,37 @@ impl_piece_lookups! {
 
 /// Implement side occupancy and diagonal/hv slider lookups
 impl Board {
+    /// Game phase from the non-pawn material left on the board, capped to MAX_PHASE
+    pub fn phase(&self) -> i32 {
+        let phase = self.knights().count_bits()
+            + self.bishops().count_bits()
+            + 2 * self.rooks().count_bits()
+            + 4 * self.queens().count_bits();
+
+        (phase as i32).min(MAX_PHASE)
+    }
+
+    /// Whether the given side has any knight, bishop, rook or queen left
+    pub fn has_non_pawn_material(&self, side: Color) -> bool {
+        let pieces = self.piece_bb[side.knight() as usize]
+            | self.piece_bb[side.bishop() as usize]
+            | self.piece_bb[side.rook() as usize]
+            | self.piece_bb[side.queen() as usize];
+
+        pieces != EMPTY_BB
+    }
+
+    /// Hash of the board built from scratch out of the pieces, side to move, castling rights and
+    /// en passant square. Debug builds check the incremental hash against it after every move.
+    pub fn recompute_hash(&self) -> ZHash {
+        ZHash::new(self)
+    }
+
+    /// Whether a pawn of the given side attacks the en passant square
+    pub(super) fn can_capture_ep(&self, ep: Square, side: Color) -> bool {
+        pawn_attacks(ep, !side) & self.piece_bb[side.pawn() as usize] != EMPTY_BB
+    }
+
     pub const fn own_occupancy(&self) -> BitBoard {
         self.side_occupancy[self.side as usize]
     }
@@ -268,15 +384,24 @@ impl Board {
             castling_rights: NO_RIGHTS,
             en_passant: None,
             halfmoves: 0,
+            fullmoves: 1,
             hash: NULL_HASH,
+            pawn_hash: NULL_HASH,
+            psqt_score: Score::ZERO,
             checkers: EMPTY_BB,
+            chess960: false,
         }
     }
 
+    /// Looks for which piece is on the given Square, if any
+    pub fn piece_on(&self, square: Square) -> Option<Piece> {
+        self.piece[square as usize]
+    }
+
     /// Looks for which piece is on the given Square
     /// Panics if no piece is on that square
     pub fn piece_at(&self, square: Square) -> Piece {
-        self.piece[square as usize].unwrap()
+        self.piece_on(square).unwrap()
     }
 
     /// Return piece captured by a capture Move
@@ -289,8 +414,22 @@ impl Board {
         }
     }
 
+    /// Return the king target, rook source and rook target squares of a castling Move.
+    /// Chess960 castling moves target the rook, standard ones target the king destination.
+    pub fn castling_squares(&self, m: Move) -> (Square, Square, Square) {
+        let (src, tgt) = (m.get_src(), m.get_tgt());
+
+        if self.chess960 {
+            let (king_tgt, rook_tgt) = castling_targets(src, tgt);
+            (king_tgt, tgt, rook_tgt)
+        } else {
+            let (rook_src, rook_tgt) = rook_castling_move(tgt);
+            (tgt, rook_src, rook_tgt)
+        }
+    }
+
     /// Set the piece on the board at the given square (remove first, set later)
-    fn set_piece(&mut self, piece: Piece, square: Square) {
+    pub(super) fn set_piece(&mut self, piece: Piece, square: Square) {
         let p = piece as usize;
         let c = piece.color() as usize;
 
@@ -299,6 +438,10 @@ impl Board {
         self.side_occupancy[c] = self.side_occupancy[c].set_bit(square);
         self.piece[square as usize] = Some(piece);
         self.hash.toggle_piece(piece, square);
+        self.psqt_score += PSQT[p][square as usize];
+        if piece.is_pawn() {
+            self.pawn_hash.toggle_piece(piece, square);
+        }
     }
 
     /// Remove the piece at the given square on the board (set first, remove later)
@@ -313,75 +456,157 @@ impl Board {
         self.side_occupancy[c] = self.side_occupancy[c].pop_bit(square);
         self.piece[square as usize] = None;
         self.hash.toggle_piece(piece, square);
+        self.psqt_score -= PSQT[p][square as usize];
+        if piece.is_pawn() {
+            self.pawn_hash.toggle_piece(piece, square);
+        }
     }
 
     /// Makes (legal) move on the board
     /// Supplying illegal moves will lead to illegal board states.
     pub fn make_move(&self, m: Move) -> Board {
         let mut new = self.clone();
-        let (src, tgt) = (m.get_src(), m.get_tgt());
+        new.make_move_in_place(m);
+
+        new
+    }
+
+    /// Makes (legal) move on the board without copying it, returning what is needed to undo it
+    /// Supplying illegal moves will lead to illegal board states.
+    pub fn make_move_in_place(&mut self, m: Move) -> UndoInfo {
+        let (src, mut tgt) = (m.get_src(), m.get_tgt());
         let piece = self.piece_at(src); // must exist
         let move_type = m.get_type();
         let capture = move_type.is_capture();
+        let side = self.side;
+
+        let undo = UndoInfo {
+            captured: capture.then(|| self.get_capture(m)),
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            halfmoves: self.halfmoves,
+            hash: self.hash,
+            pawn_hash: self.pawn_hash,
+            checkers: self.checkers,
+        };
 
         // Remove moving piece and reset halfmoves
-        new.remove_piece(src);
+        self.remove_piece(src);
         if capture || piece.is_pawn() {
-            new.halfmoves = 0
+            self.halfmoves = 0
         } else {
-            new.halfmoves += 1;
+            self.halfmoves += 1;
+        }
+        if side == Color::Black {
+            self.fullmoves += 1;
         }
 
         // Handle pieces affected by the move (captures/castles..)
         if move_type == MoveType::EnPassant {
-            new.remove_piece(tgt.forward(!self.side));
+            self.remove_piece(tgt.forward(!side));
         } else if capture {
-            new.remove_piece(tgt);
+            self.remove_piece(tgt);
         } else if move_type == MoveType::Castle {
-            let rook = self.side.rook();
-            let (rook_src, rook_tgt) = rook_castling_move(tgt);
+            let (king_tgt, rook_src, rook_tgt) = self.castling_squares(m);
 
-            new.remove_piece(rook_src);
-            new.set_piece(rook, rook_tgt);
+            self.remove_piece(rook_src);
+            self.set_piece(side.rook(), rook_tgt);
+            tgt = king_tgt;
         }
 
         // Move the piece to the new square
         if move_type.is_promotion() {
-            new.set_piece(move_type.get_promotion(self.side), tgt);
+            self.set_piece(move_type.get_promotion(side), tgt);
         } else {
-            new.set_piece(piece, tgt);
+            self.set_piece(piece, tgt);
         }
 
         // Handle enpassant
         if let Some(square) = self.en_passant {
-            new.en_passant = None;
-            new.hash.toggle_ep(square);
+            self.en_passant = None;
+            self.hash.toggle_ep(square);
         }
 
-        // Handle double push
+        // Handle double push, only setting en passant if an enemy pawn can capture
         if move_type == MoveType::DoublePush {
-            let ep_tgt = src.forward(self.side);
+            let ep_tgt = src.forward(side);
 
-            new.en_passant = Some(ep_tgt);
-            new.hash.toggle_ep(ep_tgt);
+            if self.can_capture_ep(ep_tgt, !side) {
+                self.en_passant = Some(ep_tgt);
+                self.hash.toggle_ep(ep_tgt);
+            }
         }
 
         // Handle castling rights
-        let new_rights = self.castling_rights.update(src, tgt);
-        new.castling_rights = new_rights;
-        new.hash.swap_castle(self.castling_rights, new_rights);
+        let new_rights = undo.castling_rights.update(src, tgt);
+        self.castling_rights = new_rights;
+        self.hash.swap_castle(undo.castling_rights, new_rights);
+
+        self.side = !side;
+        self.hash.toggle_side();
+        self.map_checkers();
+        debug_assert_eq!(self.hash, self.recompute_hash());
+        debug_assert_eq!(self.psqt_score, psqt_from_scratch(self));
+        debug_assert_eq!(self.pawn_hash, ZHash::new_pawn(self));
+
+        undo
+    }
 
-        new.side = !self.side;
-        new.hash.toggle_side();
-        new.map_checkers();
+    /// Takes back the last move made in place, restoring the board exactly as it was
+    pub fn undo_move(&mut self, m: Move, undo: UndoInfo) {
+        let (src, tgt) = (m.get_src(), m.get_tgt());
+        let move_type = m.get_type();
+        let side = !self.side;
 
-        new
+        // Put the moving pieces back, along with the captured one
+        if move_type == MoveType::Castle {
+            let (king_tgt, rook_src, rook_tgt) = self.castling_squares(m);
+
+            self.remove_piece(king_tgt);
+            self.remove_piece(rook_tgt);
+            self.set_piece(side.king(), src);
+            self.set_piece(side.rook(), rook_src);
+        } else {
+            let piece = match move_type.is_promotion() {
+                true => side.pawn(),
+                false => self.piece_at(tgt),
+            };
+
+            self.remove_piece(tgt);
+            self.set_piece(piece, src);
+        }
+
+        if let Some(captured) = undo.captured {
+            let square = match move_type {
+                MoveType::EnPassant => tgt.forward(!side),
+                _ => tgt,
+            };
+            self.set_piece(captured, square);
+        }
+
+        // Hashes are restored whole rather than toggled back
+        if side == Color::Black {
+            self.fullmoves -= 1;
+        }
+        self.side = side;
+        self.castling_rights = undo.castling_rights;
+        self.en_passant = undo.en_passant;
+        self.halfmoves = undo.halfmoves;
+        self.hash = undo.hash;
+        self.pawn_hash = undo.pawn_hash;
+        self.checkers = undo.checkers;
+    }
+
+    /// Makes the move only if it is legal on the board.
+    /// Any move is accepted, since it is checked with is_legal first.
+    pub fn make_move_checked(&self, m: Move) -> Option<Board> {
+        self.is_legal(m).then(|| self.make_move(m))
     }
 
     /// Make move with NNUE accumulator increments
     pub fn make_move_nnue(&self, m: Move, nnue_state: &mut Box<NNUEState>) -> Board {
         let mut new = self.clone();
-        let (src, tgt) = (m.get_src(), m.get_tgt());
+        let (src, mut tgt) = (m.get_src(), m.get_tgt());
         let piece = self.piece_at(src);
         let move_type = m.get_type();
         let capture = move_type.is_capture();
@@ -395,6 +620,9 @@ impl Board {
         } else {
             new.halfmoves += 1;
         }
+        if self.side == Color::Black {
+            new.fullmoves += 1;
+        }
 
         if move_type == MoveType::EnPassant {
             let ep_target = tgt.forward(!self.side);
@@ -406,11 +634,12 @@ impl Board {
             nnue_state.manual_update::<OFF>(self.piece_at(tgt), tgt);
         } else if move_type == MoveType::Castle {
             let rook = self.side.rook();
-            let (rook_src, rook_tgt) = rook_castling_move(tgt);
+            let (king_tgt, rook_src, rook_tgt) = self.castling_squares(m);
 
             new.remove_piece(rook_src);
             new.set_piece(rook, rook_tgt);
             nnue_state.move_update(rook, rook_src, rook_tgt);
+            tgt = king_tgt;
         }
 
         if move_type.is_promotion() {
@@ -432,8 +661,10 @@ impl Board {
         if move_type == MoveType::DoublePush {
             let ep_tgt = src.forward(self.side);
 
-            new.en_passant = Some(ep_tgt);
-            new.hash.toggle_ep(ep_tgt);
+            if new.can_capture_ep(ep_tgt, !self.side) {
+                new.en_passant = Some(ep_tgt);
+                new.hash.toggle_ep(ep_tgt);
+            }
         }
 
         let new_rights = self.castling_rights.update(src, tgt);
@@ -443,6 +674,9 @@ impl Board {
         new.side = !self.side;
         new.hash.toggle_side();
         new.map_checkers();
+        debug_assert_eq!(new.hash, new.recompute_hash());
+        debug_assert_eq!(new.psqt_score, psqt_from_scratch(&new));
+        debug_assert_eq!(new.pawn_hash, ZHash::new_pawn(&new));
 
         new
     }
@@ -458,50 +692,79 @@ impl Board {
             new.hash.toggle_ep(square);
         }
         new.map_checkers();
+        debug_assert_eq!(new.hash, new.recompute_hash());
+
+        new
+    }
+
+    /// Mirror the board vertically, swapping the colors of all pieces and the side to move.
+    /// The result is the same position seen from the other side of the board.
+    pub fn mirror(&self) -> Board {
+        let mut new = Board::new();
+
+        for square in ALL_SQUARES {
+            if let Some(piece) = self.piece_on(square) {
+                new.set_piece(piece.opposite_color(), square.flipv());
+            }
+        }
+
+        new.side = !self.side;
+        new.castling_rights = self.castling_rights.mirror();
+        new.chess960 = self.chess960;
+        new.en_passant = self.en_passant.map(|square| square.flipv());
+        new.halfmoves = self.halfmoves;
+        new.fullmoves = self.fullmoves;
+        new.hash = new.recompute_hash();
+        new.map_checkers();
+
+        new
+    }
+
+    /// Mirror the board horizontally, swapping the a and h files.
+    /// Castling rights are tied to the king and rook files, so they are dropped.
+    pub fn flip_files(&self) -> Board {
+        let mut new = Board::new();
+
+        for square in ALL_SQUARES {
+            if let Some(piece) = self.piece_on(square) {
+                new.set_piece(piece, square.fliph());
+            }
+        }
+
+        new.side = self.side;
+        new.chess960 = self.chess960;
+        new.en_passant = self.en_passant.map(|square| square.fliph());
+        new.halfmoves = self.halfmoves;
+        new.fullmoves = self.fullmoves;
+        new.hash = new.recompute_hash();
+        new.map_checkers();
 
         new
     }
 
     /// Set attackers to all enemy pieces directly attacking the king.
     /// If there is at least one attacker, initialize the bitboards for blocking/capturing the check
-    fn map_checkers(&mut self) {
+    pub(super) fn map_checkers(&mut self) {
         let square = self.own_king().lsb();
 
-        self.checkers = self.opp_pawns() & pawn_attacks(square, self.side)   | // pawns
-            self.opp_knights() & knight_attacks(square)                      | // knights
-            self.opp_queen_bishop() & bishop_attacks(square, self.occupancy) | // bishops + queens
-            self.opp_queen_rook()   & rook_attacks(square, self.occupancy)   | // rooks + queens
-            self.opp_king() & king_attacks(square); // kings
+        self.checkers = self.attackers_to(square, self.occupancy) & self.opp_occupancy();
     }
 
-    /// Returns a BitBoard of all squares attacked by pieces who can attack any of the squares around
-    /// the king. The only relevant information is that in the squares around him, the rest is not
-    /// used but is kept because it does not cause problems.
-    ///
-    /// We only look at the squares from which a piece could threaten squares adjacent to the king.
+    /// Returns a BitBoard of the squares around the king which are attacked by the enemy, which
+    /// are the only ones used by king moves and castling. Squares holding own pieces are skipped,
+    /// since the king can't move there anyway.
     ///
     /// We pretend the king is not on the board so that sliders also attack behind the king, since
     /// otherwise that square would be considered not attacked
     fn map_king_threats(&self) -> BitBoard {
         let king_square = self.own_king().lsb();
-        let ksq = king_square as usize;
         let occupancies = self.occupancy.pop_bit(king_square);
         let mut threats = EMPTY_BB;
 
-        for sq in self.opp_pawns() & PAWN_THREATS[self.side as usize][ksq] {
-            threats |= pawn_attacks(sq, !self.side);
-        }
-        for sq in self.opp_knights() & KNIGHT_THREATS[ksq] {
-            threats |= knight_attacks(sq);
-        }
-        for sq in self.opp_queen_bishop() & DIAG_THREATS[ksq] {
-            threats |= bishop_attacks(sq, occupancies);
-        }
-        for sq in self.opp_queen_rook() & HV_THREATS[ksq] {
-            threats |= rook_attacks(sq, occupancies);
-        }
-        for sq in self.opp_king() & KING_THREATS[ksq] {
-            threats |= king_attacks(sq);
+        for sq in king_attacks(king_square) & !self.own_occupancy() {
+            if self.attackers_to(sq, occupancies) & self.opp_occupancy() != EMPTY_BB {
+                threats = threats.set_bit(sq);
+            }
         }
 
         threats
@@ -590,13 +853,13 @@ impl Board {
         block_check: BitBoard,
         move_list: &mut MoveList,
     ) {
-        const START_RANKS: [Rank; 2] = [Rank::Second, Rank::Seventh];
-
-        let side = self.side as usize;
         let pawn_bb = self.own_pawns() & !diag_pins; // diag pinned pawns cannot move
 
         for src in pawn_bb {
-            let target: Square = src.forward(self.side);
+            // pawns never stand on their last rank
+            let Some(target) = pawn_push(src, self.side) else {
+                continue;
+            };
 
             // pawns pinned along a rank cannot be pushed
             if hv_pins.get_bit(src) && !hv_pins.get_bit(target) {
@@ -610,9 +873,7 @@ impl Board {
                 }
 
                 // double pawn push
-                if src.rank() == START_RANKS[side] {
-                    let target = src.forward(self.side).forward(self.side);
-
+                if let Some(target) = pawn_double_push(src, self.side) {
                     if !(self.occupancy.get_bit(target)) && block_check.get_bit(target) {
                         move_list.push(Move::new(src, target, MoveType::DoublePush));
                     }
@@ -754,60 +1015,126 @@ impl Board {
         }
     }
 
-    /// Generate all legal moves for any standard piece.
+    /// Generate all legal Chess960 castling moves, for any king and rook start squares.
+    /// The squares between the king (rook) and its target must be empty, apart from the king and
+    /// the castling rook themselves. The squares the king walks through cannot be attacked, which
+    /// is checked without the castling rook, as it could be shielding the king target.
+    fn gen_chess960_castles(&self, move_list: &mut MoveList) {
+        let king = self.own_king().lsb();
+
+        for kingside in [true, false] {
+            if !self.castling_rights.has_right(self.side, kingside) {
+                continue;
+            }
+
+            let rook = self.castling_rights.rook_square(self.side, kingside);
+            let (king_tgt, rook_tgt) = castling_targets(king, rook);
+            let occupancy = self.occupancy.pop_bit(king).pop_bit(rook);
+
+            let king_path = BETWEEN[king as usize][king_tgt as usize] | king_tgt.to_board();
+            let rook_path = BETWEEN[rook as usize][rook_tgt as usize] | rook_tgt.to_board();
+            if (king_path | rook_path) & occupancy != EMPTY_BB {
+                continue;
+            }
+
+            let attacked = king_path
+                .into_iter()
+                .any(|sq| self.attackers_to(sq, occupancy) & self.opp_occupancy() != EMPTY_BB);
+            if !attacked {
+                move_list.push(Move::new(king, rook, MoveType::Castle));
+            }
+        }
+    }
+
+    /// Pieces of the given kind which are free to move, given the pin masks.
     /// PIECE is chess::Piece as usize
-    /// if QUIET==false, only generate captures
-    fn gen_piece_moves<const PIECE: usize, const QUIET: bool>(
+    fn movable_pieces<const PIECE: usize>(
         &self,
-        check_mask: BitBoard,
         diag_pins: BitBoard,
         hv_pins: BitBoard,
-        move_list: &mut MoveList,
-    ) {
-        let side = self.side as usize;
-        let mut piece_bb = self.piece_bb[PIECE + side];
+    ) -> BitBoard {
+        let piece_bb = self.piece_bb[PIECE + self.side as usize];
 
         if PIECE == N {
-            piece_bb &= !(diag_pins | hv_pins); // pinned knights cannot move
+            piece_bb & !(diag_pins | hv_pins) // pinned knights cannot move
         } else if PIECE == B {
-            piece_bb &= !hv_pins; // hv-pinned bishops cannot move
+            piece_bb & !hv_pins // hv-pinned bishops cannot move
         } else if PIECE == R {
-            piece_bb &= !diag_pins // diag-pinned rooks cannot move
+            piece_bb & !diag_pins // diag-pinned rooks cannot move
+        } else {
+            piece_bb
         }
+    }
 
-        for source in piece_bb {
-            let mut targets = EMPTY_BB;
+    /// Squares a movable piece attacks from the source square, restricted to its pin ray
+    fn piece_targets<const PIECE: usize>(
+        &self,
+        source: Square,
+        diag_pins: BitBoard,
+        hv_pins: BitBoard,
+    ) -> BitBoard {
+        let mut targets = EMPTY_BB;
 
-            if PIECE == N {
-                targets = knight_attacks(source);
-            } else if PIECE == B {
-                targets = bishop_attacks(source, self.occupancy);
+        if PIECE == N {
+            targets = knight_attacks(source);
+        } else if PIECE == B {
+            targets = bishop_attacks(source, self.occupancy);
 
-                if diag_pins.get_bit(source) {
-                    targets &= diag_pins // move along diagonal pin ray
-                }
-            } else if PIECE == R {
-                targets = rook_attacks(source, self.occupancy);
+            if diag_pins.get_bit(source) {
+                targets &= diag_pins // move along diagonal pin ray
+            }
+        } else if PIECE == R {
+            targets = rook_attacks(source, self.occupancy);
 
-                if hv_pins.get_bit(source) {
-                    targets &= hv_pins // move along orthogonal pin ray
-                }
-            } else if PIECE == Q {
-                // queen, when pinned, behaves like a rook or a bishop
-                if diag_pins.get_bit(source) {
-                    targets = bishop_attacks(source, self.occupancy) & diag_pins;
-                } else if hv_pins.get_bit(source) {
-                    targets = rook_attacks(source, self.occupancy) & hv_pins;
-                } else {
-                    targets = queen_attacks(source, self.occupancy);
-                }
+            if hv_pins.get_bit(source) {
+                targets &= hv_pins // move along orthogonal pin ray
             }
-            targets &= check_mask;
+        } else if PIECE == Q {
+            // queen, when pinned, behaves like a rook or a bishop
+            if diag_pins.get_bit(source) {
+                targets = bishop_attacks(source, self.occupancy) & diag_pins;
+            } else if hv_pins.get_bit(source) {
+                targets = rook_attacks(source, self.occupancy) & hv_pins;
+            } else {
+                targets = queen_attacks(source, self.occupancy);
+            }
+        }
 
+        targets
+    }
+
+    /// Generate all legal moves for any standard piece.
+    /// PIECE is chess::Piece as usize
+    /// if QUIET==false, only generate captures
+    fn gen_piece_moves<const PIECE: usize, const QUIET: bool>(
+        &self,
+        check_mask: BitBoard,
+        diag_pins: BitBoard,
+        hv_pins: BitBoard,
+        move_list: &mut MoveList,
+    ) {
+        for source in self.movable_pieces::<PIECE>(diag_pins, hv_pins) {
+            let targets = self.piece_targets::<PIECE>(source, diag_pins, hv_pins) & check_mask;
             self.insert_moves::<QUIET>(source, targets, move_list);
         }
     }
 
+    /// Count all legal moves for any standard piece, straight from the target bitboards
+    fn count_piece_moves<const PIECE: usize>(
+        &self,
+        check_mask: BitBoard,
+        diag_pins: BitBoard,
+        hv_pins: BitBoard,
+    ) -> usize {
+        self.movable_pieces::<PIECE>(diag_pins, hv_pins)
+            .into_iter()
+            .map(|source| {
+                let targets = self.piece_targets::<PIECE>(source, diag_pins, hv_pins);
+                (targets & check_mask & !self.own_occupancy()).count_bits() as usize
+            })
+            .sum()
+    }
+
     /// Generate all legal moves, or only captures if QUIET==false
     pub fn gen_moves<const QUIET: bool>(&self) -> MoveList {
         let mut move_list: MoveList = MoveList::default();
@@ -834,8 +1161,12 @@ impl Board {
         let (diag_pins, hv_pins) = self.map_pins();
 
         if QUIET && attacker_count == 0 {
-            self.gen_kingside_castle(threats, &mut move_list);
-            self.gen_queenside_castle(threats, &mut move_list);
+            if self.chess960 {
+                self.gen_chess960_castles(&mut move_list);
+            } else {
+                self.gen_kingside_castle(threats, &mut move_list);
+                self.gen_queenside_castle(threats, &mut move_list);
+            }
         }
 
         self.gen_pawn_captures(
@@ -857,64 +1188,294 @@ impl Board {
         move_list
     }
 
-    /// Finds legal move in board from the uci-formatted move string
-    pub fn find_move(&self, move_str: &str) -> Option<Move> {
-        self.gen_moves::<QUIETS>()
-            .moves
-            .into_iter()
-            .find(|m| m.to_string() == move_str)
-    }
-}
+    /// Number of legal moves, as gen_moves::<QUIETS>().len(). King and piece moves are counted
+    /// from their target bitboards without building moves, while pawn moves and castling, with
+    /// their promotions and special cases, still go through the generators.
+    pub fn count_moves(&self) -> usize {
+        let attacker_count = self.checkers.count_bits();
+        let threats = self.map_king_threats();
 
-/// SEE
-impl Board {
-    /// Returns bitboard with all pieces attacking a square
-    fn map_all_attackers(&self, square: Square, blockers: BitBoard) -> BitBoard {
-        self.piece_bb[0] & pawn_attacks(square, Color::Black)
-            | self.piece_bb[1] & pawn_attacks(square, Color::White)
-            | self.knights() & knight_attacks(square)
-            | (self.bishops() | self.queens()) & bishop_attacks(square, blockers)
-            | (self.rooks() | self.queens()) & rook_attacks(square, blockers)
-            | self.kings() & king_attacks(square)
-    }
+        let king_targets = king_attacks(self.own_king().lsb()) & !threats & !self.own_occupancy();
+        let mut count = king_targets.count_bits() as usize;
 
-    /// Returns the least valuable of the attackers within the attacker map
-    fn get_lva(&self, attackers: BitBoard, side: Color) -> Option<(Square, Piece)> {
-        for piece in PIECES[side as usize] {
-            let squares = attackers & self.piece_bb[piece as usize];
+        // with double checks, only king moves are legal, so we stop here
+        if attacker_count > 1 {
+            return count;
+        }
 
-            if squares != EMPTY_BB {
-                return Some((squares.lsb(), piece));
+        let (block_check, capture_check) = if attacker_count == 1 {
+            (
+                BETWEEN[self.own_king().lsb() as usize][self.checkers.lsb() as usize],
+                self.checkers,
+            )
+        } else {
+            (FULL_BB, FULL_BB)
+        };
+        let check_mask = capture_check | block_check;
+        let (diag_pins, hv_pins) = self.map_pins();
+
+        let mut move_list = MoveList::default();
+        if attacker_count == 0 {
+            if self.chess960 {
+                self.gen_chess960_castles(&mut move_list);
+            } else {
+                self.gen_kingside_castle(threats, &mut move_list);
+                self.gen_queenside_castle(threats, &mut move_list);
             }
         }
+        self.gen_pawn_captures(
+            diag_pins,
+            hv_pins,
+            block_check,
+            capture_check,
+            &mut move_list,
+        );
+        self.gen_pawn_quiets(diag_pins, hv_pins, block_check, &mut move_list);
+        count += move_list.len();
 
-        None
+        count += self.count_piece_moves::<N>(check_mask, diag_pins, hv_pins);
+        count += self.count_piece_moves::<B>(check_mask, diag_pins, hv_pins);
+        count += self.count_piece_moves::<R>(check_mask, diag_pins, hv_pins);
+        count += self.count_piece_moves::<Q>(check_mask, diag_pins, hv_pins);
+
+        count
     }
 
-    /// Checks if the static exchange after a move is enough to beat the given threshold
-    /// This can be used for both captures and quiet moves.
-    /// This implementation is basically that seen in Viri, which in turn is that of Ethereal
-    pub fn see(&self, m: Move, threshold: Eval) -> bool {
-        let src = m.get_src();
-        let tgt = m.get_tgt();
-        let mt = m.get_type();
+    /// Finds legal move in board from the uci-formatted move string, see parse_move
+    pub fn find_move(&self, move_str: &str) -> Option<Move> {
+        self.parse_move(move_str)
+    }
 
-        // Castling cannot have bad SEE, since all squares the king passes through are not attacked
-        if mt == MoveType::Castle {
-            return true;
+    /// Decodes a uci-formatted move string and finds the matching legal move, returning None for
+    /// malformed strings and illegal moves.
+    /// Castling is also accepted as king takes rook, as sent by GUIs in Chess960 mode.
+    pub fn parse_move(&self, move_str: &str) -> Option<Move> {
+        let src: Square = move_str.get(0..2)?.parse().ok()?;
+        let tgt: Square = move_str.get(2..4)?.parse().ok()?;
+        let promotion = match move_str.get(4..)? {
+            "" => None,
+            piece => Some(Piece::try_from(piece.parse::<char>().ok()?).ok()? as u8 >> 1),
+        };
+
+        let move_list = self.gen_moves::<QUIETS>();
+        move_list.moves[..move_list.len()]
+            .iter()
+            .copied()
+            .find(|&m| {
+                let move_type = m.get_type();
+                let move_promotion = move_type
+                    .is_promotion()
+                    .then(|| move_type.get_promotion(self.side) as u8 >> 1);
+
+                m.get_src() == src
+                    && move_promotion == promotion
+                    && (m.get_tgt() == tgt
+                        || move_type == MoveType::Castle && self.castling_squares(m).1 == tgt)
+            })
+    }
+}
+
+/// Move validation
+impl Board {
+    /// Checks whether a move from an outside source (tt, user input) is legal, without
+    /// generating every legal move. The move type must agree with the pieces on the source and
+    /// target squares, and the king may not be left in check.
+    pub fn is_legal(&self, m: Move) -> bool {
+        if m == NULL_MOVE {
+            return false;
         }
 
-        // Piece being swapped off is the promoted piece
-        let victim = if mt.is_promotion() {
-            mt.get_promotion(self.side)
-        } else {
-            self.piece_at(src)
+        let (src, tgt, move_type) = (m.get_src(), m.get_tgt(), m.get_type());
+        let Some(piece) = self.piece_on(src).filter(|p| p.color() == self.side) else {
+            return false;
         };
 
-        // Get the static move value (also works for quiets)
-        let mut move_value = if mt.is_capture() {
-            if mt == MoveType::EnPassant {
-                PIECE_VALUES[Piece::WP as usize]
+        if move_type == MoveType::Castle {
+            return piece.is_king() && self.checkers == EMPTY_BB && self.castle_is_legal(m);
+        }
+
+        // Captures must land on an enemy piece, and every other move on an empty square
+        let captured = self.piece_on(tgt);
+        let capture_ok = match move_type {
+            MoveType::EnPassant => captured.is_none(),
+            _ if move_type.is_capture() => {
+                captured.is_some_and(|p| p.color() != self.side && !p.is_king())
+            }
+            _ => captured.is_none(),
+        };
+        if !capture_ok {
+            return false;
+        }
+
+        let pseudo_legal = if piece.is_pawn() {
+            let last_rank = tgt.relative_rank(self.side) == 7;
+
+            last_rank == move_type.is_promotion()
+                && match move_type {
+                    MoveType::DoublePush => {
+                        pawn_double_push(src, self.side) == Some(tgt)
+                            && !self.occupancy.get_bit(src.forward(self.side))
+                    }
+                    MoveType::EnPassant => {
+                        self.en_passant == Some(tgt) && pawn_attacks(src, self.side).get_bit(tgt)
+                    }
+                    _ if move_type.is_capture() => pawn_attacks(src, self.side).get_bit(tgt),
+                    _ => pawn_push(src, self.side) == Some(tgt),
+                }
+        } else {
+            let attacks = match piece as usize >> 1 {
+                1 => knight_attacks(src),
+                2 => bishop_attacks(src, self.occupancy),
+                3 => rook_attacks(src, self.occupancy),
+                4 => queen_attacks(src, self.occupancy),
+                _ => king_attacks(src),
+            };
+
+            matches!(move_type, MoveType::Quiet | MoveType::Capture) && attacks.get_bit(tgt)
+        };
+        if !pseudo_legal {
+            return false;
+        }
+
+        // Look for enemy pieces attacking the king after the move, apart from the captured one
+        let removed = match move_type {
+            MoveType::EnPassant => tgt.forward(!self.side),
+            _ => tgt,
+        };
+        let occupancy = self.occupancy.pop_bit(src).pop_bit(removed).set_bit(tgt);
+        let king = if piece.is_king() {
+            tgt
+        } else {
+            self.own_king().lsb()
+        };
+        let enemies = self.opp_occupancy().pop_bit(removed);
+
+        self.attackers_to(king, occupancy) & enemies == EMPTY_BB
+    }
+
+    /// Checks a castling move against the castling moves generated for the board
+    fn castle_is_legal(&self, m: Move) -> bool {
+        let mut move_list = MoveList::default();
+        if self.chess960 {
+            self.gen_chess960_castles(&mut move_list);
+        } else {
+            let threats = self.map_king_threats();
+            self.gen_kingside_castle(threats, &mut move_list);
+            self.gen_queenside_castle(threats, &mut move_list);
+        }
+
+        move_list.moves[..move_list.len()].contains(&m)
+    }
+}
+
+/// Draw detection
+impl Board {
+    /// Draw by insufficient material, as a search heuristic: also covers some positions where mate
+    /// is possible but can't be forced, such as knvkn away from the edge. For the rules of the
+    /// game, use is_dead_position.
+    /// Some of the logic is taken from Tantabus
+    pub fn insufficient_material(&self) -> bool {
+        const WHITE_SQUARES: BitBoard = BitBoard(12273903644374837845);
+        const CORNERS: BitBoard = BitBoard(9295429630892703873);
+        const EDGES: BitBoard = BitBoard(18411139144890810879);
+
+        let kings = self.kings();
+        let knights = self.knights();
+        let bishops = self.bishops();
+
+        match self.occupancy.count_bits() {
+            2 => true,
+            3 => knights | bishops != EMPTY_BB, // 1 knight or 1 bishop
+            4 => {
+                let one_each = self.side_occupancy[0].count_bits() == 2;
+                let knight_count = knights.count_bits();
+                let bishop_count = bishops.count_bits();
+                let king_in_corner = kings & CORNERS != EMPTY_BB;
+                let king_on_edge = kings & EDGES != EMPTY_BB;
+
+                (knight_count == 2 && !king_on_edge) || // knvkn, king not on edge
+                (bishop_count == 2 && (
+                    (bishops & WHITE_SQUARES).count_bits() != 1 || // same color bishops
+                    (one_each && !king_in_corner))) ||  // one bishop each, king not in corner
+                (knight_count == 1 && bishop_count == 1 && one_each && !king_in_corner)
+                // knvkb, king not in corner
+            }
+            _ => false,
+        }
+    }
+
+    /// Dead position, where no sequence of legal moves can lead to a mate: bare kings, a single
+    /// minor piece, or only bishops which all stand on squares of the same color
+    pub fn is_dead_position(&self) -> bool {
+        const WHITE_SQUARES: BitBoard = BitBoard(12273903644374837845);
+
+        let minors = self.knights() | self.bishops();
+        if self.occupancy != self.kings() | minors {
+            return false;
+        }
+
+        let bishops = self.bishops();
+        minors.count_bits() <= 1
+            || (self.knights() == EMPTY_BB
+                && (bishops & WHITE_SQUARES == EMPTY_BB || bishops & !WHITE_SQUARES == EMPTY_BB))
+    }
+}
+
+/// SEE
+impl Board {
+    /// Returns bitboard with the pieces of both colors attacking a square, with sliders blocked by
+    /// the given occupancy
+    pub fn attackers_to(&self, square: Square, occupancy: BitBoard) -> BitBoard {
+        self.piece_bb[0] & pawn_attacks(square, Color::Black)
+            | self.piece_bb[1] & pawn_attacks(square, Color::White)
+            | self.knights() & knight_attacks(square)
+            | (self.bishops() | self.queens()) & bishop_attacks(square, occupancy)
+            | (self.rooks() | self.queens()) & rook_attacks(square, occupancy)
+            | self.kings() & king_attacks(square)
+    }
+
+    /// Checks if any piece of the given side attacks the square
+    pub fn is_square_attacked(&self, square: Square, by: Color) -> bool {
+        self.attackers_to(square, self.occupancy) & self.side_occupancy[by as usize] != EMPTY_BB
+    }
+
+    /// Returns the least valuable of the attackers within the attacker map
+    fn get_lva(&self, attackers: BitBoard, side: Color) -> Option<(Square, Piece)> {
+        for piece in PIECES[side as usize] {
+            let squares = attackers & self.piece_bb[piece as usize];
+
+            if squares != EMPTY_BB {
+                return Some((squares.lsb(), piece));
+            }
+        }
+
+        None
+    }
+
+    /// Checks if the static exchange after a move is enough to beat the given threshold
+    /// This can be used for both captures and quiet moves.
+    /// This implementation is basically that seen in Viri, which in turn is that of Ethereal
+    pub fn see(&self, m: Move, threshold: Eval) -> bool {
+        let src = m.get_src();
+        let tgt = m.get_tgt();
+        let mt = m.get_type();
+
+        // Castling cannot have bad SEE, since all squares the king passes through are not attacked
+        if mt == MoveType::Castle {
+            return true;
+        }
+
+        // Piece being swapped off is the promoted piece
+        let victim = if mt.is_promotion() {
+            mt.get_promotion(self.side)
+        } else {
+            self.piece_at(src)
+        };
+
+        // Get the static move value (also works for quiets)
+        let mut move_value = if mt.is_capture() {
+            if mt == MoveType::EnPassant {
+                PIECE_VALUES[Piece::WP as usize]
             } else {
                 PIECE_VALUES[self.piece_at(tgt) as usize]
             }
@@ -949,7 +1510,7 @@ impl Board {
         }
 
         // Get all pieces covering the exchange square and start exchanging
-        let mut attackers = self.map_all_attackers(tgt, occs) & occs;
+        let mut attackers = self.attackers_to(tgt, occs) & occs;
         let mut side_to_move = !self.side;
 
         loop {
@@ -995,10 +1556,52 @@ impl Board {
     }
 }
 
+/// Node counts of perft subtrees, stored under the board hash together with the depth.
+/// Entries are always replaced on store.
+#[derive(Clone, Copy, Default)]
+struct PerftEntry {
+    hash: ZHash,
+    depth: usize,
+    nodes: u64,
+}
+
+pub struct PerftTable {
+    table: Vec<PerftEntry>,
+}
+pub const PERFT_TABLE_SIZE: usize = 16;
+
+impl PerftTable {
+    /// Create an empty perft table of the given size in MiB
+    pub fn new(mb_size: usize) -> Self {
+        let size = (mb_size << 20) / size_of::<PerftEntry>();
+        Self {
+            table: vec![PerftEntry::default(); size],
+        }
+    }
+
+    /// Same multiply-shift indexing as the tt
+    fn get_key(&self, hash: ZHash) -> usize {
+        ((hash.0 as u128 * self.table.len() as u128) >> 64) as usize
+    }
+
+    fn probe(&self, hash: ZHash, depth: usize) -> Option<u64> {
+        let entry = self.table[self.get_key(hash)];
+        (entry.hash == hash && entry.depth == depth).then_some(entry.nodes)
+    }
+
+    fn insert(&mut self, hash: ZHash, depth: usize, nodes: u64) {
+        let key = self.get_key(hash);
+        self.table[key] = PerftEntry { hash, depth, nodes };
+    }
+}
+
 /// Perft
 impl Board {
-    /// Recursive move generation
-    fn perft_driver(&self, depth: usize) -> u64 {
+    /// Recursive move generation, with leaf nodes counted in bulk. Subtrees are looked up in the
+    /// table when one is given, which relies on the board hash being free of collisions.
+    /// Copying make_move is kept over make_move_in_place, which measured slower here (see
+    /// perft_copy_vs_in_place).
+    fn perft_driver(&self, depth: usize, mut table: Option<&mut PerftTable>) -> u64 {
         let move_list = self.gen_moves::<QUIETS>();
 
         if depth == 1 {
@@ -1007,50 +1610,179 @@ impl Board {
             return 1;
         }
 
+        if let Some(nodes) = table.as_ref().and_then(|t| t.probe(self.hash, depth)) {
+            return nodes;
+        }
+
         let mut nodes = 0;
-        for i in 0..move_list.len() {
-            let m = move_list.moves[i];
-            let new_board = self.make_move(m);
-            nodes += new_board.perft_driver(depth - 1);
+        for &m in &move_list.moves[..move_list.len()] {
+            nodes += self
+                .make_move(m)
+                .perft_driver(depth - 1, table.as_deref_mut());
+        }
+
+        if let Some(t) = table {
+            t.insert(self.hash, depth, nodes);
         }
 
         nodes
     }
 
-    /// Cumulative (divide) perft
-    pub fn perft(&self, depth: usize) -> u64 {
+    /// Number of leaf nodes at the given depth, with the plain tree walk used for checking move
+    /// generation
+    pub fn perft_total(&self, depth: usize) -> u64 {
+        self.perft_driver(depth, None)
+    }
+
+    /// Number of leaf nodes at the given depth, as perft_total, with the moves at depth 1 counted
+    /// by count_moves instead of being generated
+    pub fn perft_bulk(&self, depth: usize) -> u64 {
+        match depth {
+            0 => 1,
+            1 => self.count_moves() as u64,
+            _ => {
+                let move_list = self.gen_moves::<QUIETS>();
+                move_list.moves[..move_list.len()]
+                    .iter()
+                    .map(|&m| self.make_move(m).perft_bulk(depth - 1))
+                    .sum()
+            }
+        }
+    }
+
+    /// Number of leaf nodes at the given depth, with subtrees looked up in the table to speed up
+    /// deep runs
+    pub fn perft_hashed(&self, depth: usize, table: &mut PerftTable) -> u64 {
+        self.perft_driver(depth, Some(table))
+    }
+
+    /// Leaf nodes under each root move, in move generation order
+    pub fn perft_divide(&self, depth: usize) -> Vec<(Move, u64)> {
+        self.divide_driver(depth, None)
+    }
+
+    fn divide_driver(&self, depth: usize, mut table: Option<&mut PerftTable>) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+
         let move_list = self.gen_moves::<QUIETS>();
-        let mut total_nodes = 0;
+        move_list.moves[..move_list.len()]
+            .iter()
+            .map(|&m| {
+                let nodes = self
+                    .make_move(m)
+                    .perft_driver(depth - 1, table.as_deref_mut());
+                (m, nodes)
+            })
+            .collect()
+    }
 
-        let start = Instant::now();
-        for i in 0..move_list.len() {
-            let m = move_list.moves[i];
-            let start = Instant::now();
-            let root = self.make_move(m);
-            let nodes = root.perft_driver(depth - 1);
-            total_nodes += nodes;
-            let duration = start.elapsed();
-
-            println!(
-                "{}{} -- {} nodes in {:?}",
-                m.get_src(),
-                m.get_tgt(),
-                nodes,
-                duration
-            );
+    /// Number of leaf nodes at the given depth, with the root moves split evenly across threads.
+    /// Each thread walks its subtrees on its own copy of the board, without a table.
+    pub fn perft_parallel(&self, depth: usize, threads: usize) -> u64 {
+        if depth == 0 {
+            return 1;
         }
+
+        let move_list = self.gen_moves::<QUIETS>();
+        let moves = &move_list.moves[..move_list.len()];
+        let chunk_size = moves.len().div_ceil(threads.max(1)).max(1);
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = moves
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|&m| self.make_move(m).perft_total(depth - 1))
+                            .sum::<u64>()
+                    })
+                })
+                .collect();
+
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        })
+    }
+
+    /// Print the divide and total node count with the speed of the run
+    pub fn perft(&self, depth: usize, hashed: bool) -> u64 {
+        let mut table = hashed.then(|| PerftTable::new(PERFT_TABLE_SIZE));
+
+        let start = Instant::now();
+        let divide = self.divide_driver(depth, table.as_mut());
         let duration = start.elapsed();
 
-        let perf: u128 = total_nodes as u128 / duration.as_micros();
+        for (m, nodes) in &divide {
+            println!("{m} -- {nodes} nodes");
+        }
+
+        let total_nodes: u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+        let perf: u128 = total_nodes as u128 / duration.as_micros().max(1);
         println!("\n{total_nodes} nodes in {duration:?} - {perf}Mnodes/s");
 
         total_nodes
     }
 }
 
+/// Boards are serialized as FEN strings, and read back through the FEN parser
+#[cfg(feature = "serde")]
+impl serde::Serialize for Board {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_fen())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Board {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fen = String::deserialize(deserializer)?;
+        fen.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
     use super::*;
+    use crate::chess::tables::Prng;
+
+    #[rustfmt::skip]
+    const PERFT_SUITE: [(&str, &str, u64, usize); 16] = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "Startpos", 119060324, 6),
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "Kiwipete", 193690690, 5),
+        ("8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1", "Illegal ep move #1", 1015133, 6),
+        ("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", "Illegal ep move #2", 1134888, 6),
+        ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", "Ep capture checks opponent", 1440467, 6),
+        ("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "Short castling gives check", 661072, 6),
+        ("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", "Long castling gives check", 803711, 6),
+        ("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", "Castle rights", 1274206, 4),
+        ("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", "Castling prevented", 1720476, 4),
+        ("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", "Promote out of check", 3821001, 6),
+        ("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", "Discovered check", 1004658, 5),
+        ("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", "Promote to give check", 217342, 6),
+        ("8/P1k5/K7/8/8/8/8/8 w - - 0 1", "Under promote to give check", 92683, 6),
+        ("K1k5/8/P7/8/8/8/8/8 w - - 0 1", "Self stalemate", 2217, 6),
+        ("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", "Stalemate & checkmate #1", 567584, 7),
+        ("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", "Stalemate & checkmate #2", 23527, 4),
+    ];
+
+    /// Chess960 positions, given in Shredder-FEN and X-FEN
+    #[rustfmt::skip]
+    const CHESS960_PERFT_SUITE: [(&str, u64, usize); 10] = [
+        ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", 326672, 4),
+        ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", 667366, 4),
+        ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", 273318, 4),
+        ("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9", 382958, 4),
+        ("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9", 1171749, 4),
+        ("r1k1r2q/p1ppp1pp/8/8/8/8/P1PPP1PP/R1K1R2Q w KQkq - 0 1", 285754, 4),
+        ("r1k2r1q/p1ppp1pp/8/8/8/8/P1PPP1PP/R1K2R1Q w KQkq - 0 1", 541480, 4),
+        ("8/8/8/4B2b/6nN/8/5P2/2R1K2k w Q - 0 1", 118388, 4),
+        ("2r5/8/8/8/8/8/6PP/k2KR3 w K - 0 1", 57700, 4),
+        ("4r3/3k4/8/8/8/8/6PP/qR1K1R2 w KQ - 0 1", 405636, 4),
+    ];
 
     #[test]
     fn test_invalid_fen() {
@@ -1063,15 +1795,116 @@ mod tests {
         let invalid_ep_square =
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ a9 0 1".parse::<Board>();
 
+        let invalid_ranks = "8/8/8/4k3/8/8/4K3 w - - 0 1".parse::<Board>();
+        let invalid_kings = "8/8/8/4k3/8/8/8/8 w - - 0 1".parse::<Board>();
+        let invalid_check = "4k3/8/8/8/8/8/8/4R1K1 w - - 0 1".parse::<Board>();
+
         assert!(invalid_pieces.is_err());
+        assert!(invalid_ranks.is_err());
+        assert!(invalid_kings.is_err());
+        assert!(invalid_check.is_err());
         assert!(invalid_side.is_err());
         assert!(invalid_castle.is_err());
         assert!(invalid_ep_square.is_err());
     }
 
+    #[test]
+    fn test_validate() {
+        let invalid = [
+            ("8/8/8/4k3/8/8/8/8 w - - 0 1", "Invalid number of kings!"),
+            ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", "Invalid number of kings!"),
+            ("4k3/8/8/8/8/8/8/P3K3 w - - 0 1", "Pawns on the back ranks!"),
+            ("4k2p/8/8/8/8/8/8/4K3 b - - 0 1", "Pawns on the back ranks!"),
+            (
+                "4k3/8/8/8/8/QQQQQQQQ/QQQQQQQQ/1Q2K3 w - - 0 1",
+                "Too many pieces!",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4R1K1 w - - 0 1",
+                "Side not to move is in check!",
+            ),
+            (
+                "4k3/8/8/8/4P3/8/8/4K3 b - e2 0 1",
+                "Invalid en passant square!",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 b - e3 0 1",
+                "Invalid en passant square!",
+            ),
+            (
+                "4k3/8/8/8/4P3/4N3/8/4K3 b - e3 0 1",
+                "Invalid en passant square!",
+            ),
+            (
+                "4k3/8/8/8/4P3/8/4N3/4K3 b - e3 0 1",
+                "Invalid en passant square!",
+            ),
+            (
+                "4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1",
+                "Invalid en passant square!",
+            ),
+        ];
+        for (fen, err) in invalid {
+            assert_eq!(fen.parse::<Board>().err(), Some(err), "{fen}");
+        }
+
+        let valid = [
+            "4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "4k3/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1",
+        ];
+        for fen in valid {
+            assert!(fen.parse::<Board>().is_ok(), "{fen}");
+        }
+        assert!(Board::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let kbvkn_mate: Board = "5b1K/5k1N/8/8/8/8/8/8 b - - 1 1".parse().unwrap();
+        let kbvkn_draw: Board = "8/8/3k4/4n3/8/2KB4/8/8 w - - 0 1".parse().unwrap();
+        let krvkn: Board = "8/8/4k3/4n3/8/2KR4/8/8 w - - 0 1".parse().unwrap();
+
+        assert!(!kbvkn_mate.insufficient_material());
+        assert!(kbvkn_draw.insufficient_material());
+        assert!(!krvkn.insufficient_material());
+
+        let insufficient = |fen: &str| fen.parse::<Board>().unwrap().insufficient_material();
+        assert!(insufficient("8/8/3k4/8/8/2K5/8/8 w - - 0 1")); // kvk
+        assert!(insufficient("8/8/3k4/8/8/2KN4/8/8 b - - 0 1")); // knvk
+        assert!(insufficient("8/8/3kb3/8/8/2K5/8/8 w - - 0 1")); // kvkb
+        assert!(insufficient("8/8/3kb3/8/8/2K5/4B3/8 w - - 0 1")); // kbvkb, same colors
+        assert!(insufficient("8/8/3k4/8/8/2KB4/2B5/8 w - - 0 1")); // kbbvk, same colors
+
+        assert!(!insufficient("8/8/3k4/8/8/2KBB3/8/8 w - - 0 1")); // kbbvk
+        for piece in ['P', 'R', 'Q', 'p', 'r', 'q'] {
+            assert!(!insufficient(&format!(
+                "8/8/3k4/8/8/2K5/{piece}7/8 w - - 0 1"
+            )));
+        }
+    }
+
+    #[test]
+    fn test_dead_position() {
+        let dead = |fen: &str| fen.parse::<Board>().unwrap().is_dead_position();
+        assert!(dead("8/8/3k4/8/8/2K5/8/8 w - - 0 1")); // kvk
+        assert!(dead("8/8/3k4/8/8/2KN4/8/8 b - - 0 1")); // knvk
+        assert!(dead("8/8/3kb3/8/8/2K5/8/8 w - - 0 1")); // kvkb
+        assert!(dead("8/8/3kb3/8/8/2K5/4B3/8 w - - 0 1")); // kbvkb, same colors
+        assert!(dead("8/8/3k4/8/8/2KB4/2B5/8 w - - 0 1")); // kbbvk, same colors
+
+        // Mate is possible, even though it can't be forced
+        assert!(!dead("8/8/3kn3/8/8/2KN4/8/8 w - - 0 1")); // knvkn
+        assert!(!dead("8/8/3kb3/8/8/2K5/3B4/8 w - - 0 1")); // kbvkb, opposite colors
+        assert!(!dead("8/8/3kn3/8/8/2KB4/8/8 w - - 0 1")); // kbvkn
+        assert!(!dead("8/8/3k4/8/8/2KBB3/8/8 w - - 0 1")); // kbbvk
+        for piece in ['P', 'R', 'Q', 'p', 'r', 'q'] {
+            assert!(!dead(&format!("8/8/3k4/8/8/2K5/{piece}7/8 w - - 0 1")));
+        }
+    }
+
     #[test]
     fn test_pin_mask() {
-        init_all_tables();
         let board: Board = "R2bk3/5p2/4r1B1/1Q6/8/4Q3/4R3/2K5 b - - 0 1"
             .parse()
             .unwrap();
@@ -1091,7 +1924,6 @@ mod tests {
 
     #[test]
     fn test_legal_pawn() {
-        init_all_tables();
         let b1: Board = "8/8/8/1k6/3Pp3/8/8/4KQ2 b - d3 0 1".parse().unwrap();
         println!("{b1}");
         let m1 = b1.gen_moves::<QUIETS>(); // enpassant blocks check
@@ -1110,7 +1942,6 @@ mod tests {
 
     #[test]
     fn test_see_helpers() {
-        init_all_tables();
         let b1: Board = "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1"
             .parse()
             .unwrap();
@@ -1118,8 +1949,8 @@ mod tests {
             .parse()
             .unwrap();
 
-        let att1 = b1.map_all_attackers(Square::E5, b1.occupancy);
-        let att2 = b2.map_all_attackers(Square::E5, b2.occupancy);
+        let att1 = b1.attackers_to(Square::E5, b1.occupancy);
+        let att2 = b2.attackers_to(Square::E5, b2.occupancy);
 
         println!("{b1}\n{att1}\n{b2}\n{att2}");
 
@@ -1130,6 +1961,63 @@ mod tests {
         assert!(!att2.get_bit(Square::E1));
     }
 
+    #[test]
+    fn test_attacks() {
+        let board: Board = "4k3/8/2n5/3p4/4P3/1B3N2/8/R3K3 w Q - 0 1".parse().unwrap();
+        let attackers = |square: Square, blockers: BitBoard| {
+            let attackers = board.attackers_to(square, blockers);
+            attackers.into_iter().collect::<Vec<Square>>()
+        };
+
+        // Both colors, with knights, pawns, sliders and kings
+        assert_eq!(
+            attackers(Square::D4, board.occupancy),
+            [Square::C6, Square::F3]
+        );
+        assert_eq!(
+            attackers(Square::D5, board.occupancy),
+            [Square::E4, Square::B3]
+        );
+        assert_eq!(
+            attackers(Square::C4, board.occupancy),
+            [Square::D5, Square::B3]
+        );
+        assert_eq!(
+            attackers(Square::D1, board.occupancy),
+            [Square::B3, Square::A1, Square::E1]
+        );
+
+        // Sliders see through pieces removed from the blockers
+        assert_eq!(attackers(Square::F1, board.occupancy), [Square::E1]);
+        let blockers = board.occupancy.pop_bit(Square::E1);
+        assert_eq!(attackers(Square::F1, blockers), [Square::A1, Square::E1]);
+
+        assert!(board.is_square_attacked(Square::D5, Color::White));
+        assert!(board.is_square_attacked(Square::E4, Color::Black));
+        assert!(board.is_square_attacked(Square::C4, Color::Black));
+        assert!(board.is_square_attacked(Square::D7, Color::Black));
+        assert!(board.is_square_attacked(Square::E2, Color::White));
+        assert!(!board.is_square_attacked(Square::E2, Color::Black));
+        assert!(!board.is_square_attacked(Square::H8, Color::White));
+
+        // Checkers and king threats are built on the same attack maps
+        let checked: Board = "4k3/8/8/8/8/3n4/8/4K2r w - - 0 1".parse().unwrap();
+        assert_eq!(
+            checked.checkers.into_iter().collect::<Vec<Square>>(),
+            [Square::D3, Square::H1]
+        );
+        assert_eq!(
+            checked.attackers_to(Square::E1, checked.occupancy) & checked.opp_occupancy(),
+            checked.checkers
+        );
+
+        // The king can't step back along the rook's ray, nor onto squares the knight covers
+        let threats = checked.map_king_threats();
+        assert!(threats.get_bit(Square::D1) && threats.get_bit(Square::F1));
+        assert!(threats.get_bit(Square::F2) && !threats.get_bit(Square::D2));
+        assert_eq!(checked.gen_moves::<QUIETS>().len(), 2);
+    }
+
     #[test]
     fn test_see() {
         #[rustfmt::skip]
@@ -1149,7 +2037,6 @@ mod tests {
             ("5rk1/1pp2q1p/p1pb4/8/3P1NP1/2P5/1P1BQ1P1/5RK1 b - - 0 1", "d6f4", -108, true),
         ];
 
-        init_all_tables();
         for (b, m, t, r) in SEE_SUITE {
             let board: Board = b.parse().unwrap();
             let m = board.find_move(m).unwrap();
@@ -1160,34 +2047,538 @@ mod tests {
     }
 
     #[test]
-    fn test_perft() {
+    fn test_parse_move() {
+        let board: Board = "r3k3/1P6/8/3pP3/8/8/8/4K2R w Kq d6 0 1".parse().unwrap();
+
+        let promotion = board.parse_move("b7a8n").unwrap();
+        assert_eq!(promotion.get_type(), MoveType::KnightCapPromo);
+        assert_eq!(
+            board.parse_move("b7b8q").unwrap().get_type(),
+            MoveType::QueenPromotion
+        );
+        assert_eq!(
+            board.parse_move("e5d6").unwrap().get_type(),
+            MoveType::EnPassant
+        );
+        assert_eq!(
+            board.parse_move("e1g1").unwrap().get_type(),
+            MoveType::Castle
+        );
+
+        assert_eq!(board.parse_move("e1h1"), board.parse_move("e1g1"));
+        for (piece, move_type) in [
+            ('n', MoveType::KnightPromotion),
+            ('b', MoveType::BishopPromotion),
+            ('r', MoveType::RookPromotion),
+        ] {
+            let m = board.parse_move(&format!("b7b8{piece}")).unwrap();
+            assert_eq!(m.get_type(), move_type);
+            assert_eq!(m.to_string(), format!("b7b8{piece}"));
+        }
+
+        let start = Board::default();
+        assert_eq!(
+            start.parse_move("e2e4").unwrap().get_type(),
+            MoveType::DoublePush
+        );
+        assert_eq!(start.parse_move("e2e5"), None);
+
+        let promotion: Board = "8/4P3/8/8/8/8/k7/4K3 w - - 0 1".parse().unwrap();
+        assert_eq!(
+            promotion.parse_move("e7e8q").unwrap().get_type(),
+            MoveType::QueenPromotion
+        );
+
+        // Unparseable, pseudolegal but illegal, and missing promotion pieces
+        for move_str in [
+            "", "0000", "e1", "e5e7", "b7b8", "b7b8k", "h1h8x", "e5d6q", "b7b8qq",
+        ] {
+            assert_eq!(board.parse_move(move_str), None, "{move_str}");
+        }
+        let pinned: Board = "4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1".parse().unwrap();
+        assert_eq!(pinned.parse_move("e2c3"), None);
+
+        // find_move is only a wrapper
+        assert_eq!(board.find_move("e1h1"), board.parse_move("e1h1"));
+        assert_eq!(board.find_move("b7b8"), None);
+    }
+
+    #[test]
+    fn test_undo_move() {
+        let mut rng = Prng::new(0xCA4B);
+        let fens = PERFT_SUITE.map(|(fen, ..)| fen);
+
+        for fen in fens
+            .into_iter()
+            .chain(CHESS960_PERFT_SUITE.map(|(fen, ..)| fen))
+        {
+            let mut board: Board = fen.parse().unwrap();
+            let mut played = Vec::new();
+
+            // Random walk until the game ends, checking each step against a copied make
+            for _ in 0..200 {
+                let move_list = board.gen_moves::<QUIETS>();
+                if move_list.is_empty() {
+                    break;
+                }
+
+                let m = move_list.moves[rng.next_u64() as usize % move_list.len()];
+                let copied = board.make_move(m);
+                let before = board.clone();
+                let undo = board.make_move_in_place(m);
+                assert_eq!(board, copied, "{m}{before}");
+
+                played.push((m, undo, before));
+            }
+
+            while let Some((m, undo, before)) = played.pop() {
+                board.undo_move(m, undo);
+                assert_eq!(board, before, "{m}{board}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_make_move_checked() {
+        let board: Board = "4r1k1/8/8/8/8/8/4N3/1N2K3 w - - 0 1".parse().unwrap();
+        let legal = board.find_move("b1c3").unwrap();
+        assert_eq!(
+            board.make_move_checked(legal).unwrap().hash,
+            board.make_move(legal).hash
+        );
+
+        // The pinned knight, a king taking its own piece, and moves from empty or enemy squares
+        for (src, tgt) in [
+            (Square::E2, Square::C3),
+            (Square::E1, Square::E2),
+            (Square::A1, Square::A2),
+            (Square::G8, Square::G7),
+        ] {
+            assert!(board
+                .make_move_checked(Move::new(src, tgt, MoveType::Quiet))
+                .is_none());
+        }
+        assert!(board.make_move_checked(NULL_MOVE).is_none());
+    }
+
+    /// Every move generated in the tree below the board, up to the given depth
+    fn collect_moves(board: &Board, depth: usize, moves: &mut HashSet<Move>) {
+        let move_list = board.gen_moves::<QUIETS>();
+        for &m in &move_list.moves[..move_list.len()] {
+            moves.insert(m);
+            if depth > 1 {
+                collect_moves(&board.make_move(m), depth - 1, moves);
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_legal() {
+        use MoveType::*;
         #[rustfmt::skip]
-        const PERFT_SUITE: [(&str, &str, u64, usize); 16] = [
-            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "Startpos", 119060324, 6),
-            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "Kiwipete", 193690690, 5),
-            ("8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1", "Illegal ep move #1", 1015133, 6),
-            ("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", "Illegal ep move #2", 1134888, 6),
-            ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", "Ep capture checks opponent", 1440467, 6),
-            ("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "Short castling gives check", 661072, 6),
-            ("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", "Long castling gives check", 803711, 6),
-            ("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", "Castle rights", 1274206, 4),
-            ("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", "Castling prevented", 1720476, 4),
-            ("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", "Promote out of check", 3821001, 6),
-            ("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", "Discovered check", 1004658, 5),
-            ("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", "Promote to give check", 217342, 6),
-            ("8/P1k5/K7/8/8/8/8/8 w - - 0 1", "Under promote to give check", 92683, 6),
-            ("K1k5/8/P7/8/8/8/8/8 w - - 0 1", "Self stalemate", 2217, 6),
-            ("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", "Stalemate & checkmate #1", 567584, 7),
-            ("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", "Stalemate & checkmate #2", 23527, 4),
+        const MOVE_TYPES: [MoveType; 13] = [
+            Quiet, Castle, DoublePush, KnightPromotion, BishopPromotion, RookPromotion,
+            QueenPromotion, Capture, EnPassant, KnightCapPromo, BishopCapPromo, RookCapPromo,
+            QueenCapPromo,
         ];
 
-        init_all_tables();
+        let fens = PERFT_SUITE.map(|(fen, ..)| fen);
+        for fen in fens
+            .into_iter()
+            .chain(CHESS960_PERFT_SUITE.map(|(fen, ..)| fen))
+        {
+            let board: Board = fen.parse().unwrap();
+            let mut candidates = HashSet::new();
+            collect_moves(&board, 2, &mut candidates);
+
+            // Each move also comes with every other move type, to catch mismatched flags
+            let candidates: HashSet<Move> = candidates
+                .iter()
+                .flat_map(|&m| {
+                    MOVE_TYPES.map(|move_type| Move::new(m.get_src(), m.get_tgt(), move_type))
+                })
+                .collect();
+
+            // Moves from anywhere in the tree are checked against the root and its children
+            let move_list = board.gen_moves::<QUIETS>();
+            let children = move_list.moves[..move_list.len()]
+                .iter()
+                .map(|&m| board.make_move(m));
+            for node in std::iter::once(board.clone()).chain(children) {
+                let legal = node.gen_moves::<QUIETS>();
+                let legal = &legal.moves[..legal.len()];
+
+                for &m in &candidates {
+                    assert_eq!(
+                        node.is_legal(m),
+                        legal.contains(&m),
+                        "{m} {}",
+                        node.to_fen()
+                    );
+                }
+            }
+        }
+
+        let board: Board = "4k3/8/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
+        assert!(!board.is_legal(NULL_MOVE));
+    }
+
+    #[test]
+    fn test_recompute_hash() {
+        let mut rng = Prng::new(0x2A5E);
+
+        for _ in 0..50 {
+            let mut board = Board::default();
+
+            for _ in 0..200 {
+                let move_list = board.gen_moves::<QUIETS>();
+                if move_list.is_empty() {
+                    break;
+                }
+
+                let m = move_list.moves[rng.next_u64() as usize % move_list.len()];
+                board = board.make_move(m);
+                assert_eq!(board.hash, board.recompute_hash(), "{}", board.to_fen());
+                assert_eq!(board.make_null().hash, board.make_null().recompute_hash());
+            }
+        }
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        for (fen, ..) in PERFT_SUITE {
+            let board: Board = fen.parse().unwrap();
+            assert_eq!(board.to_fen(), fen);
+        }
+
+        // Counters are kept, and the fullmove number goes up after Black moves
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let board: Board = fen.parse().unwrap();
+        assert_eq!(board.to_fen(), fen);
+
+        let board = board.make_move(board.find_move("f1b5").unwrap());
+        assert_eq!((board.halfmoves, board.fullmoves), (3, 3));
+        let board = board.make_move(board.find_move("a7a6").unwrap());
+        assert_eq!((board.halfmoves, board.fullmoves), (0, 4));
+        assert_eq!(
+            board.to_fen(),
+            "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4"
+        );
+
+        assert_eq!(Board::default().fullmoves, 1);
+        assert_eq!(
+            "8/8/4k3/8/8/4K3/8/8 b - - 7 0".parse::<Board>().err(),
+            Some("Invalid fullmove count!")
+        );
+        assert!("8/8/4k3/8/8/4K3/8/8 b - - 7 x".parse::<Board>().is_err());
+    }
+
+    #[test]
+    fn test_fen_counters() {
+        let counters = |fen: &str| {
+            let board: Board = fen.parse().unwrap();
+            (board.halfmoves, board.fullmoves)
+        };
+
+        let position = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -";
+        assert_eq!(counters(position), (0, 1));
+        assert_eq!(counters(&format!("{position} 2")), (2, 1));
+        assert_eq!(counters(&format!("{position} 2 3")), (2, 3));
+        assert_eq!(counters(&format!("{position} 2 3 extra")), (2, 3));
+        assert_eq!(counters(&format!("{position} bm Bb5; id \"ruy\";")), (0, 1));
+        assert_eq!(counters(&format!("{position}\t 2 \n 3")), (2, 3));
+
+        assert!(format!("{position} 2x 3").parse::<Board>().is_err());
+        assert!(format!("{position} 2 0").parse::<Board>().is_err());
+        assert!(format!("{position} 2 bm").parse::<Board>().is_err());
+        assert!("8/8/4k3/8/8/4K3/8/8 b -".parse::<Board>().is_err());
+    }
+
+    /// Mailbox and bitboards agree on every square of every board reachable within the depth
+    fn check_mailbox(board: &Board, depth: usize) {
+        for square in ALL_SQUARES {
+            let bitboard = ALL_PIECES
+                .into_iter()
+                .find(|&piece| board.piece_bb[piece as usize].get_bit(square));
+            assert_eq!(board.piece_on(square), bitboard, "{square}{board}");
+        }
+
+        if depth > 0 {
+            let move_list = board.gen_moves::<QUIETS>();
+            for &m in &move_list.moves[..move_list.len()] {
+                check_mailbox(&board.make_move(m), depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mailbox() {
+        let fens = PERFT_SUITE.map(|(fen, ..)| fen);
+        for fen in fens
+            .into_iter()
+            .chain(CHESS960_PERFT_SUITE.map(|(fen, ..)| fen))
+        {
+            check_mailbox(&fen.parse().unwrap(), 2);
+        }
+
+        let board = Board::default();
+        assert_eq!(board.piece_on(Square::E1), Some(Piece::WK));
+        assert_eq!(board.piece_on(Square::E4), None);
+    }
+
+    #[test]
+    fn test_phase() {
+        let bare_kings: Board = "4k3/8/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
+        let pawns: Board = "4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1".parse().unwrap();
+        let promoted: Board = "QQQqk3/8/8/8/8/8/8/RNBQKBNR b - - 0 1".parse().unwrap();
+
+        assert_eq!(Board::default().phase(), MAX_PHASE);
+        assert_eq!(bare_kings.phase(), 0);
+        assert_eq!(pawns.phase(), 0);
+        assert_eq!(promoted.phase(), MAX_PHASE);
+
+        let minor_majors: Board = "4k3/8/8/8/8/8/8/RNBQK3 w - - 0 1".parse().unwrap();
+        assert_eq!(minor_majors.phase(), 8);
+    }
+
+    #[test]
+    fn test_perft() {
         for (fen, description, correct_count, depth) in PERFT_SUITE {
             let board: Board = fen.parse().unwrap();
             println!("{fen}\n{description}\n{board}");
 
-            let nodes = board.perft(depth);
+            let nodes = board.perft_total(depth);
             assert_eq!(nodes, correct_count);
         }
     }
+
+    /// Timing of perft with copying make_move against make_move_in_place, run with
+    /// cargo test --release perft_copy_vs_in_place -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn perft_copy_vs_in_place() {
+        fn perft_in_place(board: &mut Board, depth: usize) -> u64 {
+            let move_list = board.gen_moves::<QUIETS>();
+            if depth == 1 {
+                return move_list.len() as u64;
+            }
+
+            let mut nodes = 0;
+            for &m in &move_list.moves[..move_list.len()] {
+                let undo = board.make_move_in_place(m);
+                nodes += perft_in_place(board, depth - 1);
+                board.undo_move(m, undo);
+            }
+            nodes
+        }
+
+        // Best of a few runs, to smooth out the noise
+        let best_time = |walk: &dyn Fn() -> u64, correct_count: u64| {
+            (0..3)
+                .map(|_| {
+                    let start = Instant::now();
+                    assert_eq!(walk(), correct_count);
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+
+        for &(fen, name, correct_count, depth) in &PERFT_SUITE[..2] {
+            let board: Board = fen.parse().unwrap();
+            let copy = best_time(&|| board.perft_total(depth), correct_count);
+            let in_place = best_time(&|| perft_in_place(&mut board.clone(), depth), correct_count);
+
+            println!("{name}: copy {copy:?}, in place {in_place:?}");
+        }
+    }
+
+    #[test]
+    fn test_perft_bulk() {
+        for (fen, _, _, depth) in PERFT_SUITE {
+            let board: Board = fen.parse().unwrap();
+            assert_eq!(
+                board.perft_bulk(depth.min(4)),
+                board.perft_total(depth.min(4)),
+                "{fen}"
+            );
+        }
+
+        for (fen, correct_count, depth) in CHESS960_PERFT_SUITE {
+            let board: Board = fen.parse().unwrap();
+            assert_eq!(board.perft_bulk(depth), correct_count, "{fen}");
+        }
+
+        // Double check, where only the king can move
+        let board: Board = "4k3/8/8/8/8/3n4/8/r3K3 w - - 0 1".parse().unwrap();
+        assert_eq!(board.checkers.count_bits(), 2);
+        assert_eq!(board.count_moves(), board.gen_moves::<QUIETS>().len());
+        assert_eq!(board.perft_bulk(0), 1);
+    }
+
+    /// Timing of perft with moves counted in bulk at depth 1 against generating them, run with
+    /// cargo test --release perft_bulk_vs_total -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn perft_bulk_vs_total() {
+        let best_time = |walk: &dyn Fn() -> u64, correct_count: u64| {
+            (0..3)
+                .map(|_| {
+                    let start = Instant::now();
+                    assert_eq!(walk(), correct_count);
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+
+        for &(fen, name, correct_count, depth) in &PERFT_SUITE[..2] {
+            let board: Board = fen.parse().unwrap();
+            let total = best_time(&|| board.perft_total(depth), correct_count);
+            let bulk = best_time(&|| board.perft_bulk(depth), correct_count);
+
+            println!("{name}: total {total:?}, bulk {bulk:?}");
+        }
+    }
+
+    #[test]
+    fn test_perft_parallel() {
+        let board = Board::default();
+        assert_eq!(board.perft_parallel(6, 4), 119060324);
+        assert_eq!(board.perft_parallel(3, 1), 8902);
+        assert_eq!(board.perft_parallel(2, 64), 400);
+        assert_eq!(board.perft_parallel(0, 4), 1);
+
+        let mate: Board = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"
+            .parse()
+            .unwrap();
+        assert_eq!(mate.perft_parallel(3, 4), 0);
+    }
+
+    #[test]
+    fn test_perft_hashed() {
+        for (fen, _, correct_count, depth) in PERFT_SUITE {
+            let board: Board = fen.parse().unwrap();
+            let table = &mut PerftTable::new(PERFT_TABLE_SIZE);
+            assert_eq!(board.perft_hashed(depth, table), correct_count, "{fen}");
+        }
+
+        for (fen, correct_count, depth) in CHESS960_PERFT_SUITE {
+            let board: Board = fen.parse().unwrap();
+            let table = &mut PerftTable::new(PERFT_TABLE_SIZE);
+            assert_eq!(board.perft_hashed(depth, table), correct_count, "{fen}");
+        }
+
+        // Transpositions are found, with entries of other depths kept apart
+        let table = &mut PerftTable::new(1);
+        let board = Board::default();
+        assert_eq!(board.perft_hashed(4, table), 197281);
+        assert_eq!(table.probe(board.hash, 4), Some(197281));
+        assert_eq!(table.probe(board.hash, 3), None);
+        assert_eq!(board.perft_hashed(3, table), 8902);
+    }
+
+    #[test]
+    fn test_perft_divide() {
+        let board = Board::default();
+        let divide = board.perft_divide(5);
+        assert_eq!(divide.len(), 20);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 4865609);
+        assert_eq!(board.perft_total(5), 4865609);
+
+        let e2e4 = board.find_move("e2e4").unwrap();
+        assert!(divide.contains(&(e2e4, 405385)));
+        assert_eq!(
+            board.divide_driver(5, Some(&mut PerftTable::new(1))),
+            divide
+        );
+
+        assert!(board.perft_divide(1).iter().all(|&(_, nodes)| nodes == 1));
+        assert!(board.perft_divide(0).is_empty());
+        assert_eq!(board.perft_total(0), 1);
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+        for (fen, ..) in CHESS960_PERFT_SUITE
+            .iter()
+            .filter(|(fen, ..)| !fen.contains('K'))
+        {
+            assert_eq!(fen.parse::<Board>().unwrap().to_fen(), *fen);
+        }
+
+        // X-FEN letters stand for the outermost rooks, written back as files
+        let board: Board = "r1k1r2q/p1ppp1pp/8/8/8/8/P1PPP1PP/R1K1R2Q w KQkq - 0 1"
+            .parse()
+            .unwrap();
+        assert_eq!(board.castling_rights.to_string(), "EAea");
+        let standard: Board = "r3k2r/8/8/8/8/8/8/R3K2R w HAha - 0 1".parse().unwrap();
+        assert!(!standard.chess960);
+        assert_eq!(standard.castling_rights.to_string(), "KQkq");
+
+        // Castling is king takes rook, and the king may not move at all
+        let board: Board = "2r5/8/8/8/8/8/6PP/k2KR3 w K - 0 1".parse().unwrap();
+        let castle = board.find_move("d1e1").unwrap();
+        assert_eq!(castle.get_type(), MoveType::Castle);
+        let board = board.make_move(castle);
+        assert_eq!(board.piece_at(Square::G1), Piece::WK);
+        assert_eq!(board.piece_at(Square::F1), Piece::WR);
+        assert!(board.castling_rights.is_empty());
+
+        let board: Board = "1r2k1r1/8/8/8/8/8/8/R3K2R b KQgb - 0 1".parse().unwrap();
+        let board = board.make_move(board.find_move("e8g8").unwrap());
+        assert_eq!(board.piece_at(Square::G8), Piece::BK);
+        assert_eq!(board.piece_at(Square::F8), Piece::BR);
+        assert_eq!(board.castling_rights.to_string(), "KQ");
+
+        // Standard castling can be given either way
+        let m = standard.find_move("e1g1").unwrap();
+        assert_eq!(standard.find_move("e1h1"), Some(m));
+        assert_eq!(m.to_string(), "e1g1");
+
+        assert!("r3k2r/8/8/8/8/8/8/R3K1R1 w K - 0 1"
+            .parse::<Board>()
+            .is_ok());
+        assert!("r3k2r/8/8/8/8/8/8/R3K3 w K - 0 1".parse::<Board>().is_err());
+        assert!("r3k2r/8/8/8/8/8/8/R3K2R w B - 0 1"
+            .parse::<Board>()
+            .is_err());
+        assert!("r3k2r/8/8/8/8/8/4K3/R6R w KQ - 0 1"
+            .parse::<Board>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_perft_chess960() {
+        for (fen, correct_count, depth) in CHESS960_PERFT_SUITE {
+            let board: Board = fen.parse().unwrap();
+            println!("{fen}\n{board}");
+
+            assert!(board.chess960);
+            assert_eq!(board.perft_total(depth), correct_count);
+        }
+
+        // Chess960 castling generation agrees with the standard one on standard positions
+        for (fen, _, _, depth) in PERFT_SUITE {
+            let mut board: Board = fen.parse().unwrap();
+            let nodes = board.perft_total(depth.min(4));
+
+            board.chess960 = true;
+            assert_eq!(board.perft_total(depth.min(4)), nodes, "{fen}");
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board: Board = fen.parse().unwrap();
+        let json = serde_json::to_string(&board).unwrap();
+        let read: Board = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(json, format!("\"{fen}\""));
+        assert_eq!((read.to_fen(), read.hash), (board.to_fen(), board.hash));
+        assert!(serde_json::from_str::<Board>("\"8/8/8 w - - 0 1\"").is_err());
+        assert!(serde_json::from_str::<Board>("42").is_err());
+    }
 }
@@ -0,0 +1,285 @@
+/// Programmatic board construction, as an alternative to formatting a FEN.
+///
+/// The builder collects the pieces and the other FEN fields, and is only turned into a board by
+/// build, which runs the same checks as FEN parsing (FENs are in fact parsed through a builder).
+/// Castling rights are given by their rook squares, with the king found on the same back rank.
+/// Hashes of the finished board are computed from scratch.
+use crate::chess::{bitboard::*, board::*, castle::*, piece::*, square::*, zobrist::*};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardBuilder {
+    pieces: [Option<Piece>; SQUARE_COUNT],
+    side: Color,
+    castling_rooks: BitBoard,
+    en_passant: Option<Square>,
+    halfmoves: usize,
+    fullmoves: usize,
+}
+
+/// Empty board with White to move
+impl Default for BoardBuilder {
+    fn default() -> Self {
+        Self {
+            pieces: [None; SQUARE_COUNT],
+            side: Color::White,
+            castling_rooks: EMPTY_BB,
+            en_passant: None,
+            halfmoves: 0,
+            fullmoves: 1,
+        }
+    }
+}
+
+impl BoardBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Place a piece, replacing the one already on the square
+    pub fn piece(&mut self, piece: Piece, square: Square) -> &mut Self {
+        self.pieces[square as usize] = Some(piece);
+        self
+    }
+
+    /// Empty the square
+    pub fn clear(&mut self, square: Square) -> &mut Self {
+        self.pieces[square as usize] = None;
+        self
+    }
+
+    pub fn piece_on(&self, square: Square) -> Option<Piece> {
+        self.pieces[square as usize]
+    }
+
+    pub fn side(&mut self, side: Color) -> &mut Self {
+        self.side = side;
+        self
+    }
+
+    /// Allow castling with the rook on the square, for the side owning the rook
+    pub fn castle(&mut self, rook: Square) -> &mut Self {
+        self.castling_rooks = self.castling_rooks.set_bit(rook);
+        self
+    }
+
+    /// Add castling rights from standard FEN, Shredder-FEN (rook files, as in "HAha") or X-FEN,
+    /// where K and Q stand for the outermost rook on each side of the king.
+    pub fn castling_fen(&mut self, s: &str) -> Result<&mut Self, &'static str> {
+        if s == "-" {
+            return Ok(self);
+        }
+
+        let mut black_rights = false;
+        for token in s.chars() {
+            let side = match token.is_ascii_uppercase() {
+                true => Color::White,
+                false => Color::Black,
+            };
+            if side == Color::White && black_rights {
+                return Err("Invalid Castling Rights!");
+            }
+            black_rights |= side == Color::Black;
+
+            let back_rank = self.back_rank(side);
+            let king_bb = self.piece_bb(side.king()) & back_rank;
+            if king_bb.count_bits() != 1 {
+                return Err("Invalid Castling Rights!");
+            }
+            let king = king_bb.lsb();
+            let mut rooks = self.piece_bb(side.rook()) & back_rank;
+
+            let rook = match token.to_ascii_lowercase() {
+                'k' => rooks.filter(|&sq| sq > king).last(),
+                'q' => rooks.find(|&sq| sq < king),
+                file @ 'a'..='h' => rooks.find(|&sq| sq.file().to_char() == file),
+                _ => None,
+            };
+            let rook = rook.ok_or("Invalid Castling Rights!")?;
+
+            if self.castling_rooks.get_bit(rook) {
+                return Err("Invalid Castling Rights!");
+            }
+            self.castle(rook);
+        }
+
+        Ok(self)
+    }
+
+    /// Set the square behind a pawn which just double pushed, dropped on build if no pawn of the
+    /// side to move can capture there
+    pub fn en_passant(&mut self, square: Option<Square>) -> &mut Self {
+        self.en_passant = square;
+        self
+    }
+
+    pub fn halfmoves(&mut self, halfmoves: usize) -> &mut Self {
+        self.halfmoves = halfmoves;
+        self
+    }
+
+    pub fn fullmoves(&mut self, fullmoves: usize) -> &mut Self {
+        self.fullmoves = fullmoves;
+        self
+    }
+
+    fn piece_bb(&self, piece: Piece) -> BitBoard {
+        ALL_SQUARES
+            .into_iter()
+            .filter(|&sq| self.piece_on(sq) == Some(piece))
+            .fold(EMPTY_BB, |bb, sq| bb.set_bit(sq))
+    }
+
+    fn back_rank(&self, side: Color) -> BitBoard {
+        match side {
+            Color::White => rank_bb(Rank::First),
+            Color::Black => rank_bb(Rank::Eight),
+        }
+    }
+
+    /// Castling rights of the rooks, which must share the back rank with their king
+    fn castling_rights(&self) -> Result<CastlingRights, &'static str> {
+        let mut rights = NO_RIGHTS;
+
+        for side in [Color::White, Color::Black] {
+            let back_rank = self.back_rank(side);
+            let king_bb = self.piece_bb(side.king()) & back_rank;
+            let rooks = self.castling_rooks & back_rank;
+
+            for rook in rooks {
+                if self.piece_on(rook) != Some(side.rook()) || king_bb.count_bits() != 1 {
+                    return Err("Invalid Castling Rights!");
+                }
+                rights.add(side, king_bb.lsb(), rook)?;
+            }
+        }
+
+        let back_ranks = self.back_rank(Color::White) | self.back_rank(Color::Black);
+        if self.castling_rooks & !back_ranks != EMPTY_BB {
+            return Err("Invalid Castling Rights!");
+        }
+
+        Ok(rights)
+    }
+
+    /// Build the board, checked with Board::validate
+    pub fn build(&self) -> Result<Board, &'static str> {
+        let mut board = Board::new();
+
+        for square in ALL_SQUARES {
+            if let Some(piece) = self.piece_on(square) {
+                board.set_piece(piece, square);
+            }
+        }
+
+        board.side = self.side;
+        board.castling_rights = self.castling_rights()?;
+        board.chess960 = !board.castling_rights.is_standard();
+        board.en_passant = self.en_passant;
+        board.halfmoves = self.halfmoves;
+
+        if self.fullmoves == 0 {
+            return Err("Invalid fullmove count!");
+        }
+        board.fullmoves = self.fullmoves;
+
+        board.validate()?;
+        board.map_checkers();
+
+        // Only keep en passant squares which can be captured, so that the hash matches the one
+        // of the same position reached with make_move
+        if let Some(ep) = board.en_passant {
+            if !board.can_capture_ep(ep, board.side) {
+                board.en_passant = None;
+            }
+        }
+
+        board.hash = ZHash::new(&board);
+        board.pawn_hash = ZHash::new_pawn(&board);
+
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn test_build_kiwipete() {
+        use Piece::*;
+        use Square::*;
+
+        let mut builder = BoardBuilder::new();
+        #[rustfmt::skip]
+        let pieces = [
+            (BR, A8), (BK, E8), (BR, H8),
+            (BP, A7), (BP, C7), (BP, D7), (BQ, E7), (BP, F7), (BB, G7),
+            (BB, A6), (BN, B6), (BP, E6), (BN, F6), (BP, G6),
+            (WP, D5), (WN, E5),
+            (BP, B4), (WP, E4),
+            (WN, C3), (WQ, F3), (BP, H3),
+            (WP, A2), (WP, B2), (WP, C2), (WB, D2), (WB, E2), (WP, F2), (WP, G2), (WP, H2),
+            (WR, A1), (WK, E1), (WR, H1),
+        ];
+        for (piece, square) in pieces {
+            builder.piece(piece, square);
+        }
+        builder.castle(A1).castle(H1).castle(A8).castle(H8);
+
+        let board = builder.build().unwrap();
+        assert_eq!(board, KIWIPETE.parse().unwrap());
+        assert_eq!(board.to_fen(), KIWIPETE);
+
+        // Other fields, checked the same way as in FENs
+        let board = builder
+            .side(Color::Black)
+            .halfmoves(3)
+            .fullmoves(7)
+            .build()
+            .unwrap();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 3 7";
+        assert_eq!(board, fen.parse().unwrap());
+        assert_eq!(
+            builder.clone().castle(B1).build().err(),
+            Some("Invalid Castling Rights!")
+        );
+        assert_eq!(
+            builder.clone().castle(E4).build().err(),
+            Some("Invalid Castling Rights!")
+        );
+        assert_eq!(
+            builder.clone().clear(E1).build().err(),
+            Some("Invalid Castling Rights!")
+        );
+        assert_eq!(
+            builder.clone().fullmoves(0).build().err(),
+            Some("Invalid fullmove count!")
+        );
+        assert_eq!(
+            builder.clone().en_passant(Some(D6)).build().err(),
+            Some("Invalid en passant square!")
+        );
+    }
+
+    #[test]
+    fn test_build_en_passant() {
+        let mut builder = BoardBuilder::new();
+        builder
+            .piece(Piece::WK, Square::E1)
+            .piece(Piece::BK, Square::E8)
+            .piece(Piece::WP, Square::E4)
+            .side(Color::Black)
+            .en_passant(Some(Square::E3));
+
+        // No black pawn can take on e3
+        let board = builder.build().unwrap();
+        assert_eq!(board.en_passant, None);
+        assert_eq!(board.hash, ZHash::new(&board));
+
+        let board = builder.piece(Piece::BP, Square::D4).build().unwrap();
+        assert_eq!(board.en_passant, Some(Square::E3));
+        assert_eq!(board, "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1".parse().unwrap());
+    }
+}
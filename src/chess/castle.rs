@@ -8,11 +8,22 @@ use crate::chess::{piece::*, square::*};
 ///
 ///  WK | WQ | BK | BQ  --> only using least significant 8 bits
 ///  08   04   02   01
+///
+/// The king and rook start squares are stored alongside the flags, to support Chess960. For
+/// standard chess they are always the E and A/H files.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Debug, Hash)]
-pub struct CastlingRights(u8);
+pub struct CastlingRights {
+    flags: u8,
+    kings: [Square; 2],
+    rooks: [[Square; 2]; 2], // kingside and queenside rooks for each side
+}
 
 pub const CASTLE_COUNT: usize = 16;
-pub const NO_RIGHTS: CastlingRights = CastlingRights(0);
+pub const NO_RIGHTS: CastlingRights = CastlingRights {
+    flags: 0,
+    kings: [Square::E1, Square::E8],
+    rooks: [[Square::H1, Square::A1], [Square::H8, Square::A8]],
+};
 
 // bit masks for each right
 const WK: u8 = 0x08;
@@ -23,15 +34,6 @@ const BQ: u8 = 0x01;
 const KINGSIDE: [u8; 2] = [WK, BK];
 const QUEENSIDE: [u8; 2] = [WQ, BQ];
 
-// bit masks for removing castle rights
-const ALL: u8 = 0x0F;
-const NO_WK: u8 = ALL ^ WK;
-const NO_WQ: u8 = ALL ^ WQ;
-const NO_BK: u8 = ALL ^ BK;
-const NO_BQ: u8 = ALL ^ BQ;
-const NO_W: u8 = NO_WK & NO_WQ;
-const NO_B: u8 = NO_BK & NO_BQ;
-
 /// Returns the rook src/tgt square for a given king target square
 /// King target square must be a valid castling destination, so either C1/C8 or G1/G8
 pub const fn rook_castling_move(king_tgt: Square) -> (Square, Square) {
@@ -41,23 +43,45 @@ pub const fn rook_castling_move(king_tgt: Square) -> (Square, Square) {
     }
 }
 
-/// Prints rights to fen format
+/// Returns the king and rook target squares when castling with the given rook.
+/// Whatever the start squares, they are the same as in standard chess.
+pub const fn castling_targets(king_src: Square, rook_src: Square) -> (Square, Square) {
+    let rank = king_src.rank();
+
+    if rook_src as u8 > king_src as u8 {
+        (
+            Square::from_coords(File::G, rank),
+            Square::from_coords(File::F, rank),
+        )
+    } else {
+        (
+            Square::from_coords(File::C, rank),
+            Square::from_coords(File::D, rank),
+        )
+    }
+}
+
+/// Prints rights to fen format. Chess960 rights are printed as Shredder-FEN, with the files of
+/// the castling rooks.
 impl fmt::Display for CastlingRights {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut s: String = String::from("");
+        let standard = self.is_standard();
 
-        if self.0 & WK != 0 {
-            s.push('K')
-        };
-        if self.0 & WQ != 0 {
-            s.push('Q')
-        };
-        if self.0 & BK != 0 {
-            s.push('k')
-        };
-        if self.0 & BQ != 0 {
-            s.push('q')
-        };
+        for (side, letters) in [(Color::White, "KQ"), (Color::Black, "kq")] {
+            for (kingside, letter) in [true, false].into_iter().zip(letters.chars()) {
+                if !self.has_right(side, kingside) {
+                    continue;
+                }
+
+                let file = self.rook_square(side, kingside).file().to_char();
+                s.push(match (standard, side) {
+                    (true, _) => letter,
+                    (false, Color::White) => file.to_ascii_uppercase(),
+                    (false, Color::Black) => file,
+                });
+            }
+        }
         if s.is_empty() {
             s.push('-')
         };
@@ -66,7 +90,8 @@ impl fmt::Display for CastlingRights {
     }
 }
 
-/// Parses fen castling string to return rights
+/// Parses standard fen castling string to return rights. Chess960 rights depend on where the
+/// pieces are, so they are parsed along with the board.
 impl FromStr for CastlingRights {
     type Err = &'static str;
 
@@ -80,19 +105,19 @@ impl FromStr for CastlingRights {
         for token in s.chars() {
             match token {
                 'K' => {
-                    if rights.0 & (BK | BQ) != 0 {
+                    if rights.flags & (BK | BQ) != 0 {
                         return Err("Invalid Castling Rights!");
                     }
-                    rights.0 |= WK;
+                    rights.flags |= WK;
                 }
                 'Q' => {
-                    if rights.0 & (BK | BQ) != 0 {
+                    if rights.flags & (BK | BQ) != 0 {
                         return Err("Invalid Castling Rights!");
                     }
-                    rights.0 |= WQ;
+                    rights.flags |= WQ;
                 }
-                'k' => rights.0 |= BK,
-                'q' => rights.0 |= BQ,
+                'k' => rights.flags |= BK,
+                'q' => rights.flags |= BQ,
                 _ => return Err("Invalid Castling Rights!"),
             }
         }
@@ -104,37 +129,114 @@ impl FromStr for CastlingRights {
 impl CastlingRights {
     /// Get index of rights as usize
     pub const fn index(self) -> usize {
-        self.0 as usize
+        self.flags as usize
+    }
+
+    /// Checks whether any right is left
+    pub const fn is_empty(self) -> bool {
+        self.flags == 0
+    }
+
+    /// Swap the rights of the two sides
+    pub const fn mirror(self) -> CastlingRights {
+        let [white, black] = self.rooks;
+
+        CastlingRights {
+            flags: (self.flags & (WK | WQ)) >> 2 | (self.flags & (BK | BQ)) << 2,
+            kings: [self.kings[1].flipv(), self.kings[0].flipv()],
+            rooks: [
+                [black[0].flipv(), black[1].flipv()],
+                [white[0].flipv(), white[1].flipv()],
+            ],
+        }
     }
 
     /// Checks whether given color has kingside rights
     pub const fn has_kingside(self, side: Color) -> bool {
-        self.0 & KINGSIDE[side as usize] != 0
+        self.flags & KINGSIDE[side as usize] != 0
     }
 
     /// Checks whether given color has queenside rights
     pub const fn has_queenside(self, side: Color) -> bool {
-        self.0 & QUEENSIDE[side as usize] != 0
+        self.flags & QUEENSIDE[side as usize] != 0
+    }
+
+    /// Checks whether given color has kingside or queenside rights
+    pub const fn has_right(self, side: Color, kingside: bool) -> bool {
+        match kingside {
+            true => self.has_kingside(side),
+            false => self.has_queenside(side),
+        }
+    }
+
+    /// Start square of the king of the given color
+    pub const fn king_square(self, side: Color) -> Square {
+        self.kings[side as usize]
+    }
+
+    /// Start square of the kingside or queenside rook of the given color
+    pub const fn rook_square(self, side: Color, kingside: bool) -> Square {
+        self.rooks[side as usize][!kingside as usize]
+    }
+
+    /// Add the right to castle with the given rook, which is a kingside right if the rook is to
+    /// the right of the king
+    pub fn add(&mut self, side: Color, king: Square, rook: Square) -> Result<(), &'static str> {
+        let kingside = rook.file() > king.file();
+        let flag = match kingside {
+            true => KINGSIDE[side as usize],
+            false => QUEENSIDE[side as usize],
+        };
+        let same_king = self.flags & (KINGSIDE[side as usize] | QUEENSIDE[side as usize]) == 0
+            || self.kings[side as usize] == king;
+
+        if self.flags & flag != 0 || !same_king {
+            return Err("Invalid Castling Rights!");
+        }
+
+        self.flags |= flag;
+        self.kings[side as usize] = king;
+        self.rooks[side as usize][!kingside as usize] = rook;
+
+        Ok(())
+    }
+
+    /// Checks whether all rights refer to the standard king and rook squares
+    pub fn is_standard(self) -> bool {
+        [Color::White, Color::Black].into_iter().all(|side| {
+            let (kings, rooks) = (NO_RIGHTS.kings, NO_RIGHTS.rooks);
+            let s = side as usize;
+
+            (!self.has_kingside(side) && !self.has_queenside(side) || self.kings[s] == kings[s])
+                && (!self.has_kingside(side) || self.rooks[s][0] == rooks[s][0])
+                && (!self.has_queenside(side) || self.rooks[s][1] == rooks[s][1])
+        })
     }
 
     /// Updates rights according to move.
-    /// Based on the idea that any move starting or ending on one of the four corners of the board
-    /// will remove the rights relative to that corner, and remove all rights in case the move
-    /// starts (or ends but it's impossible) on the king start square
-    pub const fn update(self, src: Square, tgt: Square) -> CastlingRights {
-        #[rustfmt::skip]
-        const CASTLE_MASKS: [u8; SQUARE_COUNT] = [
-            NO_BQ, ALL, ALL, ALL, NO_B, ALL, ALL, NO_BK,
-            ALL, ALL, ALL, ALL, ALL, ALL, ALL, ALL,
-            ALL, ALL, ALL, ALL, ALL, ALL, ALL, ALL,
-            ALL, ALL, ALL, ALL, ALL, ALL, ALL, ALL,
-            ALL, ALL, ALL, ALL, ALL, ALL, ALL, ALL,
-            ALL, ALL, ALL, ALL, ALL, ALL, ALL, ALL,
-            ALL, ALL, ALL, ALL, ALL, ALL, ALL, ALL,
-            NO_WQ, ALL, ALL, ALL, NO_W, ALL, ALL, NO_WK,
-        ];
-
-        let new = self.0 & CASTLE_MASKS[src as usize] & CASTLE_MASKS[tgt as usize];
-        CastlingRights(new)
+    /// Any move starting or ending on the square of a castling rook removes the rights relative
+    /// to that rook, and any move starting (or ending but it's impossible) on the king start
+    /// square removes all rights of that side.
+    pub fn update(self, src: Square, tgt: Square) -> CastlingRights {
+        if self.flags == 0 {
+            return self;
+        }
+
+        let mut new = self;
+        for side in [Color::White, Color::Black] {
+            let s = side as usize;
+
+            if src == self.kings[s] || tgt == self.kings[s] {
+                new.flags &= !(KINGSIDE[s] | QUEENSIDE[s]);
+            }
+            if src == self.rooks[s][0] || tgt == self.rooks[s][0] {
+                new.flags &= !KINGSIDE[s];
+            }
+            if src == self.rooks[s][1] || tgt == self.rooks[s][1] {
+                new.flags &= !QUEENSIDE[s];
+            }
+        }
+
+        new
     }
 }
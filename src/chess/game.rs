@@ -0,0 +1,235 @@
+/// Game representation: a starting board with the legal moves played from it.
+///
+/// Every board in the game is kept, so moves can be taken back without being unmade, along with
+/// the hashes used for threefold repetition detection. Search keeps its own lighter history in
+/// the engine Position, which is built from a Game.
+use std::iter;
+
+use crate::chess::{bitboard::*, board::*, moves::*, piece::*, zobrist::*};
+
+/// Game result, the bool refers to the game being adjudicated
+#[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Debug)]
+pub enum GameResult {
+    Ongoing,
+    WhiteWin(bool),
+    BlackWin(bool),
+    Draw(bool),
+}
+pub const ADJ: bool = true;
+pub const NO_ADJ: bool = false;
+
+#[derive(Clone, Debug)]
+pub struct Game {
+    start: Board,
+    boards: Vec<Board>,
+    moves: Vec<Move>,
+    hashes: Vec<ZHash>,
+}
+
+/// Default game starts from startpos
+impl Default for Game {
+    fn default() -> Self {
+        Self::from(Board::default())
+    }
+}
+
+/// Game starting from the given board
+impl From<Board> for Game {
+    fn from(start: Board) -> Self {
+        let hashes = vec![start.hash];
+
+        Self {
+            start,
+            boards: Vec::new(),
+            moves: Vec::new(),
+            hashes,
+        }
+    }
+}
+
+impl Game {
+    /// Board the game started from
+    pub fn start(&self) -> &Board {
+        &self.start
+    }
+
+    /// Board after the last move
+    pub fn current(&self) -> &Board {
+        self.boards.last().unwrap_or(&self.start)
+    }
+
+    /// Moves played since the start
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Number of plies played since the start
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// Play the move, if it is legal in the current position
+    pub fn push(&mut self, m: Move) -> Result<(), &'static str> {
+        let new = self
+            .current()
+            .make_move_checked(m)
+            .ok_or("Move is not legal!")?;
+        self.hashes.push(new.hash);
+        self.boards.push(new);
+        self.moves.push(m);
+
+        Ok(())
+    }
+
+    /// Take back the last move, returning it
+    pub fn pop(&mut self) -> Option<Move> {
+        let m = self.moves.pop()?;
+        self.boards.pop();
+        self.hashes.pop();
+
+        Some(m)
+    }
+
+    /// Every move with the board it was played on
+    pub fn iter(&self) -> impl Iterator<Item = (&Board, Move)> {
+        iter::once(&self.start)
+            .chain(&self.boards)
+            .zip(self.moves.iter().copied())
+    }
+
+    /// Every board in the game, from the start to the current one
+    pub fn boards(&self) -> impl Iterator<Item = &Board> {
+        iter::once(&self.start).chain(&self.boards)
+    }
+
+    /// The current position occurred at least three times, with the same side to move, since the
+    /// last irreversible move
+    pub fn is_threefold(&self) -> bool {
+        let current = self.current();
+
+        self.hashes
+            .iter()
+            .rev()
+            .take(current.halfmoves + 1)
+            .step_by(2)
+            .filter(|&&hash| hash == current.hash)
+            .count()
+            >= 3
+    }
+
+    /// A hundred plies went by without captures or pawn moves. Checkmate takes precedence.
+    pub fn is_fifty_move_draw(&self) -> bool {
+        let current = self.current();
+
+        current.halfmoves >= 100
+            && (current.checkers == EMPTY_BB || !current.gen_moves::<QUIETS>().is_empty())
+    }
+
+    /// Result of the game by the rules, never adjudicated.
+    /// Only dead positions are drawn on material, unlike the search's insufficient material.
+    pub fn result(&self) -> GameResult {
+        let current = self.current();
+
+        if current.gen_moves::<QUIETS>().is_empty() {
+            match (current.checkers == EMPTY_BB, current.side) {
+                (true, _) => GameResult::Draw(NO_ADJ),
+                (false, Color::White) => GameResult::BlackWin(NO_ADJ),
+                (false, Color::Black) => GameResult::WhiteWin(NO_ADJ),
+            }
+        } else if self.is_fifty_move_draw() || self.is_threefold() || current.is_dead_position() {
+            GameResult::Draw(NO_ADJ)
+        } else {
+            GameResult::Ongoing
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Berlin defence, after which both sides shuffle a rook and a knight
+    const BERLIN: &str = "e2e4 e7e5 g1f3 b8c6 f1b5 g8f6 e1g1 f6e4 f1e1 e4d6 f3e5 f8e7 b5f1 c6e5 \
+                          e1e5 e8g8";
+    const SHUFFLE: [&str; 4] = ["e5e1", "d6e8", "e1e5", "e8d6"];
+
+    fn play(game: &mut Game, moves: &str) {
+        for move_str in moves.split_whitespace() {
+            let m = game.current().find_move(move_str).unwrap();
+            game.push(m).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_threefold() {
+        let mut game = Game::default();
+        play(&mut game, BERLIN);
+        assert_eq!(game.len(), 16);
+
+        // The position after castling comes back every four plies
+        for ply in 1..=8 {
+            assert!(!game.is_threefold(), "ply {}", game.len());
+            assert_eq!(game.result(), GameResult::Ongoing);
+            play(&mut game, SHUFFLE[(ply - 1) % 4]);
+        }
+        assert_eq!(game.len(), 24);
+        assert!(game.is_threefold());
+        assert_eq!(game.result(), GameResult::Draw(NO_ADJ));
+
+        game.pop();
+        assert!(!game.is_threefold());
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut game = Game::default();
+        play(&mut game, BERLIN);
+        let hashes = game.hashes.clone();
+        let moves = game.moves().to_vec();
+
+        while game.pop().is_some() {
+            assert_eq!(game.current().hash, hashes[game.len()]);
+            assert_eq!(game.hashes, hashes[..=game.len()]);
+        }
+        assert_eq!(game.current().hash, Board::default().hash);
+
+        for &m in &moves {
+            game.push(m).unwrap();
+        }
+        assert_eq!(game.hashes, hashes);
+
+        // Pairs hold the board each move was played on
+        for (i, (board, m)) in game.iter().enumerate() {
+            assert_eq!(board.make_move(m).hash, hashes[i + 1]);
+        }
+        assert!(game.push(moves[0]).is_err());
+        assert_eq!(game.len(), moves.len());
+    }
+
+    #[test]
+    fn test_results() {
+        let mut game = Game::default();
+        play(&mut game, "f2f3 e7e5 g2g4 d8h4");
+        assert_eq!(game.result(), GameResult::BlackWin(NO_ADJ));
+
+        let board: Board = "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1".parse().unwrap();
+        assert_eq!(Game::from(board).result(), GameResult::Draw(NO_ADJ));
+
+        let board: Board = "7k/8/8/8/8/8/8/R3K3 w - - 100 80".parse().unwrap();
+        let game = Game::from(board);
+        assert!(game.is_fifty_move_draw());
+        assert_eq!(game.result(), GameResult::Draw(NO_ADJ));
+
+        let board: Board = "7k/8/8/8/8/8/8/4K2N w - - 0 1".parse().unwrap();
+        assert_eq!(Game::from(board).result(), GameResult::Draw(NO_ADJ));
+
+        // Mate is still possible with a knight each, even if the search treats it as a draw
+        let board: Board = "8/8/3k4/8/3n4/8/2KN4/8 w - - 0 1".parse().unwrap();
+        assert!(board.insufficient_material());
+        assert_eq!(Game::from(board).result(), GameResult::Ongoing);
+    }
+}
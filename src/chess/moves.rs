@@ -75,9 +75,13 @@ const SRC: u16 = 0b0000_0000_0011_1111;
 const TGT: u16 = 0b0000_1111_1100_0000;
 const TYPE: u16 = 0b1111_0000_0000_0000;
 
-/// Prints move in uci format
+/// Prints move in uci format (the null move is "0000")
 impl fmt::Display for Move {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *self == NULL_MOVE {
+            return write!(f, "0000");
+        }
+
         let s = format!("{}{}", self.get_src(), self.get_tgt());
         let move_type = self.get_type();
 
@@ -116,15 +120,48 @@ impl Move {
     }
 }
 
+/// Moves are only validated on their own, legality depends on the board they are played on
+impl TryFrom<u16> for Move {
+    type Error = &'static str;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        let m = Move(value);
+        let move_type = (value & TYPE) >> 12;
+
+        if matches!(move_type, 0b0011 | 0b1010 | 0b1011) {
+            Err("Invalid move type!")
+        } else if m != NULL_MOVE && value & SRC == (value & TGT) >> 6 {
+            Err("Move source and target are the same!")
+        } else {
+            Ok(m)
+        }
+    }
+}
+
+/// Moves are serialized in their compact form, which needs the board they are played on to be
+/// interpreted
+#[cfg(feature = "serde")]
+impl serde::Serialize for Move {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Move {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = u16::deserialize(deserializer)?;
+        Move::try_from(value).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::chess::board::Board;
-    use crate::chess::tables::init_all_tables;
 
     #[test]
     fn test_move_constructor() {
-        init_all_tables();
         let b: Board = "R2bk3/5p2/4r1B1/1Q6/8/4Q3/4R3/2K5 b - - 0 1"
             .parse()
             .unwrap();
@@ -145,4 +182,25 @@ mod tests {
         assert_eq!(m2.get_tgt(), Square::E7);
         assert_eq!(m2.get_type(), MoveType::Quiet);
     }
+
+    #[test]
+    fn test_move_validation() {
+        let m = Move::new(Square::E7, Square::E8, MoveType::QueenPromotion);
+
+        assert_eq!(Move::try_from(m.0), Ok(m));
+        assert_eq!(Move::try_from(0), Ok(NULL_MOVE));
+        assert!(Move::try_from(m.0 & !TYPE | 0b1010 << 12).is_err());
+        assert!(Move::try_from(Move::new(Square::E7, Square::E7, MoveType::Quiet).0).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let m = Move::new(Square::E1, Square::G1, MoveType::Castle);
+        let json = serde_json::to_string(&m).unwrap();
+
+        assert_eq!(serde_json::from_str::<Move>(&json).unwrap(), m);
+        assert!(serde_json::from_str::<Move>(&(0b1011u16 << 12 | 9).to_string()).is_err());
+        assert!(serde_json::from_str::<Move>("\"e1g1\"").is_err());
+    }
 }
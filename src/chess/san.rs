@@ -0,0 +1,317 @@
+/// Standard Algebraic Notation, as found in PGN files.
+///
+/// SAN moves are resolved against the legal moves of a board: the piece letter, target square and
+/// promotion piece select the candidates, and the optional source file or rank disambiguates
+/// between them. Captures may be marked with an x, checks and annotations are ignored, and
+/// castling is written O-O or O-O-O (zeros are accepted too). Formatting goes the other way,
+/// adding a source file or rank only when another legal move of the same piece type would
+/// otherwise read the same.
+use crate::chess::{bitboard::*, board::*, moves::*, piece::*, square::*};
+
+/// Fields of a SAN move, before looking at the board
+#[derive(Debug, PartialEq, Eq)]
+struct SanMove {
+    piece: u8, // piece type, as in Piece >> 1
+    src_file: Option<char>,
+    src_rank: Option<char>,
+    tgt: Square,
+    capture: bool,
+    promotion: Option<u8>,
+}
+
+/// Piece type of a SAN piece letter, pawns have no letter
+fn piece_type(letter: char) -> Option<u8> {
+    match letter {
+        'N' | 'B' | 'R' | 'Q' | 'K' => Some(Piece::try_from(letter).ok()? as u8 >> 1),
+        _ => None,
+    }
+}
+
+impl SanMove {
+    /// Split a non-castling SAN move into its fields
+    fn parse(san: &str) -> Result<Self, &'static str> {
+        let mut chars: Vec<char> = san.chars().collect();
+
+        let piece = match chars.first().copied().and_then(piece_type) {
+            Some(piece) => {
+                chars.remove(0);
+                piece
+            }
+            None => Piece::WP as u8,
+        };
+
+        let promotion = match chars.last().copied().and_then(piece_type) {
+            Some(promotion) if piece == Piece::WP as u8 => {
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+                Some(promotion)
+            }
+            _ => None,
+        };
+
+        if chars.len() < 2 {
+            return Err("Invalid SAN move!");
+        }
+        let tgt: String = chars.split_off(chars.len() - 2).into_iter().collect();
+        let tgt: Square = tgt.parse().map_err(|_| "Invalid SAN move!")?;
+
+        let capture = chars.last() == Some(&'x');
+        if capture {
+            chars.pop();
+        }
+
+        let (src_file, src_rank) = match chars[..] {
+            [] => (None, None),
+            [file @ 'a'..='h'] => (Some(file), None),
+            [rank @ '1'..='8'] => (None, Some(rank)),
+            [file @ 'a'..='h', rank @ '1'..='8'] => (Some(file), Some(rank)),
+            _ => return Err("Invalid SAN move!"),
+        };
+
+        Ok(Self {
+            piece,
+            src_file,
+            src_rank,
+            tgt,
+            capture,
+            promotion,
+        })
+    }
+
+    /// Checks whether the legal move on the board matches the SAN fields
+    fn matches(&self, board: &Board, m: Move) -> bool {
+        let (src, move_type) = (m.get_src(), m.get_type());
+        let promotion = match move_type.is_promotion() {
+            true => Some(move_type.get_promotion(Color::White) as u8 >> 1),
+            false => None,
+        };
+
+        move_type != MoveType::Castle
+            && board.piece_at(src) as u8 >> 1 == self.piece
+            && m.get_tgt() == self.tgt
+            && promotion == self.promotion
+            && (!self.capture || move_type.is_capture())
+            && self
+                .src_file
+                .is_none_or(|file| src.file().to_char() == file)
+            && self
+                .src_rank
+                .is_none_or(|rank| src.rank().to_char() == rank)
+    }
+}
+
+impl Board {
+    /// Find the legal move given in SAN. Fails on malformed, illegal and ambiguous moves.
+    pub fn parse_san(&self, san: &str) -> Result<Move, &'static str> {
+        let san = san
+            .trim()
+            .trim_end_matches("e.p.")
+            .trim_end()
+            .trim_end_matches(['+', '#', '!', '?']);
+
+        let move_list = self.gen_moves::<QUIETS>();
+        let legal = &move_list.moves[..move_list.len()];
+
+        let mut candidates: Vec<Move> = match san {
+            "O-O" | "0-0" | "O-O-O" | "0-0-0" => {
+                let king_file = if san.len() == 3 { File::G } else { File::C };
+                legal
+                    .iter()
+                    .filter(|&&m| {
+                        m.get_type() == MoveType::Castle
+                            && self.castling_squares(m).0.file() == king_file
+                    })
+                    .copied()
+                    .collect()
+            }
+            _ => {
+                let fields = SanMove::parse(san)?;
+                legal
+                    .iter()
+                    .filter(|&&m| fields.matches(self, m))
+                    .copied()
+                    .collect()
+            }
+        };
+
+        match candidates.len() {
+            0 => Err("Illegal SAN move!"),
+            1 => Ok(candidates.pop().unwrap()),
+            _ => Err("Ambiguous SAN move!"),
+        }
+    }
+
+    /// Format the legal move in SAN, with a check or mate suffix
+    pub fn san(&self, m: Move) -> String {
+        let (src, tgt, move_type) = (m.get_src(), m.get_tgt(), m.get_type());
+        let piece = self.piece_at(src);
+        let mut san = String::new();
+
+        if move_type == MoveType::Castle {
+            san += match self.castling_squares(m).0.file() {
+                File::G => "O-O",
+                _ => "O-O-O",
+            };
+        } else if piece as u8 >> 1 == Piece::WP as u8 {
+            if move_type.is_capture() {
+                san.push(src.file().to_char());
+                san.push('x');
+            }
+            san += &tgt.to_string();
+            if move_type.is_promotion() {
+                san.push('=');
+                san.push(move_type.get_promotion(Color::White).to_char());
+            }
+        } else {
+            san.push(piece.to_char().to_ascii_uppercase());
+
+            // Other pieces of the same type which can reach the target
+            let move_list = self.gen_moves::<QUIETS>();
+            let others: Vec<Square> = move_list.moves[..move_list.len()]
+                .iter()
+                .filter(|&&other| {
+                    other.get_type() != MoveType::Castle
+                        && other.get_tgt() == tgt
+                        && other.get_src() != src
+                        && self.piece_at(other.get_src()) == piece
+                })
+                .map(|other| other.get_src())
+                .collect();
+
+            if !others.is_empty() {
+                if others.iter().all(|sq| sq.file() != src.file()) {
+                    san.push(src.file().to_char());
+                } else if others.iter().all(|sq| sq.rank() != src.rank()) {
+                    san.push(src.rank().to_char());
+                } else {
+                    san += &src.to_string();
+                }
+            }
+
+            if move_type.is_capture() {
+                san.push('x');
+            }
+            san += &tgt.to_string();
+        }
+
+        let new = self.make_move(m);
+        if new.checkers != EMPTY_BB {
+            san.push(match new.gen_moves::<QUIETS>().is_empty() {
+                true => '#',
+                false => '+',
+            });
+        }
+
+        san
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san(fen: &str, san: &str) -> Result<String, &'static str> {
+        let board: Board = fen.parse().unwrap();
+        board.parse_san(san).map(|m| m.to_string())
+    }
+
+    #[test]
+    fn test_parse_san() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(start, "Nf3"), Ok("g1f3".to_string()));
+        assert_eq!(san(start, "e4"), Ok("e2e4".to_string()));
+        assert_eq!(san(start, "e4!?"), Ok("e2e4".to_string()));
+
+        let ep = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        assert_eq!(san(ep, "exf6 e.p."), Ok("e5f6".to_string()));
+        assert_eq!(san(ep, "exf6"), Ok("e5f6".to_string()));
+        let ep = "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+        assert_eq!(san(ep, "exd6"), Ok("e5d6".to_string()));
+
+        let castles = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(castles, "O-O"), Ok("e1g1".to_string()));
+        assert_eq!(san(castles, "0-0-0"), Ok("e1c1".to_string()));
+        assert_eq!(san(castles, "Kf1"), Ok("e1f1".to_string()));
+
+        let promotion = "3r4/4P3/8/8/8/k7/8/4K3 w - - 0 1";
+        assert_eq!(san(promotion, "e8=Q+"), Ok("e7e8q".to_string()));
+        assert_eq!(san(promotion, "e8N"), Ok("e7e8n".to_string()));
+        assert_eq!(san(promotion, "exd8=R"), Ok("e7d8r".to_string()));
+        assert_eq!(san(promotion, "e8"), Err("Illegal SAN move!"));
+    }
+
+    #[test]
+    fn test_san_disambiguation() {
+        // Knights on b1 and f1 both reach d2, rooks on a1 and a7 both reach a4
+        let knights = "4k3/R7/8/8/8/8/8/RN2KN2 w - - 0 1";
+        assert_eq!(san(knights, "Nd2"), Err("Ambiguous SAN move!"));
+        assert_eq!(san(knights, "Nbd2"), Ok("b1d2".to_string()));
+        assert_eq!(san(knights, "Nfd2"), Ok("f1d2".to_string()));
+        assert_eq!(san(knights, "Ra4"), Err("Ambiguous SAN move!"));
+        assert_eq!(san(knights, "R1a4"), Ok("a1a4".to_string()));
+        assert_eq!(san(knights, "Ra7a4"), Ok("a7a4".to_string()));
+        let rooks = "4R3/8/7k/8/8/8/8/4R1K1 w - - 0 1";
+        assert_eq!(san(rooks, "Re2"), Err("Ambiguous SAN move!"));
+        assert_eq!(san(rooks, "R1e2"), Ok("e1e2".to_string()));
+        assert_eq!(san(rooks, "R8e2"), Ok("e8e2".to_string()));
+
+        // The knight on e2 is pinned, so Nc3 needs no disambiguation
+        let pinned = "4r1k1/8/8/8/8/8/4N3/1N2K3 w - - 0 1";
+        assert_eq!(san(pinned, "Nc3"), Ok("b1c3".to_string()));
+        assert_eq!(san(pinned, "Nec3"), Err("Illegal SAN move!"));
+
+        assert_eq!(san(knights, "Nd3"), Err("Illegal SAN move!"));
+        assert_eq!(san(knights, "Nxd2"), Err("Illegal SAN move!"));
+        assert_eq!(san(knights, "Zd2"), Err("Invalid SAN move!"));
+        assert_eq!(san(knights, "N"), Err("Invalid SAN move!"));
+        assert_eq!(san(knights, "Nbb1d2"), Err("Invalid SAN move!"));
+    }
+
+    #[test]
+    fn test_san_format() {
+        let format = |fen: &str, uci: &str| {
+            let board: Board = fen.parse().unwrap();
+            board.san(board.find_move(uci).unwrap())
+        };
+
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(format(start, "g1f3"), "Nf3");
+        assert_eq!(format(start, "e2e4"), "e4");
+
+        let ep = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        assert_eq!(format(ep, "e5f6"), "exf6");
+
+        let castles = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(format(castles, "e1g1"), "O-O");
+        assert_eq!(format(castles, "e1c1"), "O-O-O");
+        assert_eq!(format(castles, "a1a8"), "Rxa8+");
+
+        let promotion = "3r4/4P3/8/8/8/k7/8/4K3 w - - 0 1";
+        assert_eq!(format(promotion, "e7e8q"), "e8=Q");
+        assert_eq!(format(promotion, "e7d8n"), "exd8=N");
+
+        let fools_mate = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2";
+        assert_eq!(format(fools_mate, "d8h4"), "Qh4#");
+
+        // File, rank, or both only when needed
+        let knights = "4k3/R7/8/8/8/8/8/RN2KN2 w - - 0 1";
+        assert_eq!(format(knights, "b1d2"), "Nbd2");
+        assert_eq!(format(knights, "a1a4"), "R1a4");
+        assert_eq!(format(knights, "a7b7"), "Rb7");
+        let queens = "k7/8/3Q4/8/3Q1Q2/8/8/4K3 w - - 0 1";
+        assert_eq!(format(queens, "d4e5"), "Qd4e5");
+        assert_eq!(format(queens, "d6e5"), "Q6e5");
+        let pinned = "4r1k1/8/8/8/8/8/4N3/1N2K3 w - - 0 1";
+        assert_eq!(format(pinned, "b1c3"), "Nc3");
+
+        // Every legal move parses back from its SAN
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board: Board = kiwipete.parse().unwrap();
+        let move_list = board.gen_moves::<QUIETS>();
+        for &m in &move_list.moves[..move_list.len()] {
+            assert_eq!(board.parse_san(&board.san(m)), Ok(m), "{m}");
+        }
+    }
+}
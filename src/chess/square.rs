@@ -111,11 +111,40 @@ impl Square {
         (tf - sf, sr - tr)
     }
 
+    /// Color of the square (A8 and H1 are light, hence White)
+    pub const fn color(self) -> Color {
+        match (self.file() as u8 + self.rank() as u8) & 1 {
+            0 => Color::White,
+            _ => Color::Black,
+        }
+    }
+
+    /// Rank of the square counted from the first rank of the given side, from 0 to 7
+    pub const fn relative_rank(self, side: Color) -> usize {
+        match side {
+            Color::White => 7 - self.rank() as usize,
+            Color::Black => self.rank() as usize,
+        }
+    }
+
+    /// Chebyshev (king move) distance between current and given square
+    pub const fn distance(self, tgt: Square) -> usize {
+        let (df, dr) = self.dist(tgt);
+        let (df, dr) = (df.unsigned_abs(), dr.unsigned_abs());
+
+        (if df > dr { df } else { dr }) as usize
+    }
+
     /// Get new square by flipping the rank of the original.
     pub const fn flipv(self) -> Square {
         transmute_enum!(self as u8 ^ 56, 63)
     }
 
+    /// Get new square by flipping the file of the original.
+    pub const fn fliph(self) -> Square {
+        transmute_enum!(self as u8 ^ 7, 63)
+    }
+
     /// Get new square moving forward from original based on side.
     /// To go backwards, simply use the opposite side.
     pub const fn forward(self, side: Color) -> Square {
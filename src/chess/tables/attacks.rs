@@ -1,10 +1,93 @@
-/// Slow attack generation to initialize tables
-use super::constants::BETWEEN;
-
+/// Attack generation to initialize tables, evaluated at compile time
 use crate::chess::{bitboard::*, piece::*, square::*};
 
+/// Knight and king moves, as (file, row) offsets
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+/// Bishop and rook ray directions, as (file, row) steps
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+/// Bit of the square at the given file and row (row 0 being the eighth rank), if on the board
+const fn square_bit(file: i8, row: i8) -> Option<u64> {
+    if file >= 0 && file < 8 && row >= 0 && row < 8 {
+        Some(1u64 << (row * 8 + file))
+    } else {
+        None
+    }
+}
+
+/// Squares reached from src with the given offsets
+const fn mask_offsets(src: Square, offsets: &[(i8, i8); 8]) -> BitBoard {
+    let (file, row) = ((src as u8 % 8) as i8, (src as u8 / 8) as i8);
+    let mut attacks = 0;
+    let mut i = 0;
+
+    while i < offsets.len() {
+        if let Some(bit) = square_bit(file + offsets[i].0, row + offsets[i].1) {
+            attacks |= bit;
+        }
+        i += 1;
+    }
+
+    BitBoard(attacks)
+}
+
+/// Squares along the given rays from src, up to and including the first blocker.
+/// With edges set, the last square of every ray is left out.
+const fn mask_rays(
+    src: Square,
+    blockers: BitBoard,
+    directions: &[(i8, i8); 4],
+    edges: bool,
+) -> BitBoard {
+    let mut attacks = 0;
+    let mut i = 0;
+
+    while i < directions.len() {
+        let (df, dr) = directions[i];
+        let (mut file, mut row) = ((src as u8 % 8) as i8 + df, (src as u8 / 8) as i8 + dr);
+
+        while let Some(bit) = square_bit(file, row) {
+            if edges && square_bit(file + df, row + dr).is_none() {
+                break;
+            }
+
+            attacks |= bit;
+            if blockers.0 & bit != 0 {
+                break;
+            }
+
+            file += df;
+            row += dr;
+        }
+        i += 1;
+    }
+
+    BitBoard(attacks)
+}
+
 /// Generate bitboard for pawn attacks from square
-pub fn mask_pawn_attacks(src: Square, color: Color) -> BitBoard {
+pub const fn mask_pawn_attacks(src: Square, color: Color) -> BitBoard {
     let mut attacks = EMPTY_BB;
     let file = src.file();
 
@@ -13,90 +96,95 @@ pub fn mask_pawn_attacks(src: Square, color: Color) -> BitBoard {
         Color::Black => src.down(),
     };
 
-    if file != File::A {
+    if !matches!(file, File::A) {
         attacks = attacks.set_bit(tgt.left())
     }
-    if file != File::H {
+    if !matches!(file, File::H) {
         attacks = attacks.set_bit(tgt.right());
     }
 
     attacks
 }
 
-/// Sets all bits that sastisfy the predicate in the bitboard.
-fn mask_predicate<P: Fn(&Square) -> bool>(predicate: P) -> BitBoard {
-    ALL_SQUARES
-        .into_iter()
-        .filter(predicate)
-        .fold(EMPTY_BB, |att, sq| att.set_bit(sq))
+/// Generate bitboard for the squares from which enemy pawns could attack the square, now or
+/// after advancing: the adjacent files in front of it from the point of view of the given side
+pub const fn mask_outpost(src: Square, color: Color) -> BitBoard {
+    let square = src.to_board();
+
+    BitBoard(square.east().0 | square.west().0).front_span(color)
 }
 
-/// Generate bitboard for knight attacks from square
-pub fn mask_knight_attacks(src: Square) -> BitBoard {
-    mask_predicate(|&tgt| {
-        let dist = src.dist(tgt);
-        let (dist_file, dist_rank) = (dist.0.abs(), dist.1.abs());
+/// Generate bitboard for the squares in which enemy pawns stop a pawn of the given side from being
+/// passed: its file and the adjacent files in front of it
+pub const fn mask_passed(src: Square, color: Color) -> BitBoard {
+    let square = src.to_board();
 
-        (dist_file == 1 && dist_rank == 2) || (dist_file == 2 && dist_rank == 1)
-    })
+    BitBoard(square.0 | square.east().0 | square.west().0).front_span(color)
 }
 
-/// Generate bitboard for king attacks from square
-pub fn mask_king_attacks(src: Square) -> BitBoard {
-    mask_predicate(|&tgt| {
-        let dist = src.dist(tgt);
+/// Generate bitboard for the files adjacent to the square, where own pawns keep a pawn from being
+/// isolated
+pub const fn mask_isolated(src: Square) -> BitBoard {
+    src.to_board().adjacent_files()
+}
 
-        dist.0.abs() <= 1 && dist.1.abs() <= 1 && tgt != src
-    })
+/// Generate bitboard for the king ring: the king square and the squares around it
+pub const fn mask_king_ring(src: Square) -> BitBoard {
+    mask_king_attacks(src).set_bit(src)
 }
 
-/// Slow bishop attack gen (with blockers)
-pub fn mask_bishop_attacks(src: Square, blockers: BitBoard) -> BitBoard {
-    mask_predicate(|&tgt| {
-        let dist = src.dist(tgt);
+/// Generate bitboard for the king zone: the king ring, extended by one rank towards the enemy
+/// from the point of view of the given side
+pub const fn mask_king_zone(src: Square, color: Color) -> BitBoard {
+    let ring = mask_king_ring(src);
 
-        BETWEEN[src as usize][tgt as usize] & blockers == EMPTY_BB
-            && dist.0.abs() == dist.1.abs()
-            && src != tgt
-    })
+    BitBoard(ring.0 | ring.forward(color).0)
+}
+
+/// Generate bitboard for knight attacks from square
+pub const fn mask_knight_attacks(src: Square) -> BitBoard {
+    mask_offsets(src, &KNIGHT_OFFSETS)
+}
+
+/// Generate bitboard for king attacks from square
+pub const fn mask_king_attacks(src: Square) -> BitBoard {
+    mask_offsets(src, &KING_OFFSETS)
+}
+
+/// Slow bishop attack gen (with blockers)
+pub const fn mask_bishop_attacks(src: Square, blockers: BitBoard) -> BitBoard {
+    mask_rays(src, blockers, &BISHOP_DIRECTIONS, false)
 }
 
 /// Slow rook attack gen (with blockers)
-pub fn mask_rook_attacks(src: Square, blockers: BitBoard) -> BitBoard {
-    mask_predicate(|&tgt| {
-        let dist = src.dist(tgt);
+pub const fn mask_rook_attacks(src: Square, blockers: BitBoard) -> BitBoard {
+    mask_rays(src, blockers, &ROOK_DIRECTIONS, false)
+}
 
-        BETWEEN[src as usize][tgt as usize] & blockers == EMPTY_BB
-            && (dist.0 == 0 || dist.1 == 0)
-            && src != tgt
-    })
+/// Generate bitboard of the squares between src and tgt, excluding both, if they share a line
+pub const fn mask_between(src: Square, tgt: Square) -> BitBoard {
+    let blockers = tgt.to_board();
+    let bishop = mask_bishop_attacks(src, blockers);
+    let rook = mask_rook_attacks(src, blockers);
+
+    // Rays from both ends towards each other overlap exactly on the squares in between
+    if bishop.get_bit(tgt) {
+        BitBoard(bishop.0 & mask_bishop_attacks(tgt, src.to_board()).0)
+    } else if rook.get_bit(tgt) {
+        BitBoard(rook.0 & mask_rook_attacks(tgt, src.to_board()).0)
+    } else {
+        EMPTY_BB
+    }
 }
 
 /// Mask relevant bishop occupancy bits (attacked bits excluding the final square in each ray)
-pub fn bishop_occupancy(src: Square) -> BitBoard {
-    mask_predicate(|&tgt| {
-        let (tgt_file, tgt_rank) = tgt.coords();
-        let dist = src.dist(tgt);
-
-        tgt_file != File::A
-            && tgt_file != File::H
-            && tgt_rank != Rank::First
-            && tgt_rank != Rank::Eight
-            && dist.0.abs() == dist.1.abs()
-            && src != tgt
-    })
+pub const fn bishop_occupancy(src: Square) -> BitBoard {
+    mask_rays(src, EMPTY_BB, &BISHOP_DIRECTIONS, true)
 }
 
 /// Mask relevant rook occupancy bits (attacked bits excluding the final square in each ray)
-pub fn rook_occupancy(src: Square) -> BitBoard {
-    mask_predicate(|&tgt| {
-        let (tgt_file, tgt_rank) = tgt.coords();
-        let dist = src.dist(tgt);
-
-        ((dist.1 == 0 && tgt_file != File::A && tgt_file != File::H)
-            || (dist.0 == 0 && tgt_rank != Rank::First && tgt_rank != Rank::Eight))
-            && src != tgt
-    })
+pub const fn rook_occupancy(src: Square) -> BitBoard {
+    mask_rays(src, EMPTY_BB, &ROOK_DIRECTIONS, true)
 }
 
 /// Mask index only onto the set bits of the board.
@@ -106,11 +194,19 @@ pub fn rook_occupancy(src: Square) -> BitBoard {
 ///
 ///     Anything between has some bits set, some unset, and covers all possible combinations
 ///     of 0s and 1s on the set squares of mask
-pub fn set_occupancy(mask: BitBoard, index: usize) -> BitBoard {
-    mask.into_iter()
-        .enumerate()
-        .filter(|(count, _)| index & (1 << count) != 0)
-        .fold(EMPTY_BB, |mask, (_, square)| mask.set_bit(square))
+pub const fn set_occupancy(mask: BitBoard, index: usize) -> BitBoard {
+    let (mut mask, mut occupancy, mut count) = (mask.0, 0, 0);
+
+    while mask != 0 {
+        if index & (1 << count) != 0 {
+            occupancy |= mask & mask.wrapping_neg();
+        }
+
+        mask &= mask - 1;
+        count += 1;
+    }
+
+    BitBoard(occupancy)
 }
 
 #[cfg(test)]
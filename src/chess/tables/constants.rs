@@ -1,7 +1,5 @@
 /// Module saving various large constants to clean up the code
-
 use crate::chess::{
-    bitboard::*,
     square::*,
     piece::*,
     castle::*,
@@ -26,81 +24,106 @@ pub const EP_KEYS: [u64; SQUARE_COUNT] = [15924569520556073402, 9944164381255554
 pub const CASTLE_KEYS: [u64; CASTLE_COUNT] = [8406779754442449593, 871698248595409707, 8792824521864740815, 4589510442007724706, 4083044191740519165, 6740404646480981973, 15570744254755882244, 2329145575591560654, 510348464190612988, 9157648730753369883, 14254612341539302221, 8855270439616209896, 13893838058836631177, 12622557577655849614, 12093051889040103024, 1772028295636336235];
 pub const SIDE_KEY: u64 = 4747071328949516916;
 
-/// Masks indicating for each square where pieces threating any square around it could be.
-/// Used for legal movegen.
-pub const PAWN_THREATS: [BB64; 2] = [
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1536), BitBoard(3328), BitBoard(6912), BitBoard(13824), BitBoard(27648), BitBoard(55296), BitBoard(45056), BitBoard(24576), BitBoard(394496), BitBoard(854528), BitBoard(1774848), BitBoard(3549696), BitBoard(7099392), BitBoard(14198784), BitBoard(11554816), BitBoard(6332416), BitBoard(100992768), BitBoard(218763008), BitBoard(454369024), BitBoard(908738048), BitBoard(1817476096), BitBoard(3634952192), BitBoard(2958094336), BitBoard(1621155840), BitBoard(25854148608), BitBoard(56003330048), BitBoard(116318470144), BitBoard(232636940288), BitBoard(465273880576), BitBoard(930547761152), BitBoard(757272150016), BitBoard(415015895040), BitBoard(6618662043648), BitBoard(14336852492288), BitBoard(29777528356864), BitBoard(59555056713728), BitBoard(119110113427456), BitBoard(238220226854912), BitBoard(193861670404096), BitBoard(106244069130240), BitBoard(1694377483173888), BitBoard(3670234238025728), BitBoard(7623047259357184), BitBoard(15246094518714368), BitBoard(30492189037428736), BitBoard(60984378074857472), BitBoard(49628587623448576), BitBoard(27198481697341440), BitBoard(1415071464947712), BitBoard(2831242441523200), BitBoard(5945059371384832), BitBoard(11890118742769664), BitBoard(23780237485539328), BitBoard(47560474971078656), BitBoard(22781880927518720), BitBoard(45282286878326784)],
-    [BitBoard(460032), BitBoard(985600), BitBoard(2036992), BitBoard(4073984), BitBoard(8147968), BitBoard(16295936), BitBoard(15749120), BitBoard(14721024), BitBoard(117769728), BitBoard(252316928), BitBoard(521476864), BitBoard(1042953728), BitBoard(2085907456), BitBoard(4171814912), BitBoard(4031819776), BitBoard(3768606720), BitBoard(30149050368), BitBoard(64593133568), BitBoard(133498077184), BitBoard(266996154368), BitBoard(533992308736), BitBoard(1067984617472), BitBoard(1032145862656), BitBoard(964763320320), BitBoard(7718156894208), BitBoard(16535842193408), BitBoard(34175507759104), BitBoard(68351015518208), BitBoard(136702031036416), BitBoard(273404062072832), BitBoard(264229340839936), BitBoard(246979410001920), BitBoard(1975848164917248), BitBoard(4233175601512448), BitBoard(8748929986330624), BitBoard(17497859972661248), BitBoard(34995719945322496), BitBoard(69991439890644992), BitBoard(67642711255023616), BitBoard(63226728960491520), BitBoard(1413971953319936), BitBoard(2829043418267648), BitBoard(5940661324873728), BitBoard(11881322649747456), BitBoard(23762645299494912), BitBoard(47525290598989824), BitBoard(22711512183341056), BitBoard(45141549389971456), BitBoard(1688849860263936), BitBoard(3659174697238528), BitBoard(7599824371187712), BitBoard(15199648742375424), BitBoard(30399297484750848), BitBoard(60798594969501696), BitBoard(49539595901075456), BitBoard(27021597764222976), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
+/// Polyglot Random64 keys, laid out as in the Polyglot book format: 768 piece keys indexed by
+/// 64 * kind + 8 * row + file (kinds alternate black and white from pawns to kings, rows count from
+/// the first rank), then 4 castling keys, 8 en passant file keys and the white to move key
+pub const POLYGLOT_KEYS: [u64; 781] = [
+    0x9D39247E33776D41, 0x2AF7398005AAA5C7, 0x44DB015024623547, 0x9C15F73E62A76AE2, 0x75834465489C0C89, 0x3290AC3A203001BF, 0x0FBBAD1F61042279, 0xE83A908FF2FB60CA,
+    0x0D7E765D58755C10, 0x1A083822CEAFE02D, 0x9605D5F0E25EC3B0, 0xD021FF5CD13A2ED5, 0x40BDF15D4A672E32, 0x011355146FD56395, 0x5DB4832046F3D9E5, 0x239F8B2D7FF719CC,
+    0x05D1A1AE85B49AA1, 0x679F848F6E8FC971, 0x7449BBFF801FED0B, 0x7D11CDB1C3B7ADF0, 0x82C7709E781EB7CC, 0xF3218F1C9510786C, 0x331478F3AF51BBE6, 0x4BB38DE5E7219443,
+    0xAA649C6EBCFD50FC, 0x8DBD98A352AFD40B, 0x87D2074B81D79217, 0x19F3C751D3E92AE1, 0xB4AB30F062B19ABF, 0x7B0500AC42047AC4, 0xC9452CA81A09D85D, 0x24AA6C514DA27500,
+    0x4C9F34427501B447, 0x14A68FD73C910841, 0xA71B9B83461CBD93, 0x03488B95B0F1850F, 0x637B2B34FF93C040, 0x09D1BC9A3DD90A94, 0x3575668334A1DD3B, 0x735E2B97A4C45A23,
+    0x18727070F1BD400B, 0x1FCBACD259BF02E7, 0xD310A7C2CE9B6555, 0xBF983FE0FE5D8244, 0x9F74D14F7454A824, 0x51EBDC4AB9BA3035, 0x5C82C505DB9AB0FA, 0xFCF7FE8A3430B241,
+    0x3253A729B9BA3DDE, 0x8C74C368081B3075, 0xB9BC6C87167C33E7, 0x7EF48F2B83024E20, 0x11D505D4C351BD7F, 0x6568FCA92C76A243, 0x4DE0B0F40F32A7B8, 0x96D693460CC37E5D,
+    0x42E240CB63689F2F, 0x6D2BDCDAE2919661, 0x42880B0236E4D951, 0x5F0F4A5898171BB6, 0x39F890F579F92F88, 0x93C5B5F47356388B, 0x63DC359D8D231B78, 0xEC16CA8AEA98AD76,
+    0x5355F900C2A82DC7, 0x07FB9F855A997142, 0x5093417AA8A7ED5E, 0x7BCBC38DA25A7F3C, 0x19FC8A768CF4B6D4, 0x637A7780DECFC0D9, 0x8249A47AEE0E41F7, 0x79AD695501E7D1E8,
+    0x14ACBAF4777D5776, 0xF145B6BECCDEA195, 0xDABF2AC8201752FC, 0x24C3C94DF9C8D3F6, 0xBB6E2924F03912EA, 0x0CE26C0B95C980D9, 0xA49CD132BFBF7CC4, 0xE99D662AF4243939,
+    0x27E6AD7891165C3F, 0x8535F040B9744FF1, 0x54B3F4FA5F40D873, 0x72B12C32127FED2B, 0xEE954D3C7B411F47, 0x9A85AC909A24EAA1, 0x70AC4CD9F04F21F5, 0xF9B89D3E99A075C2,
+    0x87B3E2B2B5C907B1, 0xA366E5B8C54F48B8, 0xAE4A9346CC3F7CF2, 0x1920C04D47267BBD, 0x87BF02C6B49E2AE9, 0x092237AC237F3859, 0xFF07F64EF8ED14D0, 0x8DE8DCA9F03CC54E,
+    0x9C1633264DB49C89, 0xB3F22C3D0B0B38ED, 0x390E5FB44D01144B, 0x5BFEA5B4712768E9, 0x1E1032911FA78984, 0x9A74ACB964E78CB3, 0x4F80F7A035DAFB04, 0x6304D09A0B3738C4,
+    0x2171E64683023A08, 0x5B9B63EB9CEFF80C, 0x506AACF489889342, 0x1881AFC9A3A701D6, 0x6503080440750644, 0xDFD395339CDBF4A7, 0xEF927DBCF00C20F2, 0x7B32F7D1E03680EC,
+    0xB9FD7620E7316243, 0x05A7E8A57DB91B77, 0xB5889C6E15630A75, 0x4A750A09CE9573F7, 0xCF464CEC899A2F8A, 0xF538639CE705B824, 0x3C79A0FF5580EF7F, 0xEDE6C87F8477609D,
+    0x799E81F05BC93F31, 0x86536B8CF3428A8C, 0x97D7374C60087B73, 0xA246637CFF328532, 0x043FCAE60CC0EBA0, 0x920E449535DD359E, 0x70EB093B15B290CC, 0x73A1921916591CBD,
+    0x56436C9FE1A1AA8D, 0xEFAC4B70633B8F81, 0xBB215798D45DF7AF, 0x45F20042F24F1768, 0x930F80F4E8EB7462, 0xFF6712FFCFD75EA1, 0xAE623FD67468AA70, 0xDD2C5BC84BC8D8FC,
+    0x7EED120D54CF2DD9, 0x22FE545401165F1C, 0xC91800E98FB99929, 0x808BD68E6AC10365, 0xDEC468145B7605F6, 0x1BEDE3A3AEF53302, 0x43539603D6C55602, 0xAA969B5C691CCB7A,
+    0xA87832D392EFEE56, 0x65942C7B3C7E11AE, 0xDED2D633CAD004F6, 0x21F08570F420E565, 0xB415938D7DA94E3C, 0x91B859E59ECB6350, 0x10CFF333E0ED804A, 0x28AED140BE0BB7DD,
+    0xC5CC1D89724FA456, 0x5648F680F11A2741, 0x2D255069F0B7DAB3, 0x9BC5A38EF729ABD4, 0xEF2F054308F6A2BC, 0xAF2042F5CC5C2858, 0x480412BAB7F5BE2A, 0xAEF3AF4A563DFE43,
+    0x19AFE59AE451497F, 0x52593803DFF1E840, 0xF4F076E65F2CE6F0, 0x11379625747D5AF3, 0xBCE5D2248682C115, 0x9DA4243DE836994F, 0x066F70B33FE09017, 0x4DC4DE189B671A1C,
+    0x51039AB7712457C3, 0xC07A3F80C31FB4B4, 0xB46EE9C5E64A6E7C, 0xB3819A42ABE61C87, 0x21A007933A522A20, 0x2DF16F761598AA4F, 0x763C4A1371B368FD, 0xF793C46702E086A0,
+    0xD7288E012AEB8D31, 0xDE336A2A4BC1C44B, 0x0BF692B38D079F23, 0x2C604A7A177326B3, 0x4850E73E03EB6064, 0xCFC447F1E53C8E1B, 0xB05CA3F564268D99, 0x9AE182C8BC9474E8,
+    0xA4FC4BD4FC5558CA, 0xE755178D58FC4E76, 0x69B97DB1A4C03DFE, 0xF9B5B7C4ACC67C96, 0xFC6A82D64B8655FB, 0x9C684CB6C4D24417, 0x8EC97D2917456ED0, 0x6703DF9D2924E97E,
+    0xC547F57E42A7444E, 0x78E37644E7CAD29E, 0xFE9A44E9362F05FA, 0x08BD35CC38336615, 0x9315E5EB3A129ACE, 0x94061B871E04DF75, 0xDF1D9F9D784BA010, 0x3BBA57B68871B59D,
+    0xD2B7ADEEDED1F73F, 0xF7A255D83BC373F8, 0xD7F4F2448C0CEB81, 0xD95BE88CD210FFA7, 0x336F52F8FF4728E7, 0xA74049DAC312AC71, 0xA2F61BB6E437FDB5, 0x4F2A5CB07F6A35B3,
+    0x87D380BDA5BF7859, 0x16B9F7E06C453A21, 0x7BA2484C8A0FD54E, 0xF3A678CAD9A2E38C, 0x39B0BF7DDE437BA2, 0xFCAF55C1BF8A4424, 0x18FCF680573FA594, 0x4C0563B89F495AC3,
+    0x40E087931A00930D, 0x8CFFA9412EB642C1, 0x68CA39053261169F, 0x7A1EE967D27579E2, 0x9D1D60E5076F5B6F, 0x3810E399B6F65BA2, 0x32095B6D4AB5F9B1, 0x35CAB62109DD038A,
+    0xA90B24499FCFAFB1, 0x77A225A07CC2C6BD, 0x513E5E634C70E331, 0x4361C0CA3F692F12, 0xD941ACA44B20A45B, 0x528F7C8602C5807B, 0x52AB92BEB9613989, 0x9D1DFA2EFC557F73,
+    0x722FF175F572C348, 0x1D1260A51107FE97, 0x7A249A57EC0C9BA2, 0x04208FE9E8F7F2D6, 0x5A110C6058B920A0, 0x0CD9A497658A5698, 0x56FD23C8F9715A4C, 0x284C847B9D887AAE,
+    0x04FEABFBBDB619CB, 0x742E1E651C60BA83, 0x9A9632E65904AD3C, 0x881B82A13B51B9E2, 0x506E6744CD974924, 0xB0183DB56FFC6A79, 0x0ED9B915C66ED37E, 0x5E11E86D5873D484,
+    0xF678647E3519AC6E, 0x1B85D488D0F20CC5, 0xDAB9FE6525D89021, 0x0D151D86ADB73615, 0xA865A54EDCC0F019, 0x93C42566AEF98FFB, 0x99E7AFEABE000731, 0x48CBFF086DDF285A,
+    0x7F9B6AF1EBF78BAF, 0x58627E1A149BBA21, 0x2CD16E2ABD791E33, 0xD363EFF5F0977996, 0x0CE2A38C344A6EED, 0x1A804AADB9CFA741, 0x907F30421D78C5DE, 0x501F65EDB3034D07,
+    0x37624AE5A48FA6E9, 0x957BAF61700CFF4E, 0x3A6C27934E31188A, 0xD49503536ABCA345, 0x088E049589C432E0, 0xF943AEE7FEBF21B8, 0x6C3B8E3E336139D3, 0x364F6FFA464EE52E,
+    0xD60F6DCEDC314222, 0x56963B0DCA418FC0, 0x16F50EDF91E513AF, 0xEF1955914B609F93, 0x565601C0364E3228, 0xECB53939887E8175, 0xBAC7A9A18531294B, 0xB344C470397BBA52,
+    0x65D34954DAF3CEBD, 0xB4B81B3FA97511E2, 0xB422061193D6F6A7, 0x071582401C38434D, 0x7A13F18BBEDC4FF5, 0xBC4097B116C524D2, 0x59B97885E2F2EA28, 0x99170A5DC3115544,
+    0x6F423357E7C6A9F9, 0x325928EE6E6F8794, 0xD0E4366228B03343, 0x565C31F7DE89EA27, 0x30F5611484119414, 0xD873DB391292ED4F, 0x7BD94E1D8E17DEBC, 0xC7D9F16864A76E94,
+    0x947AE053EE56E63C, 0xC8C93882F9475F5F, 0x3A9BF55BA91F81CA, 0xD9A11FBB3D9808E4, 0x0FD22063EDC29FCA, 0xB3F256D8ACA0B0B9, 0xB03031A8B4516E84, 0x35DD37D5871448AF,
+    0xE9F6082B05542E4E, 0xEBFAFA33D7254B59, 0x9255ABB50D532280, 0xB9AB4CE57F2D34F3, 0x693501D628297551, 0xC62C58F97DD949BF, 0xCD454F8F19C5126A, 0xBBE83F4ECC2BDECB,
+    0xDC842B7E2819E230, 0xBA89142E007503B8, 0xA3BC941D0A5061CB, 0xE9F6760E32CD8021, 0x09C7E552BC76492F, 0x852F54934DA55CC9, 0x8107FCCF064FCF56, 0x098954D51FFF6580,
+    0x23B70EDB1955C4BF, 0xC330DE426430F69D, 0x4715ED43E8A45C0A, 0xA8D7E4DAB780A08D, 0x0572B974F03CE0BB, 0xB57D2E985E1419C7, 0xE8D9ECBE2CF3D73F, 0x2FE4B17170E59750,
+    0x11317BA87905E790, 0x7FBF21EC8A1F45EC, 0x1725CABFCB045B00, 0x964E915CD5E2B207, 0x3E2B8BCBF016D66D, 0xBE7444E39328A0AC, 0xF85B2B4FBCDE44B7, 0x49353FEA39BA63B1,
+    0x1DD01AAFCD53486A, 0x1FCA8A92FD719F85, 0xFC7C95D827357AFA, 0x18A6A990C8B35EBD, 0xCCCB7005C6B9C28D, 0x3BDBB92C43B17F26, 0xAA70B5B4F89695A2, 0xE94C39A54A98307F,
+    0xB7A0B174CFF6F36E, 0xD4DBA84729AF48AD, 0x2E18BC1AD9704A68, 0x2DE0966DAF2F8B1C, 0xB9C11D5B1E43A07E, 0x64972D68DEE33360, 0x94628D38D0C20584, 0xDBC0D2B6AB90A559,
+    0xD2733C4335C6A72F, 0x7E75D99D94A70F4D, 0x6CED1983376FA72B, 0x97FCAACBF030BC24, 0x7B77497B32503B12, 0x8547EDDFB81CCB94, 0x79999CDFF70902CB, 0xCFFE1939438E9B24,
+    0x829626E3892D95D7, 0x92FAE24291F2B3F1, 0x63E22C147B9C3403, 0xC678B6D860284A1C, 0x5873888850659AE7, 0x0981DCD296A8736D, 0x9F65789A6509A440, 0x9FF38FED72E9052F,
+    0xE479EE5B9930578C, 0xE7F28ECD2D49EECD, 0x56C074A581EA17FE, 0x5544F7D774B14AEF, 0x7B3F0195FC6F290F, 0x12153635B2C0CF57, 0x7F5126DBBA5E0CA7, 0x7A76956C3EAFB413,
+    0x3D5774A11D31AB39, 0x8A1B083821F40CB4, 0x7B4A38E32537DF62, 0x950113646D1D6E03, 0x4DA8979A0041E8A9, 0x3BC36E078F7515D7, 0x5D0A12F27AD310D1, 0x7F9D1A2E1EBE1327,
+    0xDA3A361B1C5157B1, 0xDCDD7D20903D0C25, 0x36833336D068F707, 0xCE68341F79893389, 0xAB9090168DD05F34, 0x43954B3252DC25E5, 0xB438C2B67F98E5E9, 0x10DCD78E3851A492,
+    0xDBC27AB5447822BF, 0x9B3CDB65F82CA382, 0xB67B7896167B4C84, 0xBFCED1B0048EAC50, 0xA9119B60369FFEBD, 0x1FFF7AC80904BF45, 0xAC12FB171817EEE7, 0xAF08DA9177DDA93D,
+    0x1B0CAB936E65C744, 0xB559EB1D04E5E932, 0xC37B45B3F8D6F2BA, 0xC3A9DC228CAAC9E9, 0xF3B8B6675A6507FF, 0x9FC477DE4ED681DA, 0x67378D8ECCEF96CB, 0x6DD856D94D259236,
+    0xA319CE15B0B4DB31, 0x073973751F12DD5E, 0x8A8E849EB32781A5, 0xE1925C71285279F5, 0x74C04BF1790C0EFE, 0x4DDA48153C94938A, 0x9D266D6A1CC0542C, 0x7440FB816508C4FE,
+    0x13328503DF48229F, 0xD6BF7BAEE43CAC40, 0x4838D65F6EF6748F, 0x1E152328F3318DEA, 0x8F8419A348F296BF, 0x72C8834A5957B511, 0xD7A023A73260B45C, 0x94EBC8ABCFB56DAE,
+    0x9FC10D0F989993E0, 0xDE68A2355B93CAE6, 0xA44CFE79AE538BBE, 0x9D1D84FCCE371425, 0x51D2B1AB2DDFB636, 0x2FD7E4B9E72CD38C, 0x65CA5B96B7552210, 0xDD69A0D8AB3B546D,
+    0x604D51B25FBF70E2, 0x73AA8A564FB7AC9E, 0x1A8C1E992B941148, 0xAAC40A2703D9BEA0, 0x764DBEAE7FA4F3A6, 0x1E99B96E70A9BE8B, 0x2C5E9DEB57EF4743, 0x3A938FEE32D29981,
+    0x26E6DB8FFDF5ADFE, 0x469356C504EC9F9D, 0xC8763C5B08D1908C, 0x3F6C6AF859D80055, 0x7F7CC39420A3A545, 0x9BFB227EBDF4C5CE, 0x89039D79D6FC5C5C, 0x8FE88B57305E2AB6,
+    0xA09E8C8C35AB96DE, 0xFA7E393983325753, 0xD6B6D0ECC617C699, 0xDFEA21EA9E7557E3, 0xB67C1FA481680AF8, 0xCA1E3785A9E724E5, 0x1CFC8BED0D681639, 0xD18D8549D140CAEA,
+    0x4ED0FE7E9DC91335, 0xE4DBF0634473F5D2, 0x1761F93A44D5AEFE, 0x53898E4C3910DA55, 0x734DE8181F6EC39A, 0x2680B122BAA28D97, 0x298AF231C85BAFAB, 0x7983EED3740847D5,
+    0x66C1A2A1A60CD889, 0x9E17E49642A3E4C1, 0xEDB454E7BADC0805, 0x50B704CAB602C329, 0x4CC317FB9CDDD023, 0x66B4835D9EAFEA22, 0x219B97E26FFC81BD, 0x261E4E4C0A333A9D,
+    0x1FE2CCA76517DB90, 0xD7504DFA8816EDBB, 0xB9571FA04DC089C8, 0x1DDC0325259B27DE, 0xCF3F4688801EB9AA, 0xF4F5D05C10CAB243, 0x38B6525C21A42B0E, 0x36F60E2BA4FA6800,
+    0xEB3593803173E0CE, 0x9C4CD6257C5A3603, 0xAF0C317D32ADAA8A, 0x258E5A80C7204C4B, 0x8B889D624D44885D, 0xF4D14597E660F855, 0xD4347F66EC8941C3, 0xE699ED85B0DFB40D,
+    0x2472F6207C2D0484, 0xC2A1E7B5B459AEB5, 0xAB4F6451CC1D45EC, 0x63767572AE3D6174, 0xA59E0BD101731A28, 0x116D0016CB948F09, 0x2CF9C8CA052F6E9F, 0x0B090A7560A968E3,
+    0xABEEDDB2DDE06FF1, 0x58EFC10B06A2068D, 0xC6E57A78FBD986E0, 0x2EAB8CA63CE802D7, 0x14A195640116F336, 0x7C0828DD624EC390, 0xD74BBE77E6116AC7, 0x804456AF10F5FB53,
+    0xEBE9EA2ADF4321C7, 0x03219A39EE587A30, 0x49787FEF17AF9924, 0xA1E9300CD8520548, 0x5B45E522E4B1B4EF, 0xB49C3B3995091A36, 0xD4490AD526F14431, 0x12A8F216AF9418C2,
+    0x001F837CC7350524, 0x1877B51E57A764D5, 0xA2853B80F17F58EE, 0x993E1DE72D36D310, 0xB3598080CE64A656, 0x252F59CF0D9F04BB, 0xD23C8E176D113600, 0x1BDA0492E7E4586E,
+    0x21E0BD5026C619BF, 0x3B097ADAF088F94E, 0x8D14DEDB30BE846E, 0xF95CFFA23AF5F6F4, 0x3871700761B3F743, 0xCA672B91E9E4FA16, 0x64C8E531BFF53B55, 0x241260ED4AD1E87D,
+    0x106C09B972D2E822, 0x7FBA195410E5CA30, 0x7884D9BC6CB569D8, 0x0647DFEDCD894A29, 0x63573FF03E224774, 0x4FC8E9560F91B123, 0x1DB956E450275779, 0xB8D91274B9E9D4FB,
+    0xA2EBEE47E2FBFCE1, 0xD9F1F30CCD97FB09, 0xEFED53D75FD64E6B, 0x2E6D02C36017F67F, 0xA9AA4D20DB084E9B, 0xB64BE8D8B25396C1, 0x70CB6AF7C2D5BCF0, 0x98F076A4F7A2322E,
+    0xBF84470805E69B5F, 0x94C3251F06F90CF3, 0x3E003E616A6591E9, 0xB925A6CD0421AFF3, 0x61BDD1307C66E300, 0xBF8D5108E27E0D48, 0x240AB57A8B888B20, 0xFC87614BAF287E07,
+    0xEF02CDD06FFDB432, 0xA1082C0466DF6C0A, 0x8215E577001332C8, 0xD39BB9C3A48DB6CF, 0x2738259634305C14, 0x61CF4F94C97DF93D, 0x1B6BACA2AE4E125B, 0x758F450C88572E0B,
+    0x959F587D507A8359, 0xB063E962E045F54D, 0x60E8ED72C0DFF5D1, 0x7B64978555326F9F, 0xFD080D236DA814BA, 0x8C90FD9B083F4558, 0x106F72FE81E2C590, 0x7976033A39F7D952,
+    0xA4EC0132764CA04B, 0x733EA705FAE4FA77, 0xB4D8F77BC3E56167, 0x9E21F4F903B33FD9, 0x9D765E419FB69F6D, 0xD30C088BA61EA5EF, 0x5D94337FBFAF7F5B, 0x1A4E4822EB4D7A59,
+    0x6FFE73E81B637FB3, 0xDDF957BC36D8B9CA, 0x64D0E29EEA8838B3, 0x08DD9BDFD96B9F63, 0x087E79E5A57D1D13, 0xE328E230E3E2B3FB, 0x1C2559E30F0946BE, 0x720BF5F26F4D2EAA,
+    0xB0774D261CC609DB, 0x443F64EC5A371195, 0x4112CF68649A260E, 0xD813F2FAB7F5C5CA, 0x660D3257380841EE, 0x59AC2C7873F910A3, 0xE846963877671A17, 0x93B633ABFA3469F8,
+    0xC0C0F5A60EF4CDCF, 0xCAF21ECD4377B28C, 0x57277707199B8175, 0x506C11B9D90E8B1D, 0xD83CC2687A19255F, 0x4A29C6465A314CD1, 0xED2DF21216235097, 0xB5635C95FF7296E2,
+    0x22AF003AB672E811, 0x52E762596BF68235, 0x9AEBA33AC6ECC6B0, 0x944F6DE09134DFB6, 0x6C47BEC883A7DE39, 0x6AD047C430A12104, 0xA5B1CFDBA0AB4067, 0x7C45D833AFF07862,
+    0x5092EF950A16DA0B, 0x9338E69C052B8E7B, 0x455A4B4CFE30E3F5, 0x6B02E63195AD0CF8, 0x6B17B224BAD6BF27, 0xD1E0CCD25BB9C169, 0xDE0C89A556B9AE70, 0x50065E535A213CF6,
+    0x9C1169FA2777B874, 0x78EDEFD694AF1EED, 0x6DC93D9526A50E68, 0xEE97F453F06791ED, 0x32AB0EDB696703D3, 0x3A6853C7E70757A7, 0x31865CED6120F37D, 0x67FEF95D92607890,
+    0x1F2B1D1F15F6DC9C, 0xB69E38A8965C6B65, 0xAA9119FF184CCCF4, 0xF43C732873F24C13, 0xFB4A3D794A9A80D2, 0x3550C2321FD6109C, 0x371F77E76BB8417E, 0x6BFA9AAE5EC05779,
+    0xCD04F3FF001A4778, 0xE3273522064480CA, 0x9F91508BFFCFC14A, 0x049A7F41061A9E60, 0xFCB6BE43A9F2FE9B, 0x08DE8A1C7797DA9B, 0x8F9887E6078735A1, 0xB5B4071DBFC73A66,
+    0x230E343DFBA08D33, 0x43ED7F5A0FAE657D, 0x3A88A0FBBCB05C63, 0x21874B8B4D2DBC4F, 0x1BDEA12E35F6A8C9, 0x53C065C6C8E63528, 0xE34A1D250E7A8D6B, 0xD6B04D3B7651DD7E,
+    0x5E90277E7CB39E2D, 0x2C046F22062DC67D, 0xB10BB459132D0A26, 0x3FA9DDFB67E2F199, 0x0E09B88E1914F7AF, 0x10E8B35AF3EEAB37, 0x9EEDECA8E272B933, 0xD4C718BC4AE8AE5F,
+    0x81536D601170FC20, 0x91B534F885818A06, 0xEC8177F83F900978, 0x190E714FADA5156E, 0xB592BF39B0364963, 0x89C350C893AE7DC1, 0xAC042E70F8B383F2, 0xB49B52E587A1EE60,
+    0xFB152FE3FF26DA89, 0x3E666E6F69AE2C15, 0x3B544EBE544C19F9, 0xE805A1E290CF2456, 0x24B33C9D7ED25117, 0xE74733427B72F0C1, 0x0A804D18B7097475, 0x57E3306D881EDB4F,
+    0x4AE7D6A36EB5DBCB, 0x2D8D5432157064C8, 0xD1E649DE1E7F268B, 0x8A328A1CEDFE552C, 0x07A3AEC79624C7DA, 0x84547DDC3E203C94, 0x990A98FD5071D263, 0x1A4FF12616EEFC89,
+    0xF6F7FD1431714200, 0x30C05B1BA332F41C, 0x8D2636B81555A786, 0x46C9FEB55D120902, 0xCCEC0A73B49C9921, 0x4E9D2827355FC492, 0x19EBB029435DCB0F, 0x4659D2B743848A2C,
+    0x963EF2C96B33BE31, 0x74F85198B05A2E7D, 0x5A0F544DD2B1FB18, 0x03727073C2E134B1, 0xC7F6AA2DE59AEA61, 0x352787BAA0D7C22F, 0x9853EAB63B5E0B35, 0xABBDCDD7ED5C0860,
+    0xCF05DAF5AC8D77B0, 0x49CAD48CEBF4A71E, 0x7A4C10EC2158C4A6, 0xD9E92AA246BF719E, 0x13AE978D09FE5557, 0x730499AF921549FF, 0x4E4B705B92903BA4, 0xFF577222C14F0A3A,
+    0x55B6344CF97AAFAE, 0xB862225B055B6960, 0xCAC09AFBDDD2CDB4, 0xDAF8E9829FE96B5F, 0xB5FDFC5D3132C498, 0x310CB380DB6F7503, 0xE87FBB46217A360E, 0x2102AE466EBB1148,
+    0xF8549E1A3AA5E00D, 0x07A69AFDCC42261A, 0xC4C118BFE78FEAAE, 0xF9F4892ED96BD438, 0x1AF3DBE25D8F45DA, 0xF5B4B0B0D2DEEEB4, 0x962ACEEFA82E1C84, 0x046E3ECAAF453CE9,
+    0xF05D129681949A4C, 0x964781CE734B3C84, 0x9C2ED44081CE5FBD, 0x522E23F3925E319E, 0x177E00F9FC32F791, 0x2BC60A63A6F3B3F2, 0x222BBFAE61725606, 0x486289DDCC3D6780,
+    0x7DC7785B8EFDFC80, 0x8AF38731C02BA980, 0x1FAB64EA29A2DDF7, 0xE4D9429322CD065A, 0x9DA058C67844F20C, 0x24C0E332B70019B0, 0x233003B5A6CFE6AD, 0xD586BD01C5C217F6,
+    0x5E5637885F29BC2B, 0x7EBA726D8C94094B, 0x0A56A5F0BFE39272, 0xD79476A84EE20D06, 0x9E4C1269BAA4BF37, 0x17EFEE45B0DEE640, 0x1D95B0A5FCF90BC6, 0x93CBE0B699C2585D,
+    0x65FA4F227A2B6D79, 0xD5F9E858292504D5, 0xC2B5A03F71471A6F, 0x59300222B4561E00, 0xCE2F8642CA0712DC, 0x7CA9723FBB2E8988, 0x2785338347F2BA08, 0xC61BB3A141E50E8C,
+    0x150F361DAB9DEC26, 0x9F6A419D382595F4, 0x64A53DC924FE7AC9, 0x142DE49FFF7A7C3D, 0x0C335248857FA9E7, 0x0A9C32D5EAE45305, 0xE6C42178C4BBB92E, 0x71F1CE2490D20B07,
+    0xF1BCC3D275AFE51A, 0xE728E8C83C334074, 0x96FBF83A12884624, 0x81A1549FD6573DA5, 0x5FA7867CAF35E149, 0x56986E2EF3ED091B, 0x917F1DD5F8886C61, 0xD20D8C88C8FFE65F,
+    0x31D71DCE64B2C310, 0xF165B587DF898190, 0xA57E6339DD2CF3A0, 0x1EF6E6DBB1961EC9, 0x70CC73D90BC26E24, 0xE21A6B35DF0C3AD7, 0x003A93D8B2806962, 0x1C99DED33CB890A1,
+    0xCF3145DE0ADD4289, 0xD0E4427A5514FB72, 0x77C621CC9FB3A483, 0x67A34DAC4356550B, 0xF8D626AAAF278509,
 ];
-pub const HV_THREATS: BB64 = [BitBoard(217020518514294783), BitBoard(506381209866600447), BitBoard(1012762419733135359), BitBoard(2025524839466205183), BitBoard(4051049678932344831), BitBoard(8102099357864624127), BitBoard(16204198715729182719), BitBoard(13889313184910737407), BitBoard(217020518530809855), BitBoard(506381209882853375), BitBoard(1012762419748929535), BitBoard(2025524839481081855), BitBoard(4051049678945386495), BitBoard(8102099357873995775), BitBoard(16204198715731214335), BitBoard(13889313184914866175), BitBoard(217020522758668035), BitBoard(506381214043602695), BitBoard(1012762423792238350), BitBoard(2025524843289509660), BitBoard(4051049682284052280), BitBoard(8102099360273137520), BitBoard(16204198716251308000), BitBoard(13889313185971830720), BitBoard(217021605090362115), BitBoard(506382279195428615), BitBoard(1012763458879294990), BitBoard(2025525818247027740), BitBoard(4051050536982493240), BitBoard(8102099974453424240), BitBoard(16204198849395286240), BitBoard(13889313456554754240), BitBoard(217298682004046595), BitBoard(506654958062864135), BitBoard(1013028441165794830), BitBoard(2025775407371656220), BitBoard(4051269339783379000), BitBoard(8102257204606824560), BitBoard(16204232934253715680), BitBoard(13889382725783175360), BitBoard(288230371907273475), BitBoard(576460748126357255), BitBoard(1080863906509753870), BitBoard(2089670223276547100), BitBoard(4107282856810133560), BitBoard(8142508123877306480), BitBoard(16212958658011652320), BitBoard(13907115648258982080), BitBoard(18446742987133354755), BitBoard(18446743004380595975), BitBoard(18446743034563268110), BitBoard(18446743094928612380), BitBoard(18446743215659300920), BitBoard(18446743457120678000), BitBoard(18446743940043432160), BitBoard(18446743802065502400), BitBoard(18446465910203155203), BitBoard(18446470325496907527), BitBoard(18446478052260974094), BitBoard(18446493505789107228), BitBoard(18446524412845373496), BitBoard(18446586226957906032), BitBoard(18446709855182971104), BitBoard(18446674532832952512)];
-pub const DIAG_THREATS: BB64 = [BitBoard(13898231836356577031), BitBoard(13898372849277538063), BitBoard(9277662560142696223), BitBoard(36241981873012286), BitBoard(426369724873852), BitBoard(72910333470898424), BitBoard(218159731644625136), BitBoard(218158621327941856), BitBoard(16208587258520864527), BitBoard(16208728275720015647), BitBoard(13898374512539279167), BitBoard(9277947361642888831), BitBoard(109150649593003262), BitBoard(218301299169229052), BitBoard(508660188064575736), BitBoard(508658530139562224), BitBoard(17327665670401756959), BitBoard(17363766073384443711), BitBoard(16209013057821556607), BitBoard(13971283145756934143), BitBoard(9495822222099283711), BitBoard(544900366193982718), BitBoard(1089799628564527356), BitBoard(1089375199761068280), BitBoard(8663833932557393724), BitBoard(17905537096125202301), BitBoard(17436670291378929659), BitBoard(16426879087831678967), BitBoard(14407015201465368559), BitBoard(10367285229692714719), BitBoard(2287543806875729086), BitBoard(2178890033190205500), BitBoard(4332197889546599544), BitBoard(9024966328082988536), BitBoard(18122272829004053489), BitBoard(17870141752841598947), BitBoard(17293820906933579719), BitBoard(16140616260869087119), BitBoard(13762148275190545951), BitBoard(4393526285406059550), BitBoard(2238015301356386544), BitBoard(4548370775551113456), BitBoard(9169081723940237792), BitBoard(18410502521190081473), BitBoard(18446318558413571971), BitBoard(18373834345256292103), BitBoard(18228584443964956431), BitBoard(17938084641378147855), BitBoard(1082850862238855392), BitBoard(2238041897383293152), BitBoard(4548423967587819712), BitBoard(9168906628725195136), BitBoard(18337812157938762497), BitBoard(18228597663384667907), BitBoard(17938111080221839111), BitBoard(17357137912837050119), BitBoard(506390096982892736), BitBoard(1085120384102097088), BitBoard(2242580941025362048), BitBoard(4485162981562351616), BitBoard(8970324863613075712), BitBoard(17940367148442911489), BitBoard(17361650050355103491), BitBoard(16204215583058560771)];
-pub const KNIGHT_THREATS: BB64 = [BitBoard(118294540), BitBoard(253695261), BitBoard(524233275), BitBoard(1048532343), BitBoard(2097064686), BitBoard(4177286364), BitBoard(4042827960), BitBoard(3769634864), BitBoard(30283861007), BitBoard(64946642207), BitBoard(134205094719), BitBoard(268427032447), BitBoard(536854064894), BitBoard(1069396319484), BitBoard(1034969200888), BitBoard(965041205488), BitBoard(7752668417805), BitBoard(16626340405023), BitBoard(34356504248127), BitBoard(68717320306559), BitBoard(137434640613118), BitBoard(273765457788156), BitBoard(264952115427576), BitBoard(247050548605104), BitBoard(1984683114958087), BitBoard(4256343143685903), BitBoard(8795265087520543), BitBoard(17591633998479166), BitBoard(35183267996958332), BitBoard(70083957193768184), BitBoard(67827741549459696), BitBoard(63244940442906848), BitBoard(508078877429270272), BitBoard(1089623844783591168), BitBoard(2251587862405259008), BitBoard(4503458303610666496), BitBoard(9006916607221332992), BitBoard(17941493041604655104), BitBoard(17363901836661682176), BitBoard(16190704753384153088), BitBoard(940984105926328320), BitBoard(2242543158956064768), BitBoard(4557426490750205952), BitBoard(9187193154338422784), BitBoard(18374386308676845568), BitBoard(18229688370822905856), BitBoard(17940292495098249216), BitBoard(12749744355403628544), BitBoard(1084258158915878912), BitBoard(2241982407756480512), BitBoard(4556304988350971904), BitBoard(9184950149523177472), BitBoard(18369900299046354944), BitBoard(18220716355856891904), BitBoard(17922348465182998528), BitBoard(17307598010447822848), BitBoard(866957251984752640), BitBoard(2095615350895804416), BitBoard(4263289399636131840), BitBoard(8598918967798530048), BitBoard(17197837935597060096), BitBoard(15876592728469929984), BitBoard(13306158808741969920), BitBoard(3463462589567074304)];
-pub const KING_THREATS: BB64 = [BitBoard(459780), BitBoard(985096), BitBoard(2035985), BitBoard(4071970), BitBoard(8143940), BitBoard(16287880), BitBoard(15732752), BitBoard(14688288), BitBoard(117703684), BitBoard(252184584), BitBoard(521212177), BitBoard(1042424354), BitBoard(2084848708), BitBoard(4169697416), BitBoard(4027584528), BitBoard(3760201760), BitBoard(30132143111), BitBoard(64559253519), BitBoard(133430317343), BitBoard(266860634686), BitBoard(533721269372), BitBoard(1067442538744), BitBoard(1031061639408), BitBoard(962611650784), BitBoard(7713828636416), BitBoard(16527168900864), BitBoard(34158161239808), BitBoard(68316322479616), BitBoard(136632644959232), BitBoard(273265289918464), BitBoard(263951779688448), BitBoard(246428582600704), BitBoard(1974740130922496), BitBoard(4230955238621184), BitBoard(8744489277390848), BitBoard(17488978554781696), BitBoard(34977957109563392), BitBoard(69955914219126784), BitBoard(67571655600242688), BitBoard(63085717145780224), BitBoard(505533473516158976), BitBoard(1083124541087023104), BitBoard(2238589255012057088), BitBoard(4477178510024114176), BitBoard(8954357020048228352), BitBoard(17908714040096456704), BitBoard(17298343833662128128), BitBoard(16149943589319737344), BitBoard(289360704169836544), BitBoard(578721412634640384), BitBoard(1229782998090514432), BitBoard(2459565996181028864), BitBoard(4919131992362057728), BitBoard(9838263984724115456), BitBoard(1157443727212412928), BitBoard(2314886354913198080), BitBoard(289363972639948800), BitBoard(578729044791525376), BitBoard(1229798258109317120), BitBoard(2459596516218634240), BitBoard(4919193032437268480), BitBoard(9838386064874536960), BitBoard(1157688987024883712), BitBoard(2315096499073056768)];
-
-/// Bitboard of squares between two squares, excluding the squares themselves
-pub const BETWEEN: [BB64 ; SQUARE_COUNT] = [
-    [BitBoard(0), BitBoard(0), BitBoard(2), BitBoard(6), BitBoard(14), BitBoard(30), BitBoard(62), BitBoard(126), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(256), BitBoard(0), BitBoard(512), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(65792), BitBoard(0), BitBoard(0), BitBoard(262656), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(16843008), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(134480384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4311810304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68853957120), BitBoard(0), BitBoard(0), BitBoard(1103823438080), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35253226045952), BitBoard(0), BitBoard(282578800148736), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18049651735527936)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4), BitBoard(12), BitBoard(28), BitBoard(60), BitBoard(124), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(512), BitBoard(0), BitBoard(1024), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(131584), BitBoard(0), BitBoard(0), BitBoard(525312), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(33686016), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(268960768), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8623620608), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137707914240), BitBoard(0), BitBoard(0), BitBoard(2207646876160), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70506452091904), BitBoard(0), BitBoard(565157600297472), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(2), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8), BitBoard(24), BitBoard(56), BitBoard(120), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(512), BitBoard(0), BitBoard(1024), BitBoard(0), BitBoard(2048), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(263168), BitBoard(0), BitBoard(0), BitBoard(1050624), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(67372032), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(537921536), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17247241216), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(275415828480), BitBoard(0), BitBoard(0), BitBoard(4415293752320), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1130315200594944), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(6), BitBoard(4), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(16), BitBoard(48), BitBoard(112), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1024), BitBoard(0), BitBoard(2048), BitBoard(0), BitBoard(4096), BitBoard(0), BitBoard(0), BitBoard(132096), BitBoard(0), BitBoard(0), BitBoard(526336), BitBoard(0), BitBoard(0), BitBoard(2101248), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(134744064), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1075843072), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34494482432), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8830587504640), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2260630401189888), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(14), BitBoard(12), BitBoard(8), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(32), BitBoard(96), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2048), BitBoard(0), BitBoard(4096), BitBoard(0), BitBoard(8192), BitBoard(0), BitBoard(0), BitBoard(264192), BitBoard(0), BitBoard(0), BitBoard(1052672), BitBoard(0), BitBoard(0), BitBoard(4202496), BitBoard(33818624), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(269488128), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68988964864), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17661175009280), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4521260802379776), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(30), BitBoard(28), BitBoard(24), BitBoard(16), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(64), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4096), BitBoard(0), BitBoard(8192), BitBoard(0), BitBoard(16384), BitBoard(0), BitBoard(0), BitBoard(528384), BitBoard(0), BitBoard(0), BitBoard(2105344), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(67637248), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(538976256), BitBoard(0), BitBoard(0), BitBoard(8657571840), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137977929728), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35322350018560), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9042521604759552), BitBoard(0), BitBoard(0)],
-    [BitBoard(62), BitBoard(60), BitBoard(56), BitBoard(48), BitBoard(32), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8192), BitBoard(0), BitBoard(16384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1056768), BitBoard(0), BitBoard(0), BitBoard(4210688), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(135274496), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1077952512), BitBoard(0), BitBoard(0), BitBoard(17315143680), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(275955859456), BitBoard(0), BitBoard(2216338399232), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70644700037120), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18085043209519104), BitBoard(0)],
-    [BitBoard(126), BitBoard(124), BitBoard(120), BitBoard(112), BitBoard(96), BitBoard(64), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(16384), BitBoard(0), BitBoard(32768), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2113536), BitBoard(0), BitBoard(0), BitBoard(8421376), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(270548992), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2155905024), BitBoard(0), BitBoard(0), BitBoard(34630287360), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(551911718912), BitBoard(0), BitBoard(4432676798464), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(141289400074240), BitBoard(567382630219776), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(36170086419038208)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(512), BitBoard(1536), BitBoard(3584), BitBoard(7680), BitBoard(15872), BitBoard(32256), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(65536), BitBoard(0), BitBoard(131072), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(16842752), BitBoard(0), BitBoard(0), BitBoard(67239936), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4311810048), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34426978304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1103823437824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17626613022720), BitBoard(0), BitBoard(0), BitBoard(282578800148480), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9024825867763712), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1024), BitBoard(3072), BitBoard(7168), BitBoard(15360), BitBoard(31744), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(131072), BitBoard(0), BitBoard(262144), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(33685504), BitBoard(0), BitBoard(0), BitBoard(134479872), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8623620096), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68853956608), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2207646875648), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35253226045440), BitBoard(0), BitBoard(0), BitBoard(565157600296960), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18049651735527424)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(512), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2048), BitBoard(6144), BitBoard(14336), BitBoard(30720), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(131072), BitBoard(0), BitBoard(262144), BitBoard(0), BitBoard(524288), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(67371008), BitBoard(0), BitBoard(0), BitBoard(268959744), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17247240192), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137707913216), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4415293751296), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70506452090880), BitBoard(0), BitBoard(0), BitBoard(1130315200593920), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1536), BitBoard(1024), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4096), BitBoard(12288), BitBoard(28672), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(262144), BitBoard(0), BitBoard(524288), BitBoard(0), BitBoard(1048576), BitBoard(0), BitBoard(0), BitBoard(33816576), BitBoard(0), BitBoard(0), BitBoard(134742016), BitBoard(0), BitBoard(0), BitBoard(537919488), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34494480384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(275415826432), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8830587502592), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2260630401187840), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(3584), BitBoard(3072), BitBoard(2048), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8192), BitBoard(24576), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(524288), BitBoard(0), BitBoard(1048576), BitBoard(0), BitBoard(2097152), BitBoard(0), BitBoard(0), BitBoard(67633152), BitBoard(0), BitBoard(0), BitBoard(269484032), BitBoard(0), BitBoard(0), BitBoard(1075838976), BitBoard(8657567744), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68988960768), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17661175005184), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4521260802375680), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(7680), BitBoard(7168), BitBoard(6144), BitBoard(4096), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(16384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1048576), BitBoard(0), BitBoard(2097152), BitBoard(0), BitBoard(4194304), BitBoard(0), BitBoard(0), BitBoard(135266304), BitBoard(0), BitBoard(0), BitBoard(538968064), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17315135488), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137977921536), BitBoard(0), BitBoard(0), BitBoard(2216338391040), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35322350010368), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9042521604751360), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(15872), BitBoard(15360), BitBoard(14336), BitBoard(12288), BitBoard(8192), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2097152), BitBoard(0), BitBoard(4194304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(270532608), BitBoard(0), BitBoard(0), BitBoard(1077936128), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34630270976), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(275955843072), BitBoard(0), BitBoard(0), BitBoard(4432676782080), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70644700020736), BitBoard(0), BitBoard(567382630203392), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18085043209502720), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(32256), BitBoard(31744), BitBoard(30720), BitBoard(28672), BitBoard(24576), BitBoard(16384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4194304), BitBoard(0), BitBoard(8388608), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(541065216), BitBoard(0), BitBoard(0), BitBoard(2155872256), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(69260541952), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(551911686144), BitBoard(0), BitBoard(0), BitBoard(8865353564160), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(141289400041472), BitBoard(0), BitBoard(1134765260406784), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(36170086419005440)],
-    [BitBoard(256), BitBoard(0), BitBoard(512), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(131072), BitBoard(393216), BitBoard(917504), BitBoard(1966080), BitBoard(4063232), BitBoard(8257536), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(16777216), BitBoard(0), BitBoard(33554432), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4311744512), BitBoard(0), BitBoard(0), BitBoard(17213423616), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1103823372288), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8813306445824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(282578800082944), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4512412933816320), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(512), BitBoard(0), BitBoard(1024), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(262144), BitBoard(786432), BitBoard(1835008), BitBoard(3932160), BitBoard(8126464), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(33554432), BitBoard(0), BitBoard(67108864), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8623489024), BitBoard(0), BitBoard(0), BitBoard(34426847232), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2207646744576), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17626612891648), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(565157600165888), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9024825867632640), BitBoard(0)],
-    [BitBoard(512), BitBoard(0), BitBoard(1024), BitBoard(0), BitBoard(2048), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(131072), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(524288), BitBoard(1572864), BitBoard(3670016), BitBoard(7864320), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(33554432), BitBoard(0), BitBoard(67108864), BitBoard(0), BitBoard(134217728), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17246978048), BitBoard(0), BitBoard(0), BitBoard(68853694464), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4415293489152), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35253225783296), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1130315200331776), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18049651735265280)],
-    [BitBoard(0), BitBoard(1024), BitBoard(0), BitBoard(2048), BitBoard(0), BitBoard(4096), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(393216), BitBoard(262144), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1048576), BitBoard(3145728), BitBoard(7340032), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(67108864), BitBoard(0), BitBoard(134217728), BitBoard(0), BitBoard(268435456), BitBoard(0), BitBoard(0), BitBoard(8657043456), BitBoard(0), BitBoard(0), BitBoard(34493956096), BitBoard(0), BitBoard(0), BitBoard(137707388928), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8830586978304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70506451566592), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2260630400663552), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(2048), BitBoard(0), BitBoard(4096), BitBoard(0), BitBoard(8192), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(917504), BitBoard(786432), BitBoard(524288), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2097152), BitBoard(6291456), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(134217728), BitBoard(0), BitBoard(268435456), BitBoard(0), BitBoard(536870912), BitBoard(0), BitBoard(0), BitBoard(17314086912), BitBoard(0), BitBoard(0), BitBoard(68987912192), BitBoard(0), BitBoard(0), BitBoard(275414777856), BitBoard(2216337342464), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17661173956608), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4521260801327104), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4096), BitBoard(0), BitBoard(8192), BitBoard(0), BitBoard(16384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1966080), BitBoard(1835008), BitBoard(1572864), BitBoard(1048576), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4194304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(268435456), BitBoard(0), BitBoard(536870912), BitBoard(0), BitBoard(1073741824), BitBoard(0), BitBoard(0), BitBoard(34628173824), BitBoard(0), BitBoard(0), BitBoard(137975824384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4432674684928), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35322347913216), BitBoard(0), BitBoard(0), BitBoard(567382628106240), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9042521602654208), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8192), BitBoard(0), BitBoard(16384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4063232), BitBoard(3932160), BitBoard(3670016), BitBoard(3145728), BitBoard(2097152), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(536870912), BitBoard(0), BitBoard(1073741824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(69256347648), BitBoard(0), BitBoard(0), BitBoard(275951648768), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8865349369856), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70644695826432), BitBoard(0), BitBoard(0), BitBoard(1134765256212480), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18085043205308416), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(16384), BitBoard(0), BitBoard(32768), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8257536), BitBoard(8126464), BitBoard(7864320), BitBoard(7340032), BitBoard(6291456), BitBoard(4194304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1073741824), BitBoard(0), BitBoard(2147483648), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(138512695296), BitBoard(0), BitBoard(0), BitBoard(551903297536), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17730698739712), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(141289391652864), BitBoard(0), BitBoard(0), BitBoard(2269530512424960), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(36170086410616832)],
-    [BitBoard(65792), BitBoard(0), BitBoard(0), BitBoard(132096), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(65536), BitBoard(0), BitBoard(131072), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(33554432), BitBoard(100663296), BitBoard(234881024), BitBoard(503316480), BitBoard(1040187392), BitBoard(2113929216), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4294967296), BitBoard(0), BitBoard(8589934592), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1103806595072), BitBoard(0), BitBoard(0), BitBoard(4406636445696), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(282578783305728), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2256206450130944), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(131584), BitBoard(0), BitBoard(0), BitBoard(264192), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(131072), BitBoard(0), BitBoard(262144), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(67108864), BitBoard(201326592), BitBoard(469762048), BitBoard(1006632960), BitBoard(2080374784), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8589934592), BitBoard(0), BitBoard(17179869184), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2207613190144), BitBoard(0), BitBoard(0), BitBoard(8813272891392), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(565157566611456), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4512412900261888), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(263168), BitBoard(0), BitBoard(0), BitBoard(528384), BitBoard(0), BitBoard(0), BitBoard(131072), BitBoard(0), BitBoard(262144), BitBoard(0), BitBoard(524288), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(33554432), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(134217728), BitBoard(402653184), BitBoard(939524096), BitBoard(2013265920), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8589934592), BitBoard(0), BitBoard(17179869184), BitBoard(0), BitBoard(34359738368), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4415226380288), BitBoard(0), BitBoard(0), BitBoard(17626545782784), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1130315133222912), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9024825800523776), BitBoard(0)],
-    [BitBoard(262656), BitBoard(0), BitBoard(0), BitBoard(526336), BitBoard(0), BitBoard(0), BitBoard(1056768), BitBoard(0), BitBoard(0), BitBoard(262144), BitBoard(0), BitBoard(524288), BitBoard(0), BitBoard(1048576), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(100663296), BitBoard(67108864), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(268435456), BitBoard(805306368), BitBoard(1879048192), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17179869184), BitBoard(0), BitBoard(34359738368), BitBoard(0), BitBoard(68719476736), BitBoard(0), BitBoard(0), BitBoard(2216203124736), BitBoard(0), BitBoard(0), BitBoard(8830452760576), BitBoard(0), BitBoard(0), BitBoard(35253091565568), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2260630266445824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18049651601047552)],
-    [BitBoard(0), BitBoard(525312), BitBoard(0), BitBoard(0), BitBoard(1052672), BitBoard(0), BitBoard(0), BitBoard(2113536), BitBoard(0), BitBoard(0), BitBoard(524288), BitBoard(0), BitBoard(1048576), BitBoard(0), BitBoard(2097152), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(234881024), BitBoard(201326592), BitBoard(134217728), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(536870912), BitBoard(1610612736), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34359738368), BitBoard(0), BitBoard(68719476736), BitBoard(0), BitBoard(137438953472), BitBoard(0), BitBoard(0), BitBoard(4432406249472), BitBoard(0), BitBoard(0), BitBoard(17660905521152), BitBoard(0), BitBoard(0), BitBoard(70506183131136), BitBoard(567382359670784), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4521260532891648), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(1050624), BitBoard(0), BitBoard(0), BitBoard(2105344), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1048576), BitBoard(0), BitBoard(2097152), BitBoard(0), BitBoard(4194304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(503316480), BitBoard(469762048), BitBoard(402653184), BitBoard(268435456), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1073741824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68719476736), BitBoard(0), BitBoard(137438953472), BitBoard(0), BitBoard(274877906944), BitBoard(0), BitBoard(0), BitBoard(8864812498944), BitBoard(0), BitBoard(0), BitBoard(35321811042304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1134764719341568), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9042521065783296), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2101248), BitBoard(0), BitBoard(0), BitBoard(4210688), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2097152), BitBoard(0), BitBoard(4194304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1040187392), BitBoard(1006632960), BitBoard(939524096), BitBoard(805306368), BitBoard(536870912), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137438953472), BitBoard(0), BitBoard(274877906944), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17729624997888), BitBoard(0), BitBoard(0), BitBoard(70643622084608), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2269529438683136), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18085042131566592), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4202496), BitBoard(0), BitBoard(0), BitBoard(8421376), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4194304), BitBoard(0), BitBoard(8388608), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2113929216), BitBoard(2080374784), BitBoard(2013265920), BitBoard(1879048192), BitBoard(1610612736), BitBoard(1073741824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(274877906944), BitBoard(0), BitBoard(549755813888), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35459249995776), BitBoard(0), BitBoard(0), BitBoard(141287244169216), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4539058877366272), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(36170084263133184)],
-    [BitBoard(16843008), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(33818624), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(16842752), BitBoard(0), BitBoard(0), BitBoard(33816576), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(16777216), BitBoard(0), BitBoard(33554432), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8589934592), BitBoard(25769803776), BitBoard(60129542144), BitBoard(128849018880), BitBoard(266287972352), BitBoard(541165879296), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1099511627776), BitBoard(0), BitBoard(2199023255552), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(282574488338432), BitBoard(0), BitBoard(0), BitBoard(1128098930098176), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(33686016), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(67637248), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(33685504), BitBoard(0), BitBoard(0), BitBoard(67633152), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(33554432), BitBoard(0), BitBoard(67108864), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17179869184), BitBoard(51539607552), BitBoard(120259084288), BitBoard(257698037760), BitBoard(532575944704), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2199023255552), BitBoard(0), BitBoard(4398046511104), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(565148976676864), BitBoard(0), BitBoard(0), BitBoard(2256197860196352), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(67372032), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(135274496), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(67371008), BitBoard(0), BitBoard(0), BitBoard(135266304), BitBoard(0), BitBoard(0), BitBoard(33554432), BitBoard(0), BitBoard(67108864), BitBoard(0), BitBoard(134217728), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8589934592), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34359738368), BitBoard(103079215104), BitBoard(240518168576), BitBoard(515396075520), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2199023255552), BitBoard(0), BitBoard(4398046511104), BitBoard(0), BitBoard(8796093022208), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1130297953353728), BitBoard(0), BitBoard(0), BitBoard(4512395720392704), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(134744064), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(270548992), BitBoard(67239936), BitBoard(0), BitBoard(0), BitBoard(134742016), BitBoard(0), BitBoard(0), BitBoard(270532608), BitBoard(0), BitBoard(0), BitBoard(67108864), BitBoard(0), BitBoard(134217728), BitBoard(0), BitBoard(268435456), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(25769803776), BitBoard(17179869184), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68719476736), BitBoard(206158430208), BitBoard(481036337152), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4398046511104), BitBoard(0), BitBoard(8796093022208), BitBoard(0), BitBoard(17592186044416), BitBoard(0), BitBoard(0), BitBoard(567347999932416), BitBoard(0), BitBoard(0), BitBoard(2260595906707456), BitBoard(0), BitBoard(0), BitBoard(9024791440785408), BitBoard(0)],
-    [BitBoard(134480384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(269488128), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(134479872), BitBoard(0), BitBoard(0), BitBoard(269484032), BitBoard(0), BitBoard(0), BitBoard(541065216), BitBoard(0), BitBoard(0), BitBoard(134217728), BitBoard(0), BitBoard(268435456), BitBoard(0), BitBoard(536870912), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(60129542144), BitBoard(51539607552), BitBoard(34359738368), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137438953472), BitBoard(412316860416), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8796093022208), BitBoard(0), BitBoard(17592186044416), BitBoard(0), BitBoard(35184372088832), BitBoard(0), BitBoard(0), BitBoard(1134695999864832), BitBoard(0), BitBoard(0), BitBoard(4521191813414912), BitBoard(0), BitBoard(0), BitBoard(18049582881570816)],
-    [BitBoard(0), BitBoard(268960768), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(538976256), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(268959744), BitBoard(0), BitBoard(0), BitBoard(538968064), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(268435456), BitBoard(0), BitBoard(536870912), BitBoard(0), BitBoard(1073741824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(128849018880), BitBoard(120259084288), BitBoard(103079215104), BitBoard(68719476736), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(274877906944), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17592186044416), BitBoard(0), BitBoard(35184372088832), BitBoard(0), BitBoard(70368744177664), BitBoard(0), BitBoard(0), BitBoard(2269391999729664), BitBoard(0), BitBoard(0), BitBoard(9042383626829824), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(537921536), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1077952512), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(537919488), BitBoard(0), BitBoard(0), BitBoard(1077936128), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(536870912), BitBoard(0), BitBoard(1073741824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(266287972352), BitBoard(257698037760), BitBoard(240518168576), BitBoard(206158430208), BitBoard(137438953472), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35184372088832), BitBoard(0), BitBoard(70368744177664), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4538783999459328), BitBoard(0), BitBoard(0), BitBoard(18084767253659648), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1075843072), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2155905024), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1075838976), BitBoard(0), BitBoard(0), BitBoard(2155872256), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1073741824), BitBoard(0), BitBoard(2147483648), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(541165879296), BitBoard(532575944704), BitBoard(515396075520), BitBoard(481036337152), BitBoard(412316860416), BitBoard(274877906944), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70368744177664), BitBoard(0), BitBoard(140737488355328), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9077567998918656), BitBoard(0), BitBoard(0), BitBoard(36169534507319296)],
-    [BitBoard(4311810304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8657571840), BitBoard(0), BitBoard(0), BitBoard(4311810048), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8657567744), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4311744512), BitBoard(0), BitBoard(0), BitBoard(8657043456), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4294967296), BitBoard(0), BitBoard(8589934592), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2199023255552), BitBoard(6597069766656), BitBoard(15393162788864), BitBoard(32985348833280), BitBoard(68169720922112), BitBoard(138538465099776), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(281474976710656), BitBoard(0), BitBoard(562949953421312), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(8623620608), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17315143680), BitBoard(0), BitBoard(0), BitBoard(8623620096), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17315135488), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8623489024), BitBoard(0), BitBoard(0), BitBoard(17314086912), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8589934592), BitBoard(0), BitBoard(17179869184), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4398046511104), BitBoard(13194139533312), BitBoard(30786325577728), BitBoard(65970697666560), BitBoard(136339441844224), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(562949953421312), BitBoard(0), BitBoard(1125899906842624), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(17247241216), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34630287360), BitBoard(0), BitBoard(0), BitBoard(17247240192), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34630270976), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17246978048), BitBoard(0), BitBoard(0), BitBoard(34628173824), BitBoard(0), BitBoard(0), BitBoard(8589934592), BitBoard(0), BitBoard(17179869184), BitBoard(0), BitBoard(34359738368), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2199023255552), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8796093022208), BitBoard(26388279066624), BitBoard(61572651155456), BitBoard(131941395333120), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(562949953421312), BitBoard(0), BitBoard(1125899906842624), BitBoard(0), BitBoard(2251799813685248), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34494482432), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34494480384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(69260541952), BitBoard(17213423616), BitBoard(0), BitBoard(0), BitBoard(34493956096), BitBoard(0), BitBoard(0), BitBoard(69256347648), BitBoard(0), BitBoard(0), BitBoard(17179869184), BitBoard(0), BitBoard(34359738368), BitBoard(0), BitBoard(68719476736), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(6597069766656), BitBoard(4398046511104), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17592186044416), BitBoard(52776558133248), BitBoard(123145302310912), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1125899906842624), BitBoard(0), BitBoard(2251799813685248), BitBoard(0), BitBoard(4503599627370496), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68988964864), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34426978304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68988960768), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(34426847232), BitBoard(0), BitBoard(0), BitBoard(68987912192), BitBoard(0), BitBoard(0), BitBoard(138512695296), BitBoard(0), BitBoard(0), BitBoard(34359738368), BitBoard(0), BitBoard(68719476736), BitBoard(0), BitBoard(137438953472), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(15393162788864), BitBoard(13194139533312), BitBoard(8796093022208), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35184372088832), BitBoard(105553116266496), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2251799813685248), BitBoard(0), BitBoard(4503599627370496), BitBoard(0), BitBoard(9007199254740992), BitBoard(0)],
-    [BitBoard(68853957120), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137977929728), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68853956608), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137977921536), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68853694464), BitBoard(0), BitBoard(0), BitBoard(137975824384), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68719476736), BitBoard(0), BitBoard(137438953472), BitBoard(0), BitBoard(274877906944), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(32985348833280), BitBoard(30786325577728), BitBoard(26388279066624), BitBoard(17592186044416), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70368744177664), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4503599627370496), BitBoard(0), BitBoard(9007199254740992), BitBoard(0), BitBoard(18014398509481984)],
-    [BitBoard(0), BitBoard(137707914240), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(275955859456), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137707913216), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(275955843072), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137707388928), BitBoard(0), BitBoard(0), BitBoard(275951648768), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(137438953472), BitBoard(0), BitBoard(274877906944), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(68169720922112), BitBoard(65970697666560), BitBoard(61572651155456), BitBoard(52776558133248), BitBoard(35184372088832), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9007199254740992), BitBoard(0), BitBoard(18014398509481984), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(275415828480), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(551911718912), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(275415826432), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(551911686144), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(275414777856), BitBoard(0), BitBoard(0), BitBoard(551903297536), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(274877906944), BitBoard(0), BitBoard(549755813888), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(138538465099776), BitBoard(136339441844224), BitBoard(131941395333120), BitBoard(123145302310912), BitBoard(105553116266496), BitBoard(70368744177664), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18014398509481984), BitBoard(0), BitBoard(36028797018963968)],
-    [BitBoard(1103823438080), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2216338399232), BitBoard(0), BitBoard(1103823437824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2216338391040), BitBoard(0), BitBoard(0), BitBoard(1103823372288), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2216337342464), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1103806595072), BitBoard(0), BitBoard(0), BitBoard(2216203124736), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1099511627776), BitBoard(0), BitBoard(2199023255552), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(562949953421312), BitBoard(1688849860263936), BitBoard(3940649673949184), BitBoard(8444249301319680), BitBoard(17451448556060672), BitBoard(35465847065542656), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(2207646876160), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4432676798464), BitBoard(0), BitBoard(2207646875648), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4432676782080), BitBoard(0), BitBoard(0), BitBoard(2207646744576), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4432674684928), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2207613190144), BitBoard(0), BitBoard(0), BitBoard(4432406249472), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2199023255552), BitBoard(0), BitBoard(4398046511104), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1125899906842624), BitBoard(3377699720527872), BitBoard(7881299347898368), BitBoard(16888498602639360), BitBoard(34902897112121344), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(4415293752320), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4415293751296), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8865353564160), BitBoard(0), BitBoard(0), BitBoard(4415293489152), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8865349369856), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4415226380288), BitBoard(0), BitBoard(0), BitBoard(8864812498944), BitBoard(0), BitBoard(0), BitBoard(2199023255552), BitBoard(0), BitBoard(4398046511104), BitBoard(0), BitBoard(8796093022208), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(562949953421312), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2251799813685248), BitBoard(6755399441055744), BitBoard(15762598695796736), BitBoard(33776997205278720), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8830587504640), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8830587502592), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8830586978304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17730698739712), BitBoard(4406636445696), BitBoard(0), BitBoard(0), BitBoard(8830452760576), BitBoard(0), BitBoard(0), BitBoard(17729624997888), BitBoard(0), BitBoard(0), BitBoard(4398046511104), BitBoard(0), BitBoard(8796093022208), BitBoard(0), BitBoard(17592186044416), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1688849860263936), BitBoard(1125899906842624), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4503599627370496), BitBoard(13510798882111488), BitBoard(31525197391593472), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17661175009280), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17661175005184), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8813306445824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17661173956608), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8813272891392), BitBoard(0), BitBoard(0), BitBoard(17660905521152), BitBoard(0), BitBoard(0), BitBoard(35459249995776), BitBoard(0), BitBoard(0), BitBoard(8796093022208), BitBoard(0), BitBoard(17592186044416), BitBoard(0), BitBoard(35184372088832), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(3940649673949184), BitBoard(3377699720527872), BitBoard(2251799813685248), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9007199254740992), BitBoard(27021597764222976), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35322350018560), BitBoard(0), BitBoard(0), BitBoard(17626613022720), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35322350010368), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17626612891648), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35322347913216), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17626545782784), BitBoard(0), BitBoard(0), BitBoard(35321811042304), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17592186044416), BitBoard(0), BitBoard(35184372088832), BitBoard(0), BitBoard(70368744177664), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(8444249301319680), BitBoard(7881299347898368), BitBoard(6755399441055744), BitBoard(4503599627370496), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18014398509481984), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(35253226045952), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70644700037120), BitBoard(0), BitBoard(0), BitBoard(35253226045440), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70644700020736), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35253225783296), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70644695826432), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35253091565568), BitBoard(0), BitBoard(0), BitBoard(70643622084608), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35184372088832), BitBoard(0), BitBoard(70368744177664), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(17451448556060672), BitBoard(16888498602639360), BitBoard(15762598695796736), BitBoard(13510798882111488), BitBoard(9007199254740992), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(0), BitBoard(70506452091904), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(141289400074240), BitBoard(0), BitBoard(0), BitBoard(70506452090880), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(141289400041472), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70506451566592), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(141289391652864), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70506183131136), BitBoard(0), BitBoard(0), BitBoard(141287244169216), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(70368744177664), BitBoard(0), BitBoard(140737488355328), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(35465847065542656), BitBoard(34902897112121344), BitBoard(33776997205278720), BitBoard(31525197391593472), BitBoard(27021597764222976), BitBoard(18014398509481984), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(282578800148736), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(567382630219776), BitBoard(282578800148480), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(567382630203392), BitBoard(0), BitBoard(282578800082944), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(567382628106240), BitBoard(0), BitBoard(0), BitBoard(282578783305728), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(567382359670784), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(282574488338432), BitBoard(0), BitBoard(0), BitBoard(567347999932416), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(281474976710656), BitBoard(0), BitBoard(562949953421312), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(144115188075855872), BitBoard(432345564227567616), BitBoard(1008806316530991104), BitBoard(2161727821137838080), BitBoard(4467570830351532032), BitBoard(9079256848778919936)],
-    [BitBoard(0), BitBoard(565157600297472), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(565157600296960), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1134765260406784), BitBoard(0), BitBoard(565157600165888), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1134765256212480), BitBoard(0), BitBoard(0), BitBoard(565157566611456), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1134764719341568), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(565148976676864), BitBoard(0), BitBoard(0), BitBoard(1134695999864832), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(562949953421312), BitBoard(0), BitBoard(1125899906842624), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(288230376151711744), BitBoard(864691128455135232), BitBoard(2017612633061982208), BitBoard(4323455642275676160), BitBoard(8935141660703064064)],
-    [BitBoard(0), BitBoard(0), BitBoard(1130315200594944), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1130315200593920), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1130315200331776), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2269530512424960), BitBoard(0), BitBoard(0), BitBoard(1130315133222912), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2269529438683136), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1130297953353728), BitBoard(0), BitBoard(0), BitBoard(2269391999729664), BitBoard(0), BitBoard(0), BitBoard(562949953421312), BitBoard(0), BitBoard(1125899906842624), BitBoard(0), BitBoard(2251799813685248), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(144115188075855872), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(576460752303423488), BitBoard(1729382256910270464), BitBoard(4035225266123964416), BitBoard(8646911284551352320)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2260630401189888), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2260630401187840), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2260630400663552), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2260630266445824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4539058877366272), BitBoard(1128098930098176), BitBoard(0), BitBoard(0), BitBoard(2260595906707456), BitBoard(0), BitBoard(0), BitBoard(4538783999459328), BitBoard(0), BitBoard(0), BitBoard(1125899906842624), BitBoard(0), BitBoard(2251799813685248), BitBoard(0), BitBoard(4503599627370496), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(432345564227567616), BitBoard(288230376151711744), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1152921504606846976), BitBoard(3458764513820540928), BitBoard(8070450532247928832)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4521260802379776), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4521260802375680), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4521260801327104), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2256206450130944), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4521260532891648), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2256197860196352), BitBoard(0), BitBoard(0), BitBoard(4521191813414912), BitBoard(0), BitBoard(0), BitBoard(9077567998918656), BitBoard(0), BitBoard(0), BitBoard(2251799813685248), BitBoard(0), BitBoard(4503599627370496), BitBoard(0), BitBoard(9007199254740992), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(1008806316530991104), BitBoard(864691128455135232), BitBoard(576460752303423488), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2305843009213693952), BitBoard(6917529027641081856)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9042521604759552), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9042521604751360), BitBoard(0), BitBoard(0), BitBoard(4512412933816320), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9042521602654208), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4512412900261888), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9042521065783296), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4512395720392704), BitBoard(0), BitBoard(0), BitBoard(9042383626829824), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4503599627370496), BitBoard(0), BitBoard(9007199254740992), BitBoard(0), BitBoard(18014398509481984), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(2161727821137838080), BitBoard(2017612633061982208), BitBoard(1729382256910270464), BitBoard(1152921504606846976), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4611686018427387904)],
-    [BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18085043209519104), BitBoard(0), BitBoard(9024825867763712), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18085043209502720), BitBoard(0), BitBoard(0), BitBoard(9024825867632640), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18085043205308416), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9024825800523776), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18085042131566592), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9024791440785408), BitBoard(0), BitBoard(0), BitBoard(18084767253659648), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9007199254740992), BitBoard(0), BitBoard(18014398509481984), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(4467570830351532032), BitBoard(4323455642275676160), BitBoard(4035225266123964416), BitBoard(3458764513820540928), BitBoard(2305843009213693952), BitBoard(0), BitBoard(0), BitBoard(0)],
-    [BitBoard(18049651735527936), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(36170086419038208), BitBoard(0), BitBoard(18049651735527424), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(36170086419005440), BitBoard(0), BitBoard(0), BitBoard(18049651735265280), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(36170086410616832), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18049651601047552), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(36170084263133184), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18049582881570816), BitBoard(0), BitBoard(0), BitBoard(36169534507319296), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(18014398509481984), BitBoard(0), BitBoard(36028797018963968), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(0), BitBoard(9079256848778919936), BitBoard(8935141660703064064), BitBoard(8646911284551352320), BitBoard(8070450532247928832), BitBoard(6917529027641081856), BitBoard(4611686018427387904), BitBoard(0), BitBoard(0)]
-];
\ No newline at end of file
@@ -4,7 +4,7 @@ use super::attacks::*;
 use crate::chess::{bitboard::*, square::*};
 
 /// All attacks are stored in the same buffer, each square for bishop/rook gets its slice of this
-static mut ATTACKS: [BitBoard; 87988] = [EMPTY_BB; 87988];
+const ATTACKS_SIZE: usize = 87988;
 
 // Black magics, contain the magic number and attack table index
 #[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Debug, Default)]
@@ -76,8 +76,8 @@ const DEFAULT_ROOK_MAGICS: [BlackMagic; SQUARE_COUNT] = [
     M!(0x0002000308482882,  1009)
 ];
 
-// Magics are lazily initialized at startup
-pub struct Magics {
+// Magics are lazily initialized on first use
+struct Magics {
     magics: [BlackMagic; SQUARE_COUNT],
     notmasks: BB64,
     shift: usize,
@@ -85,7 +85,7 @@ pub struct Magics {
     attack_gen: fn(Square, BitBoard) -> BitBoard,
 }
 
-pub static mut BISHOP_MAGICS: Magics = Magics {
+const BISHOP_MAGICS: Magics = Magics {
     magics: DEFAULT_BISHOP_MAGICS,
     notmasks: EMPTY_BB64,
     shift: 9,
@@ -93,7 +93,7 @@ pub static mut BISHOP_MAGICS: Magics = Magics {
     attack_gen: mask_bishop_attacks,
 };
 
-pub static mut ROOK_MAGICS: Magics = Magics {
+const ROOK_MAGICS: Magics = Magics {
     magics: DEFAULT_ROOK_MAGICS,
     notmasks: EMPTY_BB64,
     shift: 12,
@@ -102,7 +102,8 @@ pub static mut ROOK_MAGICS: Magics = Magics {
 };
 
 impl Magics {
-    pub fn init(&mut self) {
+    /// Fill in the masks, and write the attacks of every square into the shared buffer
+    fn init(&mut self, attacks: &mut [BitBoard]) {
         for square in ALL_SQUARES {
             let size = 1 << self.shift;
             let occupancies = (self.occupancy_gen)(square);
@@ -112,7 +113,7 @@ impl Magics {
                 let blockers = set_occupancy(occupancies, idx);
                 let index = self.magic_map(square, blockers);
 
-                unsafe { ATTACKS[index] = (self.attack_gen)(square, blockers) }
+                attacks[index] = (self.attack_gen)(square, blockers);
             }
         }
     }
@@ -128,9 +129,44 @@ impl Magics {
 
         relevant_occs + bm.index
     }
+}
+
+/// Bishop and rook magics, along with the attack buffer they share
+pub struct Sliders {
+    bishop: Magics,
+    rook: Magics,
+    attacks: Box<[BitBoard]>,
+}
+
+impl Sliders {
+    pub fn new() -> Self {
+        let mut sliders = Self {
+            bishop: BISHOP_MAGICS,
+            rook: ROOK_MAGICS,
+            attacks: vec![EMPTY_BB; ATTACKS_SIZE].into_boxed_slice(),
+        };
+
+        sliders.bishop.init(&mut sliders.attacks);
+        sliders.rook.init(&mut sliders.attacks);
+        sliders
+    }
 
-    /// Get slider attack from square with given blockers
-    pub fn attacks(&self, square: Square, blockers: BitBoard) -> BitBoard {
-        unsafe { *ATTACKS.get_unchecked(self.magic_map(square, blockers)) }
+    /// Get bishop attacks from square with given blockers
+    pub fn bishop_attacks(&self, square: Square, blockers: BitBoard) -> BitBoard {
+        // Magic indices are always within the buffer
+        unsafe {
+            *self
+                .attacks
+                .get_unchecked(self.bishop.magic_map(square, blockers))
+        }
+    }
+
+    /// Get rook attacks from square with given blockers
+    pub fn rook_attacks(&self, square: Square, blockers: BitBoard) -> BitBoard {
+        unsafe {
+            *self
+                .attacks
+                .get_unchecked(self.rook.magic_map(square, blockers))
+        }
     }
 }
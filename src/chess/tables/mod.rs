@@ -5,87 +5,239 @@
 ///
 /// Move tables are used for attacks only and exclude pawn quiet moves, which are calculated on
 /// the fly by the move generator.
-/// I spent a lot of time trying to get const evaluation for the tables to work, but it took far
-/// too long to compile and made the code extremely messy. Since I did not want to go the code
-/// generation route, I ported the static mut idea from Weiawaga.
+/// Leaper attacks, evaluation masks and the between table are generated by const fns at compile
+/// time. Filling the magic attack buffer takes far more steps than the const evaluator allows, so
+/// the slider, lmr and cuckoo tables are still built on first access, each one in a OnceLock.
 mod attacks;
 mod magics;
+mod prng;
 
 #[rustfmt::skip]
 mod constants;
 
 use std::cmp::min;
+use std::sync::OnceLock;
 
 use attacks::*;
 pub use constants::*;
 use magics::*;
+pub use prng::*;
 
-use crate::chess::{bitboard::*, piece::*, square::*};
+use crate::chess::{bitboard::*, moves::*, piece::*, square::*};
 use crate::engine::search_params::{LMR_BASE, LMR_FACTOR};
 
-/// Precalculated attack tables for leaper pieces
+/// Precalculated attack tables for leaper pieces, and evaluation masks
 struct Tables {
     pub pawn_attacks: [BB64; 2],
+    pub pawn_pushes: [[Option<Square>; SQUARE_COUNT]; 2],
+    pub pawn_double_pushes: [[Option<Square>; SQUARE_COUNT]; 2],
     pub knight_attacks: BB64,
     pub king_attacks: BB64,
+    pub outpost_masks: [BB64; 2],
+    pub king_rings: BB64,
+    pub king_zones: [BB64; 2],
+    pub passed_masks: [BB64; 2],
+    pub isolated_masks: BB64,
+    pub front_spans: [BB64; 2],
+    pub rear_spans: [BB64; 2],
 }
 
-static mut TABLES: Tables = Tables {
-    pawn_attacks: [EMPTY_BB64; 2],
-    knight_attacks: EMPTY_BB64,
-    king_attacks: EMPTY_BB64,
-};
+static TABLES: Tables = Tables::new();
+static SLIDERS: OnceLock<Sliders> = OnceLock::new();
 
-/// Leaper attack table initialization
+/// Bitboard of squares between two squares, excluding the squares themselves
+pub static BETWEEN: [BB64; SQUARE_COUNT] = between_table();
+
+/// Leaper attack table generation
 impl Tables {
-    fn init(&mut self) {
-        for square in ALL_SQUARES {
-            if square.rank() != Rank::Eight {
-                self.pawn_attacks[0][square as usize] = mask_pawn_attacks(square, Color::White);
+    const fn new() -> Self {
+        let mut tables = Self {
+            pawn_attacks: [EMPTY_BB64; 2],
+            pawn_pushes: [[None; SQUARE_COUNT]; 2],
+            pawn_double_pushes: [[None; SQUARE_COUNT]; 2],
+            knight_attacks: EMPTY_BB64,
+            king_attacks: EMPTY_BB64,
+            outpost_masks: [EMPTY_BB64; 2],
+            king_rings: EMPTY_BB64,
+            king_zones: [EMPTY_BB64; 2],
+            passed_masks: [EMPTY_BB64; 2],
+            isolated_masks: EMPTY_BB64,
+            front_spans: [EMPTY_BB64; 2],
+            rear_spans: [EMPTY_BB64; 2],
+        };
+
+        let mut sq = 0;
+        while sq < SQUARE_COUNT {
+            let square = ALL_SQUARES[sq];
+            if !matches!(square.rank(), Rank::Eight) {
+                tables.pawn_attacks[0][sq] = mask_pawn_attacks(square, Color::White);
+                tables.pawn_pushes[0][sq] = Some(square.forward(Color::White));
             }
-            if square.rank() != Rank::First {
-                self.pawn_attacks[1][square as usize] = mask_pawn_attacks(square, Color::Black);
+            if !matches!(square.rank(), Rank::First) {
+                tables.pawn_attacks[1][sq] = mask_pawn_attacks(square, Color::Black);
+                tables.pawn_pushes[1][sq] = Some(square.forward(Color::Black));
+            }
+            if square.is_start_square(Color::White) {
+                tables.pawn_double_pushes[0][sq] = Some(square.up().up());
             }
+            if square.is_start_square(Color::Black) {
+                tables.pawn_double_pushes[1][sq] = Some(square.down().down());
+            }
+
+            tables.knight_attacks[sq] = mask_knight_attacks(square);
+            tables.king_attacks[sq] = mask_king_attacks(square);
+
+            tables.outpost_masks[0][sq] = mask_outpost(square, Color::White);
+            tables.outpost_masks[1][sq] = mask_outpost(square, Color::Black);
+            tables.king_rings[sq] = mask_king_ring(square);
+            tables.king_zones[0][sq] = mask_king_zone(square, Color::White);
+            tables.king_zones[1][sq] = mask_king_zone(square, Color::Black);
 
-            self.knight_attacks[square as usize] = mask_knight_attacks(square);
-            self.king_attacks[square as usize] = mask_king_attacks(square);
+            tables.passed_masks[0][sq] = mask_passed(square, Color::White);
+            tables.passed_masks[1][sq] = mask_passed(square, Color::Black);
+            tables.isolated_masks[sq] = mask_isolated(square);
+            tables.front_spans[0][sq] = square.to_board().front_span(Color::White);
+            tables.front_spans[1][sq] = square.to_board().front_span(Color::Black);
+            tables.rear_spans[0][sq] = square.to_board().rear_span(Color::White);
+            tables.rear_spans[1][sq] = square.to_board().rear_span(Color::Black);
+            sq += 1;
         }
+
+        tables
     }
 }
 
+/// Squares between every pair of squares
+const fn between_table() -> [BB64; SQUARE_COUNT] {
+    let mut between = [EMPTY_BB64; SQUARE_COUNT];
+
+    let mut src = 0;
+    while src < SQUARE_COUNT {
+        let mut tgt = 0;
+        while tgt < SQUARE_COUNT {
+            between[src][tgt] = mask_between(ALL_SQUARES[src], ALL_SQUARES[tgt]);
+            tgt += 1;
+        }
+        src += 1;
+    }
+
+    between
+}
+
 /// Precalculated lmr reduction table (values from Asymptote)
 /// Using ln(depth) * ln(move_count) we can have near-linear tree growth.
 struct LMRTable {
     reductions: [[usize; 64]; 64],
 }
 
-static mut LMR_TABLE: LMRTable = LMRTable {
-    reductions: [[0; 64]; 64],
-};
+static LMR_TABLE: OnceLock<LMRTable> = OnceLock::new();
 
 impl LMRTable {
-    fn init(&mut self) {
-        for depth in 1..64 {
-            for move_count in 1..64 {
-                let reduction =
-                    LMR_BASE + (depth as f32).ln() * (move_count as f32).ln() / LMR_FACTOR;
+    fn new() -> Self {
+        let mut reductions = [[0; 64]; 64];
 
-                self.reductions[depth][move_count] = reduction as usize;
+        for (depth, row) in reductions.iter_mut().enumerate().skip(1) {
+            for (move_count, reduction) in row.iter_mut().enumerate().skip(1) {
+                *reduction = (LMR_BASE
+                    + (depth as f32).ln() * (move_count as f32).ln() / LMR_FACTOR)
+                    as usize;
             }
         }
+
+        Self { reductions }
     }
 }
 
-/// Initialize all attack tables
-pub fn init_all_tables() {
-    unsafe {
-        TABLES.init();
-        LMR_TABLE.init();
-        BISHOP_MAGICS.init();
-        ROOK_MAGICS.init();
+/// Cuckoo hash table of all reversible moves (Marcel van Kervinck's upcoming repetition scheme)
+/// Keys are the zobrist difference between the positions before and after the move, so that
+/// two positions differing by one reversible move can be matched with a single lookup.
+struct CuckooTable {
+    keys: [u64; CUCKOO_SIZE],
+    moves: [Move; CUCKOO_SIZE],
+}
+
+const CUCKOO_SIZE: usize = 8192;
+
+static CUCKOO_TABLE: OnceLock<Box<CuckooTable>> = OnceLock::new();
+
+const fn cuckoo_h1(key: u64) -> usize {
+    (key & 0x1FFF) as usize
+}
+
+const fn cuckoo_h2(key: u64) -> usize {
+    ((key >> 16) & 0x1FFF) as usize
+}
+
+/// Cuckoo table initialization, from the leaper and slider attack tables
+impl CuckooTable {
+    fn new() -> Box<Self> {
+        let mut table = Box::new(CuckooTable {
+            keys: [0; CUCKOO_SIZE],
+            moves: [NULL_MOVE; CUCKOO_SIZE],
+        });
+
+        for piece in ALL_PIECES {
+            if piece.is_pawn() {
+                continue;
+            }
+
+            for src in ALL_SQUARES {
+                let targets = if piece.is_knight() {
+                    knight_attacks(src)
+                } else if piece.is_bishop() {
+                    bishop_attacks(src, EMPTY_BB)
+                } else if piece.is_rook() {
+                    rook_attacks(src, EMPTY_BB)
+                } else if piece.is_queen() {
+                    queen_attacks(src, EMPTY_BB)
+                } else {
+                    king_attacks(src)
+                };
+
+                for tgt in targets {
+                    if tgt <= src {
+                        continue;
+                    }
+
+                    let mut key = PIECE_KEYS[piece as usize][src as usize]
+                        ^ PIECE_KEYS[piece as usize][tgt as usize]
+                        ^ SIDE_KEY;
+                    let mut m = Move::new(src, tgt, MoveType::Quiet);
+                    let mut i = cuckoo_h1(key);
+
+                    // Insert, kicking out the previous entry until an empty slot is found
+                    loop {
+                        std::mem::swap(&mut table.keys[i], &mut key);
+                        std::mem::swap(&mut table.moves[i], &mut m);
+
+                        if m == NULL_MOVE {
+                            break;
+                        }
+
+                        i = if i == cuckoo_h1(key) {
+                            cuckoo_h2(key)
+                        } else {
+                            cuckoo_h1(key)
+                        };
+                    }
+                }
+            }
+        }
+
+        table
     }
 }
 
+/// Tables used to be initialized manually before any use, they now need no setup
+#[deprecated(note = "tables are generated at compile time or initialized on first access")]
+#[allow(dead_code)]
+pub fn init_all_tables() {}
+
+#[inline(always)]
+fn sliders() -> &'static Sliders {
+    SLIDERS.get_or_init(Sliders::new)
+}
+
 /// Gets pawn attacks from tables
 pub fn pawn_attacks(square: Square, side: Color) -> BitBoard {
     unsafe {
@@ -96,6 +248,26 @@ pub fn pawn_attacks(square: Square, side: Color) -> BitBoard {
     }
 }
 
+/// Gets the square a pawn of the given side is pushed to, none on the last rank
+pub fn pawn_push(square: Square, side: Color) -> Option<Square> {
+    unsafe {
+        *TABLES
+            .pawn_pushes
+            .get_unchecked(side as usize)
+            .get_unchecked(square as usize)
+    }
+}
+
+/// Gets the square a pawn of the given side is double pushed to, only set on its starting rank
+pub fn pawn_double_push(square: Square, side: Color) -> Option<Square> {
+    unsafe {
+        *TABLES
+            .pawn_double_pushes
+            .get_unchecked(side as usize)
+            .get_unchecked(square as usize)
+    }
+}
+
 /// Gets knight attacks from tables
 pub fn knight_attacks(square: Square) -> BitBoard {
     unsafe { *TABLES.knight_attacks.get_unchecked(square as usize) }
@@ -106,14 +278,74 @@ pub fn king_attacks(square: Square) -> BitBoard {
     unsafe { *TABLES.king_attacks.get_unchecked(square as usize) }
 }
 
+/// Gets the squares from which enemy pawns could attack the square, for a piece of the given side
+pub fn outpost_mask(square: Square, side: Color) -> BitBoard {
+    unsafe {
+        *TABLES
+            .outpost_masks
+            .get_unchecked(side as usize)
+            .get_unchecked(square as usize)
+    }
+}
+
+/// Gets the king ring, the king square and the squares around it
+pub fn king_ring(square: Square) -> BitBoard {
+    unsafe { *TABLES.king_rings.get_unchecked(square as usize) }
+}
+
+/// Gets the king zone of a king of the given side
+pub fn king_zone(square: Square, side: Color) -> BitBoard {
+    unsafe {
+        *TABLES
+            .king_zones
+            .get_unchecked(side as usize)
+            .get_unchecked(square as usize)
+    }
+}
+
+/// Gets the squares in which enemy pawns stop a pawn of the given side from being passed
+pub fn passed_mask(square: Square, side: Color) -> BitBoard {
+    unsafe {
+        *TABLES
+            .passed_masks
+            .get_unchecked(side as usize)
+            .get_unchecked(square as usize)
+    }
+}
+
+/// Gets the files adjacent to the square
+pub fn isolated_mask(square: Square) -> BitBoard {
+    unsafe { *TABLES.isolated_masks.get_unchecked(square as usize) }
+}
+
+/// Gets the squares in front of the square from the point of view of the given side
+pub fn front_span(square: Square, side: Color) -> BitBoard {
+    unsafe {
+        *TABLES
+            .front_spans
+            .get_unchecked(side as usize)
+            .get_unchecked(square as usize)
+    }
+}
+
+/// Gets the squares behind the square from the point of view of the given side
+pub fn rear_span(square: Square, side: Color) -> BitBoard {
+    unsafe {
+        *TABLES
+            .rear_spans
+            .get_unchecked(side as usize)
+            .get_unchecked(square as usize)
+    }
+}
+
 /// Gets bishop attacks based on the blocker bitboard
 pub fn bishop_attacks(square: Square, blockers: BitBoard) -> BitBoard {
-    unsafe { BISHOP_MAGICS.attacks(square, blockers) }
+    sliders().bishop_attacks(square, blockers)
 }
 
 /// Gets rook attacks based on the blocker bitboard
 pub fn rook_attacks(square: Square, blockers: BitBoard) -> BitBoard {
-    unsafe { ROOK_MAGICS.attacks(square, blockers) }
+    sliders().rook_attacks(square, blockers)
 }
 
 /// Gets queen attacks based on the blocker bitboard
@@ -121,20 +353,106 @@ pub fn queen_attacks(square: Square, blockers: BitBoard) -> BitBoard {
     rook_attacks(square, blockers) | bishop_attacks(square, blockers)
 }
 
+/// Looks up the reversible move linking two positions, given the xor of their hashes
+pub fn cuckoo_move(key: u64) -> Option<Move> {
+    let table = CUCKOO_TABLE.get_or_init(CuckooTable::new);
+
+    [cuckoo_h1(key), cuckoo_h2(key)]
+        .into_iter()
+        .find(|&i| table.keys[i] == key)
+        .map(|i| table.moves[i])
+}
+
 /// Gets the lmr reduction given depth and move count
 pub fn lmr_reduction(depth: usize, move_count: usize) -> usize {
     let d = min(depth, 63);
     let m = min(move_count, 63);
-    unsafe { *LMR_TABLE.reductions.get_unchecked(d).get_unchecked(m) }
+    let table = LMR_TABLE.get_or_init(LMRTable::new);
+    unsafe { *table.reductions.get_unchecked(d).get_unchecked(m) }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chess::board::*;
+
+    // Slow generators for the tables, with offsets as (files right, ranks towards the eighth)
+    const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+        (1, 2),
+        (2, 1),
+        (2, -1),
+        (1, -2),
+        (-1, -2),
+        (-2, -1),
+        (-2, 1),
+        (-1, 2),
+    ];
+    const KING_OFFSETS: [(i8, i8); 8] = [
+        (0, 1),
+        (1, 1),
+        (1, 0),
+        (1, -1),
+        (0, -1),
+        (-1, -1),
+        (-1, 0),
+        (-1, 1),
+    ];
+    const DIAGONALS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+    const LINES: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+    /// Square at the given offset from the original, if it is on the board
+    fn try_offset(square: Square, df: i8, dr: i8) -> Option<Square> {
+        let (file, rank) = (square.file() as i8 + df, square.rank() as i8 - dr);
+
+        ((0..8).contains(&file) && (0..8).contains(&rank))
+            .then(|| Square::from((rank * 8 + file) as usize))
+    }
+
+    /// Leaper attacks, one offset at a time
+    fn slow_leaper_attacks(square: Square, offsets: &[(i8, i8)]) -> BitBoard {
+        offsets
+            .iter()
+            .filter_map(|&(df, dr)| try_offset(square, df, dr))
+            .fold(EMPTY_BB, |bb, sq| bb.set_bit(sq))
+    }
+
+    /// Slider attacks, walking every ray until it leaves the board or hits a blocker
+    fn slow_slider_attacks(
+        square: Square,
+        blockers: BitBoard,
+        directions: &[(i8, i8)],
+    ) -> BitBoard {
+        let mut attacks = EMPTY_BB;
+
+        for &(df, dr) in directions {
+            let mut current = square;
+            while let Some(next) = try_offset(current, df, dr) {
+                attacks = attacks.set_bit(next);
+                if blockers.get_bit(next) {
+                    break;
+                }
+                current = next;
+            }
+        }
+
+        attacks
+    }
+
+    /// Squares between two squares: the intersection of the rays from each square towards the
+    /// other, stopped by it. Empty when the squares are not aligned.
+    fn slow_between(src: Square, tgt: Square) -> BitBoard {
+        DIAGONALS
+            .iter()
+            .chain(&LINES)
+            .map(|&(df, dr)| {
+                slow_slider_attacks(src, tgt.to_board(), &[(df, dr)])
+                    & slow_slider_attacks(tgt, src.to_board(), &[(-df, -dr)])
+            })
+            .fold(EMPTY_BB, |between, ray| between | ray)
+    }
 
     #[test]
     fn bishop_table_attacks() {
-        init_all_tables();
         let bb1 = bishop_attacks(Square::E4, BitBoard(1161084283129857));
         let bb2 = bishop_attacks(Square::B7, BitBoard(35253091631104));
 
@@ -146,7 +464,6 @@ mod tests {
 
     #[test]
     fn rook_table_attacks() {
-        init_all_tables();
         let bb1 = rook_attacks(Square::A8, BitBoard(1099511627778));
         let bb2 = rook_attacks(Square::E4, BitBoard(76561335399223296));
 
@@ -155,4 +472,205 @@ mod tests {
         assert_eq!(bb1, BitBoard(1103823438082));
         assert_eq!(bb2, BitBoard(4521393946365952));
     }
+
+    #[test]
+    fn leaper_tables_match_slow_generator() {
+        for square in ALL_SQUARES {
+            assert_eq!(
+                knight_attacks(square),
+                slow_leaper_attacks(square, &KNIGHT_OFFSETS)
+            );
+            assert_eq!(
+                king_attacks(square),
+                slow_leaper_attacks(square, &KING_OFFSETS)
+            );
+
+            // Pawns never stand on their promotion rank, so those entries are left empty
+            if square.rank() != Rank::Eight {
+                let white = slow_leaper_attacks(square, &[(-1, 1), (1, 1)]);
+                assert_eq!(pawn_attacks(square, Color::White), white);
+            }
+            if square.rank() != Rank::First {
+                let black = slow_leaper_attacks(square, &[(-1, -1), (1, -1)]);
+                assert_eq!(pawn_attacks(square, Color::Black), black);
+            }
+        }
+    }
+
+    #[test]
+    fn pawn_pushes_match_slow_generator() {
+        for square in ALL_SQUARES {
+            for (side, ahead, start) in [
+                (Color::White, 1, Rank::Second),
+                (Color::Black, -1, Rank::Seventh),
+            ] {
+                let push = try_offset(square, 0, ahead);
+                let double = try_offset(square, 0, 2 * ahead).filter(|_| square.rank() == start);
+
+                assert_eq!(pawn_push(square, side), push);
+                assert_eq!(pawn_double_push(square, side), double);
+            }
+        }
+    }
+
+    #[test]
+    fn slider_tables_match_slow_generator() {
+        let mut rng = Prng::new(0xB17B0A2D);
+
+        for square in ALL_SQUARES {
+            // Empty and full boards, then random boards of varying density
+            let mut occupancies = vec![EMPTY_BB, FULL_BB];
+            occupancies.extend((0..300).map(|i| {
+                let mut bb = rng.next_u64();
+                for _ in 0..i % 4 {
+                    bb &= rng.next_u64();
+                }
+                BitBoard(bb)
+            }));
+
+            for blockers in occupancies {
+                let bishop = slow_slider_attacks(square, blockers, &DIAGONALS);
+                let rook = slow_slider_attacks(square, blockers, &LINES);
+
+                assert_eq!(
+                    bishop_attacks(square, blockers),
+                    bishop,
+                    "{square} {blockers:?}"
+                );
+                assert_eq!(
+                    rook_attacks(square, blockers),
+                    rook,
+                    "{square} {blockers:?}"
+                );
+                assert_eq!(queen_attacks(square, blockers), bishop | rook);
+            }
+        }
+    }
+
+    #[test]
+    fn between_matches_slow_rays() {
+        for src in ALL_SQUARES {
+            for tgt in ALL_SQUARES {
+                let between = slow_between(src, tgt);
+
+                assert_eq!(BETWEEN[src as usize][tgt as usize], between, "{src} {tgt}");
+                assert_eq!(BETWEEN[tgt as usize][src as usize], between);
+            }
+        }
+    }
+
+    #[test]
+    fn eval_masks_match_brute_force() {
+        let squares = |predicate: &dyn Fn(Square) -> bool| {
+            ALL_SQUARES
+                .into_iter()
+                .filter(|&sq| predicate(sq))
+                .fold(EMPTY_BB, |bb, sq| bb.set_bit(sq))
+        };
+        let mirror = |bb: BitBoard| BitBoard(bb.0.swap_bytes());
+
+        for src in ALL_SQUARES {
+            // Ranks ahead are positive for White, negative for Black
+            for (side, ahead) in [(Color::White, 1), (Color::Black, -1)] {
+                let front = squares(&|tgt| {
+                    let (df, dr) = src.dist(tgt);
+                    df == 0 && dr * ahead > 0
+                });
+                let rear = squares(&|tgt| {
+                    let (df, dr) = src.dist(tgt);
+                    df == 0 && dr * ahead < 0
+                });
+                let passed = squares(&|tgt| {
+                    let (df, dr) = src.dist(tgt);
+                    df.abs() <= 1 && dr * ahead > 0
+                });
+                let outpost = squares(&|tgt| {
+                    let (df, dr) = src.dist(tgt);
+                    df.abs() == 1 && dr * ahead > 0
+                });
+
+                assert_eq!(front_span(src, side), front);
+                assert_eq!(rear_span(src, side), rear);
+                assert_eq!(passed_mask(src, side), passed);
+                assert_eq!(outpost_mask(src, side), outpost);
+            }
+
+            // Kings on the edge must not wrap around to the other side of the board
+            let ring = squares(&|tgt| src.distance(tgt) <= 1);
+            assert_eq!(king_ring(src), ring);
+            for (side, ahead) in [(Color::White, 1), (Color::Black, -1)] {
+                let zone = ring
+                    | squares(&|tgt| {
+                        let (df, dr) = src.dist(tgt);
+                        df.abs() <= 1 && dr == 2 * ahead
+                    });
+                assert_eq!(king_zone(src, side), zone);
+            }
+
+            let isolated = squares(&|tgt| src.dist(tgt).0.abs() == 1);
+            assert_eq!(isolated_mask(src), isolated);
+
+            let black = src.flipv();
+            assert_eq!(
+                passed_mask(black, Color::Black),
+                mirror(passed_mask(src, Color::White))
+            );
+            assert_eq!(
+                outpost_mask(black, Color::Black),
+                mirror(outpost_mask(src, Color::White))
+            );
+            assert_eq!(
+                front_span(black, Color::Black),
+                mirror(front_span(src, Color::White))
+            );
+            assert_eq!(
+                rear_span(black, Color::Black),
+                mirror(rear_span(src, Color::White))
+            );
+            assert_eq!(isolated_mask(black), mirror(isolated_mask(src)));
+            assert_eq!(king_ring(black), mirror(king_ring(src)));
+            assert_eq!(
+                king_zone(black, Color::Black),
+                mirror(king_zone(src, Color::White))
+            );
+        }
+    }
+
+    #[test]
+    fn cuckoo_table_size() {
+        let count = CUCKOO_TABLE
+            .get_or_init(CuckooTable::new)
+            .moves
+            .iter()
+            .filter(|&&m| m != NULL_MOVE)
+            .count();
+
+        assert_eq!(count, 3668);
+    }
+
+    #[test]
+    fn concurrent_first_use() {
+        // No setup: every thread races to build the tables on its first lookup
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    let board = Board::default();
+                    let kiwipete: Board =
+                        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"
+                            .parse()
+                            .unwrap();
+
+                    (
+                        board.gen_moves::<QUIETS>().len(),
+                        kiwipete.gen_moves::<QUIETS>().len(),
+                        lmr_reduction(10, 10),
+                    )
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), (20, 48, lmr_reduction(10, 10)));
+        }
+    }
 }
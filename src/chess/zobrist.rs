@@ -35,6 +35,19 @@ impl ZHash {
         hash
     }
 
+    /// Hash of the pawns only, used to index pawn structure caches
+    pub fn new_pawn(board: &Board) -> ZHash {
+        let mut hash: ZHash = ZHash(0);
+
+        for piece in [Piece::WP, Piece::BP] {
+            for square in board.piece_bb[piece as usize] {
+                hash.toggle_piece(piece, square);
+            }
+        }
+
+        hash
+    }
+
     /// Toggle when piece moves to/from square
     pub fn toggle_piece(&mut self, piece: Piece, square: Square) {
         self.0 ^= PIECE_KEYS[piece as usize][square as usize];
@@ -82,6 +95,7 @@ mod tests {
 
         assert_eq!(ZHash::new(&b1), ZHash(11231077536533049824)); // correct start hash
         assert_eq!(ZHash::new(&b2), b2.hash); // try_from() builds hash correctly
+        assert_eq!(ZHash::new_pawn(&b2), b2.pawn_hash);
     }
 
     #[test]
@@ -108,12 +122,13 @@ mod tests {
 
         // z1 is the same as we obtained through incremental hash updates in make move
         assert_eq!(z1, b2.hash);
+        assert_eq!(b1.pawn_hash, b2.pawn_hash);
     }
 
     #[test]
     pub fn test_hash_enpassant() {
         // testing white pawn capturing en passant
-        let b1: Board = "rnbqkb1r/pp1p1pPp/8/2p1pP2/1P1P4/3P3P/P1P1P3/RNBQKBNR w KQkq e6 0 1"
+        let b1: Board = "rnbqkb1r/pp1p1pPp/8/2p1pP2/1P1P4/3P4/P1P1P3/RNBQKBNR w KQkq e6 0 1"
             .parse()
             .unwrap();
         let m = Move::new(Square::F5, Square::E6, MoveType::EnPassant);
@@ -129,12 +144,30 @@ mod tests {
 
         // z1 is the same as we obtained through incremental hash updates in make move
         assert_eq!(z1, b2.hash);
+        assert_eq!(ZHash::new_pawn(&b2), b2.pawn_hash);
+
+        // En passant squares no pawn can capture on are dropped, from FENs and double pushes
+        let phantom: Board = "4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1".parse().unwrap();
+        let plain: Board = "4k3/8/8/8/4P3/8/8/4K3 b - - 0 1".parse().unwrap();
+        assert_eq!((phantom.hash, phantom.en_passant), (plain.hash, None));
+        assert_eq!(phantom, plain);
+
+        let b1: Board = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1".parse().unwrap();
+        let pushed = b1.make_move(b1.find_move("e2e4").unwrap());
+        assert_eq!(pushed, plain);
+        assert_eq!(ZHash::new(&pushed), pushed.hash);
+
+        let b1: Board = "4k3/8/8/8/5p2/8/4P3/4K3 w - - 0 1".parse().unwrap();
+        let pushed = b1.make_move(b1.find_move("e2e4").unwrap());
+        assert_eq!(pushed.en_passant, Some(Square::E3));
+        assert_eq!(ZHash::new(&pushed), pushed.hash);
+        assert_eq!(pushed.to_fen(), "4k3/8/8/8/4Pp2/8/8/4K3 b - e3 0 1");
     }
 
     #[test]
     pub fn test_hash_null() {
         // testing null move
-        let b: Board = "rnbqkb1r/pp1p1pPp/8/2p1pP2/1P1P4/3P3P/P1P1P3/RNBQKBNR w KQkq e6 0 1"
+        let b: Board = "rnbqkb1r/pp1p1pPp/8/2p1pP2/1P1P4/3P4/P1P1P3/RNBQKBNR w KQkq e6 0 1"
             .parse()
             .unwrap();
         let mut z1 = b.hash;
@@ -144,5 +177,16 @@ mod tests {
         // z1 is the same as we obtained through incremental hash updates in make move
         let b2 = b.make_null();
         assert_eq!(z1, b2.hash);
+        assert_eq!(ZHash::new(&b2), b2.hash);
+        assert_eq!((b2.side, b2.en_passant), (Color::Black, None));
+
+        // Passing twice only loses the en passant square
+        let b3 = b2.make_null();
+        assert_eq!(ZHash::new(&b3), b3.hash);
+        let mut z3 = b.hash;
+        z3.toggle_ep(Square::E6);
+        assert_eq!(b3.hash, z3);
+        assert_eq!(b3.make_null().make_null().hash, b3.hash);
+        assert_eq!(b3.to_fen(), b.to_fen().replace(" e6 ", " - "));
     }
 }
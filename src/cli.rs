@@ -0,0 +1,182 @@
+/// Command line arguments, parsed by hand to keep the base engine free of dependencies.
+///
+/// With no arguments Carp speaks UCI. The other commands run once and exit, with a non-zero exit
+/// code on failure:
+///
+///     carp bench [depth]                  OpenBench benchmark, at depth 13 by default
+///     carp perft [--hash] <depth> [fen]   divide perft, from the starting position by default
+///     carp eval <fen>                     traced static evaluation
+///     carp datagen ...                    data generation and other tools, with the tools feature
+///
+/// FENs can be given either as a single quoted argument or as separate words.
+use crate::chess::board::*;
+use crate::engine::{bench, hce, position::*, search_params::*};
+
+const USAGE: &str =
+    "Usage: carp [bench [depth] | perft [--hash] <depth> [fen] | eval <fen> | datagen ...]";
+
+#[derive(Debug)]
+pub enum CliCommand {
+    Uci,
+    Bench(usize),
+    Perft(usize, bool, Box<Board>),
+    Eval(Box<Board>),
+    Tools,
+}
+
+impl CliCommand {
+    /// Parse the arguments following the executable name
+    pub fn parse<S: AsRef<str>>(args: &[S]) -> Result<Self, &'static str> {
+        let mut args = args.iter().map(AsRef::as_ref);
+
+        match args.next() {
+            None => Ok(Self::Uci),
+            Some("bench") => {
+                let depth = match args.next() {
+                    Some(depth) => depth.parse().map_err(|_| "Could not parse depth!")?,
+                    None => bench::BENCH_DEPTH,
+                };
+
+                match args.next() {
+                    Some(_) => Err("Too many arguments!"),
+                    None if depth == 0 => Err("Depth must be positive!"),
+                    None => Ok(Self::Bench(depth)),
+                }
+            }
+            Some("perft") => {
+                let mut args = args.peekable();
+                let hashed = args.next_if_eq(&"--hash").is_some();
+                let depth: usize = args
+                    .next()
+                    .ok_or("No depth!")?
+                    .parse()
+                    .map_err(|_| "Could not parse depth!")?;
+                if depth == 0 {
+                    return Err("Depth must be positive!");
+                }
+
+                let fen = args.collect::<Vec<&str>>().join(" ");
+                let board = match fen.is_empty() {
+                    true => Board::default(),
+                    false => fen.parse()?,
+                };
+
+                Ok(Self::Perft(depth, hashed, Box::new(board)))
+            }
+            Some("eval") => {
+                let fen = args.collect::<Vec<&str>>().join(" ");
+                if fen.is_empty() {
+                    return Err("No fen!");
+                }
+
+                Ok(Self::Eval(Box::new(fen.parse()?)))
+            }
+            Some("datagen" | "merge" | "selfplay" | "tune") => Ok(Self::Tools),
+            Some(_) => Err("Unknown command!"),
+        }
+    }
+
+    /// Run a command which exits when done, the UCI loop is left to the caller
+    pub fn run(self) -> Result<(), &'static str> {
+        match self {
+            Self::Uci => Ok(()),
+            Self::Bench(depth) => {
+                bench::run_benchmark(depth);
+                Ok(())
+            }
+            Self::Perft(depth, hashed, board) => {
+                board.perft(depth, hashed);
+                Ok(())
+            }
+            Self::Eval(board) => {
+                let position = Position::from(*board);
+                let pawn_table = &mut hce::PawnTable::new(1);
+                println!(
+                    "Static evaluation: {}\n",
+                    position.evaluate(pawn_table, -INFINITY, INFINITY)
+                );
+                print!("{}", hce::trace(&position.board, pawn_table));
+                Ok(())
+            }
+            Self::Tools => Self::run_tools(),
+        }
+    }
+
+    #[cfg(feature = "tools")]
+    fn run_tools() -> Result<(), &'static str> {
+        crate::tools::parse_cli();
+        Ok(())
+    }
+
+    #[cfg(not(feature = "tools"))]
+    fn run_tools() -> Result<(), &'static str> {
+        Err("Carp was built without the tools feature!")
+    }
+}
+
+/// Print the error with the usage string
+pub fn report(err: &str) {
+    eprintln!("{err}\n{USAGE}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn parse(args: &str) -> Result<CliCommand, &'static str> {
+        CliCommand::parse(&args.split_whitespace().collect::<Vec<&str>>())
+    }
+
+    /// FEN of the board given to perft or eval
+    fn fen(command: Result<CliCommand, &'static str>) -> String {
+        match command {
+            Ok(CliCommand::Perft(_, _, board) | CliCommand::Eval(board)) => board.to_fen(),
+            other => panic!("Unexpected command {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert!(matches!(parse(""), Ok(CliCommand::Uci)));
+        assert!(matches!(
+            parse("bench"),
+            Ok(CliCommand::Bench(bench::BENCH_DEPTH))
+        ));
+        assert!(matches!(parse("bench 7"), Ok(CliCommand::Bench(7))));
+        assert!(matches!(
+            parse("perft 4"),
+            Ok(CliCommand::Perft(4, false, _))
+        ));
+        assert!(matches!(
+            parse("perft --hash 6"),
+            Ok(CliCommand::Perft(6, true, _))
+        ));
+        assert!(matches!(
+            parse("datagen -g 256 -t 32"),
+            Ok(CliCommand::Tools)
+        ));
+
+        // FENs are accepted quoted as one argument or split into words
+        assert_eq!(fen(parse("perft 4")), Board::default().to_fen());
+        assert_eq!(fen(parse(&format!("perft 3 {KIWIPETE}"))), KIWIPETE);
+        assert_eq!(fen(CliCommand::parse(&["perft", "3", KIWIPETE])), KIWIPETE);
+        assert_eq!(fen(parse(&format!("eval {KIWIPETE}"))), KIWIPETE);
+        assert_eq!(fen(CliCommand::parse(&["eval", KIWIPETE])), KIWIPETE);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("bench x").is_err());
+        assert!(parse("bench 0").is_err());
+        assert!(parse("bench 7 8").is_err());
+        assert!(parse("perft").is_err());
+        assert!(parse("perft 0").is_err());
+        assert!(parse("perft --hash").is_err());
+        assert!(parse("perft 4 --hash").is_err());
+        assert!(parse("perft 3 8/8/8 w").is_err());
+        assert!(parse("eval").is_err());
+        assert!(parse("go depth 5").is_err());
+    }
+}
@@ -57,22 +57,94 @@ const TEST_POSITIONS: [&str; 50] = [
     "fen 2r2b2/5p2/5k2/p1r1pP2/P2pB3/1P3P2/K1P3R1/7R w - - 23 93",
 ];
 
-/// Runs benchmark positions to depth 16
-pub fn run_benchmark() {
-    let mut nodes = 0;
-    let mut time = 0;
+pub const BENCH_DEPTH: usize = 13;
+const BENCH_HASH_MB: usize = 16;
+
+/// Accumulated result of a bench run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchResult {
+    pub nodes: u64,
+    pub time: u64,
+}
+
+impl BenchResult {
+    /// Nodes searched per second
+    pub fn nps(&self) -> u64 {
+        self.nodes * 1000000 / self.time.max(1)
+    }
+}
+
+/// Searches every benchmark position to a fixed depth, each with a fresh tt
+/// and fresh history tables, accumulating nodes and time (in microseconds)
+pub fn run_bench(depth: usize, hash_mb: usize) -> BenchResult {
+    let mut result = BenchResult { nodes: 0, time: 0 };
 
     for fen in TEST_POSITIONS {
         let mut position: Position = fen.parse().unwrap();
-        let mut t = Thread::fixed_depth(13);
+        let mut t = Thread::fixed_depth(depth);
+        let tt = TT::new(hash_mb);
 
         let start = Instant::now();
-        position.iterative_search::<false>(&mut t, &TT::default());
+        position.iterative_search::<false>(&mut t, &tt);
 
-        nodes += t.nodes;
-        time += start.elapsed().as_micros() as u64;
+        result.nodes += t.nodes;
+        result.time += start.elapsed().as_micros() as u64;
     }
 
-    let nps = nodes * 1000000 / time;
-    println!("{nodes} nodes {nps} nps");
+    result
+}
+
+/// Runs benchmark positions to the given depth (13 for OpenBench) and prints the node count
+pub fn run_benchmark(depth: usize) {
+    let result = run_bench(depth, BENCH_HASH_MB);
+    println!("{} nodes {} nps", result.nodes, result.nps());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_is_deterministic() {
+        let first = run_bench(4, 1);
+        let second = run_bench(4, 1);
+
+        assert!(first.nodes > 0);
+        assert_eq!(first.nodes, second.nodes);
+    }
+
+    /// Strip the wall-clock dependent fields from an info line
+    fn timeless_info(t: &Thread) -> String {
+        let info = t.to_string();
+        let mut tokens = info.split_whitespace();
+        let mut kept = Vec::new();
+
+        while let Some(token) = tokens.next() {
+            if token == "time" || token == "nps" {
+                tokens.next();
+            } else {
+                kept.push(token);
+            }
+        }
+
+        kept.join(" ")
+    }
+
+    #[test]
+    fn test_fixed_depth_info_is_reproducible() {
+        let run = || {
+            TEST_POSITIONS
+                .iter()
+                .map(|fen| {
+                    let mut position: Position = fen.parse().unwrap();
+                    let mut t = Thread::fixed_depth(4);
+                    position.iterative_search::<false>(&mut t, &TT::new(1));
+
+                    timeless_info(&t)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(), run());
+    }
 }
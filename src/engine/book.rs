@@ -0,0 +1,330 @@
+/// Opening books in the Polyglot format.
+///
+/// A book is a list of 16 byte big-endian entries (position key, move, weight, learn data) sorted
+/// by key, so that probing a position is a binary search. Books are built from games given as
+/// lines of UCI moves from the starting position: every move played up to a maximum ply is counted,
+/// and duplicates are merged into a single entry weighted by the number of games playing it.
+///
+/// Positions are keyed with the Polyglot Random64 key and moves use the Polyglot encoding, so books
+/// can be exchanged with other engines and tools.
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::chess::{board::*, moves::*, piece::*, square::*, tables::*};
+
+const ENTRY_SIZE: usize = 16;
+
+// Offsets of the castling, en passant and side to move keys in POLYGLOT_KEYS
+const CASTLE_KEY_OFFSET: usize = 768;
+const EP_KEY_OFFSET: usize = 772;
+const TURN_KEY_OFFSET: usize = 780;
+
+/// Polyglot key of the position. Polyglot lists the black piece of each type first, the reverse
+/// of Carp's piece order. The en passant file is only hashed when a pawn of the side to move can
+/// capture, which the board already guarantees for its en passant square.
+pub fn polyglot_key(board: &Board) -> u64 {
+    let mut key = 0;
+
+    for piece in ALL_PIECES {
+        let kind = piece as usize ^ 1;
+        for square in board.piece_bb[piece as usize] {
+            let row = 7 - square.rank() as usize;
+            key ^= POLYGLOT_KEYS[64 * kind + 8 * row + square.file() as usize];
+        }
+    }
+
+    let rights = [
+        (Color::White, true),
+        (Color::White, false),
+        (Color::Black, true),
+        (Color::Black, false),
+    ];
+    for (i, (side, kingside)) in rights.into_iter().enumerate() {
+        if board.castling_rights.has_right(side, kingside) {
+            key ^= POLYGLOT_KEYS[CASTLE_KEY_OFFSET + i];
+        }
+    }
+
+    if let Some(square) = board.en_passant {
+        key ^= POLYGLOT_KEYS[EP_KEY_OFFSET + square.file() as usize];
+    }
+
+    if board.side == Color::White {
+        key ^= POLYGLOT_KEYS[TURN_KEY_OFFSET];
+    }
+
+    key
+}
+
+/// A single book entry, the weight of a move in a position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub book_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.book_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            book_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+}
+
+/// Polyglot move encoding: target file and row, source file and row (rows counted from the first
+/// rank) and promotion piece, 3 bits each. Castling is encoded as the king capturing its rook.
+pub fn encode_move(board: &Board, m: Move) -> u16 {
+    let coords = |square: Square| (square.file() as u16, 7 - square.rank() as u16);
+    let move_type = m.get_type();
+    let tgt = match move_type {
+        MoveType::Castle => board.castling_squares(m).1,
+        _ => m.get_tgt(),
+    };
+
+    let (src_file, src_row) = coords(m.get_src());
+    let (tgt_file, tgt_row) = coords(tgt);
+    let promotion = match move_type.is_promotion() {
+        true => (move_type as u16 & 0b11) + 1,
+        false => 0,
+    };
+
+    tgt_file | tgt_row << 3 | src_file << 6 | src_row << 9 | promotion << 12
+}
+
+/// Polyglot book, with its entries sorted by key
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    /// Read a book from its binary representation, which must be sorted by key
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err("Book size is not a multiple of the entry size!");
+        }
+
+        let entries: Vec<BookEntry> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(BookEntry::from_bytes)
+            .collect();
+        if entries.windows(2).any(|pair| pair[0].key > pair[1].key) {
+            return Err("Book entries are not sorted!");
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Binary representation of the book
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries.iter().flat_map(|e| e.to_bytes()).collect()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, &'static str> {
+        Self::from_bytes(&fs::read(path).map_err(|_| "Could not read book file!")?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), &'static str> {
+        fs::write(path, self.to_bytes()).map_err(|_| "Could not write book file!")
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Legal book moves in the position with their weights, in book order
+    pub fn probe(&self, board: &Board) -> Vec<(Move, u16)> {
+        let key = polyglot_key(board);
+        let start = self.entries.partition_point(|e| e.key < key);
+        let move_list = board.gen_moves::<QUIETS>();
+        let legal = &move_list.moves[..move_list.len()];
+
+        self.entries[start..]
+            .iter()
+            .take_while(|e| e.key == key)
+            .filter_map(|e| {
+                let m = legal
+                    .iter()
+                    .find(|&&m| encode_move(board, m) == e.book_move)?;
+                Some((*m, e.weight))
+            })
+            .collect()
+    }
+}
+
+/// Accumulates the moves played in a collection of games, up to the given ply
+pub struct BookBuilder {
+    max_ply: usize,
+    counts: HashMap<(u64, u16), u32>,
+}
+
+impl BookBuilder {
+    pub fn new(max_ply: usize) -> Self {
+        Self {
+            max_ply,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Add a game given as UCI moves separated by whitespace, from the starting position
+    pub fn add_game(&mut self, game: &str) -> Result<(), &'static str> {
+        let mut board = Board::default();
+
+        for move_str in game.split_whitespace().take(self.max_ply) {
+            let m = board.find_move(move_str).ok_or("Illegal move in game!")?;
+            *self
+                .counts
+                .entry((polyglot_key(&board), encode_move(&board, m)))
+                .or_default() += 1;
+            board = board.make_move(m);
+        }
+
+        Ok(())
+    }
+
+    /// Add every game in the text, one per line. Empty lines are skipped.
+    pub fn add_games(&mut self, text: &str) -> Result<(), &'static str> {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .try_for_each(|line| self.add_game(line))
+    }
+
+    /// Book sorted by key, then by decreasing weight. Weights saturate at the Polyglot maximum.
+    pub fn build(&self) -> Book {
+        let mut entries: Vec<BookEntry> = self
+            .counts
+            .iter()
+            .map(|(&(key, book_move), &count)| BookEntry {
+                key,
+                book_move,
+                weight: count.min(u16::MAX as u32) as u16,
+                learn: 0,
+            })
+            .collect();
+        entries.sort_by_key(|e| (e.key, std::cmp::Reverse(e.weight), e.book_move));
+
+        Book { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES: &str = "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6\n\
+                         e2e4 c7c5 g1f3 d7d6 d2d4 c5d4\n\n\
+                         d2d4 g8f6 c2c4 e7e6 g1f3 d7d5\n";
+
+    fn play(moves: &str) -> Board {
+        moves.split_whitespace().fold(Board::default(), |board, m| {
+            board.make_move(board.find_move(m).unwrap())
+        })
+    }
+
+    #[test]
+    fn test_polyglot_key() {
+        // Keys published with the Polyglot book format
+        let keys = [
+            ("", 0x463b96181691fc9c),
+            ("e2e4", 0x823c9b50fd114196),
+            ("e2e4 d7d5", 0x0756b94461c50fb0),
+            ("e2e4 d7d5 e4e5", 0x662fafb965db29d4),
+            ("e2e4 d7d5 e4e5 f7f5", 0x22a48b5a8e47ff78),
+            ("e2e4 d7d5 e4e5 f7f5 e1e2", 0x652a607ca3f242c1),
+            ("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7", 0x00fdd303c946bdd9),
+            ("a2a4 b7b5 h2h4 b5b4 c2c4", 0x3c8123ea7b067637),
+            ("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3", 0x5c3f9b829b279560),
+        ];
+
+        for (moves, key) in keys {
+            assert_eq!(polyglot_key(&play(moves)), key, "{moves}");
+        }
+
+        // The same position from a FEN, with an en passant square no pawn can capture on
+        let board: Board = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+            .parse()
+            .unwrap();
+        assert_eq!(polyglot_key(&board), 0x823c9b50fd114196);
+    }
+
+    #[test]
+    fn test_move_encoding() {
+        let board: Board = "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1".parse().unwrap();
+        let encode = |m: &str| encode_move(&board, board.find_move(m).unwrap());
+
+        assert_eq!(encode("e1g1"), 7 | 4 << 6);
+        assert_eq!(encode("e1c1"), 4 << 6);
+        assert_eq!(encode("b7a8q"), 7 << 3 | 1 << 6 | 6 << 9 | 4 << 12);
+        assert_eq!(encode("b7b8n"), 1 | 7 << 3 | 1 << 6 | 6 << 9 | 1 << 12);
+
+        // Chess960 castling already targets the rook
+        let board: Board = "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            encode_move(&board, board.find_move("e1g1").unwrap()),
+            6 | 4 << 6
+        );
+    }
+
+    #[test]
+    fn test_build_and_probe() {
+        let mut builder = BookBuilder::new(4);
+        builder.add_games(GAMES).unwrap();
+        let book = builder.build();
+
+        // Two games start with e4 and one with d4, all moves past the fourth ply are cut
+        let moves = book.probe(&Board::default());
+        let moves: Vec<(String, u16)> = moves.iter().map(|(m, w)| (m.to_string(), *w)).collect();
+        assert_eq!(moves, [("e2e4".to_string(), 2), ("d2d4".to_string(), 1)]);
+
+        let replies = book.probe(&play("e2e4"));
+        assert_eq!(replies.len(), 2);
+        assert!(replies.iter().all(|&(_, weight)| weight == 1));
+
+        assert_eq!(book.probe(&play("e2e4 e7e5 g1f3"))[0].0.to_string(), "b8c6");
+        assert!(book.probe(&play("e2e4 e7e5 g1f3 b8c6")).is_empty());
+        assert!(book.probe(&play("g1f3")).is_empty());
+        assert_eq!(book.len(), 11);
+
+        assert!(BookBuilder::new(4).add_game("e2e4 e2e4").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut builder = BookBuilder::new(8);
+        builder.add_games(GAMES).unwrap();
+        let book = builder.build();
+
+        let bytes = book.to_bytes();
+        assert_eq!(bytes.len(), book.len() * ENTRY_SIZE);
+        assert_eq!(Book::from_bytes(&bytes).unwrap(), book);
+
+        let path = std::env::temp_dir().join("carp_book_round_trip.bin");
+        book.save(&path).unwrap();
+        assert_eq!(Book::load(&path).unwrap(), book);
+        fs::remove_file(path).unwrap();
+
+        assert!(Book::from_bytes(&bytes[1..]).is_err());
+        let unsorted: Vec<u8> = bytes.chunks(ENTRY_SIZE).rev().flatten().copied().collect();
+        assert!(Book::from_bytes(&unsorted).is_err());
+    }
+}
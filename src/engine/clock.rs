@@ -7,6 +7,7 @@ use std::{
 };
 
 use crate::chess::{moves::*, square::*};
+use crate::engine::search_params::*;
 
 /// Time Controls supported by the UCI protocol.
 #[derive(Clone, Debug)]
@@ -74,8 +75,47 @@ impl FromStr for TimeControl {
     }
 }
 
-const CHECK_FREQUENCY: u64 = 2048; // Nodes between checking time/atomic access
+pub const CHECK_FREQUENCY: u64 = 1024; // Nodes between checking time/atomic access
 const OVERHEAD: u64 = 5;
+const CEILING_RESERVE: u64 = 10; // Kept aside on top of the overhead, past any budget
+
+/// Moves added to movestogo, keeping some time in hand for the last moves before the control
+const MOVES_TO_GO_BUFFER: u64 = 2;
+
+/// Optimal and maximum time for a move, given the remaining time and increment of the side to
+/// move. With movestogo, the remaining time is split over the moves left in the period, and the
+/// maximum time never exceeds the remaining time minus the overhead, so that the last move before
+/// the time control cannot overshoot into the next period.
+/// Below LOW_TIME_THRESHOLD, only depth 1 is searched, within a minimal budget.
+fn allocate_time(time: u64, inc: u64, movestogo: Option<u64>) -> (Duration, Duration) {
+    let low_time = time < LOW_TIME_THRESHOLD;
+
+    // When below overhead, make opt and max time 0
+    let time = time - OVERHEAD.min(time);
+    let inc = if time < OVERHEAD { 0 } else { inc };
+    let limit = TIME_MAX_FRACTION * time as f64;
+
+    let (opt, max) = if low_time {
+        let max_time = time as f64 * TIME_HARD_MARGIN / TIME_FACTOR as f64;
+        (0.0, max_time.max(1.0).min(time as f64))
+    } else if let Some(moves) = movestogo {
+        let moves = moves.clamp(1, 50) + MOVES_TO_GO_BUFFER;
+
+        let opt_time = (time as f64 / moves as f64 + inc as f64 * TIME_INC_MARGIN).min(limit);
+        (opt_time, (4.0 * opt_time).min(limit))
+    } else {
+        // This time allocation formula is taken from Svart by Crippa
+        let total = time as f64 / TIME_FACTOR as f64 + inc as f64 * TIME_INC_MARGIN;
+        let max_time = (TIME_HARD_MARGIN * total).min(limit);
+
+        ((TIME_SOFT_MARGIN * total).min(max_time), max_time)
+    };
+
+    (
+        Duration::from_millis(opt as u64),
+        Duration::from_millis(max as u64),
+    )
+}
 
 /// Clocks handle time management during search.
 /// Contains async counters used to synchronize time management/node counting across threads.
@@ -85,8 +125,11 @@ pub struct Clock {
     global_nodes: Arc<AtomicU64>,
     time_control: TimeControl,
     start_time: Instant,
+    base_opt_time: Duration,
     opt_time: Duration,
     max_time: Duration,
+    ceiling: Duration,
+    iteration_start: Duration,
     pub last_nodes: u64,
     node_count: [[u64; SQUARE_COUNT]; SQUARE_COUNT],
 }
@@ -124,38 +167,33 @@ impl Clock {
                     }
                 };
 
-                // When below overhead, make opt and max time 0
-                let time = time - OVERHEAD.min(time);
-                let inc = if time < OVERHEAD { 0 } else { inc };
-
-                // This time allocation formula is taken from Svart by Crippa
-                let (opt, max) = if let Some(moves) = movestogo {
-                    let scale = 0.7 / (moves.min(50) as f64);
-                    let eight = 0.8 * time as f64;
-
-                    let opt_time = (scale * time as f64).min(eight);
-                    (opt_time, (5.0 * opt_time).min(eight))
-                } else {
-                    let total = ((time / 20) + (inc * 3 / 4)) as f64;
-
-                    (0.6 * total, (2.0 * total).min(time as f64))
-                };
-
-                (
-                    Duration::from_millis(opt as u64),
-                    Duration::from_millis(max as u64),
-                )
+                allocate_time(time, inc, movestogo)
             }
             _ => (Duration::ZERO, Duration::ZERO),
         };
 
+        // Absolute limit on the search time, whatever the budgets.
+        // With less time left than the reserve, the hard budget (which still keeps the overhead)
+        // takes over, so that depth 1 can run instead of falling back to the first legal move.
+        let ceiling = match time_control {
+            TimeControl::FixedTime(time) => Duration::from_millis(time - OVERHEAD.min(time)),
+            TimeControl::Variable { wtime, btime, .. } => {
+                let time = if white_to_move { wtime } else { btime };
+                Duration::from_millis(time.saturating_sub(OVERHEAD + CEILING_RESERVE)).max(max_time)
+            }
+            _ => Duration::MAX,
+        };
+
         Self {
             global_stop,
             global_nodes,
             time_control,
             start_time: Instant::now(),
+            base_opt_time: opt_time,
             opt_time,
             max_time,
+            ceiling,
+            iteration_start: Duration::ZERO,
             last_nodes: 0,
             node_count: [[0; SQUARE_COUNT]; SQUARE_COUNT],
         }
@@ -171,20 +209,104 @@ impl Clock {
         self.global_nodes.load(Ordering::SeqCst)
     }
 
+    /// Add the nodes searched since the last batch to the global count.
+    /// Called at the end of a search, so that the global count is exact once all threads are done.
+    pub fn flush_nodes(&mut self, nodes: u64) {
+        self.global_nodes
+            .fetch_add(nodes - self.last_nodes, Ordering::SeqCst);
+        self.last_nodes = nodes;
+    }
+
     /// Returns time elapsed from clock start.
     pub fn elapsed(&self) -> Duration {
         self.start_time.elapsed()
     }
 
+    /// Returns time elapsed from clock start, in microseconds.
+    pub fn elapsed_micros(&self) -> u64 {
+        self.elapsed().as_micros() as u64
+    }
+
+    /// Time left before the soft limit, after which no new iteration starts.
+    /// None for searches not limited by time.
+    pub fn soft_remaining(&self) -> Option<Duration> {
+        self.is_timed()
+            .then(|| self.opt_time.saturating_sub(self.elapsed()))
+    }
+
+    /// Time left before the search is interrupted, by the hard limit or the ceiling.
+    /// None for searches not limited by time.
+    pub fn hard_remaining(&self) -> Option<Duration> {
+        self.is_timed().then(|| {
+            self.max_time
+                .min(self.ceiling)
+                .saturating_sub(self.elapsed())
+        })
+    }
+
+    /// Whether search decisions depend on the wall clock.
+    /// Fixed depth/nodes and infinite searches must never look at elapsed time, so that they
+    /// are fully reproducible.
+    pub fn is_timed(&self) -> bool {
+        matches!(
+            self.time_control,
+            TimeControl::FixedTime(_) | TimeControl::Variable { .. }
+        )
+    }
+
     /// Checks whether there is any time to begin the search
     /// This should only ever be called before beginning a search.
     pub fn no_search_time(&self) -> bool {
-        match self.time_control {
-            TimeControl::FixedTime(_) | TimeControl::Variable { .. } => {
-                self.opt_time == Duration::ZERO
-            }
-            _ => false,
+        self.is_timed() && self.max_time == Duration::ZERO
+    }
+
+    /// Whether the search went past the time that can be spent without flagging.
+    /// Untimed searches never reach the ceiling, and never look at the clock.
+    fn past_ceiling(&self) -> bool {
+        self.is_timed() && self.elapsed() >= self.ceiling
+    }
+
+    /// Soft and hard time budgets for this search.
+    /// The soft budget bounds starting a new iteration, the hard one interrupts the search.
+    pub fn budgets(&self) -> (Duration, Duration) {
+        (self.opt_time, self.max_time)
+    }
+
+    /// Scale the soft limit from its initial value by the given factor, clamped to the configured
+    /// range and never above the hard limit or the ceiling.
+    /// Lets iterative deepening think longer when the best move changes or the score collapses.
+    pub fn scale_soft_limit(&mut self, factor: f64) {
+        let factor = factor.clamp(TIME_SOFT_SCALE_MIN, TIME_SOFT_SCALE_MAX);
+        self.opt_time = self
+            .base_opt_time
+            .mul_f64(factor)
+            .min(self.max_time)
+            .min(self.ceiling);
+    }
+
+    /// Checks whether the next iteration is predicted to complete within the hard limit, its
+    /// duration being the one of the previous iteration times the given branching factor.
+    /// Called once at the start of every iteration, which is when iterations are timed.
+    pub fn should_start_iteration(&mut self, depth: usize, predicted_branching: f64) -> bool {
+        if !self.is_timed() {
+            return true;
+        }
+
+        let elapsed = self.elapsed();
+        let last_iteration = elapsed - self.iteration_start;
+        self.iteration_start = elapsed;
+
+        if depth < TIME_PREDICTION_LOWER_LIMIT {
+            return true;
+        }
+
+        let predicted = elapsed + last_iteration.mul_f64(predicted_branching);
+        let start = predicted < self.max_time.min(self.ceiling);
+        if !start {
+            self.global_stop.store(true, Ordering::SeqCst);
         }
+
+        start
     }
 
     /// Update the number of nodes searched by a single move.
@@ -200,11 +322,16 @@ impl Clock {
             return false;
         }
 
-        // at least depth 1
+        // at least depth 1, which is still interrupted by the ceiling
         if depth == 1 {
             return true;
         }
 
+        if self.past_ceiling() {
+            self.global_stop.store(true, Ordering::SeqCst);
+            return false;
+        }
+
         let start = match self.time_control {
             TimeControl::FixedDepth(d) => depth <= d,
             TimeControl::FixedNodes(n) => self.global_nodes() <= n,
@@ -238,23 +365,35 @@ impl Clock {
     /// Only loads/stores atomics and checks the time every CHECK_FREQUENCY nodes.
     pub fn continue_search(&mut self, nodes: u64) -> bool {
         let searched = nodes - self.last_nodes;
+        if searched < CHECK_FREQUENCY {
+            return true;
+        }
 
-        if searched >= CHECK_FREQUENCY {
-            self.global_nodes.fetch_add(searched, Ordering::SeqCst);
-            self.last_nodes = nodes;
+        let global_nodes = self.global_nodes.fetch_add(searched, Ordering::SeqCst) + searched;
+        self.last_nodes = nodes;
 
-            if self.global_stop.load(Ordering::SeqCst) {
+        // Node limits are shared by all threads, and checked against the global count
+        if let TimeControl::FixedNodes(limit) = self.time_control {
+            if global_nodes >= limit {
+                self.global_stop.store(true, Ordering::SeqCst);
                 return false;
             }
         }
 
-        let proceed = match self.time_control {
-            TimeControl::FixedTime(_) | TimeControl::Variable { .. } => {
-                searched < CHECK_FREQUENCY || self.elapsed() < self.max_time
-            }
-            _ => true,
-        };
+        // Only timed searches may be interrupted by the clock
+        let elapsed = self.is_timed().then(|| self.elapsed());
+
+        // The ceiling comes before anything else, so that no budget can make the engine flag
+        if elapsed.is_some_and(|elapsed| elapsed >= self.ceiling) {
+            self.global_stop.store(true, Ordering::SeqCst);
+            return false;
+        }
 
+        if self.global_stop.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        let proceed = elapsed.is_none_or(|elapsed| elapsed < self.max_time);
         if !proceed {
             self.global_stop.store(true, Ordering::SeqCst);
         }
@@ -262,3 +401,283 @@ impl Clock {
         proceed
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_control() {
+        let tc: TimeControl = "wtime 60000 btime 60000 movestogo 30".parse().unwrap();
+        assert!(matches!(
+            tc,
+            TimeControl::Variable {
+                wtime: 60000,
+                btime: 60000,
+                winc: None,
+                binc: None,
+                movestogo: Some(30),
+            }
+        ));
+
+        // The side to move's clock is split over the moves left, plus the buffer
+        let clock = Clock::new(
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicU64::new(0)),
+            tc,
+            false,
+        );
+        let (opt, max) = clock.budgets();
+        assert_eq!(opt, Duration::from_millis((60000 - OVERHEAD) / 32));
+        assert!(opt < max && max < Duration::from_millis(60000 / 2));
+
+        let tc: TimeControl = "wtime -20 btime 1000 winc 100 binc 200".parse().unwrap();
+        assert!(matches!(
+            tc,
+            TimeControl::Variable {
+                wtime: 0,
+                btime: 1000,
+                winc: Some(100),
+                binc: Some(200),
+                movestogo: None,
+            }
+        ));
+
+        assert!(matches!("infinite".parse(), Ok(TimeControl::Infinite)));
+        assert!(matches!("depth 7".parse(), Ok(TimeControl::FixedDepth(7))));
+        assert!(matches!(
+            "nodes 5000".parse(),
+            Ok(TimeControl::FixedNodes(5000))
+        ));
+        assert!(matches!(
+            "movetime 250".parse(),
+            Ok(TimeControl::FixedTime(250))
+        ));
+
+        for invalid in ["wtime 1000", "depth", "movetime soon", "ponder"] {
+            assert!(invalid.parse::<TimeControl>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_movestogo_allocation() {
+        let remaining = 120_000;
+        let budgets: Vec<(Duration, Duration)> = [40, 2, 1]
+            .iter()
+            .map(|&moves| allocate_time(remaining, 0, Some(moves)))
+            .collect();
+
+        // Roughly remaining / (movestogo + buffer) for a long period
+        let (opt, _) = budgets[0];
+        assert_eq!(opt, Duration::from_millis((remaining - OVERHEAD) / 42));
+
+        for pair in budgets.windows(2) {
+            assert!(pair[1].0 > pair[0].0);
+            assert!(pair[1].1 >= pair[0].1);
+        }
+
+        for (opt, max) in budgets {
+            assert!(opt <= max);
+            assert!(max <= Duration::from_millis(remaining - OVERHEAD));
+        }
+
+        // Nearly flagging: nothing beyond the remaining time, and no time at all under overhead
+        let (_, max) = allocate_time(50, 1000, Some(1));
+        assert!(max <= Duration::from_millis(50 - OVERHEAD));
+        assert_eq!(
+            allocate_time(3, 1000, Some(1)),
+            (Duration::ZERO, Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_allocation_grid() {
+        let remaining = [0, 5, 20, 50, 99, 100, 500, 1000, 10_000, 60_000, 600_000];
+        let increments = [0, 10, 100, 1000, 5000];
+        let budget = |r: usize, i: usize| allocate_time(remaining[r], increments[i], None);
+
+        for (r, &time) in remaining.iter().enumerate() {
+            for i in 0..increments.len() {
+                let (opt, max) = budget(r, i);
+                let limit = (time - OVERHEAD.min(time)) as f64 * TIME_MAX_FRACTION;
+
+                assert!(opt <= max);
+                assert!(max.as_millis() as f64 <= limit);
+
+                // More time on the clock or a larger increment never shrinks the budgets
+                if r > 0 {
+                    let (prev_opt, prev_max) = budget(r - 1, i);
+                    assert!(opt >= prev_opt && max >= prev_max);
+                }
+                if i > 0 {
+                    let (prev_opt, prev_max) = budget(r, i - 1);
+                    assert!(opt >= prev_opt && max >= prev_max);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_low_time_floor() {
+        // Down to below the ceiling reserve, which would otherwise leave no time at all
+        for time in [6, 10, 14, 20, 50, 99] {
+            // Only depth 1, which always starts, within a small hard budget
+            let (opt, max) = allocate_time(time, 1000, None);
+            assert_eq!(opt, Duration::ZERO);
+            assert!(max > Duration::ZERO && max <= Duration::from_millis(time / 5));
+
+            let mut clock = Clock::new(
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(AtomicU64::new(0)),
+                TimeControl::Variable {
+                    wtime: time,
+                    btime: time,
+                    winc: Some(1000),
+                    binc: Some(1000),
+                    movestogo: None,
+                },
+                true,
+            );
+            assert!(!clock.no_search_time());
+            assert!(clock.ceiling >= max);
+            assert!(clock.start_search(1, 0, NULL_MOVE));
+            assert!(!clock.start_search(2, 100, NULL_MOVE));
+        }
+
+        // Nothing left at all
+        assert_eq!(
+            allocate_time(OVERHEAD, 0, None),
+            (Duration::ZERO, Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_ceiling() {
+        let mut clock = Clock::new(
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicU64::new(0)),
+            TimeControl::Variable {
+                wtime: 40,
+                btime: 40,
+                winc: None,
+                binc: None,
+                movestogo: None,
+            },
+            false,
+        );
+        assert_eq!(
+            clock.ceiling,
+            Duration::from_millis(40 - OVERHEAD - CEILING_RESERVE)
+        );
+
+        // A budget past the remaining time is still cut off by the ceiling
+        clock.opt_time = Duration::MAX;
+        clock.max_time = Duration::MAX;
+        assert!(clock.continue_search(CHECK_FREQUENCY));
+
+        std::thread::sleep(clock.ceiling);
+        assert!(!clock.continue_search(2 * CHECK_FREQUENCY));
+        assert!(!clock.start_search(2, 0, NULL_MOVE));
+
+        // Untimed searches have no ceiling
+        let mut clock = Clock::spin_clock(
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicU64::new(0)),
+        );
+        assert!(clock.continue_search(CHECK_FREQUENCY));
+        assert!(!clock.past_ceiling());
+    }
+
+    fn timed_clock(time: u64) -> Clock {
+        Clock::new(
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicU64::new(0)),
+            TimeControl::Variable {
+                wtime: time,
+                btime: time,
+                winc: None,
+                binc: None,
+                movestogo: None,
+            },
+            true,
+        )
+    }
+
+    #[test]
+    fn test_scale_soft_limit() {
+        let mut clock = timed_clock(60_000);
+        let (base, max) = clock.budgets();
+
+        clock.scale_soft_limit(1.5);
+        assert_eq!(clock.budgets(), (base.mul_f64(1.5), max));
+
+        // Factors apply to the initial soft limit, and do not compound
+        clock.scale_soft_limit(1.5);
+        assert_eq!(clock.budgets().0, base.mul_f64(1.5));
+
+        clock.scale_soft_limit(100.0);
+        assert_eq!(clock.budgets().0, base.mul_f64(TIME_SOFT_SCALE_MAX));
+        clock.scale_soft_limit(0.0);
+        assert_eq!(clock.budgets().0, base.mul_f64(TIME_SOFT_SCALE_MIN));
+
+        // Never above the hard limit and the ceiling
+        clock.max_time = base;
+        clock.scale_soft_limit(2.0);
+        assert_eq!(clock.budgets().0, base);
+        clock.ceiling = base / 2;
+        clock.scale_soft_limit(2.0);
+        assert_eq!(clock.budgets().0, base / 2);
+    }
+
+    #[test]
+    fn test_should_start_iteration() {
+        let mut clock = timed_clock(60_000);
+        clock.max_time = Duration::from_millis(1000);
+
+        // Previous iteration took from 100ms to 200ms
+        clock.iteration_start = Duration::from_millis(100);
+        clock.start_time = Instant::now() - Duration::from_millis(200);
+        assert!(clock.should_start_iteration(10, 2.0));
+        assert!(!clock.global_stop.load(Ordering::SeqCst));
+
+        // Predicted to end past the hard limit
+        clock.iteration_start = Duration::from_millis(100);
+        assert!(!clock.should_start_iteration(10, 9.0));
+        assert!(clock.global_stop.load(Ordering::SeqCst));
+
+        // Shallow iterations always start, and untimed searches never look at the clock
+        clock.iteration_start = Duration::from_millis(100);
+        assert!(clock.should_start_iteration(TIME_PREDICTION_LOWER_LIMIT - 1, 100.0));
+
+        let mut clock = Clock::spin_clock(
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicU64::new(0)),
+        );
+        clock.start_time = Instant::now() - Duration::from_millis(200);
+        assert!(clock.should_start_iteration(20, 1000.0));
+    }
+
+    #[test]
+    fn test_remaining() {
+        let clock = timed_clock(60_000);
+        let (soft, hard) = clock.budgets();
+        assert!(clock.soft_remaining().unwrap() <= soft);
+        assert!(clock.hard_remaining().unwrap() <= hard);
+        assert!(clock.soft_remaining() < clock.hard_remaining());
+
+        let clock = Clock::spin_clock(
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicU64::new(0)),
+        );
+        assert_eq!(clock.soft_remaining(), None);
+        assert_eq!(clock.hard_remaining(), None);
+    }
+
+    #[test]
+    fn test_sudden_death_allocation() {
+        let (opt, max) = allocate_time(60_000, 1000, None);
+        assert!(opt < max);
+        assert!(max <= Duration::from_millis(60_000 - OVERHEAD));
+        assert_eq!(opt, Duration::from_millis(2249));
+    }
+}
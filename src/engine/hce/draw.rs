@@ -0,0 +1,86 @@
+/// Known draw recognition.
+///
+/// Trivially drawn endings are recognized from a material key packing the piece counts, which
+/// makes the lookup a handful of comparisons. The weak side never has pawns in any of them.
+use crate::chess::{bitboard::*, board::*, piece::*};
+
+/// Piece counts packed in 4 bits each, indexed by piece (kings excluded)
+pub fn material_key(board: &Board) -> u64 {
+    (0..10).fold(0, |key, piece| {
+        key | (board.piece_bb[piece].count_bits() as u64) << (4 * piece)
+    })
+}
+
+/// Material key of the given pieces, besides the kings
+const fn signature(pieces: &[Piece]) -> u64 {
+    let mut key = 0;
+
+    let mut i = 0;
+    while i < pieces.len() {
+        key += 1 << (4 * pieces[i] as u64);
+        i += 1;
+    }
+
+    key
+}
+
+/// KvK, KNvK, KBvK and KNNvK, for both colors
+const KNOWN_DRAWS: [u64; 7] = [
+    signature(&[]),
+    signature(&[Piece::WN]),
+    signature(&[Piece::BN]),
+    signature(&[Piece::WB]),
+    signature(&[Piece::BB]),
+    signature(&[Piece::WN, Piece::WN]),
+    signature(&[Piece::BN, Piece::BN]),
+];
+
+/// KBvKB, drawn when the bishops are on the same color
+const BISHOPS: u64 = signature(&[Piece::WB, Piece::BB]);
+
+/// Check whether the position is a trivially drawn ending
+pub fn is_known_draw(board: &Board) -> bool {
+    let key = material_key(board);
+
+    KNOWN_DRAWS.contains(&key)
+        || key == BISHOPS
+            && (board.bishops() & LIGHT_SQUARES_BB == EMPTY_BB
+                || board.bishops() & DARK_SQUARES_BB == EMPTY_BB)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::hce::{evaluate, PawnTable};
+
+    fn known_draw(fen: &str) -> bool {
+        let board: Board = fen.parse().unwrap();
+        let mirrored = board.mirror();
+        assert_eq!(is_known_draw(&board), is_known_draw(&mirrored));
+
+        let eval = evaluate(&board, &mut PawnTable::new(1));
+        assert_eq!(is_known_draw(&board), eval == 0);
+
+        is_known_draw(&board)
+    }
+
+    #[test]
+    fn test_known_draws() {
+        assert!(known_draw("8/8/4k3/8/8/8/4K3/8 w - - 0 1"));
+        assert!(known_draw("8/8/4k3/8/8/2N5/4K3/8 w - - 0 1"));
+        assert!(known_draw("8/8/4k3/8/8/2B5/4K3/8 b - - 0 1"));
+        assert!(known_draw("8/8/4k3/8/8/2NN4/4K3/8 w - - 0 1"));
+        assert!(known_draw("8/8/4k3/4b3/8/2B5/4K3/8 w - - 0 1"));
+    }
+
+    #[test]
+    fn test_not_known_draws() {
+        assert!(!known_draw("8/8/4k3/4p3/8/2NN4/4K3/8 w - - 0 1"));
+        assert!(!known_draw("8/8/4k3/3b4/8/2B5/4K3/8 w - - 0 1"));
+        assert!(!known_draw("8/8/4k3/8/8/2BB4/4K3/8 w - - 0 1"));
+        assert!(!known_draw("8/8/4k3/8/8/2BN4/4K3/8 w - - 0 1"));
+        assert!(!known_draw("8/8/4k3/8/8/2N5/4K3/4n3 w - - 0 1"));
+        assert!(!known_draw("8/8/4k3/8/8/2N5/4KP2/8 w - - 0 1"));
+        assert!(!known_draw("8/8/4k3/8/8/8/4K3/R7 w - - 0 1"));
+    }
+}
@@ -0,0 +1,167 @@
+/// Tunable evaluation parameters.
+///
+/// The evaluation terms read their weights from an EvalParams struct, so that the tuner can
+/// evaluate positions with modified weights. DEFAULT_PARAMS holds the constants from params.rs,
+/// which the incrementally updated psqt score and the pawn table are built on.
+use crate::chess::square::*;
+use crate::engine::hce::{params::*, score::*};
+use crate::engine::search_params::Eval;
+
+/// Parameters made of Scores, which can be flattened into (mg, eg) values and printed back as
+/// Rust source
+pub trait Tunable {
+    /// Every value in declaration order, middlegame before endgame
+    fn flatten(&self, values: &mut Vec<Eval>);
+
+    /// Read the values back in the order written by flatten
+    fn unflatten(&mut self, values: &mut impl Iterator<Item = Eval>);
+
+    /// Rust source for the value
+    fn source(&self, indent: usize) -> String;
+}
+
+impl Tunable for Score {
+    fn flatten(&self, values: &mut Vec<Eval>) {
+        values.extend([self.mg(), self.eg()]);
+    }
+
+    fn unflatten(&mut self, values: &mut impl Iterator<Item = Eval>) {
+        let (mg, eg) = (values.next().unwrap(), values.next().unwrap());
+        *self = Score::new(mg, eg);
+    }
+
+    fn source(&self, _: usize) -> String {
+        format!("S!({}, {})", self.mg(), self.eg())
+    }
+}
+
+impl<T: Tunable, const N: usize> Tunable for [T; N] {
+    fn flatten(&self, values: &mut Vec<Eval>) {
+        self.iter().for_each(|x| x.flatten(values));
+    }
+
+    fn unflatten(&mut self, values: &mut impl Iterator<Item = Eval>) {
+        self.iter_mut().for_each(|x| x.unflatten(values));
+    }
+
+    /// Rows of 8 values, nested arrays on their own lines
+    fn source(&self, indent: usize) -> String {
+        let pad = " ".repeat(4 * (indent + 1));
+        let items: Vec<String> = self.iter().map(|x| x.source(indent + 1)).collect();
+        let rows: Vec<String> = match items[0].starts_with('[') {
+            true => items.iter().map(|item| format!("{pad}{item},")).collect(),
+            false => items
+                .chunks(8)
+                .map(|row| format!("{pad}{},", row.join(", ")))
+                .collect(),
+        };
+
+        format!("[\n{}\n{}]", rows.join("\n"), " ".repeat(4 * indent))
+    }
+}
+
+/// Declare the EvalParams struct and its defaults from a list of fields and default constants
+macro_rules! eval_params {
+    ($($field:ident: $ty:ty = $default:ident),* $(,)?) => {
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct EvalParams {
+            $(pub $field: $ty),*
+        }
+
+        pub const DEFAULT_PARAMS: EvalParams = EvalParams {
+            $($field: $default),*
+        };
+
+        impl EvalParams {
+            /// Every parameter as a flat vector of values
+            pub fn to_vec(&self) -> Vec<Eval> {
+                let mut values = Vec::new();
+                $(self.$field.flatten(&mut values);)*
+
+                values
+            }
+
+            /// Parameters read back from a flat vector of values
+            pub fn from_slice(values: &[Eval]) -> Self {
+                let mut params = DEFAULT_PARAMS;
+                let mut values = values.iter().copied();
+                $(params.$field.unflatten(&mut values);)*
+
+                params
+            }
+
+            /// Rust source declaring every parameter as a constant, in the style of params.rs
+            pub fn to_source(&self) -> String {
+                let mut source = String::new();
+                $(source += &format!(
+                    "pub const {}: {} = {};\n\n",
+                    stringify!($default),
+                    stringify!($ty),
+                    self.$field.source(0),
+                );)*
+
+                source
+            }
+        }
+    };
+}
+
+eval_params! {
+    piece_values: [Score; 6] = PIECE_VALUES,
+    pst: [[Score; SQUARE_COUNT]; 6] = PST,
+    tempo: Score = TEMPO,
+    doubled_pawn: Score = DOUBLED_PAWN,
+    isolated_pawn: Score = ISOLATED_PAWN,
+    backward_pawn: Score = BACKWARD_PAWN,
+    passed_pawn: [Score; 8] = PASSED_PAWN,
+    passed_blocked: [Score; 8] = PASSED_BLOCKED,
+    passed_unsafe: [Score; 8] = PASSED_UNSAFE,
+    passed_connected: [Score; 8] = PASSED_CONNECTED,
+    candidate_passer: [Score; 8] = CANDIDATE_PASSER,
+    passed_rook_behind: Score = PASSED_ROOK_BEHIND,
+    passed_enemy_rook_behind: Score = PASSED_ENEMY_ROOK_BEHIND,
+    passed_king_own: [Score; 8] = PASSED_KING_OWN,
+    passed_king_enemy: [Score; 8] = PASSED_KING_ENEMY,
+    bishop_pair: Score = BISHOP_PAIR,
+    imbalance_ours: [[Score; 5]; 5] = IMBALANCE_OURS,
+    imbalance_theirs: [[Score; 5]; 5] = IMBALANCE_THEIRS,
+    bad_bishop_pawn: Score = BAD_BISHOP_PAWN,
+    knight_outpost: Score = KNIGHT_OUTPOST,
+    bishop_outpost: Score = BISHOP_OUTPOST,
+    knight_on_rim: Score = KNIGHT_ON_RIM,
+    rook_semi_open_file: Score = ROOK_SEMI_OPEN_FILE,
+    rook_open_file: Score = ROOK_OPEN_FILE,
+    rook_seventh_rank: Score = ROOK_SEVENTH_RANK,
+    rook_connected: Score = ROOK_CONNECTED,
+    king_shield: [Score; 8] = KING_SHIELD,
+    king_semi_open_file: Score = KING_SEMI_OPEN_FILE,
+    king_open_file: Score = KING_OPEN_FILE,
+    pawn_storm: [Score; 8] = PAWN_STORM,
+    king_safety_table: [Score; 64] = KING_SAFETY_TABLE,
+    king_tropism: [Score; 6] = KING_TROPISM,
+    mobility: [Score; 4] = MOBILITY,
+    threat_by_pawn: Score = THREAT_BY_PAWN,
+    threat_by_lesser: Score = THREAT_BY_LESSER,
+    hanging_piece: Score = HANGING_PIECE,
+    space: Score = SPACE,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_roundtrip() {
+        let values = DEFAULT_PARAMS.to_vec();
+        assert_eq!(EvalParams::from_slice(&values), DEFAULT_PARAMS);
+
+        let shifted: Vec<Eval> = values.iter().map(|v| v + 1).collect();
+        let params = EvalParams::from_slice(&shifted);
+        assert_eq!(params.tempo, TEMPO + Score::new(1, 1));
+        assert_eq!(params.to_vec(), shifted);
+
+        let source = DEFAULT_PARAMS.to_source();
+        assert!(source.contains("pub const TEMPO: Score = S!(20, 10);"));
+        assert!(source.contains("pub const KING_SHIELD: [Score; 8] = [\n    S!("));
+    }
+}
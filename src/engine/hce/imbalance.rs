@@ -0,0 +1,93 @@
+/// Material imbalance.
+///
+/// Piece values depend on the rest of the material: knights gain value with more own pawns and
+/// rooks lose it, pieces of the same kind are partly redundant, and minor pieces work well
+/// against rooks and queens. The terms are a quadratic form over the piece counts of both sides.
+/// It only takes a few multiplications, so it is computed at every evaluation rather than cached
+/// (the pawn hash key does not cover pieces).
+use crate::chess::{board::*, piece::*};
+use crate::engine::hce::{eval_params::*, score::*};
+
+/// Imbalance score for the given side
+pub fn imbalance(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let counts = |side: Color| -> [i32; 5] {
+        std::array::from_fn(|piece_type| {
+            board.piece_bb[PIECES[side as usize][piece_type] as usize].count_bits() as i32
+        })
+    };
+    let (ours, theirs) = (counts(side), counts(!side));
+    let mut score = Score::ZERO;
+
+    for pt1 in 0..5 {
+        if ours[pt1] == 0 {
+            continue;
+        }
+
+        let (own_weights, enemy_weights) =
+            (params.imbalance_ours[pt1], params.imbalance_theirs[pt1]);
+        let mut value = own_weights[pt1] * (ours[pt1] - 1);
+        for (&weight, &count) in own_weights.iter().zip(&ours).take(pt1) {
+            value += weight * count;
+        }
+        for (&weight, &count) in enemy_weights.iter().zip(&theirs) {
+            value += weight * count;
+        }
+
+        score += value * ours[pt1];
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::hce::params::*;
+
+    fn imbalance_of(fen: &str) -> Score {
+        let board: Board = fen.parse().unwrap();
+        imbalance(&board, Color::White, &DEFAULT_PARAMS)
+            - imbalance(&board, Color::Black, &DEFAULT_PARAMS)
+    }
+
+    #[test]
+    fn test_knight_and_rook_pawns() {
+        // Knight against rook, with both sides holding the same pawns
+        let heavy = imbalance_of("4k3/pppppppp/8/1r6/8/2N5/PPPPPPPP/4K3 w - - 0 1");
+        let light = imbalance_of("4k3/pp6/8/1r6/8/2N5/PP6/4K3 w - - 0 1");
+
+        // The knight side gains from more pawns, the rook side loses
+        assert!(heavy.mg() > light.mg() && heavy.eg() > light.eg());
+        assert_eq!(
+            heavy - light,
+            (IMBALANCE_OURS[1][0] - IMBALANCE_OURS[3][0]) * 6
+        );
+    }
+
+    #[test]
+    fn test_redundancy() {
+        let rooks = imbalance_of("4k3/8/8/8/8/8/8/R3K2R w - - 0 1");
+        let rook = imbalance_of("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+
+        assert_eq!(rook, Score::ZERO);
+        assert_eq!(rooks, IMBALANCE_OURS[3][3] * 2);
+    }
+
+    #[test]
+    fn test_two_minors_against_rook() {
+        // Two minors against rook and pawn, counted for the minors
+        let score = imbalance_of("4k3/pppp4/8/8/3r4/8/PPP5/1N2KB2 w - - 0 1");
+        assert!(score.mg() > 0 && score.eg() > 0);
+
+        let board: Board = "4k3/pppp4/8/8/3r4/8/PPP5/1N2KB2 w - - 0 1".parse().unwrap();
+        let mirrored = board.mirror();
+        assert_eq!(
+            imbalance(&board, Color::White, &DEFAULT_PARAMS),
+            imbalance(&mirrored, Color::Black, &DEFAULT_PARAMS)
+        );
+        assert_eq!(
+            imbalance(&board, Color::Black, &DEFAULT_PARAMS),
+            imbalance(&mirrored, Color::White, &DEFAULT_PARAMS)
+        );
+    }
+}
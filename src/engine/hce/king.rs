@@ -0,0 +1,213 @@
+/// King safety terms.
+///
+/// The pawn shield and the files around the king are scored on their own, while enemy pieces
+/// attacking the king zone are accumulated in attack units and mapped through a safety table.
+/// With the kings on opposite wings, pawns advancing on the enemy king are rewarded.
+use crate::chess::{bitboard::*, board::*, piece::*, square::*, tables::*};
+use crate::engine::hce::{eval_params::*, params::*, score::*};
+
+/// Pawn shield and (semi-)open file penalties on the three files around the king
+pub fn king_shelter(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let pawns = board.piece_bb[side.pawn() as usize];
+    let enemy_pawns = board.piece_bb[(!side).pawn() as usize];
+    let king = board.piece_bb[side.king() as usize].lsb();
+    let ahead = rank_bb(king.rank()).front_span(side);
+    let mut score = Score::ZERO;
+
+    let king_file = king.file() as usize;
+    for &file in &ALL_FILES[king_file.saturating_sub(1)..=(king_file + 1).min(7)] {
+        let file = file_bb(file);
+
+        let distance = (pawns & file & ahead)
+            .map(|pawn| pawn.relative_rank(side) - king.relative_rank(side))
+            .min()
+            .unwrap_or(0);
+        score += params.king_shield[distance];
+
+        if pawns & file == EMPTY_BB {
+            score += match enemy_pawns & file {
+                EMPTY_BB => params.king_open_file,
+                _ => params.king_semi_open_file,
+            };
+        }
+    }
+
+    score
+}
+
+/// Bonus for own pawns storming the three files around the enemy king, when the kings are on
+/// opposite wings
+pub fn pawn_storm(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let pawns = board.piece_bb[side.pawn() as usize];
+    let king = board.piece_bb[side.king() as usize].lsb();
+    let enemy_king = board.piece_bb[(!side).king() as usize].lsb();
+
+    if (king.file() as u8).abs_diff(enemy_king.file() as u8) < PAWN_STORM_FILE_DISTANCE {
+        return Score::ZERO;
+    }
+
+    let files = king_ring(enemy_king).file_fill();
+    let behind = rank_bb(enemy_king.rank()).rear_span(side);
+
+    (pawns & files & behind)
+        .map(|pawn| params.pawn_storm[enemy_king.relative_rank(side) - pawn.relative_rank(side)])
+        .sum()
+}
+
+/// Attack units and number of enemy pieces attacking the king zone of the given side
+pub fn king_attacks(board: &Board, side: Color) -> (usize, u32) {
+    let king = board.piece_bb[side.king() as usize].lsb();
+    let zone = king_zone(king, side);
+    let (mut units, mut attackers) = (0, 0);
+
+    for piece in [Piece::WN, Piece::WB, Piece::WR, Piece::WQ] {
+        let piece_type = piece as usize >> 1;
+
+        for square in board.piece_bb[PIECES[!side as usize][piece_type] as usize] {
+            let attacks = match piece {
+                Piece::WN => knight_attacks(square),
+                Piece::WB => bishop_attacks(square, board.occupancy),
+                Piece::WR => rook_attacks(square, board.occupancy),
+                _ => queen_attacks(square, board.occupancy),
+            } & zone;
+
+            if attacks != EMPTY_BB {
+                attackers += 1;
+                units += KING_ATTACK_WEIGHTS[piece_type] * attacks.count_bits() as i32;
+            }
+        }
+    }
+
+    (units as usize, attackers)
+}
+
+/// Bonus for own pieces close to the enemy king
+pub fn king_tropism(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let enemy_king = board.piece_bb[(!side).king() as usize].lsb();
+    let mut score = Score::ZERO;
+
+    for (piece_type, &weight) in params.king_tropism.iter().enumerate().take(5).skip(1) {
+        for square in board.piece_bb[PIECES[side as usize][piece_type] as usize] {
+            score += weight * (7 - square.distance(enemy_king)) as i32;
+        }
+    }
+
+    score
+}
+
+/// King safety score for the given side
+pub fn king_safety(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let (units, attackers) = king_attacks(board, side);
+    let mut score = king_shelter(board, side, params) + pawn_storm(board, side, params);
+
+    if attackers >= KING_ATTACKERS_MIN {
+        score += params.king_safety_table[units.min(params.king_safety_table.len() - 1)];
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::hce::{trace, PawnTable};
+
+    #[test]
+    fn test_king_shelter() {
+        let intact: Board = "4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1".parse().unwrap();
+        let advanced: Board = "4k3/8/8/8/7P/6P1/5P2/6K1 w - - 0 1".parse().unwrap();
+        let open: Board = "4k3/8/8/8/8/8/5P1P/6K1 w - - 0 1".parse().unwrap();
+
+        assert_eq!(
+            king_shelter(&intact, Color::White, &DEFAULT_PARAMS),
+            Score::ZERO
+        );
+        assert_eq!(
+            king_shelter(&advanced, Color::White, &DEFAULT_PARAMS),
+            KING_SHIELD[2] + KING_SHIELD[3]
+        );
+        assert_eq!(
+            king_shelter(&open, Color::White, &DEFAULT_PARAMS),
+            KING_SHIELD[0] + KING_OPEN_FILE
+        );
+    }
+
+    #[test]
+    fn test_castled_king_is_safer() {
+        let castled: Board =
+            "r2q1rk1/ppp2ppp/2np1n2/2b1p3/2B1P1b1/2NP1N2/PPP2PPP/R1BQ1RK1 w - - 0 1"
+                .parse()
+                .unwrap();
+        let exposed: Board = "r2q1rk1/ppp2ppp/2np1n2/2b1p3/2B1PPbP/2NP1NP1/PPP5/R1BQ1RK1 w - - 0 1"
+            .parse()
+            .unwrap();
+
+        let castled = king_safety(&castled, Color::White, &DEFAULT_PARAMS);
+        let exposed = king_safety(&exposed, Color::White, &DEFAULT_PARAMS);
+
+        assert!(castled.mg() > exposed.mg() + 50);
+    }
+
+    #[test]
+    fn test_king_tropism() {
+        let far: Board = "6k1/5ppp/8/8/8/8/5PPP/Q5K1 w - - 0 1".parse().unwrap();
+        let close: Board = "6k1/5ppp/8/8/8/2Q5/5PPP/6K1 w - - 0 1".parse().unwrap();
+
+        assert_eq!(Square::A1.distance(Square::G8), 7);
+        assert_eq!(Square::C3.distance(Square::G8), 5);
+        assert_eq!(
+            king_tropism(&close, Color::White, &DEFAULT_PARAMS),
+            king_tropism(&far, Color::White, &DEFAULT_PARAMS)
+                + KING_TROPISM[Piece::WQ as usize >> 1] * 2
+        );
+    }
+
+    #[test]
+    fn test_pawn_storm() {
+        // Same pawns, with the white king castled short or long against a king on b8
+        let opposite: Board = "1k6/ppp5/8/1PP5/P7/8/5PPP/6K1 w - - 0 1".parse().unwrap();
+        let same: Board = "1k6/ppp5/8/1PP5/P7/8/5PPP/2K5 w - - 0 1".parse().unwrap();
+
+        assert_eq!(
+            pawn_storm(&opposite, Color::White, &DEFAULT_PARAMS),
+            PAWN_STORM[4] + PAWN_STORM[3] * 2
+        );
+        assert_eq!(
+            pawn_storm(&same, Color::White, &DEFAULT_PARAMS),
+            Score::ZERO
+        );
+        assert_eq!(
+            pawn_storm(&opposite, Color::Black, &DEFAULT_PARAMS),
+            Score::ZERO
+        );
+
+        for board in [&opposite, &same] {
+            let trace = trace(board, &mut PawnTable::new(1));
+            assert_eq!(
+                trace.king_safety[0],
+                king_shelter(board, Color::White, &DEFAULT_PARAMS)
+                    + pawn_storm(board, Color::White, &DEFAULT_PARAMS)
+            );
+        }
+
+        let storm = pawn_storm(&opposite, Color::White, &DEFAULT_PARAMS);
+        assert!(storm.mg() > 0);
+        assert_eq!(storm.eg(), 0);
+    }
+
+    #[test]
+    fn test_pawn_endgame() {
+        let board: Board = "8/8/4k3/8/2p5/8/PP6/6K1 w - - 0 1".parse().unwrap();
+
+        assert!(king_shelter(&board, Color::White, &DEFAULT_PARAMS).mg() < 0);
+        assert_eq!(king_attacks(&board, Color::White), (0, 0));
+        assert_eq!(board.phase(), 0);
+
+        for side in [Color::White, Color::Black] {
+            assert_eq!(
+                king_safety(&board, side, &DEFAULT_PARAMS).taper(board.phase()),
+                0
+            );
+        }
+    }
+}
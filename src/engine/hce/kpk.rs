@@ -0,0 +1,272 @@
+/// King and pawn versus king bitbase.
+///
+/// Every KPK position with White holding the pawn on files A to D is classified by retrograde
+/// analysis, propagating wins and draws until nothing changes, and the wins are stored as bits
+/// (24 KB in total). Other positions are mirrored onto these before probing.
+/// The bitbase is generated lazily at the first probe, and needs the attack tables initialized.
+use std::sync::OnceLock;
+
+use crate::chess::{bitboard::*, board::*, piece::*, square::*, tables::*};
+use crate::engine::search_params::Eval;
+
+/// White king, black king, side to move, pawn file (A to D) and pawn rank (2 to 7)
+const MAX_INDEX: usize = 2 * 24 * 64 * 64;
+
+/// Evaluation of a won KPK position, before adding the pawn rank
+pub const KPK_WIN: Eval = 1000;
+
+static BITBASE: OnceLock<Vec<u32>> = OnceLock::new();
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct KPKResult(u8);
+
+const INVALID: KPKResult = KPKResult(0);
+const UNKNOWN: KPKResult = KPKResult(1);
+const DRAW: KPKResult = KPKResult(2);
+const WIN: KPKResult = KPKResult(4);
+
+/// Index of a normalized position, with the pawn on files A to D and on ranks 2 to 7
+fn index(side: Color, white_king: Square, black_king: Square, pawn: Square) -> usize {
+    white_king as usize
+        | (black_king as usize) << 6
+        | (side as usize) << 12
+        | (pawn.file() as usize) << 13
+        | (6 - pawn.relative_rank(Color::White)) << 15
+}
+
+/// A position decoded from its index
+struct KPKPosition {
+    side: Color,
+    kings: [Square; 2],
+    pawn: Square,
+    result: KPKResult,
+}
+
+impl KPKPosition {
+    fn new(idx: usize) -> Self {
+        let kings = [Square::from(idx & 63), Square::from((idx >> 6) & 63)];
+        let side = if (idx >> 12) & 1 == 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let pawn = Square::from_coords(ALL_FILES[(idx >> 13) & 3], ALL_RANKS[1 + (idx >> 15)]);
+        let [white_king, black_king] = kings;
+        let push = pawn.forward(Color::White);
+
+        let result = if white_king.distance(black_king) <= 1
+            || white_king == pawn
+            || black_king == pawn
+            || side == Color::White && pawn_attacks(pawn, Color::White).get_bit(black_king)
+        {
+            INVALID
+        } else if side == Color::White
+            && pawn.relative_rank(Color::White) == 6
+            && white_king != push
+            && black_king != push
+            && (black_king.distance(push) > 1 || white_king.distance(push) == 1)
+        {
+            // The pawn promotes without being captured
+            WIN
+        } else if side == Color::Black
+            && (king_attacks(black_king)
+                & !(king_attacks(white_king) | pawn_attacks(pawn, Color::White))
+                == EMPTY_BB
+                || king_attacks(black_king).get_bit(pawn)
+                    && !king_attacks(white_king).get_bit(pawn))
+        {
+            // Stalemate, or the pawn is captured
+            DRAW
+        } else {
+            UNKNOWN
+        };
+
+        Self {
+            side,
+            kings,
+            pawn,
+            result,
+        }
+    }
+
+    /// Classify the position from the results of the positions it leads to
+    fn classify(&self, db: &[KPKPosition]) -> KPKResult {
+        let (good, bad) = match self.side {
+            Color::White => (WIN, DRAW),
+            Color::Black => (DRAW, WIN),
+        };
+        let [white_king, black_king] = self.kings;
+
+        let mut r = INVALID.0;
+        for square in king_attacks(self.kings[self.side as usize]) {
+            r |= match self.side {
+                Color::White => {
+                    db[index(Color::Black, square, black_king, self.pawn)]
+                        .result
+                        .0
+                }
+                Color::Black => {
+                    db[index(Color::White, white_king, square, self.pawn)]
+                        .result
+                        .0
+                }
+            };
+        }
+
+        if self.side == Color::White {
+            let rank = self.pawn.relative_rank(Color::White);
+            let push = self.pawn.forward(Color::White);
+
+            if rank < 6 {
+                r |= db[index(Color::Black, white_king, black_king, push)]
+                    .result
+                    .0;
+            }
+            if rank == 1 && push != white_king && push != black_king {
+                let double_push = push.forward(Color::White);
+                r |= db[index(Color::Black, white_king, black_king, double_push)]
+                    .result
+                    .0;
+            }
+        }
+
+        if r & good.0 != 0 {
+            good
+        } else if r & UNKNOWN.0 != 0 {
+            UNKNOWN
+        } else {
+            bad
+        }
+    }
+}
+
+/// Run the retrograde analysis, returning the win bits
+fn generate() -> Vec<u32> {
+    let mut db: Vec<KPKPosition> = (0..MAX_INDEX).map(KPKPosition::new).collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for idx in 0..MAX_INDEX {
+            if db[idx].result == UNKNOWN {
+                let result = db[idx].classify(&db);
+
+                if result != UNKNOWN {
+                    db[idx].result = result;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let mut bits = vec![0; MAX_INDEX / 32];
+    for (idx, position) in db.iter().enumerate() {
+        if position.result == WIN {
+            bits[idx / 32] |= 1 << (idx % 32);
+        }
+    }
+
+    bits
+}
+
+/// Check whether the position is won for White, who holds the pawn
+pub fn probe(strong_king: Square, pawn: Square, weak_king: Square, side_to_move: Color) -> bool {
+    let bits = BITBASE.get_or_init(generate);
+
+    // Mirror horizontally to get the pawn on files A to D
+    let flip = |square: Square| match pawn.file() as usize {
+        0..=3 => square,
+        _ => Square::from(square as usize ^ 7),
+    };
+    let idx = index(side_to_move, flip(strong_king), flip(weak_king), flip(pawn));
+
+    bits[idx / 32] & (1 << (idx % 32)) != 0
+}
+
+/// Exact evaluation of KPK positions from the side to move's point of view, None otherwise
+pub fn evaluate(board: &Board) -> Option<Eval> {
+    if board.occupancy.count_bits() != 3 || board.pawns() == EMPTY_BB {
+        return None;
+    }
+
+    let strong_side = if board.piece_bb[Piece::WP as usize] != EMPTY_BB {
+        Color::White
+    } else {
+        Color::Black
+    };
+
+    // Flip vertically to get White holding the pawn
+    let flip = |square: Square| match strong_side {
+        Color::White => square,
+        Color::Black => square.flipv(),
+    };
+    let strong_king = flip(board.piece_bb[strong_side.king() as usize].lsb());
+    let weak_king = flip(board.piece_bb[(!strong_side).king() as usize].lsb());
+    let pawn = flip(board.pawns().lsb());
+    let side_to_move = if board.side == strong_side {
+        Color::White
+    } else {
+        Color::Black
+    };
+
+    if !probe(strong_king, pawn, weak_king, side_to_move) {
+        return Some(0);
+    }
+
+    let eval = KPK_WIN + 20 * pawn.relative_rank(Color::White) as Eval;
+    Some(if board.side == strong_side {
+        eval
+    } else {
+        -eval
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kpk(fen: &str) -> Eval {
+        evaluate(&fen.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_kpk_opposition() {
+        // The side to move loses the opposition
+        assert_eq!(kpk("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), 0);
+        assert!(kpk("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1") < -KPK_WIN);
+        assert_eq!(kpk("8/8/8/4p3/4k3/8/4K3/8 b - - 0 1"), 0);
+        assert!(kpk("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1") < -KPK_WIN);
+
+        // A king on the sixth rank in front of its pawn always wins
+        assert!(kpk("4k3/8/3K4/4P3/8/8/8/8 w - - 0 1") > KPK_WIN);
+        assert!(kpk("4k3/8/3K4/4P3/8/8/8/8 b - - 0 1") < -KPK_WIN);
+
+        // The key squares of a pawn on the fifth rank are two ranks ahead
+        assert!(kpk("8/8/5K2/1k1P4/8/8/8/8 b - - 0 1") < -KPK_WIN);
+    }
+
+    #[test]
+    fn test_kpk_rook_pawn() {
+        assert_eq!(kpk("k7/8/8/P1K5/8/8/8/8 w - - 0 1"), 0);
+        assert_eq!(kpk("k7/8/8/P1K5/8/8/8/8 b - - 0 1"), 0);
+        assert_eq!(kpk("8/8/8/8/8/5K2/7p/7k w - - 0 1"), 0);
+
+        // the defending king is too far from the corner
+        assert!(kpk("8/8/8/P7/8/1K6/8/6k1 w - - 0 1") > KPK_WIN);
+    }
+
+    #[test]
+    fn test_kpk_mirrored() {
+        let fens = [
+            "8/4k3/8/4K3/4P3/8/8/8 w - - 0 1",
+            "8/2k5/8/8/2K3P1/8/8/8 b - - 0 1",
+            "8/8/8/8/1p6/3k4/8/2K5 w - - 0 1",
+        ];
+
+        for fen in fens {
+            let board: Board = fen.parse().unwrap();
+            assert_eq!(evaluate(&board), evaluate(&board.mirror()));
+        }
+    }
+}
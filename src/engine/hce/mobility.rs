@@ -0,0 +1,65 @@
+/// Mobility term.
+///
+/// Counts the squares each knight, bishop, rook and queen attacks, own pieces excluded. Each
+/// piece is scored relative to the typical mobility of its type, so that an average piece adds
+/// nothing on top of its psqt value while trapped pieces are penalized.
+use crate::chess::{board::*, piece::*, tables::*};
+use crate::engine::hce::{eval_params::*, params::*, score::*};
+
+/// Mobility score for the given side
+pub fn mobility(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let targets = !board.side_occupancy[side as usize];
+    let mut score = Score::ZERO;
+
+    for piece in [Piece::WN, Piece::WB, Piece::WR, Piece::WQ] {
+        let piece_type = piece as usize >> 1;
+        let weight = params.mobility[piece_type - 1];
+        let base = MOBILITY_BASE[piece_type - 1];
+
+        for square in board.piece_bb[PIECES[side as usize][piece_type] as usize] {
+            let attacks = match piece {
+                Piece::WN => knight_attacks(square),
+                Piece::WB => bishop_attacks(square, board.occupancy),
+                Piece::WR => rook_attacks(square, board.occupancy),
+                _ => queen_attacks(square, board.occupancy),
+            };
+
+            score += weight * ((attacks & targets).count_bits() as i32 - base);
+        }
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trapped_bishop() {
+        let trapped: Board = "4k3/8/8/8/8/8/1P1P4/2B1K3 w - - 0 1".parse().unwrap();
+        let open: Board = "4k3/8/8/8/5B2/8/1P1P4/4K3 w - - 0 1".parse().unwrap();
+
+        let trapped = mobility(&trapped, Color::White, &DEFAULT_PARAMS);
+        let open = mobility(&open, Color::White, &DEFAULT_PARAMS);
+
+        assert_eq!(trapped, MOBILITY[1] * -MOBILITY_BASE[1]);
+        assert_eq!(open, MOBILITY[1] * (9 - MOBILITY_BASE[1]));
+        assert!(open.mg() > trapped.mg() && open.eg() > trapped.eg());
+    }
+
+    #[test]
+    fn test_startpos_mobility() {
+        let board = Board::default();
+
+        // Only the knights can move, to two squares each
+        let expected = MOBILITY[0] * (2 * (2 - MOBILITY_BASE[0]))
+            + MOBILITY[1] * (-2 * MOBILITY_BASE[1])
+            + MOBILITY[2] * (-2 * MOBILITY_BASE[2])
+            + MOBILITY[3] * -MOBILITY_BASE[3];
+
+        for side in [Color::White, Color::Black] {
+            assert_eq!(mobility(&board, side, &DEFAULT_PARAMS), expected);
+        }
+    }
+}
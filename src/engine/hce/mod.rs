@@ -0,0 +1,433 @@
+/// Hand-crafted (classical) evaluation.
+///
+/// Every term is computed separately for both sides as a tapered Score, from White's point of
+/// view. The total is interpolated between middlegame and endgame using the game phase, and
+/// returned from the point of view of the side to move.
+mod draw;
+mod eval_params;
+mod imbalance;
+mod king;
+pub mod kpk;
+mod mobility;
+mod mopup;
+#[rustfmt::skip]
+mod params;
+mod pawn_table;
+mod pawns;
+mod pieces;
+mod scale;
+mod score;
+mod space;
+#[cfg(test)]
+pub mod symmetry;
+mod threats;
+mod trace;
+mod tuner;
+
+pub use draw::*;
+pub use eval_params::*;
+pub use imbalance::*;
+pub use king::*;
+pub use mobility::*;
+pub use mopup::*;
+pub use params::*;
+pub use pawn_table::*;
+pub use pawns::*;
+pub use pieces::*;
+pub use scale::*;
+pub use score::*;
+pub use space::*;
+pub use threats::*;
+pub use trace::*;
+pub use tuner::*;
+
+#[cfg(debug_assertions)]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::chess::{bitboard::*, board::*, piece::*, square::*};
+use crate::engine::search_params::{Eval, INFINITY};
+
+/// Square as seen from the given side, so that tables can be written from White's perspective
+const fn relative_square(square: Square, side: Color) -> usize {
+    square as usize ^ (56 * side as usize)
+}
+
+/// Material and piece-square score of every piece on every square, from White's point of view.
+/// Black pieces hold negated scores, so that the board can keep a single running sum.
+pub const PSQT: [[Score; SQUARE_COUNT]; PIECE_COUNT] = init_psqt();
+
+const fn init_psqt() -> [[Score; SQUARE_COUNT]; PIECE_COUNT] {
+    let mut psqt = [[Score::ZERO; SQUARE_COUNT]; PIECE_COUNT];
+
+    let mut piece = 0;
+    while piece < PIECE_COUNT {
+        let (piece_type, black) = (piece >> 1, piece & 1);
+        let value = PIECE_VALUES[piece_type];
+
+        let mut square = 0;
+        while square < SQUARE_COUNT {
+            let pst = PST[piece_type][square ^ (56 * black)];
+            let (mg, eg) = (value.mg() + pst.mg(), value.eg() + pst.eg());
+
+            psqt[piece][square] = match black {
+                0 => Score::new(mg, eg),
+                _ => Score::new(-mg, -eg),
+            };
+            square += 1;
+        }
+        piece += 1;
+    }
+
+    psqt
+}
+
+/// Material and piece-square table score for the given side
+pub fn psqt(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let mut score = Score::ZERO;
+
+    for (piece_type, (&value, table)) in params.piece_values.iter().zip(&params.pst).enumerate() {
+        for square in board.piece_bb[PIECES[side as usize][piece_type] as usize] {
+            score += value + table[relative_square(square, side)];
+        }
+    }
+
+    score
+}
+
+/// Recompute the incrementally updated board psqt score, used to check it in debug builds
+pub fn psqt_from_scratch(board: &Board) -> Score {
+    psqt(board, Color::White, &DEFAULT_PARAMS) - psqt(board, Color::Black, &DEFAULT_PARAMS)
+}
+
+/// Sum of all the evaluation terms for the given side, apart from material, psqt and the cached
+/// pawn structure. The terms are recorded in the trace when TRACE is set.
+fn evaluate_side<const TRACE: bool>(
+    board: &Board,
+    side: Color,
+    attacks: &Attacks,
+    passed: BitBoard,
+    params: &EvalParams,
+    trace: &mut EvalTrace,
+) -> Score {
+    let passers = passers(board, side, passed, params);
+    let knights = knights(board, side, params);
+    let bishops = bishops(board, side, params);
+    let rooks = rooks(board, side, params);
+    let imbalance = imbalance(board, side, params);
+    let king_safety = king_safety(board, side, params);
+    let king_tropism = king_tropism(board, side, params);
+    let mobility = mobility(board, side, params);
+    let threats = threats(board, side, attacks, params);
+    let space = space(board, side, attacks, params);
+
+    if TRACE {
+        let s = side as usize;
+        trace.psqt[s] = psqt(board, side, params);
+        trace.pawns[s] = pawn_structure(board, side, params);
+        trace.passers[s] = passers;
+        trace.knights[s] = knights;
+        trace.bishops[s] = bishops;
+        trace.rooks[s] = rooks;
+        trace.imbalance[s] = imbalance;
+        trace.king_safety[s] = king_safety;
+        trace.king_tropism[s] = king_tropism;
+        trace.mobility[s] = mobility;
+        trace.threats[s] = threats;
+        trace.space[s] = space;
+    }
+
+    passers
+        + knights
+        + bishops
+        + rooks
+        + imbalance
+        + king_safety
+        + king_tropism
+        + mobility
+        + threats
+        + space
+}
+
+/// Number of lazy evaluation exits, only counted in debug builds
+#[cfg(debug_assertions)]
+pub static LAZY_EXITS: AtomicU64 = AtomicU64::new(0);
+
+/// Scale and taper a White score, returning it from the point of view of the side to move
+fn finish<const TRACE: bool>(
+    board: &Board,
+    score: Score,
+    params: &EvalParams,
+    trace: &mut EvalTrace,
+) -> Eval {
+    let strong_side = if score.eg() > 0 {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let scale = scale_factor(board, strong_side);
+    let mop_up = mop_up(board);
+    let eval = score
+        .scale_eg(scale as Eval, SCALE_NORMAL as Eval)
+        .taper(board.phase())
+        + mop_up;
+    let tempo = params.tempo.taper(board.phase());
+    let eval = match board.side {
+        Color::White => eval + tempo,
+        Color::Black => -eval + tempo,
+    };
+
+    if TRACE {
+        trace.phase = board.phase();
+        trace.scale = scale;
+        trace.mop_up = mop_up;
+        trace.tempo = tempo;
+        trace.eval = eval;
+    }
+
+    eval
+}
+
+/// Static classical evaluation, from the point of view of the side to move
+pub fn evaluate(board: &Board, pawn_table: &mut PawnTable) -> Eval {
+    evaluate_lazy(board, pawn_table, -INFINITY, INFINITY)
+}
+
+/// Static classical evaluation, skipping the expensive terms when material, psqt and pawn
+/// structure alone are more than LAZY_MARGIN outside the (alpha, beta) window
+pub fn evaluate_lazy(board: &Board, pawn_table: &mut PawnTable, alpha: Eval, beta: Eval) -> Eval {
+    let trace = &mut EvalTrace::default();
+    evaluate_impl::<false>(board, &DEFAULT_PARAMS, Some(pawn_table), alpha, beta, trace)
+}
+
+/// Static classical evaluation with the given parameters. The incremental psqt score and the
+/// pawn table assume the default parameters, so both are recomputed from scratch.
+pub fn evaluate_with(board: &Board, params: &EvalParams) -> Eval {
+    let trace = &mut EvalTrace::default();
+    evaluate_impl::<false>(board, params, None, -INFINITY, INFINITY, trace)
+}
+
+/// Full classical evaluation, recording every term
+pub fn trace(board: &Board, pawn_table: &mut PawnTable) -> EvalTrace {
+    let mut trace = EvalTrace::default();
+    evaluate_impl::<true>(
+        board,
+        &DEFAULT_PARAMS,
+        Some(pawn_table),
+        -INFINITY,
+        INFINITY,
+        &mut trace,
+    );
+
+    trace
+}
+
+fn evaluate_impl<const TRACE: bool>(
+    board: &Board,
+    params: &EvalParams,
+    pawn_table: Option<&mut PawnTable>,
+    alpha: Eval,
+    beta: Eval,
+    trace: &mut EvalTrace,
+) -> Eval {
+    if is_known_draw(board) {
+        if TRACE {
+            trace.exact = Some("known draw");
+        }
+        return 0;
+    }
+    if let Some(eval) = kpk::evaluate(board) {
+        if TRACE {
+            trace.exact = Some("KPK bitbase");
+            trace.eval = eval;
+        }
+        return eval;
+    }
+
+    let (psqt, pawns) = match pawn_table {
+        Some(pawn_table) => (board.psqt_score, pawn_entry(board, pawn_table)),
+        None => (
+            psqt(board, Color::White, params) - psqt(board, Color::Black, params),
+            pawn_entry_from_scratch(board, params),
+        ),
+    };
+    let core = psqt + pawns.score;
+    let lazy = finish::<false>(board, core, params, trace);
+    if lazy + LAZY_MARGIN <= alpha || lazy - LAZY_MARGIN >= beta {
+        #[cfg(debug_assertions)]
+        LAZY_EXITS.fetch_add(1, Ordering::Relaxed);
+
+        return lazy;
+    }
+
+    let attacks = Attacks::new(board);
+    let white = evaluate_side::<TRACE>(
+        board,
+        Color::White,
+        &attacks,
+        pawns.passed[0],
+        params,
+        trace,
+    );
+    let black = evaluate_side::<TRACE>(
+        board,
+        Color::Black,
+        &attacks,
+        pawns.passed[1],
+        params,
+        trace,
+    );
+
+    finish::<TRACE>(board, core + white - black, params, trace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::tables::Prng;
+
+    fn eval(board: &Board) -> Eval {
+        evaluate(board, &mut PawnTable::new(1))
+    }
+
+    #[test]
+    fn test_startpos_is_balanced() {
+        let board = Board::default();
+
+        assert_eq!(board.phase(), MAX_PHASE);
+        assert_eq!(eval(&board), TEMPO.mg());
+    }
+
+    #[test]
+    fn test_tempo() {
+        let board: Board = "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 0 1"
+            .parse()
+            .unwrap();
+
+        // eval from White's perspective, with White and then Black to move
+        let white = eval(&board);
+        let black = -eval(&board.make_null());
+        assert_eq!(white - black, 2 * TEMPO.taper(board.phase()));
+    }
+
+    #[test]
+    fn test_endgame_taper() {
+        let board: Board = "8/8/8/4k3/8/8/8/R3K3 w - - 0 1".parse().unwrap();
+        let rook = PIECE_VALUES[Piece::WR as usize >> 1];
+
+        assert_eq!(board.phase(), 2);
+        assert!(eval(&board) > rook.eg() / 2);
+        assert_eq!(
+            eval(&board) - TEMPO.eg(),
+            TEMPO.eg() - eval(&board.make_null())
+        );
+    }
+
+    #[test]
+    fn test_mirror_symmetry() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "6k1/5p2/6p1/8/7p/8/6PP/6K1 b - - 0 1",
+            "2r3k1/1R3ppp/8/3p4/8/4R3/5PPP/6K1 w - - 0 1",
+            "r2q1rk1/pp2bppp/2np1n2/3Np3/4P3/5N2/PPP2PPP/R2QKB1R w KQ - 0 1",
+        ];
+
+        for fen in fens {
+            let board: Board = fen.parse().unwrap();
+            let mirrored = board.mirror();
+
+            assert_eq!(mirrored.mirror().to_fen(), board.to_fen());
+            assert_eq!(eval(&board), eval(&mirrored), "{fen}");
+        }
+    }
+
+    #[test]
+    fn test_incremental_psqt() {
+        let mut rng = Prng::new(0xCA4B);
+
+        for _ in 0..50 {
+            let mut board = Board::default();
+            assert_eq!(board.psqt_score, psqt_from_scratch(&board));
+
+            for _ in 0..200 {
+                let move_list = board.gen_moves::<QUIETS>();
+                if move_list.is_empty() {
+                    break;
+                }
+
+                let m = move_list.moves[rng.next_u64() as usize % move_list.len()];
+                board = board.make_move(m);
+                assert_eq!(board.psqt_score, psqt_from_scratch(&board));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lazy_eval() {
+        let mut rng = Prng::new(0x1A27);
+        let mut pawn_table = PawnTable::new(1);
+
+        for _ in 0..20 {
+            let mut board = Board::default();
+
+            for _ in 0..100 {
+                let move_list = board.gen_moves::<QUIETS>();
+                if move_list.is_empty() {
+                    break;
+                }
+
+                let m = move_list.moves[rng.next_u64() as usize % move_list.len()];
+                board = board.make_move(m);
+
+                let full = eval(&board);
+                let alpha = full + (rng.next_u64() % 2001) as Eval - 1000;
+                let beta = alpha + 1 + (rng.next_u64() % 200) as Eval;
+                let lazy = evaluate_lazy(&board, &mut pawn_table, alpha, beta);
+
+                // Lazy exits are only taken far outside the window, on the same side as the
+                // full evaluation, and the full evaluation is returned otherwise
+                if lazy != full {
+                    assert!(lazy + LAZY_MARGIN <= alpha || lazy - LAZY_MARGIN >= beta);
+                    assert_eq!(lazy < alpha, full < alpha, "{}", board.to_fen());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "2r3k1/1R3ppp/8/3p4/8/4R3/5PPP/6K1 w - - 0 1",
+            "7k/8/8/8/8/8/8/Q3K3 b - - 0 1",
+            "8/8/4k3/8/8/2NN4/4K3/8 w - - 0 1",
+            "8/4k3/8/4K3/4P3/8/8/8 b - - 0 1",
+        ];
+
+        for fen in fens {
+            let board: Board = fen.parse().unwrap();
+            let trace = trace(&board, &mut PawnTable::new(1));
+            assert_eq!(trace.eval, eval(&board), "{fen}");
+
+            if trace.exact.is_none() {
+                let white = trace.white_eval();
+                let eval = match board.side {
+                    Color::White => white + trace.tempo,
+                    Color::Black => -white + trace.tempo,
+                };
+
+                let [white_pawns, black_pawns] = trace.pawns;
+                assert_eq!(trace.psqt[0] - trace.psqt[1], board.psqt_score);
+                assert_eq!(
+                    white_pawns - black_pawns,
+                    pawn_entry_from_scratch(&board, &DEFAULT_PARAMS).score
+                );
+                assert_eq!(eval, trace.eval, "{fen}");
+            }
+        }
+    }
+}
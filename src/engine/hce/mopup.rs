@@ -0,0 +1,161 @@
+/// Mop-up evaluation for basic mating endgames.
+///
+/// Against a lone king, material alone gives the search no gradient to follow. The mop-up term
+/// rewards driving the defending king to the edge, or to a corner of the bishop's color with
+/// bishop and knight, and bringing the attacking king closer. It stays well below a minor piece.
+use crate::chess::{bitboard::*, board::*, piece::*, square::*};
+use crate::engine::hce::params::*;
+use crate::engine::search_params::Eval;
+
+/// Manhattan distance between two squares
+fn manhattan(a: Square, b: Square) -> i32 {
+    let (df, dr) = a.dist(b);
+    df.abs() as i32 + dr.abs() as i32
+}
+
+/// Manhattan distance from the four central squares, from 0 to 6
+fn center_distance(square: Square) -> i32 {
+    let (file, rank) = (square.file() as i32, square.rank() as i32);
+    (3 - file).max(file - 4) + (3 - rank).max(rank - 4)
+}
+
+/// The side has enough pieces to force mate against a lone king
+fn has_mating_material(board: &Board, side: Color) -> bool {
+    let count = |piece: Piece| board.piece_bb[piece as usize].count_bits();
+    let bishops = board.piece_bb[side.bishop() as usize];
+
+    count(side.queen()) + count(side.rook()) > 0
+        || bishops & LIGHT_SQUARES_BB != EMPTY_BB && bishops & DARK_SQUARES_BB != EMPTY_BB
+        || count(side.bishop()) > 0 && count(side.knight()) > 0
+        || count(side.knight()) >= 3
+}
+
+/// How far the defending king has been pushed, from 0 in the center up to 12 or 14 in the
+/// corners. With a single bishop and knight only the corners of the bishop's color count.
+fn push_to_edge(board: &Board, strong_side: Color, weak_king: Square) -> i32 {
+    let count = |piece: Piece| board.piece_bb[piece as usize].count_bits();
+    let bishops = board.piece_bb[strong_side.bishop() as usize];
+
+    if count(strong_side.bishop()) == 1
+        && count(strong_side.knight()) == 1
+        && count(strong_side.rook()) + count(strong_side.queen()) == 0
+    {
+        let corners = match bishops.lsb().color() {
+            Color::White => [Square::A8, Square::H1],
+            Color::Black => [Square::H8, Square::A1],
+        };
+
+        let distance = corners.map(|corner| manhattan(weak_king, corner));
+        return 14 - distance[0].min(distance[1]);
+    }
+
+    2 * center_distance(weak_king)
+}
+
+/// Mop-up bonus from White's point of view, zero unless one side is left with a bare king
+pub fn mop_up(board: &Board) -> Eval {
+    let strong_side = match board.side_occupancy.map(|occupancy| occupancy.count_bits()) {
+        [_, 1] => Color::White,
+        [1, _] => Color::Black,
+        _ => return 0,
+    };
+
+    if !has_mating_material(board, strong_side) {
+        return 0;
+    }
+
+    let strong_king = board.piece_bb[strong_side.king() as usize].lsb();
+    let weak_king = board.piece_bb[(!strong_side).king() as usize].lsb();
+    let bonus = MOP_UP_PUSH * push_to_edge(board, strong_side, weak_king)
+        + MOP_UP_PROXIMITY * (14 - manhattan(strong_king, weak_king));
+
+    match strong_side {
+        Color::White => bonus,
+        Color::Black => -bonus,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::hce::{evaluate, PawnTable};
+    use crate::engine::search_params::MATE;
+
+    /// Plain fixed-depth negamax over the classical evaluation
+    fn negamax(board: &Board, depth: usize, ply: Eval, mut alpha: Eval, beta: Eval) -> Eval {
+        let move_list = board.gen_moves::<QUIETS>();
+        if move_list.is_empty() {
+            return match board.checkers {
+                EMPTY_BB => 0,
+                _ => ply - MATE,
+            };
+        }
+        if depth == 0 {
+            return evaluate(board, &mut PawnTable::new(1));
+        }
+
+        for &m in &move_list.moves[..move_list.len()] {
+            let eval = -negamax(&board.make_move(m), depth - 1, ply + 1, -beta, -alpha);
+            alpha = alpha.max(eval);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        alpha
+    }
+
+    /// Play the given number of moves, the strong side searching at the given depth and the
+    /// defending king greedily picking the reply with the best static evaluation
+    fn play(fen: &str, depth: usize, moves: usize) -> Board {
+        let mut board: Board = fen.parse().unwrap();
+
+        for _ in 0..moves {
+            let move_list = board.gen_moves::<QUIETS>();
+            let search_depth = if board.side == Color::White { depth } else { 1 };
+            let Some(&best) = move_list.moves[..move_list.len()]
+                .iter()
+                .max_by_key(|&&m| -negamax(&board.make_move(m), search_depth - 1, 1, -MATE, MATE))
+            else {
+                break;
+            };
+
+            board = board.make_move(best);
+        }
+
+        board
+    }
+
+    #[test]
+    fn test_mop_up_gradient() {
+        let eval = |fen: &str| mop_up(&fen.parse().unwrap());
+
+        assert!(eval("7k/8/8/8/8/8/8/1Q2K3 w - - 0 1") > eval("8/8/8/3k4/8/8/8/1Q2K3 w - - 0 1"));
+        assert!(eval("7k/8/5K2/8/8/8/8/R7 w - - 0 1") > eval("7k/8/8/8/8/8/8/R3K3 w - - 0 1"));
+        assert_eq!(
+            eval("7K/8/5k2/8/8/8/8/r7 w - - 0 1"),
+            -eval("7k/8/5K2/8/8/8/8/R7 w - - 0 1")
+        );
+
+        // Light squared bishop, so the defending king is mated in a8 or h1
+        let right = eval("k7/8/8/8/8/8/8/4KBN1 w - - 0 1");
+        let wrong = eval("7k/8/8/8/8/8/8/4KBN1 w - - 0 1");
+        assert!(right > wrong);
+
+        // No mop-up without mating material, or with pieces on both sides
+        assert_eq!(eval("7k/8/8/8/8/8/8/4KN2 w - - 0 1"), 0);
+        assert_eq!(eval("7k/8/8/8/8/8/8/4KNN1 w - - 0 1"), 0);
+        assert_eq!(eval("7k/6n1/8/8/8/8/8/Q3K3 w - - 0 1"), 0);
+    }
+
+    #[test]
+    fn test_mop_up_mates() {
+        let board = play("8/8/8/3k4/8/8/8/Q3K3 w - - 0 1", 3, 60);
+        assert!(board.gen_moves::<QUIETS>().is_empty());
+        assert_ne!(board.checkers, EMPTY_BB);
+
+        let board = play("8/8/8/4k3/8/8/8/R3K3 w - - 0 1", 3, 60);
+        let weak_king = board.piece_bb[Piece::BK as usize].lsb();
+        assert!(center_distance(weak_king) >= 5);
+    }
+}
@@ -0,0 +1,262 @@
+/// Evaluation parameters for the hand-crafted evaluation.
+///
+/// Material values and piece-square tables are taken from PeSTO by Ronald Friederich, with the
+/// tables averaged across files so that the evaluation is symmetric.
+/// Tables are written from White's point of view, with A8 as the first entry.
+use crate::chess::square::*;
+use crate::engine::hce::score::*;
+use crate::S;
+
+/// Material values, indexed by piece type
+pub const PIECE_VALUES: [Score; 6] = [
+    S!(82, 94), S!(337, 281), S!(365, 297), S!(477, 512), S!(1025, 936), S!(0, 0),
+];
+
+/// Side to move bonus. It only makes up for stand pat undervaluing the side to move, so it is
+/// not part of the position value itself and must be left out of draw adjudication.
+pub const TEMPO: Score = S!(20, 10);
+
+/// Pawn structure penalties, per pawn
+pub const DOUBLED_PAWN: Score = S!(-8, -24);
+pub const ISOLATED_PAWN: Score = S!(-12, -14);
+pub const BACKWARD_PAWN: Score = S!(-9, -11);
+
+/// Passed pawn bonuses, indexed by relative rank
+pub const PASSED_PAWN: [Score; 8] = [
+    S!(0, 0), S!(2, 8), S!(4, 14), S!(10, 28), S!(26, 54), S!(52, 102), S!(84, 164), S!(0, 0),
+];
+
+/// Passed pawn penalty when an enemy piece stands on the stop square, by relative rank
+pub const PASSED_BLOCKED: [Score; 8] = [
+    S!(0, 0), S!(0, -3), S!(0, -5), S!(-3, -12), S!(-8, -26), S!(-15, -46), S!(-20, -72), S!(0, 0),
+];
+
+/// Passed pawn penalty when the enemy attacks the (empty) stop square, by relative rank
+pub const PASSED_UNSAFE: [Score; 8] = [
+    S!(0, 0), S!(0, -2), S!(0, -4), S!(-2, -8), S!(-5, -16), S!(-8, -26), S!(-10, -36), S!(0, 0),
+];
+
+/// Passed pawn bonus for a neighbouring passer within one rank, by relative rank
+pub const PASSED_CONNECTED: [Score; 8] = [
+    S!(0, 0), S!(2, 3), S!(3, 5), S!(5, 10), S!(10, 20), S!(20, 40), S!(30, 62), S!(0, 0),
+];
+
+/// Bonus for candidate passers, pawns which can become passed by advancing, by relative rank
+pub const CANDIDATE_PASSER: [Score; 8] = [
+    S!(0, 0), S!(1, 3), S!(2, 5), S!(4, 10), S!(9, 18), S!(18, 34), S!(0, 0), S!(0, 0),
+];
+
+/// Passed pawn bonus for an own rook behind it on the same file
+pub const PASSED_ROOK_BEHIND: Score = S!(6, 22);
+
+/// Passed pawn penalty for an enemy rook behind it on the same file, restraining it
+pub const PASSED_ENEMY_ROOK_BEHIND: Score = S!(-4, -18);
+
+/// Passed pawn bonus per square of distance between the stop square and the own or enemy king,
+/// by relative rank. Only relevant in the endgame.
+pub const PASSED_KING_OWN: [Score; 8] = [
+    S!(0, 0), S!(0, 0), S!(0, 0), S!(0, -2), S!(0, -5), S!(0, -8), S!(0, -10), S!(0, 0),
+];
+pub const PASSED_KING_ENEMY: [Score; 8] = [
+    S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 4), S!(0, 10), S!(0, 16), S!(0, 22), S!(0, 0),
+];
+
+/// Bonus for holding bishops on both square colors
+pub const BISHOP_PAIR: Score = S!(24, 58);
+
+/// Material imbalance, per own piece of the row type and per own piece of the column type
+/// (other pieces of the same type on the diagonal), indexed by piece type from pawn to queen
+pub const IMBALANCE_OURS: [[Score; 5]; 5] = [
+    [S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0)],
+    [S!(3, 4), S!(-4, -6), S!(0, 0), S!(0, 0), S!(0, 0)],
+    [S!(1, 2), S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0)],
+    [S!(-3, -5), S!(0, 0), S!(0, 0), S!(-12, -18), S!(0, 0)],
+    [S!(0, 0), S!(0, 0), S!(0, 0), S!(-10, -14), S!(-24, -30)],
+];
+
+/// Material imbalance, per own piece of the row type and per enemy piece of the column type
+pub const IMBALANCE_THEIRS: [[Score; 5]; 5] = [
+    [S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0)],
+    [S!(0, 0), S!(0, 0), S!(0, 0), S!(10, 4), S!(6, 6)],
+    [S!(0, 0), S!(0, 0), S!(0, 0), S!(10, 4), S!(6, 6)],
+    [S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0)],
+    [S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0)],
+];
+
+/// Bishop penalty per own pawn on its square color
+pub const BAD_BISHOP_PAWN: Score = S!(-2, -6);
+
+/// Minor pieces on a pawn defended square which enemy pawns can never attack
+pub const KNIGHT_OUTPOST: Score = S!(26, 14);
+pub const BISHOP_OUTPOST: Score = S!(12, 6);
+
+/// Knight penalty on the A and H files
+pub const KNIGHT_ON_RIM: Score = S!(-10, -8);
+
+/// Rook bonuses for files without own pawns (semi-open) and without any pawn (open)
+pub const ROOK_SEMI_OPEN_FILE: Score = S!(12, 8);
+pub const ROOK_OPEN_FILE: Score = S!(30, 10);
+
+/// Rook on the enemy second rank, while it holds the enemy king or enemy pawns
+pub const ROOK_SEVENTH_RANK: Score = S!(6, 24);
+
+/// Rooks doubled on a file, scored once per pair
+pub const ROOK_CONNECTED: Score = S!(10, 4);
+
+/// King shield penalty per file around the king, indexed by the rank distance to the closest
+/// own pawn in front of the king (0 when there is none)
+pub const KING_SHIELD: [Score; 8] = [
+    S!(-36, 0), S!(0, 0), S!(-10, 0), S!(-22, 0), S!(-30, 0), S!(-34, 0), S!(-36, 0), S!(-36, 0),
+];
+
+/// King penalties for files around the king without own pawns (semi-open) or without pawns (open)
+pub const KING_SEMI_OPEN_FILE: Score = S!(-14, 0);
+pub const KING_OPEN_FILE: Score = S!(-24, 0);
+
+/// Pawn storm bonus per own pawn on the three files around the enemy king, indexed by its rank
+/// distance to the enemy king. Only applied when the kings are on opposite wings.
+pub const PAWN_STORM: [Score; 8] = [
+    S!(0, 0), S!(30, 0), S!(26, 0), S!(16, 0), S!(6, 0), S!(0, 0), S!(0, 0), S!(0, 0),
+];
+
+/// Minimum file distance between the kings for them to be on opposite wings
+pub const PAWN_STORM_FILE_DISTANCE: u8 = 4;
+
+/// Attack units per king zone square attacked, indexed by attacker piece type
+pub const KING_ATTACK_WEIGHTS: [i32; 6] = [0, 2, 2, 3, 4, 0];
+
+/// Minimum number of pieces attacking the king zone before the safety table is applied
+pub const KING_ATTACKERS_MIN: u32 = 2;
+
+/// King safety score, indexed by attack units
+pub const KING_SAFETY_TABLE: [Score; 64] = [
+    S!(0, 0), S!(0, 0), S!(-1, 0), S!(-3, -1), S!(-6, -2), S!(-9, -2), S!(-13, -3), S!(-17, -4),
+    S!(-22, -6), S!(-28, -7), S!(-35, -9), S!(-42, -10), S!(-50, -12), S!(-59, -15), S!(-69, -17), S!(-79, -20),
+    S!(-90, -22), S!(-101, -25), S!(-113, -28), S!(-126, -32), S!(-140, -35), S!(-154, -38), S!(-169, -42), S!(-185, -46),
+    S!(-202, -50), S!(-219, -55), S!(-237, -59), S!(-255, -64), S!(-274, -68), S!(-294, -74), S!(-315, -79), S!(-336, -84),
+    S!(-358, -90), S!(-381, -95), S!(-405, -101), S!(-429, -107), S!(-454, -114), S!(-479, -120), S!(-500, -125), S!(-500, -125),
+    S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125),
+    S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125),
+    S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125), S!(-500, -125),
+];
+
+/// Bonus per square of closeness (7 - distance) to the enemy king, indexed by piece type
+pub const KING_TROPISM: [Score; 6] = [S!(0, 0), S!(3, 0), S!(1, 0), S!(2, 0), S!(4, 1), S!(0, 0)];
+
+/// Bonus per square attacked and not occupied by own pieces, indexed by piece type from knight
+/// to queen
+pub const MOBILITY: [Score; 4] = [S!(4, 4), S!(5, 5), S!(2, 4), S!(1, 2)];
+
+/// Typical number of mobility squares per piece type from knight to queen, which score zero
+pub const MOBILITY_BASE: [i32; 4] = [4, 6, 7, 13];
+
+/// Bonus per enemy piece (not pawn) attacked by an own pawn
+pub const THREAT_BY_PAWN: Score = S!(48, 28);
+
+/// Bonus per enemy rook or queen attacked by a lower valued piece
+pub const THREAT_BY_LESSER: Score = S!(32, 18);
+
+/// Penalty per own piece (not pawn or king) attacked and not defended
+pub const HANGING_PIECE: Score = S!(-28, -16);
+
+/// Space bonus per safe central square, weighted by the number of own pieces (divided by 4)
+pub const SPACE: Score = S!(1, 0);
+
+/// Minimum non-pawn material (in phase units) each side needs for space to be scored
+pub const SPACE_MIN_MATERIAL: i32 = 8;
+
+/// Lazy evaluation skips the remaining terms when material, psqt and pawn structure are further
+/// than this outside the search window
+pub const LAZY_MARGIN: i32 = 700;
+
+/// Endgame scale factors, out of SCALE_NORMAL
+pub const SCALE_NORMAL: u8 = 64;
+
+/// Opposite colored bishops with no other pieces
+pub const SCALE_OCB: u8 = 32;
+
+/// Rook pawns only, with a bishop not controlling the promotion square and the enemy king there
+pub const SCALE_WRONG_BISHOP: u8 = 0;
+
+/// No pawns and at most a minor piece up (KBvK, KRvKB, KRNvKR...)
+pub const SCALE_MINOR_UP: u8 = 8;
+
+/// Stronger side only up in pawns, with few of them, indexed by its pawn count
+pub const SCALE_FEW_PAWNS: [u8; 3] = [SCALE_NORMAL, 48, 56];
+
+/// Mop-up bonus per unit the lone king has been pushed towards the edge
+pub const MOP_UP_PUSH: i32 = 10;
+
+/// Mop-up bonus per unit of closeness (14 - manhattan distance) between the kings
+pub const MOP_UP_PROXIMITY: i32 = 4;
+
+/// Piece-square tables, indexed by piece type
+pub const PST: [[Score; SQUARE_COUNT]; 6] = [
+    PAWN_PST, KNIGHT_PST, BISHOP_PST, ROOK_PST, QUEEN_PST, KING_PST,
+];
+
+pub const PAWN_PST: [Score; SQUARE_COUNT] = [
+    S!(   0,    0), S!(   0,    0), S!(   0,    0), S!(   0,    0), S!(   0,    0), S!(   0,    0), S!(   0,    0), S!(   0,    0),
+    S!(  43,  182), S!(  84,  169), S!(  93,  145), S!(  81,  140), S!(  81,  140), S!(  93,  145), S!(  84,  169), S!(  43,  182),
+    S!( -13,   89), S!(  16,   91), S!(  41,   69), S!(  48,   61), S!(  48,   61), S!(  41,   69), S!(  16,   91), S!( -13,   89),
+    S!( -19,   24), S!(  15,   20), S!(   9,    8), S!(  22,    1), S!(  22,    1), S!(   9,    8), S!(  15,   20), S!( -19,   24),
+    S!( -26,    6), S!(   4,    6), S!(   0,   -6), S!(  14,   -7), S!(  14,   -7), S!(   0,   -6), S!(   4,    6), S!( -26,    6),
+    S!( -19,   -2), S!(  14,    3), S!(  -1,   -6), S!(  -4,    0), S!(  -4,    0), S!(  -1,   -6), S!(  14,    3), S!( -19,   -2),
+    S!( -29,    3), S!(  18,    5), S!(   2,    4), S!( -19,   11), S!( -19,   11), S!(   2,    4), S!(  18,    5), S!( -29,    3),
+    S!(   0,    0), S!(   0,    0), S!(   0,    0), S!(   0,    0), S!(   0,    0), S!(   0,    0), S!(   0,    0), S!(   0,    0),
+];
+
+pub const KNIGHT_PST: [Score; SQUARE_COUNT] = [
+    S!(-137,  -79), S!( -52,  -51), S!( -66,  -20), S!(   6,  -30), S!(   6,  -30), S!( -66,  -20), S!( -52,  -51), S!(-137,  -79),
+    S!( -45,  -39), S!( -17,  -16), S!(  67,  -25), S!(  29,   -6), S!(  29,   -6), S!(  67,  -25), S!( -17,  -16), S!( -45,  -39),
+    S!(  -2,  -33), S!(  66,  -20), S!(  83,    0), S!(  74,    4), S!(  74,    4), S!(  83,    0), S!(  66,  -20), S!(  -2,  -33),
+    S!(   6,  -18), S!(  17,    5), S!(  44,   16), S!(  45,   22), S!(  45,   22), S!(  44,   16), S!(  17,    5), S!(   6,  -18),
+    S!( -11,  -18), S!(  12,   -1), S!(  17,   16), S!(  20,   20), S!(  20,   20), S!(  17,   16), S!(  12,   -1), S!( -11,  -18),
+    S!( -20,  -23), S!(   8,  -12), S!(  14,   -2), S!(  14,   12), S!(  14,   12), S!(  14,   -2), S!(   8,  -12), S!( -20,  -23),
+    S!( -24,  -43), S!( -34,  -22), S!(   3,  -15), S!(  -2,   -4), S!(  -2,   -4), S!(   3,  -15), S!( -34,  -22), S!( -24,  -43),
+    S!( -64,  -47), S!( -20,  -51), S!( -43,  -21), S!( -25,  -19), S!( -25,  -19), S!( -43,  -21), S!( -20,  -51), S!( -64,  -47),
+];
+
+pub const BISHOP_PST: [Score; SQUARE_COUNT] = [
+    S!( -19,  -19), S!(   5,  -19), S!( -62,  -10), S!( -31,   -8), S!( -31,   -8), S!( -62,  -10), S!(   5,  -19), S!( -19,  -19),
+    S!( -37,  -11), S!(  17,   -4), S!(  20,   -3), S!(   8,   -8), S!(   8,   -8), S!(  20,   -3), S!(  17,   -4), S!( -37,  -11),
+    S!(  -9,    3), S!(  37,   -4), S!(  46,    3), S!(  37,   -2), S!(  37,   -2), S!(  46,    3), S!(  37,   -4), S!(  -9,    3),
+    S!(  -3,   -1), S!(   6,    6), S!(  28,   11), S!(  43,   11), S!(  43,   11), S!(  28,   11), S!(   6,    6), S!(  -3,   -1),
+    S!(  -1,   -8), S!(  11,    0), S!(  12,   11), S!(  30,   13), S!(  30,   13), S!(  12,   11), S!(  11,    0), S!(  -1,   -8),
+    S!(   5,  -14), S!(  16,   -5), S!(  21,    5), S!(  14,   11), S!(  14,   11), S!(  21,    5), S!(  16,   -5), S!(   5,  -14),
+    S!(   2,  -21), S!(  24,  -17), S!(  18,   -8), S!(   3,    1), S!(   3,    1), S!(  18,   -8), S!(  24,  -17), S!(   2,  -21),
+    S!( -27,  -20), S!( -21,   -7), S!( -13,  -20), S!( -17,   -7), S!( -17,   -7), S!( -13,  -20), S!( -21,   -7), S!( -27,  -20),
+];
+
+pub const ROOK_PST: [Score; SQUARE_COUNT] = [
+    S!(  37,    9), S!(  36,    9), S!(  20,   15), S!(  57,   13), S!(  57,   13), S!(  20,   15), S!(  36,    9), S!(  37,    9),
+    S!(  35,    7), S!(  29,   10), S!(  62,    8), S!(  71,    4), S!(  71,    4), S!(  62,    8), S!(  29,   10), S!(  35,    7),
+    S!(   5,    2), S!(  40,    1), S!(  35,    2), S!(  26,    4), S!(  26,    4), S!(  35,    2), S!(  40,    1), S!(   5,    2),
+    S!( -22,    3), S!( -10,    1), S!(  21,    7), S!(  25,    1), S!(  25,    1), S!(  21,    7), S!( -10,    1), S!( -22,    3),
+    S!( -30,   -4), S!( -10,   -2), S!( -10,    1), S!(   4,   -1), S!(   4,   -1), S!( -10,    1), S!( -10,   -2), S!( -30,   -4),
+    S!( -39,  -10), S!( -15,   -4), S!(  -8,   -9), S!(  -7,   -4), S!(  -7,   -4), S!(  -8,   -9), S!( -15,   -4), S!( -39,  -10),
+    S!( -58,   -5), S!( -11,   -9), S!(  -5,   -5), S!(  -5,   -4), S!(  -5,   -4), S!(  -5,   -5), S!( -11,   -9), S!( -58,   -5),
+    S!( -23,  -15), S!( -25,    3), S!(   4,   -5), S!(  16,   -3), S!(  16,   -3), S!(   4,   -5), S!( -25,    3), S!( -23,  -15),
+];
+
+pub const QUEEN_PST: [Score; SQUARE_COUNT] = [
+    S!(   8,    5), S!(  21,   16), S!(  36,   20), S!(  35,   27), S!(  35,   27), S!(  36,   20), S!(  21,   16), S!(   8,    5),
+    S!(  15,   -9), S!(  -6,   25), S!(  26,   28), S!(  -8,   49), S!(  -8,   49), S!(  26,   28), S!(  -6,   25), S!(  15,   -9),
+    S!(  22,   -6), S!(  15,   12), S!(  31,   22), S!(  18,   48), S!(  18,   48), S!(  31,   22), S!(  15,   12), S!(  22,   -6),
+    S!( -13,   19), S!( -15,   39), S!(   0,   32), S!(  -9,   51), S!(  -9,   51), S!(   0,   32), S!( -15,   39), S!( -13,   19),
+    S!(  -6,    2), S!( -12,   33), S!(  -7,   26), S!(  -6,   39), S!(  -6,   39), S!(  -7,   26), S!( -12,   33), S!(  -6,    2),
+    S!(  -5,   -6), S!(   8,   -9), S!(  -5,   16), S!(  -4,    7), S!(  -4,    7), S!(  -5,   16), S!(   8,   -9), S!(  -5,   -6),
+    S!( -17,  -27), S!(  -6,  -30), S!(  13,  -27), S!(   5,  -16), S!(   5,  -16), S!(  13,  -27), S!(  -6,  -30), S!( -17,  -27),
+    S!( -26,  -37), S!( -25,  -24), S!( -17,  -27), S!(  -3,  -24), S!(  -3,  -24), S!( -17,  -27), S!( -25,  -24), S!( -26,  -37),
+];
+
+pub const KING_PST: [Score; SQUARE_COUNT] = [
+    S!( -26,  -46), S!(  12,  -16), S!(  -9,   -2), S!( -36,  -15), S!( -36,  -15), S!(  -9,   -2), S!(  12,  -16), S!( -26,  -46),
+    S!(   0,   -1), S!( -20,   20), S!( -12,   26), S!(  -8,   17), S!(  -8,   17), S!( -12,   26), S!( -20,   20), S!(   0,   -1),
+    S!( -16,   11), S!(  23,   30), S!(   4,   34), S!( -18,   17), S!( -18,   17), S!(   4,   34), S!(  23,   30), S!( -16,   11),
+    S!( -27,   -3), S!( -17,   24), S!( -19,   28), S!( -29,   26), S!( -29,   26), S!( -19,   28), S!( -17,   24), S!( -27,   -3),
+    S!( -50,  -15), S!( -17,    2), S!( -36,   22), S!( -43,   25), S!( -43,   25), S!( -36,   22), S!( -17,    2), S!( -50,  -15),
+    S!( -21,  -14), S!( -15,    2), S!( -26,   13), S!( -45,   22), S!( -45,   22), S!( -26,   13), S!( -15,    2), S!( -21,  -14),
+    S!(   4,  -22), S!(   8,   -8), S!( -12,    4), S!( -54,   13), S!( -54,   13), S!( -12,    4), S!(   8,   -8), S!(   4,  -22),
+    S!(  -1,  -48), S!(  30,  -29), S!(  -8,  -18), S!( -23,  -20), S!( -23,  -20), S!(  -8,  -18), S!(  30,  -29), S!(  -1,  -48),
+];
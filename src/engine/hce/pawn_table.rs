@@ -0,0 +1,96 @@
+/// Pawn hash table, caching the pawn structure evaluation.
+///
+/// Pawn terms only change when a pawn moves or is captured, so they are stored under the pawn
+/// hash of the board alongside the passed pawns of both sides, which the passer term reuses.
+/// Entries are always replaced on store, and probes are counted to report the hit rate.
+use std::mem::size_of;
+
+use crate::chess::{bitboard::*, zobrist::*};
+use crate::engine::hce::score::*;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PawnEntry {
+    key: u64,
+    pub score: Score,
+    pub passed: [BitBoard; 2],
+}
+
+impl PawnEntry {
+    pub fn new(pawn_hash: ZHash, score: Score, passed: [BitBoard; 2]) -> Self {
+        Self {
+            key: pawn_hash.0,
+            score,
+            passed,
+        }
+    }
+}
+
+pub struct PawnTable {
+    table: Vec<PawnEntry>,
+    probes: u64,
+    hits: u64,
+}
+pub const PAWN_TABLE_SIZE: usize = 2;
+
+// Default to 2 MiB size
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new(PAWN_TABLE_SIZE)
+    }
+}
+
+impl PawnTable {
+    /// Create an empty pawn table of the given size in MiB
+    pub fn new(mb_size: usize) -> Self {
+        let mut pawn_table = PawnTable {
+            table: Vec::new(),
+            probes: 0,
+            hits: 0,
+        };
+        pawn_table.resize(mb_size);
+
+        pawn_table
+    }
+
+    /// Same multiply-shift indexing as the tt
+    fn get_key(&self, pawn_hash: ZHash) -> usize {
+        ((pawn_hash.0 as u128 * self.table.len() as u128) >> 64) as usize
+    }
+
+    /// Resize the table to the given size in MiB, clearing it
+    pub fn resize(&mut self, mb_size: usize) {
+        let new_size = (mb_size << 20) / size_of::<PawnEntry>();
+        self.table = vec![PawnEntry::default(); new_size];
+        (self.probes, self.hits) = (0, 0);
+    }
+
+    /// Reset the table to empty entries
+    pub fn clear(&mut self) {
+        self.table.fill(PawnEntry::default());
+        (self.probes, self.hits) = (0, 0);
+    }
+
+    /// Look for the entry of the given pawn hash
+    pub fn probe(&mut self, pawn_hash: ZHash) -> Option<PawnEntry> {
+        let entry = self.table[self.get_key(pawn_hash)];
+        let hit = entry.key == pawn_hash.0 && entry.key != 0;
+
+        self.probes += 1;
+        self.hits += hit as u64;
+        hit.then_some(entry)
+    }
+
+    /// Fraction of probes which found their entry since the table was last cleared
+    pub fn hit_rate(&self) -> f64 {
+        match self.probes {
+            0 => 0.0,
+            probes => self.hits as f64 / probes as f64,
+        }
+    }
+
+    /// Store the entry, overwriting whatever is in its slot
+    pub fn insert(&mut self, entry: PawnEntry) {
+        let index = self.get_key(ZHash(entry.key));
+        self.table[index] = entry;
+    }
+}
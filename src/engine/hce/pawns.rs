@@ -0,0 +1,385 @@
+/// Pawn structure terms.
+///
+/// Every pattern is detected set-wise on the pawn bitboards, returning the affected pawns so that
+/// they can be counted (and traced) separately.
+use crate::chess::{bitboard::*, board::*, piece::*, tables::*};
+use crate::engine::hce::{eval_params::*, pawn_table::*, score::*};
+
+/// Own pawns with another own pawn in front of them on the same file.
+/// A file holding n pawns yields n - 1 doubled pawns.
+pub fn doubled_pawns(pawns: BitBoard, side: Color) -> BitBoard {
+    pawns & pawns.rear_span(side)
+}
+
+/// Own pawns without any own pawn on the adjacent files
+pub fn isolated_pawns(pawns: BitBoard) -> BitBoard {
+    pawns & !pawns.adjacent_files()
+}
+
+/// Own pawns which can not be defended by another pawn, either now or after advancing it, and
+/// whose stop square is controlled by an enemy pawn. Isolated pawns are scored on their own.
+pub fn backward_pawns(pawns: BitBoard, enemy_pawns: BitBoard, side: Color) -> BitBoard {
+    let defended = pawns.pawn_attacks(side).fill(side);
+    let stops = pawns.forward(side) & enemy_pawns.pawn_attacks(!side) & !defended;
+
+    stops.forward(!side) & pawns & !isolated_pawns(pawns)
+}
+
+/// Own pawns without enemy pawns in front of them, on the same or on the adjacent files
+pub fn passed_pawns(pawns: BitBoard, enemy_pawns: BitBoard, side: Color) -> BitBoard {
+    let span = enemy_pawns.front_span(!side);
+
+    pawns & !(span | span.east() | span.west())
+}
+
+/// Own pawns which are not passed, but have no enemy pawn in front of them on their file, and at
+/// least as many own pawns level with or behind them on the adjacent files (helpers) as enemy pawns
+/// in front of them there (sentries). Trading helpers for sentries eventually makes them passed.
+pub fn candidate_passers(pawns: BitBoard, enemy_pawns: BitBoard, side: Color) -> BitBoard {
+    let open = pawns & !enemy_pawns.front_span(!side) & !passed_pawns(pawns, enemy_pawns, side);
+
+    let mut candidates = EMPTY_BB;
+    for square in open {
+        let sentries = enemy_pawns & outpost_mask(square, side);
+        let helpers = pawns & isolated_mask(square) & !outpost_mask(square, side);
+
+        if helpers.count_bits() >= sentries.count_bits() {
+            candidates = candidates.set_bit(square);
+        }
+    }
+
+    candidates
+}
+
+/// Passed pawn score for the given side, given its passed pawns.
+/// The bonus is reduced when the stop square is blockaded or attacked by the enemy, and raised
+/// for passers next to each other. Following the Tarrasch rule, rooks belong behind passers, be
+/// it to support their advance or to restrain them. In the endgame, both kings should stay close
+/// to the stop square.
+pub fn passers(board: &Board, side: Color, passed: BitBoard, params: &EvalParams) -> Score {
+    let rooks = board.piece_bb[side.rook() as usize];
+    let enemy_rooks = board.piece_bb[(!side).rook() as usize];
+    let enemies = board.side_occupancy[!side as usize];
+    let king = board.piece_bb[side.king() as usize].lsb();
+    let enemy_king = board.piece_bb[(!side).king() as usize].lsb();
+
+    let mut score = Score::ZERO;
+    for square in passed {
+        let rank = square.relative_rank(side);
+        let stop = square.forward(side);
+        score += params.passed_pawn[rank];
+
+        if enemies.get_bit(stop) {
+            score += params.passed_blocked[rank];
+        } else if board.is_square_attacked(stop, !side) {
+            score += params.passed_unsafe[rank];
+        }
+
+        let sides = square.to_board().east() | square.to_board().west();
+        if passed & (sides | sides.north() | sides.south()) != EMPTY_BB {
+            score += params.passed_connected[rank];
+        }
+
+        let behind = rear_span(square, side) & rook_attacks(square, board.occupancy);
+        if rooks & behind != EMPTY_BB {
+            score += params.passed_rook_behind;
+        }
+        if enemy_rooks & behind != EMPTY_BB {
+            score += params.passed_enemy_rook_behind;
+        }
+
+        score += params.passed_king_own[rank] * king.distance(stop) as i32
+            + params.passed_king_enemy[rank] * enemy_king.distance(stop) as i32;
+    }
+
+    score
+}
+
+/// Pawn structure score for the given side
+pub fn pawn_structure(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let pawns = board.piece_bb[side.pawn() as usize];
+    let enemy_pawns = board.piece_bb[(!side).pawn() as usize];
+    let candidates: Score = candidate_passers(pawns, enemy_pawns, side)
+        .map(|square| params.candidate_passer[square.relative_rank(side)])
+        .sum();
+
+    params.doubled_pawn * doubled_pawns(pawns, side).count_bits() as i32
+        + params.isolated_pawn * isolated_pawns(pawns).count_bits() as i32
+        + params.backward_pawn * backward_pawns(pawns, enemy_pawns, side).count_bits() as i32
+        + candidates
+}
+
+/// Pawn structure score (from White's point of view) and passed pawns of both sides
+pub fn pawn_entry_from_scratch(board: &Board, params: &EvalParams) -> PawnEntry {
+    let pawns = |side: Color| board.piece_bb[side.pawn() as usize];
+    let passed =
+        [Color::White, Color::Black].map(|side| passed_pawns(pawns(side), pawns(!side), side));
+    let score =
+        pawn_structure(board, Color::White, params) - pawn_structure(board, Color::Black, params);
+
+    PawnEntry::new(board.pawn_hash, score, passed)
+}
+
+/// Pawn structure score and passed pawns with the default parameters, probing the pawn table
+/// before computing them
+pub fn pawn_entry(board: &Board, pawn_table: &mut PawnTable) -> PawnEntry {
+    if let Some(entry) = pawn_table.probe(board.pawn_hash) {
+        return entry;
+    }
+
+    let entry = pawn_entry_from_scratch(board, &DEFAULT_PARAMS);
+    pawn_table.insert(entry);
+
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::square::*;
+    use crate::chess::tables::Prng;
+    use crate::engine::hce::{evaluate, params::*, trace};
+
+    /// Count doubled, isolated and backward pawns for both sides, checking that the mirrored
+    /// board yields the same counts with colors swapped
+    fn counts(fen: &str) -> [(u32, u32, u32); 2] {
+        let board: Board = fen.parse().unwrap();
+        let count = |board: &Board, side: Color| {
+            let pawns = board.piece_bb[side.pawn() as usize];
+            let enemy_pawns = board.piece_bb[(!side).pawn() as usize];
+
+            (
+                doubled_pawns(pawns, side).count_bits(),
+                isolated_pawns(pawns).count_bits(),
+                backward_pawns(pawns, enemy_pawns, side).count_bits(),
+            )
+        };
+
+        let result = [count(&board, Color::White), count(&board, Color::Black)];
+        let mirrored = board.mirror();
+        assert_eq!(count(&mirrored, Color::Black), result[0]);
+        assert_eq!(count(&mirrored, Color::White), result[1]);
+
+        result
+    }
+
+    fn white_passers(board: &Board) -> Score {
+        passers(
+            board,
+            Color::White,
+            pawn_entry_from_scratch(board, &DEFAULT_PARAMS).passed[0],
+            &DEFAULT_PARAMS,
+        )
+    }
+
+    #[test]
+    fn test_doubled_pawns() {
+        assert_eq!(counts("4k3/8/8/8/8/3PP3/4P3/4K3 w - - 0 1")[0], (1, 0, 0));
+        assert_eq!(counts("4k3/8/8/8/4P3/3PP3/4P3/4K3 w - - 0 1")[0], (2, 0, 0));
+    }
+
+    #[test]
+    fn test_isolated_pawns() {
+        assert_eq!(counts("4k3/8/8/8/8/8/PP2P3/4K3 w - - 0 1")[0], (0, 1, 0));
+        assert_eq!(counts("4k3/8/8/8/8/4P3/PP2P3/4K3 w - - 0 1")[0], (1, 2, 0));
+    }
+
+    #[test]
+    fn test_backward_pawns() {
+        // d3 can not be supported by c4, and d4 is controlled by e5 (which is isolated)
+        assert_eq!(
+            counts("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1"),
+            [(0, 0, 1), (0, 1, 0)]
+        );
+
+        // pawns ahead on the adjacent file can not help
+        assert_eq!(counts("4k3/8/8/8/3P1p2/8/4P3/4K3 w - - 0 1")[0], (0, 0, 1));
+
+        // a pawn level with it already defends the stop square
+        assert_eq!(counts("4k3/8/8/5p2/8/3PP3/8/4K3 w - - 0 1")[0], (0, 0, 0));
+
+        // a pawn behind it can advance to defend the stop square
+        assert_eq!(counts("4k3/8/8/5p2/8/4P3/3P4/4K3 w - - 0 1")[0], (0, 0, 0));
+
+        // the stop square is not controlled by an enemy pawn
+        assert_eq!(counts("4k3/8/8/8/2P5/3P4/8/4K3 w - - 0 1")[0], (0, 0, 0));
+    }
+
+    #[test]
+    fn test_outside_passed_pawn() {
+        let board: Board = "8/5kpp/8/8/P7/8/5KPP/8 w - - 0 1".parse().unwrap();
+        let passed = |side: Color| {
+            passed_pawns(
+                board.piece_bb[side.pawn() as usize],
+                board.piece_bb[(!side).pawn() as usize],
+                side,
+            )
+        };
+
+        assert_eq!(passed(Color::White), Square::A4.to_board());
+        assert_eq!(passed(Color::Black), EMPTY_BB);
+        let mut pawn_table = PawnTable::new(1);
+        assert!(evaluate(&board, &mut pawn_table) > 50);
+        assert!(evaluate(&board.make_null(), &mut pawn_table) < -50);
+    }
+
+    #[test]
+    fn test_blockaded_passer() {
+        let free: Board = "4k3/8/8/8/3P4/8/8/4K2n w - - 0 1".parse().unwrap();
+        let unsafe_stop: Board = "4k3/8/5n2/8/3P4/8/8/4K3 w - - 0 1".parse().unwrap();
+        let blocked: Board = "4k3/8/8/3n4/3P4/8/8/4K3 w - - 0 1".parse().unwrap();
+
+        let free = white_passers(&free);
+        let unsafe_stop = white_passers(&unsafe_stop);
+        let blocked = white_passers(&blocked);
+
+        assert!(free.eg() > unsafe_stop.eg() && unsafe_stop.eg() > blocked.eg());
+        assert!(blocked.eg() > 0);
+    }
+
+    #[test]
+    fn test_supported_passers() {
+        let alone: Board = "4k3/8/8/8/3P4/8/8/4K3 w - - 0 1".parse().unwrap();
+        let connected: Board = "4k3/8/8/8/3PP3/8/8/4K3 w - - 0 1".parse().unwrap();
+        let rook: Board = "4k3/8/8/8/3P4/8/8/3RK3 w - - 0 1".parse().unwrap();
+
+        let alone = white_passers(&alone);
+        assert_eq!(white_passers(&connected), (alone + PASSED_CONNECTED[3]) * 2);
+        assert_eq!(white_passers(&rook), alone + PASSED_ROOK_BEHIND);
+    }
+
+    #[test]
+    fn test_candidate_passers() {
+        let candidates = |board: &Board, side: Color| {
+            candidate_passers(
+                board.piece_bb[side.pawn() as usize],
+                board.piece_bb[(!side).pawn() as usize],
+                side,
+            )
+        };
+
+        // Queenside majority: a2 is supported by b2 against b7
+        let majority: Board = "4k3/1p3ppp/8/8/8/8/PP3PPP/4K3 w - - 0 1".parse().unwrap();
+        assert_eq!(candidates(&majority, Color::White), Square::A2.to_board());
+        assert_eq!(
+            candidates(&majority.mirror(), Color::Black),
+            Square::A7.to_board()
+        );
+        assert_eq!(candidates(&majority, Color::Black), EMPTY_BB);
+
+        let trace = trace(&majority, &mut PawnTable::new(1));
+        assert_eq!(trace.pawns[0], CANDIDATE_PASSER[1]);
+
+        // Two sentries against a single helper
+        let outnumbered: Board = "4k3/p1p5/8/8/8/8/1P6/4K3 w - - 0 1".parse().unwrap();
+        assert_eq!(candidates(&outnumbered, Color::White), EMPTY_BB);
+
+        // Helpers level with the pawn count, helpers ahead of it do not
+        let level: Board = "4k3/2p5/8/8/8/1PP5/8/4K3 w - - 0 1".parse().unwrap();
+        let ahead: Board = "4k3/2p5/8/8/2P5/1P6/8/4K3 w - - 0 1".parse().unwrap();
+        assert_eq!(candidates(&level, Color::White), Square::B3.to_board());
+        assert_eq!(candidates(&ahead, Color::White), EMPTY_BB);
+    }
+
+    #[test]
+    fn test_connected_passers() {
+        let single: Board = "kn6/8/5P2/8/8/8/8/KN6 w - - 0 1".parse().unwrap();
+        let connected: Board = "kn6/8/5PP1/8/8/8/8/KN6 w - - 0 1".parse().unwrap();
+
+        let single = trace(&single, &mut PawnTable::new(1)).passers[0];
+        let connected = trace(&connected, &mut PawnTable::new(1)).passers[0];
+
+        // Each passer is worth much more with the other one beside it. The g-pawn stop square is
+        // also one step further from the black king.
+        assert_eq!(
+            connected,
+            (single + PASSED_CONNECTED[5]) * 2 + PASSED_KING_ENEMY[5]
+        );
+        assert!(connected.eg() * 2 > single.eg() * 5);
+    }
+
+    #[test]
+    fn test_tarrasch_rule() {
+        let white_passers = |fen: &str| {
+            let board: Board = fen.parse().unwrap();
+            trace(&board, &mut PawnTable::new(1)).passers[0]
+        };
+
+        // White rook behind the d-pawn rather than beside it
+        let own_behind = white_passers("6k1/r7/8/3P4/8/8/8/3R2K1 w - - 0 1");
+        let own_aside = white_passers("6k1/r7/8/3P4/8/8/8/R5K1 w - - 0 1");
+        assert_eq!(own_behind, own_aside + PASSED_ROOK_BEHIND);
+
+        // Black rook behind the d-pawn, restraining it from a safe distance
+        let enemy_behind = white_passers("6k1/8/8/3P4/8/8/7K/R2r4 w - - 0 1");
+        let enemy_aside = white_passers("6k1/8/8/3P4/8/8/7K/Rr6 w - - 0 1");
+        assert_eq!(enemy_behind, enemy_aside + PASSED_ENEMY_ROOK_BEHIND);
+        assert!(enemy_behind.eg() < enemy_aside.eg());
+    }
+
+    #[test]
+    fn test_passer_king_proximity() {
+        let white_passers = |fen: &str| {
+            let board: Board = fen.parse().unwrap();
+            trace(&board, &mut PawnTable::new(1)).passers[0]
+        };
+
+        // Escorting king against a distant one
+        let escorted = white_passers("7k/r7/4K3/4P3/8/8/8/R7 w - - 0 1");
+        let distant = white_passers("7k/r7/8/4P3/8/8/8/R5K1 w - - 0 1");
+        assert!(escorted.eg() > distant.eg());
+        assert_eq!(escorted.mg(), distant.mg());
+
+        // The defending king in front of the pawn holds it back
+        let blockaded = white_passers("8/r3k3/8/4P3/8/8/8/R5K1 w - - 0 1");
+        assert!(blockaded.eg() < distant.eg());
+
+        // The closer the pawn to promotion, the more the kings matter
+        let far = white_passers("7k/r7/8/8/8/4P3/8/R5K1 w - - 0 1");
+        let far_escorted = white_passers("7k/r7/8/8/4K3/4P3/8/R7 w - - 0 1");
+        assert!(escorted.eg() - distant.eg() > far_escorted.eg() - far.eg());
+    }
+
+    #[test]
+    fn test_pawn_table() {
+        let mut pawn_table = PawnTable::new(1);
+        let mut rng = Prng::new(0x9A3B);
+
+        for _ in 0..100 {
+            let mut board = Board::default();
+
+            for _ in 0..100 {
+                let cached = pawn_entry(&board, &mut pawn_table);
+                assert_eq!(cached, pawn_entry_from_scratch(&board, &DEFAULT_PARAMS));
+
+                let move_list = board.gen_moves::<QUIETS>();
+                if move_list.is_empty() {
+                    break;
+                }
+                board = board.make_move(move_list.moves[rng.next_u64() as usize % move_list.len()]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pawn_table_shared_entry() {
+        let mut pawn_table = PawnTable::new(1);
+        let board: Board = "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 0 1"
+            .parse()
+            .unwrap();
+        let other: Board = "4k3/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/3QK3 b - - 0 1"
+            .parse()
+            .unwrap();
+
+        assert_ne!(board.hash, other.hash);
+        assert_eq!(board.pawn_hash, other.pawn_hash);
+
+        let entry = pawn_entry(&board, &mut pawn_table);
+        assert_eq!(pawn_table.hit_rate(), 0.0);
+        assert_eq!(pawn_entry(&other, &mut pawn_table), entry);
+        assert_eq!(pawn_table.hit_rate(), 0.5);
+
+        pawn_table.clear();
+        assert_eq!(pawn_table.hit_rate(), 0.0);
+    }
+}
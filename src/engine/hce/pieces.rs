@@ -0,0 +1,200 @@
+/// Piece specific terms.
+use crate::chess::{bitboard::*, board::*, piece::*, square::*, tables::*};
+use crate::engine::hce::{eval_params::*, score::*};
+
+/// Squares in enemy territory (relative ranks 4 to 6) defended by an own pawn, which no enemy
+/// pawn can ever attack
+pub fn outposts(board: &Board, side: Color) -> BitBoard {
+    let pawns = board.piece_bb[side.pawn() as usize];
+    let enemy_pawns = board.piece_bb[(!side).pawn() as usize];
+    let territory = match side {
+        Color::White => rank_bb(Rank::Fourth) | rank_bb(Rank::Fifth) | rank_bb(Rank::Sixth),
+        Color::Black => rank_bb(Rank::Fifth) | rank_bb(Rank::Fourth) | rank_bb(Rank::Third),
+    };
+
+    let mut outposts = EMPTY_BB;
+    for square in territory & pawns.pawn_attacks(side) {
+        if outpost_mask(square, side) & enemy_pawns == EMPTY_BB {
+            outposts = outposts.set_bit(square);
+        }
+    }
+
+    outposts
+}
+
+/// Knights on outposts and on the rim
+pub fn knights(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let knights = board.piece_bb[side.knight() as usize];
+    let rim = FILE_A_BB | FILE_H_BB;
+
+    params.knight_outpost * (knights & outposts(board, side)).count_bits() as i32
+        + params.knight_on_rim * (knights & rim).count_bits() as i32
+}
+
+/// Bishop pair bonus, and penalty for bishops hemmed in by own pawns on their square color
+pub fn bishops(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let bishops = board.piece_bb[side.bishop() as usize];
+    let pawns = board.piece_bb[side.pawn() as usize];
+    let mut score = Score::ZERO;
+
+    if bishops & LIGHT_SQUARES_BB != EMPTY_BB && bishops & DARK_SQUARES_BB != EMPTY_BB {
+        score += params.bishop_pair;
+    }
+
+    score += params.bishop_outpost * (bishops & outposts(board, side)).count_bits() as i32;
+
+    for square in bishops {
+        let blockers = pawns & SQUARE_COLOR_BB[square.color() as usize];
+        score += params.bad_bishop_pawn * blockers.count_bits() as i32;
+    }
+
+    score
+}
+
+/// Rooks on (semi-)open files, on the seventh rank and doubled on a file
+pub fn rooks(board: &Board, side: Color, params: &EvalParams) -> Score {
+    let rooks = board.piece_bb[side.rook() as usize];
+    let pawns = board.piece_bb[side.pawn() as usize];
+    let enemy_pawns = board.piece_bb[(!side).pawn() as usize];
+    let enemy_king = board.piece_bb[(!side).king() as usize].lsb();
+    let mut score = Score::ZERO;
+
+    for square in rooks {
+        let file = file_bb(square.file());
+
+        if file & pawns == EMPTY_BB {
+            score += match file & enemy_pawns {
+                EMPTY_BB => params.rook_open_file,
+                _ => params.rook_semi_open_file,
+            };
+        }
+
+        if square.relative_rank(side) == 6
+            && (enemy_king.relative_rank(side) == 7
+                || rank_bb(square.rank()) & enemy_pawns != EMPTY_BB)
+        {
+            score += params.rook_seventh_rank;
+        }
+
+        let ahead = front_span(square, side);
+        if rook_attacks(square, board.occupancy) & ahead & rooks != EMPTY_BB {
+            score += params.rook_connected;
+        }
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::hce::params::*;
+
+    #[test]
+    fn test_bishop_pair() {
+        let board: Board = "r1bqk2r/pppp1ppp/5n2/4p3/4P3/5N2/PPPP1PPP/R1BQKB1R w KQkq - 0 1"
+            .parse()
+            .unwrap();
+
+        let white = bishops(&board, Color::White, &DEFAULT_PARAMS) - BAD_BISHOP_PAWN * 8;
+        let black = bishops(&board, Color::Black, &DEFAULT_PARAMS) - BAD_BISHOP_PAWN * 4;
+
+        assert_eq!(white, BISHOP_PAIR);
+        assert_eq!(black, Score::ZERO);
+    }
+
+    #[test]
+    fn test_bad_bishop() {
+        let bad: Board = "4k3/8/8/3p1p2/2pPpPp1/2P1P1P1/3B4/4K3 w - - 0 1"
+            .parse()
+            .unwrap();
+        let good: Board = "4k3/8/8/3p1p2/2pPpPp1/2P1P1P1/4B3/4K3 w - - 0 1"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            bishops(&bad, Color::White, &DEFAULT_PARAMS),
+            BAD_BISHOP_PAWN * 5
+        );
+        assert_eq!(bishops(&good, Color::White, &DEFAULT_PARAMS), Score::ZERO);
+    }
+
+    #[test]
+    fn test_rook_files() {
+        let closed: Board = "4k3/3p4/8/8/8/8/3P4/3RK3 w - - 0 1".parse().unwrap();
+        let semi_open: Board = "4k3/3p4/8/8/8/8/4P3/3RK3 w - - 0 1".parse().unwrap();
+        let open: Board = "4k3/4p3/8/8/8/8/4P3/3RK3 w - - 0 1".parse().unwrap();
+
+        assert_eq!(rooks(&closed, Color::White, &DEFAULT_PARAMS), Score::ZERO);
+        assert_eq!(
+            rooks(&semi_open, Color::White, &DEFAULT_PARAMS),
+            ROOK_SEMI_OPEN_FILE
+        );
+        assert_eq!(rooks(&open, Color::White, &DEFAULT_PARAMS), ROOK_OPEN_FILE);
+    }
+
+    #[test]
+    fn test_rook_seventh_rank() {
+        let king: Board = "6k1/2R5/8/8/8/8/2P5/4K3 w - - 0 1".parse().unwrap();
+        let pawns: Board = "8/2R2pk1/8/8/8/8/2P5/4K3 w - - 0 1".parse().unwrap();
+        let neither: Board = "8/2R5/6k1/8/8/8/2P5/4K3 w - - 0 1".parse().unwrap();
+
+        assert_eq!(
+            rooks(&king, Color::White, &DEFAULT_PARAMS),
+            ROOK_SEVENTH_RANK
+        );
+        assert_eq!(
+            rooks(&pawns, Color::White, &DEFAULT_PARAMS),
+            ROOK_SEVENTH_RANK
+        );
+        assert_eq!(rooks(&neither, Color::White, &DEFAULT_PARAMS), Score::ZERO);
+    }
+
+    #[test]
+    fn test_connected_rooks() {
+        let doubled: Board = "4k3/2p5/8/8/8/2R5/8/2R1K3 w - - 0 1".parse().unwrap();
+        let blocked: Board = "4k3/2p5/8/8/2R5/2P5/8/2R1K3 w - - 0 1".parse().unwrap();
+        let apart: Board = "4k3/2p5/8/8/8/8/2P5/R2RK3 w - - 0 1".parse().unwrap();
+
+        assert_eq!(
+            rooks(&doubled, Color::White, &DEFAULT_PARAMS),
+            ROOK_SEMI_OPEN_FILE * 2 + ROOK_CONNECTED
+        );
+        assert_eq!(rooks(&blocked, Color::White, &DEFAULT_PARAMS), Score::ZERO);
+        assert_eq!(
+            rooks(&apart, Color::White, &DEFAULT_PARAMS),
+            ROOK_OPEN_FILE * 2
+        );
+    }
+
+    #[test]
+    fn test_knight_outpost() {
+        let board: Board = "r2q1rk1/pp2bppp/2np1n2/3Np3/4P3/5N2/PPP2PPP/R2QKB1R w KQ - 0 1"
+            .parse()
+            .unwrap();
+
+        assert!(outposts(&board, Color::White).get_bit(Square::D5));
+        assert_eq!(
+            knights(&board, Color::White, &DEFAULT_PARAMS),
+            KNIGHT_OUTPOST
+        );
+        assert_eq!(knights(&board, Color::Black, &DEFAULT_PARAMS), Score::ZERO);
+
+        // a pawn on c7 can chase the knight away
+        let chased: Board = "r2q1rk1/ppp1bppp/3p1n2/3Np3/4P3/5N2/PPP2PPP/R2QKB1R w KQ - 0 1"
+            .parse()
+            .unwrap();
+        assert!(!outposts(&chased, Color::White).get_bit(Square::D5));
+        assert_eq!(knights(&chased, Color::White, &DEFAULT_PARAMS), Score::ZERO);
+    }
+
+    #[test]
+    fn test_knight_on_rim() {
+        let board: Board = "4k3/8/8/8/7N/8/8/N3K3 w - - 0 1".parse().unwrap();
+
+        assert_eq!(
+            knights(&board, Color::White, &DEFAULT_PARAMS),
+            KNIGHT_ON_RIM * 2
+        );
+    }
+}
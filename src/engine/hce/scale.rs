@@ -0,0 +1,160 @@
+/// Endgame scale factors.
+///
+/// Some material configurations are far more drawish than the raw material suggests. The scale
+/// factor applies to the endgame component of the score only, out of SCALE_NORMAL.
+use crate::chess::{bitboard::*, board::*, piece::*, square::*};
+use crate::engine::hce::params::*;
+
+/// Non-pawn material of a side, using the endgame material values
+fn non_pawn_material(board: &Board, side: Color) -> i32 {
+    (1..5)
+        .map(|piece_type| {
+            let count = board.piece_bb[PIECES[side as usize][piece_type] as usize].count_bits();
+            count as i32 * PIECE_VALUES[piece_type].eg()
+        })
+        .sum()
+}
+
+/// Both sides have a single bishop on opposite colors, and no other pieces
+fn is_ocb_ending(board: &Board) -> bool {
+    let bishops = board.bishops();
+    let others = board.knights() | board.rooks() | board.queens();
+
+    others == EMPTY_BB
+        && board.piece_bb[Piece::WB as usize].count_bits() == 1
+        && board.piece_bb[Piece::BB as usize].count_bits() == 1
+        && bishops & LIGHT_SQUARES_BB != EMPTY_BB
+        && bishops & DARK_SQUARES_BB != EMPTY_BB
+}
+
+/// The strong side only has bishops and pawns on a single rook file, the bishops do not control
+/// the promotion square and the weak king stands next to it
+fn is_wrong_bishop(board: &Board, strong_side: Color) -> bool {
+    let pawns = board.piece_bb[strong_side.pawn() as usize];
+    let bishops = board.piece_bb[strong_side.bishop() as usize];
+    let pieces = board.side_occupancy[strong_side as usize] & !pawns & !bishops;
+    let weak_king = board.piece_bb[(!strong_side).king() as usize].lsb();
+
+    if pawns == EMPTY_BB || bishops == EMPTY_BB || pieces.count_bits() != 1 {
+        return false;
+    }
+
+    [File::A, File::H].into_iter().any(|file| {
+        let promotion = Square::from_coords(
+            file,
+            match strong_side {
+                Color::White => Rank::Eight,
+                Color::Black => Rank::First,
+            },
+        );
+
+        pawns & !file_bb(file) == EMPTY_BB
+            && bishops & SQUARE_COLOR_BB[promotion.color() as usize] == EMPTY_BB
+            && weak_king.distance(promotion) <= 1
+    })
+}
+
+/// Scale factor for the endgame score of the given stronger side
+pub fn scale_factor(board: &Board, strong_side: Color) -> u8 {
+    let strong_pawns = board.piece_bb[strong_side.pawn() as usize].count_bits() as usize;
+    let strong_material = non_pawn_material(board, strong_side);
+    let weak_material = non_pawn_material(board, !strong_side);
+
+    if is_wrong_bishop(board, strong_side) {
+        return SCALE_WRONG_BISHOP;
+    }
+
+    if strong_pawns == 0 && strong_material - weak_material <= PIECE_VALUES[2].eg() {
+        return SCALE_MINOR_UP;
+    }
+
+    if is_ocb_ending(board) {
+        return SCALE_OCB;
+    }
+
+    match SCALE_FEW_PAWNS.get(strong_pawns) {
+        Some(&scale) if strong_material == weak_material => scale,
+        _ => SCALE_NORMAL,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::hce::{evaluate, PawnTable};
+
+    fn eval(fen: &str) -> i32 {
+        evaluate(&fen.parse().unwrap(), &mut PawnTable::new(1))
+    }
+
+    fn scale(fen: &str, strong_side: Color) -> u8 {
+        scale_factor(&fen.parse().unwrap(), strong_side)
+    }
+
+    #[test]
+    fn test_ocb_ending() {
+        let ocb = "8/4k3/2b5/3p1p2/3P1P2/4B1P1/4K3/8 w - - 0 1";
+        let rooks = "7r/4k3/2b5/3p1p2/3P1P2/4B1P1/4K3/R7 w - - 0 1";
+
+        assert_eq!(scale(ocb, Color::White), SCALE_OCB);
+        assert_eq!(scale(rooks, Color::White), SCALE_NORMAL);
+        assert!(eval(ocb) < 80);
+        assert!(eval(rooks) > eval(ocb));
+    }
+
+    #[test]
+    fn test_wrong_bishop() {
+        let wrong = "7k/8/6K1/7P/8/8/4B3/8 w - - 0 1";
+        let right = "7k/8/6K1/7P/8/8/3B4/8 w - - 0 1";
+        let far = "8/8/6K1/7P/8/2k5/4B3/8 w - - 0 1";
+
+        assert_eq!(scale(wrong, Color::White), SCALE_WRONG_BISHOP);
+        assert_eq!(scale(right, Color::White), SCALE_NORMAL);
+        assert_eq!(scale(far, Color::White), SCALE_NORMAL);
+        assert!(eval(wrong) < eval(right) / 4);
+    }
+
+    #[test]
+    fn test_no_pawns() {
+        assert_eq!(
+            scale("8/8/4k3/8/8/2B5/4K3/8 w - - 0 1", Color::White),
+            SCALE_MINOR_UP
+        );
+        assert_eq!(
+            scale("8/8/4k3/4b3/8/8/4K3/R7 w - - 0 1", Color::White),
+            SCALE_MINOR_UP
+        );
+        assert_eq!(
+            scale("8/8/4k3/4r3/8/8/4K3/R3N3 w - - 0 1", Color::White),
+            SCALE_MINOR_UP
+        );
+        assert_eq!(
+            scale("8/8/4k3/8/8/8/4K3/R7 w - - 0 1", Color::White),
+            SCALE_NORMAL
+        );
+        assert_eq!(
+            scale("8/8/4k3/4n3/8/8/4K3/Q7 w - - 0 1", Color::White),
+            SCALE_NORMAL
+        );
+    }
+
+    #[test]
+    fn test_few_pawns() {
+        assert_eq!(
+            scale("8/8/4k3/4p3/8/8/4K3/8 b - - 0 1", Color::Black),
+            SCALE_FEW_PAWNS[1]
+        );
+        assert_eq!(
+            scale("8/8/4k3/4p3/8/8/4K3/3B4 b - - 0 1", Color::Black),
+            SCALE_NORMAL
+        );
+        assert_eq!(
+            scale("8/5p2/4k3/4p3/8/8/4K3/8 b - - 0 1", Color::Black),
+            SCALE_FEW_PAWNS[2]
+        );
+        assert_eq!(
+            scale("8/5p2/4k3/2p1p3/8/8/4K3/8 b - - 0 1", Color::Black),
+            SCALE_NORMAL
+        );
+    }
+}
@@ -0,0 +1,126 @@
+/// Tapered scores, holding a middlegame and an endgame value packed in a single i32.
+///
+/// The endgame value sits in the upper 16 bits and the middlegame value in the lower 16, so that
+/// adding, subtracting and multiplying scores by integers works on both halves at once.
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use crate::engine::search_params::*;
+
+/// Maximum game phase, reached with all minor and major pieces on the board
+pub const MAX_PHASE: i32 = 24;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Score(i32);
+
+/// Build a Score from its middlegame and endgame values
+#[macro_export]
+macro_rules! S {
+    ($mg:expr, $eg:expr) => {
+        $crate::engine::hce::Score::new($mg, $eg)
+    };
+}
+
+impl Score {
+    pub const ZERO: Score = Score(0);
+
+    pub const fn new(mg: Eval, eg: Eval) -> Self {
+        Self((eg << 16).wrapping_add(mg))
+    }
+
+    /// Middlegame value
+    pub const fn mg(self) -> Eval {
+        self.0 as i16 as Eval
+    }
+
+    /// Endgame value (rounded to undo the borrow from a negative middlegame value)
+    pub const fn eg(self) -> Eval {
+        (self.0.wrapping_add(0x8000) >> 16) as i16 as Eval
+    }
+
+    /// Scale the endgame value by num / den
+    pub const fn scale_eg(self, num: Eval, den: Eval) -> Self {
+        Self::new(self.mg(), self.eg() * num / den)
+    }
+
+    /// Interpolate between middlegame and endgame values given the game phase
+    pub const fn taper(self, phase: i32) -> Eval {
+        (self.mg() * phase + self.eg() * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Score {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0.wrapping_add(other.0))
+    }
+}
+
+impl Sub for Score {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl Neg for Score {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(self.0.wrapping_neg())
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Self;
+
+    fn mul(self, other: i32) -> Self {
+        Self(self.0.wrapping_mul(other))
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl std::iter::Sum for Score {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Score::ZERO, |acc, s| acc + s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_packing() {
+        let pairs = [(0, 0), (12, -7), (-300, 450), (-1, -1), (2000, -2000)];
+
+        for (mg1, eg1) in pairs {
+            for (mg2, eg2) in pairs {
+                let (s1, s2) = (S!(mg1, eg1), S!(mg2, eg2));
+
+                assert_eq!((s1.mg(), s1.eg()), (mg1, eg1));
+                assert_eq!(((s1 + s2).mg(), (s1 + s2).eg()), (mg1 + mg2, eg1 + eg2));
+                assert_eq!(((s1 - s2).mg(), (s1 - s2).eg()), (mg1 - mg2, eg1 - eg2));
+                assert_eq!(((-s1).mg(), (-s1).eg()), (-mg1, -eg1));
+                assert_eq!(((s1 * 3).mg(), (s1 * 3).eg()), (3 * mg1, 3 * eg1));
+            }
+        }
+
+        assert_eq!(S!(100, 20).taper(MAX_PHASE), 100);
+        assert_eq!(S!(100, 20).taper(0), 20);
+        assert_eq!(S!(100, 20).taper(MAX_PHASE / 2), 60);
+        assert_eq!(S!(-100, -20).scale_eg(1, 2), S!(-100, -10));
+    }
+}
@@ -0,0 +1,70 @@
+/// Space term for the middlegame.
+///
+/// Counts the central squares on files C to F and on the own second to fourth ranks which are not
+/// attacked by enemy pawns nor occupied by own pawns, counting squares right behind own pawns
+/// twice. The count is weighted by the number of own pieces which could make use of it.
+use crate::chess::{bitboard::*, board::*, piece::*, square::*};
+use crate::engine::hce::{eval_params::*, params::*, score::*, threats::*};
+
+/// Non-pawn material of a side, counted in phase units
+fn material(board: &Board, side: Color) -> i32 {
+    let count = |piece: Piece| board.piece_bb[piece as usize].count_bits() as i32;
+
+    count(side.knight()) + count(side.bishop()) + 2 * count(side.rook()) + 4 * count(side.queen())
+}
+
+/// Space score for the given side, zero unless both sides have enough material left
+pub fn space(board: &Board, side: Color, attacks: &Attacks, params: &EvalParams) -> Score {
+    if material(board, side) < SPACE_MIN_MATERIAL || material(board, !side) < SPACE_MIN_MATERIAL {
+        return Score::ZERO;
+    }
+
+    let pawns = board.piece_bb[side.pawn() as usize];
+    let center = file_bb(File::C) | file_bb(File::D) | file_bb(File::E) | file_bb(File::F);
+    let ranks = match side {
+        Color::White => rank_bb(Rank::Second) | rank_bb(Rank::Third) | rank_bb(Rank::Fourth),
+        Color::Black => rank_bb(Rank::Seventh) | rank_bb(Rank::Sixth) | rank_bb(Rank::Fifth),
+    };
+
+    let safe = center & ranks & !pawns & !attacks.by_piece[!side as usize][0];
+    let behind = pawns.rear_span(side) & safe;
+    let king = board.piece_bb[side.king() as usize];
+    let pieces = (board.side_occupancy[side as usize] & !pawns & !king).count_bits() as i32;
+
+    params.space * ((safe.count_bits() + behind.count_bits()) as i32 * pieces / 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_space() {
+        let board: Board = "r1bqk2r/pp1nbppp/2pp1n2/4p3/2PPPP2/2N2N2/PP4PP/R1BQKB1R w KQkq - 0 1"
+            .parse()
+            .unwrap();
+        let attacks = Attacks::new(&board);
+
+        let white = space(&board, Color::White, &attacks, &DEFAULT_PARAMS);
+        let black = space(&board, Color::Black, &attacks, &DEFAULT_PARAMS);
+        assert!(white.mg() > black.mg());
+        assert_eq!((white.eg(), black.eg()), (0, 0));
+    }
+
+    #[test]
+    fn test_no_space_in_endgame() {
+        let board: Board = "4k3/pp1n1ppp/2pp4/4p3/2PPPP2/2N5/PP4PP/4K3 w - - 0 1"
+            .parse()
+            .unwrap();
+        let attacks = Attacks::new(&board);
+
+        assert_eq!(
+            space(&board, Color::White, &attacks, &DEFAULT_PARAMS),
+            Score::ZERO
+        );
+        assert_eq!(
+            space(&board, Color::Black, &attacks, &DEFAULT_PARAMS),
+            Score::ZERO
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::chess::tables::Prng;
+/// Symmetry checks for the classical evaluation.
+///
+/// Mirroring a position, piece colors and side to move included, must give the opposite evaluation
+/// from White's point of view, and flipping it across the middle files must leave it unchanged
+/// when no side can castle. Most sign errors in a new term break one of these. The tempo bonus of
+/// the side to move is the only intentional asymmetry, and is taken out before comparing.
+use crate::chess::{board::*, moves::*, piece::*};
+use crate::engine::hce::{evaluate, trace, PawnTable};
+use crate::engine::search_params::Eval;
+
+/// Evaluation from White's point of view, without the tempo bonus of the side to move
+fn white_eval(board: &Board, pawn_table: &mut PawnTable) -> Eval {
+    let trace = trace(board, pawn_table);
+    let eval = trace.eval - trace.tempo;
+
+    match board.side {
+        Color::White => eval,
+        Color::Black => -eval,
+    }
+}
+
+/// Check the mirror and file flip symmetries of the given board
+pub fn assert_board_symmetry(board: &Board, pawn_table: &mut PawnTable) {
+    let fen = board.to_fen();
+    let eval = white_eval(board, pawn_table);
+    let mirrored = board.mirror();
+
+    assert_eq!(eval, -white_eval(&mirrored, pawn_table), "mirror: {fen}");
+
+    // The side to move gets the tempo in both positions
+    assert_eq!(
+        evaluate(board, pawn_table),
+        evaluate(&mirrored, pawn_table),
+        "mirror: {fen}"
+    );
+
+    if board.castling_rights.is_empty() {
+        let flipped = board.flip_files();
+        assert_eq!(eval, white_eval(&flipped, pawn_table), "file flip: {fen}");
+    }
+}
+
+/// Check the mirror and file flip symmetries of the given position
+pub fn assert_eval_symmetry(fen: &str) {
+    let board: Board = fen.parse().unwrap();
+    assert_board_symmetry(&board, &mut PawnTable::new(1));
+}
+
+/// Check the symmetries along random games from the given position, returning the number of
+/// positions checked
+pub fn assert_playout_symmetry(fen: &str, games: usize, plies: usize, seed: u64) -> usize {
+    let mut rng = Prng::new(seed);
+    let mut pawn_table = PawnTable::new(1);
+    let mut checked = 0;
+
+    for _ in 0..games {
+        let mut board: Board = fen.parse().unwrap();
+
+        for _ in 0..plies {
+            assert_board_symmetry(&board, &mut pawn_table);
+            checked += 1;
+
+            let move_list = board.gen_moves::<QUIETS>();
+            if move_list.is_empty() || board.halfmoves >= 100 {
+                break;
+            }
+
+            let m: Move = move_list.moves[rng.next_u64() as usize % move_list.len()];
+            board = board.make_move(m);
+        }
+    }
+
+    checked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flips() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1";
+        let board: Board = fen.parse().unwrap();
+
+        assert_eq!(board.flip_files().flip_files().to_fen(), fen);
+        assert_eq!(
+            board.flip_files().to_fen(),
+            "r2k3r/1bpqpp1p/1pnp2nb/3NP3/3P2p1/p1Q2N2/PPPBBPPP/R2K3R w - - 0 1"
+        );
+        assert_eq!(
+            "8/8/8/8/3pP3/8/8/K6k b - e3 0 1"
+                .parse::<Board>()
+                .unwrap()
+                .flip_files()
+                .to_fen(),
+            "8/8/8/8/3Pp3/8/8/k6K b - d3 0 1"
+        );
+    }
+
+    #[test]
+    fn test_eval_symmetry() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "2r3k1/1R3ppp/8/3p4/8/4R3/5PPP/6K1 w - - 0 1",
+            "8/8/8/4k3/8/8/3PK3/8 w - - 0 1",
+            "7k/8/8/8/8/8/8/4KBN1 w - - 0 1",
+            "8/5k2/8/8/2B5/8/1P6/1K6 b - - 0 1",
+        ];
+
+        for fen in fens {
+            assert_eval_symmetry(fen);
+        }
+    }
+
+    #[test]
+    fn test_random_playouts() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 1",
+            "4k3/pp3ppp/2n5/8/8/5N2/PPP2PPP/4K3 w - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "8/8/4k3/8/8/3PK3/8/8 w - - 0 1",
+            "8/8/8/3k4/8/8/8/R3KBN1 w - - 0 1",
+        ];
+
+        let checked: usize = fens
+            .iter()
+            .zip(1..)
+            .map(|(fen, seed)| assert_playout_symmetry(fen, 8, 60, seed))
+            .sum();
+        assert!(checked >= 2000);
+    }
+}
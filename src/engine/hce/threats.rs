@@ -0,0 +1,98 @@
+/// Threat terms, working on the full attack maps of both sides.
+use crate::chess::{bitboard::*, board::*, piece::*, tables::*};
+use crate::engine::hce::{eval_params::*, score::*};
+
+/// Squares attacked by each side, by piece type and in total.
+/// Computed once per evaluation and shared by all terms which need it.
+pub struct Attacks {
+    pub by_piece: [[BitBoard; 6]; 2],
+    pub all: [BitBoard; 2],
+}
+
+impl Attacks {
+    pub fn new(board: &Board) -> Self {
+        let mut by_piece = [[EMPTY_BB; 6]; 2];
+        let mut all = [EMPTY_BB; 2];
+
+        for side in [Color::White, Color::Black] {
+            let s = side as usize;
+            let pieces = |piece_type: usize| board.piece_bb[PIECES[s][piece_type] as usize];
+
+            by_piece[s][0] = pieces(0).pawn_attacks(side);
+            for square in pieces(1) {
+                by_piece[s][1] |= knight_attacks(square);
+            }
+            for square in pieces(2) {
+                by_piece[s][2] |= bishop_attacks(square, board.occupancy);
+            }
+            for square in pieces(3) {
+                by_piece[s][3] |= rook_attacks(square, board.occupancy);
+            }
+            for square in pieces(4) {
+                by_piece[s][4] |= queen_attacks(square, board.occupancy);
+            }
+            by_piece[s][5] = king_attacks(pieces(5).lsb());
+
+            all[s] = by_piece[s].iter().fold(EMPTY_BB, |acc, &bb| acc | bb);
+        }
+
+        Self { by_piece, all }
+    }
+}
+
+/// Enemy pieces attacked by own pawns, enemy majors attacked by lesser pieces and own pieces
+/// left hanging
+pub fn threats(board: &Board, side: Color, attacks: &Attacks, params: &EvalParams) -> Score {
+    let (us, them) = (side as usize, !side as usize);
+    let pieces = |side: usize, piece_type: usize| board.piece_bb[PIECES[side][piece_type] as usize];
+
+    let enemy_pieces = board.side_occupancy[them] & !pieces(them, 0) & !pieces(them, 5);
+    let by_pawns = attacks.by_piece[us][0] & enemy_pieces;
+
+    let minors = attacks.by_piece[us][1] | attacks.by_piece[us][2];
+    let by_lesser = minors & pieces(them, 3) | (minors | attacks.by_piece[us][3]) & pieces(them, 4);
+
+    let own_pieces = board.side_occupancy[us] & !pieces(us, 0) & !pieces(us, 5);
+    let hanging = own_pieces & attacks.all[them] & !attacks.all[us];
+
+    params.threat_by_pawn * by_pawns.count_bits() as i32
+        + params.threat_by_lesser * (by_lesser & !by_pawns).count_bits() as i32
+        + params.hanging_piece * hanging.count_bits() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::hce::params::*;
+
+    fn threats_of(fen: &str, side: Color) -> Score {
+        let board: Board = fen.parse().unwrap();
+        threats(&board, side, &Attacks::new(&board), &DEFAULT_PARAMS)
+    }
+
+    #[test]
+    fn test_pawn_threat() {
+        let fen = "4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1";
+
+        assert_eq!(threats_of(fen, Color::White), THREAT_BY_PAWN);
+        assert_eq!(threats_of(fen, Color::Black), HANGING_PIECE);
+    }
+
+    #[test]
+    fn test_hanging_piece() {
+        let loose = "4k3/8/8/8/8/R1b5/8/7K w - - 0 1";
+        let defended = "4k3/8/8/8/3p4/R1b5/8/7K w - - 0 1";
+
+        assert_eq!(threats_of(loose, Color::Black), HANGING_PIECE);
+        assert_eq!(threats_of(defended, Color::Black), Score::ZERO);
+        assert_eq!(threats_of(loose, Color::White), Score::ZERO);
+    }
+
+    #[test]
+    fn test_lesser_threat() {
+        let fen = "4k3/8/8/8/1r6/8/3B4/4K3 w - - 0 1";
+
+        assert_eq!(threats_of(fen, Color::White), THREAT_BY_LESSER);
+        assert_eq!(threats_of(fen, Color::Black), HANGING_PIECE);
+    }
+}
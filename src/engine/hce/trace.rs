@@ -0,0 +1,118 @@
+/// Evaluation trace.
+///
+/// Records the contribution of every evaluation term for both colors, along with the values used
+/// to turn their sum into the final evaluation. It backs the `eval` UCI command and lets the
+/// tuner check that the terms add up to the returned score.
+use std::fmt;
+
+use crate::engine::hce::{params::*, score::*};
+use crate::engine::search_params::Eval;
+
+#[derive(Clone, Debug, Default)]
+pub struct EvalTrace {
+    pub psqt: [Score; 2],
+    pub pawns: [Score; 2],
+    pub passers: [Score; 2],
+    pub knights: [Score; 2],
+    pub bishops: [Score; 2],
+    pub rooks: [Score; 2],
+    pub imbalance: [Score; 2],
+    pub king_safety: [Score; 2],
+    pub king_tropism: [Score; 2],
+    pub mobility: [Score; 2],
+    pub threats: [Score; 2],
+    pub space: [Score; 2],
+    pub phase: i32,
+    pub scale: u8,
+    pub mop_up: Eval,
+    pub tempo: Eval,
+    /// Set when the position is scored without the terms (known draws, bitbases)
+    pub exact: Option<&'static str>,
+    /// Final evaluation, from the point of view of the side to move
+    pub eval: Eval,
+}
+
+impl EvalTrace {
+    /// Named per-color terms, in evaluation order
+    pub fn terms(&self) -> [(&'static str, [Score; 2]); 12] {
+        [
+            ("Material/PSQT", self.psqt),
+            ("Pawns", self.pawns),
+            ("Passed pawns", self.passers),
+            ("Knights", self.knights),
+            ("Bishops", self.bishops),
+            ("Rooks", self.rooks),
+            ("Imbalance", self.imbalance),
+            ("King safety", self.king_safety),
+            ("King tropism", self.king_tropism),
+            ("Mobility", self.mobility),
+            ("Threats", self.threats),
+            ("Space", self.space),
+        ]
+    }
+
+    /// Sum of all the terms, from White's point of view
+    pub fn total(&self) -> Score {
+        self.terms()
+            .iter()
+            .map(|(_, [white, black])| *white - *black)
+            .sum()
+    }
+
+    /// White evaluation rebuilt from the terms, before adding the tempo
+    pub fn white_eval(&self) -> Eval {
+        self.total()
+            .scale_eg(self.scale as Eval, SCALE_NORMAL as Eval)
+            .taper(self.phase)
+            + self.mop_up
+    }
+}
+
+/// Print the terms as an aligned table, followed by the final evaluation
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(reason) = self.exact {
+            return writeln!(f, "Classical evaluation: {} ({reason})", self.eval);
+        }
+
+        let cell = |s: Score| format!("{:>6} {:>6}", s.mg(), s.eg());
+        let line = format!("{:-<16}+{:-<15}+{:-<15}+{:-<15}", "", "", "", "");
+
+        writeln!(
+            f,
+            "{:>15} | {:^13} | {:^13} | {:^13}",
+            "Term", "White", "Black", "Total"
+        )?;
+        writeln!(
+            f,
+            "{:>15} | {:>6} {:>6} | {:>6} {:>6} | {:>6} {:>6}",
+            "", "MG", "EG", "MG", "EG", "MG", "EG"
+        )?;
+        writeln!(f, "{line}")?;
+        for (name, [white, black]) in self.terms() {
+            writeln!(
+                f,
+                "{name:>15} | {} | {} | {}",
+                cell(white),
+                cell(black),
+                cell(white - black)
+            )?;
+        }
+        writeln!(f, "{line}")?;
+        writeln!(
+            f,
+            "{:>15} | {:>13} | {:>13} | {}",
+            "Total",
+            "",
+            "",
+            cell(self.total())
+        )?;
+        writeln!(f)?;
+
+        writeln!(f, "Phase: {} / {MAX_PHASE}", self.phase)?;
+        writeln!(f, "Endgame scale: {} / {SCALE_NORMAL}", self.scale)?;
+        writeln!(f, "Mop-up: {}", self.mop_up)?;
+        writeln!(f, "Tempo: {}", self.tempo)?;
+        writeln!(f, "Classical evaluation: {} (side to move)", self.eval)
+    }
+}
@@ -0,0 +1,196 @@
+/// Texel tuning of the evaluation parameters.
+///
+/// Positions labelled with the game result are evaluated with the parameters flattened into a
+/// single vector, and the mean squared error between the expected result, a sigmoid of the
+/// evaluation, and the actual result is minimized. The sigmoid scaling constant K is fitted first
+/// and then kept fixed, while the parameters are optimized with a deterministic local search
+/// nudging every value in turn by one unit in both directions.
+use crate::chess::{board::*, piece::*};
+use crate::engine::hce::{eval_params::*, evaluate_with};
+use crate::engine::search_params::Eval;
+
+/// Digits of precision when fitting K
+const K_PRECISION: usize = 5;
+
+/// A position labelled with the result of its game, from White's point of view
+pub struct TuneEntry {
+    pub board: Board,
+    pub result: f64,
+}
+
+/// Parse "FEN; result" lines, with results 1.0, 0.5 or 0.0. Empty lines are skipped.
+pub fn parse_dataset(text: &str) -> Result<Vec<TuneEntry>, &'static str> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (fen, result) = line.rsplit_once(';').ok_or("Missing result separator!")?;
+            let board = fen.trim().parse()?;
+            let result = match result.trim() {
+                "1.0" | "1" => 1.0,
+                "0.5" => 0.5,
+                "0.0" | "0" => 0.0,
+                _ => return Err("Invalid result!"),
+            };
+
+            Ok(TuneEntry { board, result })
+        })
+        .collect()
+}
+
+/// Expected result, from 0 to 1, of the given evaluation
+fn sigmoid(eval: Eval, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval as f64 / 400.0))
+}
+
+/// Mean squared error of the evaluations against the results
+fn mean_error(entries: &[TuneEntry], evals: &[Eval], k: f64) -> f64 {
+    let total: f64 = entries
+        .iter()
+        .zip(evals)
+        .map(|(entry, &eval)| (entry.result - sigmoid(eval, k)).powi(2))
+        .sum();
+
+    total / entries.len() as f64
+}
+
+/// Evaluations of all the entries from White's point of view
+fn white_evals(entries: &[TuneEntry], params: &EvalParams) -> Vec<Eval> {
+    entries
+        .iter()
+        .map(|entry| match entry.board.side {
+            Color::White => evaluate_with(&entry.board, params),
+            Color::Black => -evaluate_with(&entry.board, params),
+        })
+        .collect()
+}
+
+/// Scaling constant K minimizing the error with the given parameters, refined one digit at a time
+pub fn fit_k(entries: &[TuneEntry], params: &EvalParams) -> f64 {
+    let evals = white_evals(entries, params);
+    let (mut k, mut step) = (1.0, 1.0);
+
+    for _ in 0..K_PRECISION {
+        let candidates = (-9..=9)
+            .map(|i| k + i as f64 * step)
+            .filter(|&candidate| candidate > 0.0);
+
+        k = candidates
+            .map(|candidate| (mean_error(entries, &evals, candidate), candidate))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap()
+            .1;
+        step /= 10.0;
+    }
+
+    k
+}
+
+/// Mean squared error of the given parameters over the dataset
+pub fn tuning_error(entries: &[TuneEntry], params: &EvalParams, k: f64) -> f64 {
+    mean_error(entries, &white_evals(entries, params), k)
+}
+
+/// Run the local search for at most the given number of passes over all the parameters, stopping
+/// early when a pass brings no improvement. The error after each pass is handed to the callback.
+pub fn tune(
+    entries: &[TuneEntry],
+    params: &EvalParams,
+    k: f64,
+    passes: usize,
+    mut report: impl FnMut(usize, f64, &EvalParams),
+) -> EvalParams {
+    let mut values = params.to_vec();
+    let mut best = tuning_error(entries, params, k);
+
+    for pass in 0..passes {
+        let mut improved = false;
+
+        for i in 0..values.len() {
+            for delta in [1, -1] {
+                values[i] += delta;
+                let error = tuning_error(entries, &EvalParams::from_slice(&values), k);
+
+                if error < best {
+                    best = error;
+                    improved = true;
+                    break;
+                }
+                values[i] -= delta;
+            }
+        }
+
+        report(pass + 1, best, &EvalParams::from_slice(&values));
+        if !improved {
+            break;
+        }
+    }
+
+    EvalParams::from_slice(&values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::moves::*;
+    use crate::chess::tables::Prng;
+    use crate::engine::hce::{evaluate, PawnTable};
+
+    /// Positions from random games, labelled with random results
+    fn synthetic_dataset() -> String {
+        let mut rng = Prng::new(0x7E8E1);
+        let mut lines = String::new();
+
+        for _ in 0..12 {
+            let mut board = Board::default();
+            for _ in 0..(8 + rng.next_u64() % 16) {
+                let move_list = board.gen_moves::<QUIETS>();
+                let m: Move = move_list.moves[rng.next_u64() as usize % move_list.len()];
+                board = board.make_move(m);
+            }
+
+            let result = ["1.0", "0.5", "0.0"][rng.next_u64() as usize % 3];
+            lines += &format!("{}; {result}\n", board.to_fen());
+        }
+
+        lines
+    }
+
+    #[test]
+    fn test_parse_dataset() {
+        let text = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1; 0.5\n\n\
+                    8/8/4k3/8/8/8/4K3/R7 w - - 0 1; 1.0\n";
+
+        let entries = parse_dataset(text).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].result, 1.0);
+
+        assert!(parse_dataset("8/8/4k3/8/8/8/4K3/R7 w - - 0 1; 2.0").is_err());
+        assert!(parse_dataset("8/8/4k3/8/8/8/4K3/R7 w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn test_tuning() {
+        let entries = parse_dataset(&synthetic_dataset()).unwrap();
+        let k = fit_k(&entries, &DEFAULT_PARAMS);
+        assert!(k > 0.0);
+
+        // Without the incremental psqt and pawn table, the default parameters give the same evals
+        for entry in &entries {
+            let eval = evaluate(&entry.board, &mut PawnTable::new(1));
+            assert_eq!(evaluate_with(&entry.board, &DEFAULT_PARAMS), eval);
+        }
+
+        let initial = tuning_error(&entries, &DEFAULT_PARAMS, k);
+        let mut errors = Vec::new();
+        let tuned = tune(&entries, &DEFAULT_PARAMS, k, 1, |_, error, _| {
+            errors.push(error)
+        });
+
+        assert!(errors[0] < initial);
+        assert_eq!(tuning_error(&entries, &tuned, k), errors[0]);
+
+        // Same dataset, same parameters
+        let again = tune(&entries, &DEFAULT_PARAMS, k, 1, |_, _, _| ());
+        assert_eq!(again, tuned);
+    }
+}
@@ -1,14 +1,18 @@
 /// Engine module contains all the logic used by Carp to play chess.
 pub mod bench;
+pub mod book;
 pub mod clock;
+pub mod hce;
 pub mod move_picker;
 pub mod nnue;
 pub mod position;
 pub mod search;
 pub mod search_params;
 pub mod search_tables;
+pub mod skill;
 pub mod thread;
 pub mod tt;
 pub mod uci;
+pub mod wdl;
 
 pub use uci::UCIReader;
@@ -10,6 +10,8 @@ QA = 255
 QB = 64
 QAB = QA * QB
 PARAM_SIZE = 2 # param size in bytes
+NET_MAGIC = b'CARPNNUE'
+NET_VERSION = 1
 
 def write_bytes(array):
     with open('net.bin', 'ab') as file:
@@ -66,3 +68,8 @@ write_bytes(feature_weights)
 write_bytes(feature_biases)
 write_bytes(output_weights)
 write_bytes(output_biases)
+
+# Also write a network file for the EvalFile option: a header followed by the same data
+with open('net.bin', 'rb') as params, open('net.nnue', 'wb') as file:
+    file.write(NET_MAGIC + struct.pack('<III', NET_VERSION, FEATURES, HIDDEN))
+    file.write(params.read())
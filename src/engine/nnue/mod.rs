@@ -1,13 +1,18 @@
 /// NNUE Implementation
 /// Carp uses a (768->768)x2->1 perspective net architecture, fully trained on self play data.
-/// Network is initialized at compile time from the 'net.bin' file in this directory.
-/// A new net can be loaded by running the convert_json.py script in the scripts folder.
+/// With the embedded-net feature, the default network is initialized at compile time from the
+/// 'net.bin' file in this directory. A new net can be produced by running the convert_json.py
+/// script in this folder, and loaded at runtime through the EvalFile option.
+/// Without a loaded network, positions are scored by the classical evaluation instead.
 ///
 /// Huge thanks to Cosmo, author of Viridithas, for the help. The code here is heavily inspired by
 /// his engine.
 use std::alloc;
+use std::fs;
 use std::mem;
 use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
 
 use super::search_params::*;
 use crate::chess::{board::*, piece::*, square::*};
@@ -29,15 +34,124 @@ const SCALE: i32 = 400;
 
 /// Container for all network parameters
 #[repr(C)]
-struct NNUEParams {
+pub struct NNUEParams {
     feature_weights: Align64<[i16; FEATURES * HIDDEN]>,
     feature_bias: Align64<[i16; HIDDEN]>,
     output_weights: Align64<[i16; HIDDEN * 2]>,
     output_bias: i16,
 }
 
+/// The weights are far too large to be printed
+impl std::fmt::Debug for NNUEParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NNUEParams").finish_non_exhaustive()
+    }
+}
+
 /// NNUE model is initialized from binary values (Viridithas format)
-const MODEL: NNUEParams = unsafe { mem::transmute(*include_bytes!("net.bin")) };
+#[cfg(feature = "embedded-net")]
+static MODEL: NNUEParams = unsafe { mem::transmute(*include_bytes!("net.bin")) };
+
+/// Network picked up by NNUE states when refreshed, null when no network is loaded.
+/// Networks loaded at runtime are leaked, so that states built on a replaced one remain valid.
+#[cfg(feature = "embedded-net")]
+static NETWORK: AtomicPtr<NNUEParams> =
+    AtomicPtr::new(&MODEL as *const NNUEParams as *mut NNUEParams);
+#[cfg(not(feature = "embedded-net"))]
+static NETWORK: AtomicPtr<NNUEParams> = AtomicPtr::new(ptr::null_mut());
+
+/// Disabling NNUE keeps the loaded network around, to be used again once enabled
+static NNUE_ENABLED: AtomicBool = AtomicBool::new(true);
+
+// Network files start with a header, followed by the parameters laid out as in net.bin
+const NET_MAGIC: &[u8; 8] = b"CARPNNUE";
+const NET_VERSION: u32 = 1;
+const HEADER_SIZE: usize = NET_MAGIC.len() + 3 * mem::size_of::<u32>();
+
+/// Currently loaded network, if any and if NNUE is enabled
+pub fn network() -> Option<&'static NNUEParams> {
+    if !NNUE_ENABLED.load(Ordering::Acquire) {
+        return None;
+    }
+
+    // The pointer is either null or points to a network living for the whole program
+    unsafe { NETWORK.load(Ordering::Acquire).as_ref() }
+}
+
+/// Enable or disable the NNUE evaluation
+pub fn set_nnue_enabled(enabled: bool) {
+    NNUE_ENABLED.store(enabled, Ordering::Release);
+}
+
+/// Go back to the network embedded in the binary
+pub fn load_embedded_network() -> Result<(), &'static str> {
+    #[cfg(feature = "embedded-net")]
+    {
+        NETWORK.store(
+            &MODEL as *const NNUEParams as *mut NNUEParams,
+            Ordering::Release,
+        );
+        Ok(())
+    }
+
+    #[cfg(not(feature = "embedded-net"))]
+    Err("No network is embedded in this build!")
+}
+
+/// Load the network file at the given path, replacing the current network.
+/// The current network is kept if the file is invalid.
+pub fn load_network(path: &str) -> Result<(), &'static str> {
+    let net = Box::leak(read_network(path)?);
+    NETWORK.store(net, Ordering::Release);
+
+    Ok(())
+}
+
+/// Read and validate a network file
+fn read_network(path: &str) -> Result<Box<NNUEParams>, &'static str> {
+    let bytes = fs::read(path).map_err(|_| "Could not read the network file!")?;
+
+    parse_network(&bytes)
+}
+
+/// Parse a network file, checking the magic bytes, format version and network dimensions
+/// Like the accumulator stack, the parameters are allocated straight on the heap
+fn parse_network(bytes: &[u8]) -> Result<Box<NNUEParams>, &'static str> {
+    if bytes.len() < HEADER_SIZE {
+        return Err("Network file is too short!");
+    }
+
+    let (header, params) = bytes.split_at(HEADER_SIZE);
+    let field = |i: usize| {
+        let start = NET_MAGIC.len() + i * mem::size_of::<u32>();
+        u32::from_le_bytes(header[start..start + 4].try_into().unwrap())
+    };
+
+    if &header[..NET_MAGIC.len()] != NET_MAGIC {
+        return Err("Not a Carp network file!");
+    }
+    if field(0) != NET_VERSION {
+        return Err("Unsupported network file version!");
+    }
+    if (field(1), field(2)) != (FEATURES as u32, HIDDEN as u32) {
+        return Err("Network dimensions do not match the architecture!");
+    }
+    if params.len() != mem::size_of::<NNUEParams>() {
+        return Err("Network file has the wrong size!");
+    }
+
+    // Any byte pattern is a valid set of i16 parameters
+    unsafe {
+        let layout = alloc::Layout::new::<NNUEParams>();
+        let ptr = alloc::alloc(layout);
+        if ptr.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        ptr::copy_nonoverlapping(params.as_ptr(), ptr, params.len());
+
+        Ok(Box::from_raw(ptr.cast()))
+    }
+}
 
 /// Generic wrapper for types aligned to 64B for AVX512 (also a Viridithas trick)
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -60,28 +174,25 @@ type SideAccumulator = Align64<[i16; HIDDEN]>;
 
 /// Accumulators contain the efficiently updated hidden layer values
 /// Each accumulator is perspective, hence both contains the white and black pov
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Accumulator {
     white: SideAccumulator,
     black: SideAccumulator,
 }
 
-impl Default for Accumulator {
-    fn default() -> Self {
+impl Accumulator {
+    /// Accumulator with no active features, holding only the feature biases
+    fn new(net: &NNUEParams) -> Self {
         Self {
-            white: MODEL.feature_bias,
-            black: MODEL.feature_bias,
+            white: net.feature_bias,
+            black: net.feature_bias,
         }
     }
-}
 
-impl Accumulator {
     /// Updates weights for a single feature, either turning them on or off
-    fn update_weights<const ON: bool>(&mut self, idx: (usize, usize)) {
-        fn update<const ON: bool>(acc: &mut SideAccumulator, idx: usize) {
-            let zip = acc
-                .iter_mut()
-                .zip(&MODEL.feature_weights[idx..idx + HIDDEN]);
+    fn update_weights<const ON: bool>(&mut self, net: &NNUEParams, idx: (usize, usize)) {
+        fn update<const ON: bool>(acc: &mut SideAccumulator, net: &NNUEParams, idx: usize) {
+            let zip = acc.iter_mut().zip(&net.feature_weights[idx..idx + HIDDEN]);
 
             for (acc_val, &weight) in zip {
                 if ON {
@@ -92,18 +203,18 @@ impl Accumulator {
             }
         }
 
-        update::<ON>(&mut self.white, idx.0);
-        update::<ON>(&mut self.black, idx.1);
+        update::<ON>(&mut self.white, net, idx.0);
+        update::<ON>(&mut self.black, net, idx.1);
     }
 
     /// Update accumulator for a quiet move.
     /// Adds in features for the destination and removes the features of the source
-    fn add_sub_weights(&mut self, from: (usize, usize), to: (usize, usize)) {
-        fn add_sub(acc: &mut SideAccumulator, from: usize, to: usize) {
+    fn add_sub_weights(&mut self, net: &NNUEParams, from: (usize, usize), to: (usize, usize)) {
+        fn add_sub(acc: &mut SideAccumulator, net: &NNUEParams, from: usize, to: usize) {
             let zip = acc.iter_mut().zip(
-                MODEL.feature_weights[from..from + HIDDEN]
+                net.feature_weights[from..from + HIDDEN]
                     .iter()
-                    .zip(&MODEL.feature_weights[to..to + HIDDEN]),
+                    .zip(&net.feature_weights[to..to + HIDDEN]),
             );
 
             for (acc_val, (&remove_weight, &add_weight)) in zip {
@@ -111,16 +222,18 @@ impl Accumulator {
             }
         }
 
-        add_sub(&mut self.white, from.0, to.0);
-        add_sub(&mut self.black, from.1, to.1);
+        add_sub(&mut self.white, net, from.0, to.0);
+        add_sub(&mut self.black, net, from.1, to.1);
     }
 }
 
-/// NNUEState is simply a stack of accumulators, updated along the search tree
+/// NNUEState is simply a stack of accumulators, updated along the search tree.
+/// The state keeps the network it was refreshed with, and is left untouched without one.
 #[derive(Debug, Clone)]
 pub struct NNUEState {
     accumulator_stack: [Accumulator; MAX_DEPTH + 1],
     current_acc: usize,
+    net: Option<&'static NNUEParams>,
 }
 
 // used for turning on/off features
@@ -140,20 +253,24 @@ impl NNUEState {
             Box::from_raw(ptr.cast())
         };
 
-        // init with feature biases and add in all features of the board
-        boxed.accumulator_stack[0] = Accumulator::default();
-        for sq in board.occupancy {
-            boxed.manual_update::<ON>(board.piece_at(sq), sq);
-        }
-
+        boxed.refresh(board);
         boxed
     }
 
-    /// Refresh the accumulator stack to the given board
+    /// Refresh the accumulator stack to the given board, with the currently loaded network
     pub fn refresh(&mut self, board: &Board) {
+        self.refresh_with(board, network());
+    }
+
+    /// Refresh the accumulator stack to the given board, with the given network
+    fn refresh_with(&mut self, board: &Board, net: Option<&'static NNUEParams>) {
         // reset the accumulator stack
         self.current_acc = 0;
-        self.accumulator_stack[self.current_acc] = Accumulator::default();
+        self.net = net;
+        let Some(net) = self.net else {
+            return;
+        };
+        self.accumulator_stack[self.current_acc] = Accumulator::new(net);
 
         // update the first accumulator
         for piece in ALL_PIECES {
@@ -176,15 +293,20 @@ impl NNUEState {
 
     /// Manually turn on or off the single given feature
     pub fn manual_update<const ON: bool>(&mut self, piece: Piece, sq: Square) {
-        self.accumulator_stack[self.current_acc].update_weights::<ON>(nnue_index(piece, sq));
+        if let Some(net) = self.net {
+            self.accumulator_stack[self.current_acc]
+                .update_weights::<ON>(net, nnue_index(piece, sq));
+        }
     }
 
     /// Efficiently update accumulator for a quiet move (that is, only changes from/to features)
     pub fn move_update(&mut self, piece: Piece, from: Square, to: Square) {
-        let from_idx = nnue_index(piece, from);
-        let to_idx = nnue_index(piece, to);
+        if let Some(net) = self.net {
+            let from_idx = nnue_index(piece, from);
+            let to_idx = nnue_index(piece, to);
 
-        self.accumulator_stack[self.current_acc].add_sub_weights(from_idx, to_idx);
+            self.accumulator_stack[self.current_acc].add_sub_weights(net, from_idx, to_idx);
+        }
     }
 
     /// Evaluate the nn from the current accumulator
@@ -192,7 +314,9 @@ impl NNUEState {
     /// with Squared CReLu and multiplies activation by weight. The result is the sum of all these
     /// with the bias.
     /// Since we are squaring activations, we need an extra quantization pass with QA.
-    pub fn evaluate(&self, side: Color) -> Eval {
+    /// Returns None when no network is loaded.
+    pub fn evaluate(&self, side: Color) -> Option<Eval> {
+        let net = self.net?;
         let acc = &self.accumulator_stack[self.current_acc];
 
         let (us, them) = match side {
@@ -200,15 +324,15 @@ impl NNUEState {
             Color::Black => (acc.black.iter(), acc.white.iter()),
         };
 
-        let mut out = MODEL.output_bias as i32;
-        for (&value, &weight) in us.zip(&MODEL.output_weights[..HIDDEN]) {
+        let mut out = net.output_bias as i32;
+        for (&value, &weight) in us.zip(&net.output_weights[..HIDDEN]) {
             out += squared_crelu(value) * (weight as i32);
         }
-        for (&value, &weight) in them.zip(&MODEL.output_weights[HIDDEN..]) {
+        for (&value, &weight) in them.zip(&net.output_weights[HIDDEN..]) {
             out += squared_crelu(value) * (weight as i32);
         }
 
-        ((out / QA) * SCALE / QAB) as Eval
+        Some(((out / QA) * SCALE / QAB) as Eval)
     }
 }
 
@@ -235,6 +359,13 @@ fn squared_crelu(value: i16) -> i32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chess::moves::*;
+    use crate::chess::tables::Prng;
+
+    /// Accumulator on top of the stack
+    fn top(state: &NNUEState) -> &Accumulator {
+        &state.accumulator_stack[state.current_acc]
+    }
 
     #[test]
     fn test_nnue_stack() {
@@ -309,4 +440,103 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_incremental_matches_refresh() {
+        let mut rng = Prng::new(0xACC);
+
+        for _ in 0..16 {
+            let mut board = Board::default();
+            let mut state = NNUEState::from_board(&board);
+            let mut history = Vec::new();
+
+            for _ in 0..48 {
+                let move_list = board.gen_moves::<QUIETS>();
+                if move_list.is_empty() {
+                    break;
+                }
+
+                let m: Move = move_list.moves[rng.next_u64() as usize % move_list.len()];
+                let new = board.make_move_nnue(m, &mut state);
+                assert_eq!(new, board.make_move(m));
+                history.push(std::mem::replace(&mut board, new));
+
+                let fresh = NNUEState::from_board(&board);
+                assert_eq!(top(&state), top(&fresh), "{}", board.to_fen());
+                assert_eq!(state.evaluate(board.side), fresh.evaluate(board.side));
+            }
+
+            // Popping the stack gives back the accumulators of the earlier positions
+            while let Some(previous) = history.pop() {
+                state.pop();
+                assert_eq!(top(&state), top(&NNUEState::from_board(&previous)));
+            }
+        }
+    }
+
+    /// Network file with the given header fields around the given parameters
+    fn network_file(version: u32, hidden: usize, params: &[u8]) -> Vec<u8> {
+        let mut bytes = NET_MAGIC.to_vec();
+        for field in [version, FEATURES as u32, hidden as u32] {
+            bytes.extend(field.to_le_bytes());
+        }
+        bytes.extend(params);
+
+        bytes
+    }
+
+    #[test]
+    fn test_corrupted_network() {
+        let params = vec![0; mem::size_of::<NNUEParams>()];
+        assert!(parse_network(&network_file(NET_VERSION, HIDDEN, &params)).is_ok());
+
+        let mut bad_magic = network_file(NET_VERSION, HIDDEN, &params);
+        bad_magic[0] = b'X';
+        assert!(parse_network(&bad_magic).is_err());
+        assert!(parse_network(&network_file(NET_VERSION + 1, HIDDEN, &params)).is_err());
+        assert!(parse_network(&network_file(NET_VERSION, 256, &params)).is_err());
+        assert!(parse_network(&network_file(NET_VERSION, HIDDEN, &params[1..])).is_err());
+        assert!(parse_network(&network_file(NET_VERSION, HIDDEN, &[0; 64])).is_err());
+        assert!(parse_network(&NET_MAGIC[..]).is_err());
+
+        // A bad file leaves the current network in place
+        let path = std::env::temp_dir().join("carp_corrupted_net.nnue");
+        std::fs::write(&path, &bad_magic).unwrap();
+        let before = NETWORK.load(Ordering::Acquire);
+
+        assert!(load_network(path.to_str().unwrap()).is_err());
+        assert!(load_network("missing_network_file.nnue").is_err());
+        assert_eq!(NETWORK.load(Ordering::Acquire), before);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "embedded-net")]
+    #[test]
+    fn test_network_from_disk() {
+        let path = std::env::temp_dir().join("carp_embedded_net.nnue");
+        let bytes = network_file(NET_VERSION, HIDDEN, include_bytes!("net.bin"));
+        std::fs::write(&path, bytes).unwrap();
+
+        let disk: &'static NNUEParams = Box::leak(read_network(path.to_str().unwrap()).unwrap());
+        std::fs::remove_file(path).unwrap();
+
+        let mut rng = Prng::new(0xD15C);
+        let mut board = Board::default();
+        for _ in 0..64 {
+            let move_list = board.gen_moves::<QUIETS>();
+            if move_list.is_empty() {
+                break;
+            }
+
+            let mut embedded = NNUEState::from_board(&board);
+            let mut loaded = NNUEState::from_board(&board);
+            embedded.refresh_with(&board, Some(&MODEL));
+            loaded.refresh_with(&board, Some(disk));
+            let eval = embedded.evaluate(board.side);
+            assert!(eval.is_some());
+            assert_eq!(eval, loaded.evaluate(board.side));
+
+            board = board.make_move(move_list.moves[rng.next_u64() as usize % move_list.len()]);
+        }
+    }
 }
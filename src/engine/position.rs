@@ -1,6 +1,6 @@
 /// Position wraps the entire game state.
-use crate::chess::{bitboard::*, board::*, moves::*, piece::*};
-use crate::engine::{move_picker::*, nnue::*, search_params::*, thread::*};
+use crate::chess::{bitboard::*, board::*, game::*, moves::*, piece::*, square::*, tables::*};
+use crate::engine::{hce, hce::PawnTable, move_picker::*, nnue::*, search_params::*, thread::*};
 
 /// Position, represents a Board's evolution along the game tree.
 /// Also incorporates move ordering and various game rules (50mr, draw detection etc)
@@ -11,49 +11,89 @@ pub struct Position {
     nnue_state: Box<NNUEState>,
 }
 
+/// Error from reading a uci position string. Move errors point at the offending move by its
+/// index in the move list, as no move is played once one of them fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PositionError {
+    Invalid(&'static str),
+    MalformedMove { index: usize, text: String },
+    IllegalMove { index: usize, text: String },
+}
+
+impl std::fmt::Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(e) => write!(f, "{e}"),
+            Self::MalformedMove { index, text } => write!(f, "Move {index} ({text}) is malformed!"),
+            Self::IllegalMove { index, text } => write!(f, "Move {index} ({text}) is not legal!"),
+        }
+    }
+}
+
+/// Whether the string is a move in uci format, legal or not
+fn is_uci_move(move_str: &str) -> bool {
+    let square = |s: Option<&str>| s.is_some_and(|s| s.parse::<Square>().is_ok());
+
+    square(move_str.get(0..2))
+        && square(move_str.get(2..4))
+        && matches!(move_str.get(4..), Some("" | "n" | "b" | "r" | "q"))
+}
+
 /// Get position from uci position string
 impl std::str::FromStr for Position {
-    type Err = &'static str;
+    type Err = PositionError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut tokens = s.split_whitespace();
-        let mut board: Board = match tokens.next() {
+        let board: Board = match tokens.next() {
             Some("startpos") => Board::default(),
             Some("fen") => {
-                let fen = &tokens.clone().take(6).collect::<Vec<&str>>().join(" ")[..];
+                // The move counters are optional, so the FEN runs up to the move list
+                let fen: Vec<&str> = tokens.clone().take_while(|&t| t != "moves").collect();
 
-                for _ in 0..6 {
+                for _ in 0..fen.len() {
                     tokens.next();
                 }
 
-                fen.parse()?
+                fen.join(" ").parse().map_err(PositionError::Invalid)?
             }
-            _ => return Err("Invalid position"),
+            _ => return Err(PositionError::Invalid("Invalid position")),
         };
 
-        let mut history = Vec::new();
-        if let Some("moves") = tokens.next() {
-            for move_str in tokens {
-                let m = board.find_move(move_str);
-
-                match m {
-                    Some(m) => {
-                        let new = board.make_move(m);
-                        history.push(board);
-                        board = new;
-                    }
-                    None => eprintln!("Move is not legal!"),
-                };
-            }
+        let moves: Vec<&str> = match tokens.next() {
+            Some("moves") => tokens.collect(),
+            _ => Vec::new(),
         };
 
+        Self::from_start_and_moves(board, &moves)
+    }
+}
+
+/// Position at the end of the game, with the game boards as history
+impl From<Game> for Position {
+    fn from(game: Game) -> Self {
+        let mut history: Vec<Board> = game.boards().cloned().collect();
+        let board = history.pop().unwrap();
         let nnue_state = NNUEState::from_board(&board);
 
-        Ok(Self {
+        Self {
             board,
             history,
             nnue_state,
-        })
+        }
+    }
+}
+
+/// Position without history
+impl From<Board> for Position {
+    fn from(board: Board) -> Self {
+        let nnue_state = NNUEState::from_board(&board);
+
+        Self {
+            board,
+            history: Vec::new(),
+            nnue_state,
+        }
     }
 }
 
@@ -65,6 +105,29 @@ impl Default for Position {
 }
 
 impl Position {
+    /// Play the uci moves from the given board. Any malformed or illegal move rejects the whole
+    /// line, instead of leaving the position out of sync with the move list.
+    pub fn from_start_and_moves<S: AsRef<str>>(
+        board: Board,
+        moves: &[S],
+    ) -> Result<Self, PositionError> {
+        let mut game = Game::from(board);
+
+        for (index, move_str) in moves.iter().map(AsRef::as_ref).enumerate() {
+            let text = move_str.to_owned();
+            if !is_uci_move(move_str) {
+                return Err(PositionError::MalformedMove { index, text });
+            }
+
+            match game.current().find_move(move_str) {
+                Some(m) if game.push(m).is_ok() => (),
+                _ => return Err(PositionError::IllegalMove { index, text }),
+            }
+        }
+
+        Ok(Self::from(game))
+    }
+
     /// Produce a move picker for the current position
     pub fn gen_moves<const QUIETS: bool>(
         &self,
@@ -109,6 +172,11 @@ impl Position {
         t.pop_move();
     }
 
+    /// Rebuild the NNUE state with the currently loaded network
+    pub fn refresh_network(&mut self) {
+        self.nnue_state.refresh(&self.board);
+    }
+
     /// Returns true if it's white to move
     pub fn white_to_move(&self) -> bool {
         self.board.side == Color::White
@@ -119,22 +187,107 @@ impl Position {
         self.board.checkers != EMPTY_BB
     }
 
-    /// Only king and pawns are on the board for the side to move. Possible Zugzwang.
-    pub fn only_king_pawns_left(&self) -> bool {
-        (self.board.own_occupancy() ^ self.board.own_king() ^ self.board.own_pawns()) == EMPTY_BB
+    /// Checks whether the given last move gave check while losing material by SEE
+    pub fn losing_check(&self, last_move: Option<Move>) -> bool {
+        match (last_move, self.history.last()) {
+            (Some(m), Some(prev)) => self.king_in_check() && !prev.see(m, 0),
+            _ => false,
+        }
     }
 
-    /// Checks if position is a rule-based draw
-    pub fn is_draw(&self, ply_from_null: usize) -> bool {
+    /// Checks whether a null move may be tried in this node.
+    /// Null moves are never made back to back, and are avoided in possible zugzwang positions,
+    /// where the side to move only has its king and pawns left.
+    pub fn null_allowed(&self, t: &Thread) -> bool {
+        t.ply_from_null > 0 && self.board.has_non_pawn_material(self.board.side)
+    }
+
+    /// Checks if position is a rule-based draw, with repetitions counted as in is_repetition.
+    /// Outside of search, use a ply of 0 to only accept a threefold repetition.
+    pub fn is_draw(&self, ply: usize, ply_from_null: usize) -> bool {
         self.board.halfmoves >= 100
-            || self.is_repetition(ply_from_null)
-            || self.insufficient_material()
+            || self.is_repetition(ply, ply_from_null)
+            || self.board.insufficient_material()
+    }
+
+    /// Checks whether the side to move can reach an earlier position with a reversible move.
+    /// Uses the cuckoo tables to find upcoming repetitions one ply before they happen.
+    /// Cycles may cross the root only if no null move was made in the current line, and as in
+    /// is_repetition, the earlier position then needs to have been repeated already.
+    pub fn has_game_cycle(&self, ply: usize, ply_from_null: usize) -> bool {
+        let end = if ply_from_null < ply {
+            ply_from_null.min(self.board.halfmoves)
+        } else {
+            self.board.halfmoves
+        }
+        .min(self.history.len());
+
+        if end < 3 {
+            return false;
+        }
+
+        let hash_at = |i: usize| self.history[self.history.len() - i].hash.0;
+        let repeated_at = |i: usize| {
+            let j = self.history.len() - i;
+            let earlier = &self.history[j];
+            (2..=earlier.halfmoves.min(j))
+                .step_by(2)
+                .any(|k| self.history[j - k].hash == earlier.hash)
+        };
+        let original = self.board.hash.0;
+        let mut other = original ^ hash_at(1) ^ SIDE_KEY;
+
+        for i in (3..=end).step_by(2) {
+            other ^= hash_at(i - 1) ^ hash_at(i) ^ SIDE_KEY;
+            if other != 0 {
+                continue;
+            }
+
+            if let Some(m) = cuckoo_move(original ^ hash_at(i)) {
+                let (src, tgt) = (m.get_src(), m.get_tgt());
+
+                if BETWEEN[src as usize][tgt as usize] & self.board.occupancy == EMPTY_BB {
+                    if ply > i {
+                        return true;
+                    }
+
+                    // Before the root, the repeating move has to be ours, and a single earlier
+                    // occurrence is not a draw yet
+                    let own = self.board.side_occupancy[self.board.side as usize];
+                    if (own.get_bit(src) || own.get_bit(tgt)) && repeated_at(i) {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Return the static evaluation of the current position, scaled towards zero as the 50 move
+    /// rule gets closer
+    pub fn evaluate(&self, pawn_table: &mut PawnTable, alpha: Eval, beta: Eval) -> Eval {
+        self.scale_to_halfmoves(self.raw_evaluate(pawn_table, alpha, beta))
+    }
+
+    /// Scale the given static eval by the halfmove clock, leaving mate scores untouched.
+    /// Since the clock is not part of the hash, the tt only holds unscaled static evals.
+    pub fn scale_to_halfmoves(&self, eval: Eval) -> Eval {
+        if eval.abs() >= MATE_IN_PLY {
+            return eval;
+        }
+
+        let factor = (HALFMOVE_SCALE_BASE - self.board.halfmoves as Eval).max(HALFMOVE_SCALE_FLOOR);
+        eval * factor / HALFMOVE_SCALE_BASE
     }
 
-    /// Return the NNUE evaluation of the current position
-    /// We scale the evaluation by the total material on the board
-    pub fn evaluate(&self) -> Eval {
-        let eval = self.nnue_state.evaluate(self.board.side);
+    /// Return the NNUE evaluation of the current position, or the classical one when no network
+    /// is loaded. We scale the NNUE evaluation by the total material on the board.
+    /// The (alpha, beta) window of the node lets the classical evaluation exit early.
+    pub fn raw_evaluate(&self, pawn_table: &mut PawnTable, alpha: Eval, beta: Eval) -> Eval {
+        let Some(eval) = self.nnue_state.evaluate(self.board.side) else {
+            return hce::evaluate_lazy(&self.board, pawn_table, alpha, beta);
+        };
 
         #[rustfmt::skip]
         let total_material =
@@ -146,64 +299,35 @@ impl Position {
         (eval * (700 + total_material / 32)) / 1024
     }
 
-    /// Check for repetitions in hash history.
-    /// We stop at the first occurrence of the position and consider that a draw.
-    fn is_repetition(&self, ply_from_null: usize) -> bool {
-        let rollback = ply_from_null.min(self.board.halfmoves);
-
-        self.history
-            .iter()
-            .rev() // step through history in reverse
-            .take(rollback + 1) // only check elements in the rollback
-            .skip(1) // first element is opponent, skip.
-            .step_by(2) // don't check opponent moves
-            .any(|b| b.hash == self.board.hash) // stop at first repetition
-    }
-
-    /// Draw by insufficient material (strictly for when it is impossible to mate):
-    /// Some of the logic is taken from Tantabus
-    fn insufficient_material(&self) -> bool {
-        const WHITE_SQUARES: BitBoard = BitBoard(12273903644374837845);
-        const CORNERS: BitBoard = BitBoard(9295429630892703873);
-        const EDGES: BitBoard = BitBoard(18411139144890810879);
-
-        let kings = self.board.kings();
-        let knights = self.board.knights();
-        let bishops = self.board.bishops();
-
-        match self.board.occupancy.count_bits() {
-            2 => true,
-            3 => knights | bishops != EMPTY_BB, // 1 knight or 1 bishop
-            4 => {
-                let one_each = self.board.side_occupancy[0].count_bits() == 2;
-                let knight_count = knights.count_bits();
-                let bishop_count = bishops.count_bits();
-                let king_in_corner = kings & CORNERS != EMPTY_BB;
-                let king_on_edge = kings & EDGES != EMPTY_BB;
-
-                (knight_count == 2 && !king_on_edge) || // knvkn, king not on edge
-                (bishop_count == 2 && (
-                    (bishops & WHITE_SQUARES).count_bits() != 1 || // same color bishops
-                    (one_each && !king_in_corner))) ||  // one bishop each, king not in corner
-                (knight_count == 1 && bishop_count == 1 && one_each && !king_in_corner)
-                // knvkb, king not in corner
-            }
-            _ => false,
+    /// Check for repetitions in hash history, up to the last irreversible or null move.
+    /// A position seen once before within the search tree (root included) is a draw, since the
+    /// same moves could be repeated again. Positions from before the root, which is `ply` plies
+    /// back, need to be a true threefold repetition. As in has_game_cycle, the game history
+    /// before the root is only searched if no null move was made in the current line.
+    pub fn is_repetition(&self, ply: usize, ply_from_null: usize) -> bool {
+        let rollback = if ply_from_null < ply {
+            ply_from_null.min(self.board.halfmoves)
+        } else {
+            self.board.halfmoves
+        }
+        .min(self.history.len().saturating_sub(1));
+
+        // Step through history in reverse, only checking positions with the same side to move
+        let mut repetitions = (2..=rollback + 1)
+            .step_by(2)
+            .filter(|&i| self.history[self.history.len() - i].hash == self.board.hash);
+
+        match repetitions.next() {
+            Some(i) => i <= ply || repetitions.next().is_some(),
+            None => false,
         }
     }
-}
 
-/// Game result, used for datagen
-/// The bool refers to the game being adjudicated
-#[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Debug)]
-pub enum GameResult {
-    Ongoing,
-    WhiteWin(bool),
-    BlackWin(bool),
-    Draw(bool),
+    /// Checks whether the position occurred three times since the last irreversible move
+    pub fn is_threefold(&self) -> bool {
+        self.is_repetition(0, self.board.halfmoves)
+    }
 }
-pub const ADJ: bool = true;
-pub const NO_ADJ: bool = false;
 
 /// Datagen-specific implementations
 impl Position {
@@ -236,7 +360,7 @@ impl Position {
             } else {
                 GameResult::Draw(NO_ADJ)
             }
-        } else if self.is_draw(self.board.halfmoves) {
+        } else if self.is_draw(0, self.board.halfmoves) {
             GameResult::Draw(NO_ADJ)
         } else {
             GameResult::Ongoing
@@ -246,19 +370,205 @@ impl Position {
 
 #[cfg(test)]
 mod tests {
-    use crate::chess::init_all_tables;
 
     use super::*;
 
     #[test]
-    fn test_draw() {
-        init_all_tables();
-        let kbvkn_mate: Position = "fen 5b1K/5k1N/8/8/8/8/8/8 b - - 1 1".parse().unwrap();
-        let kbvkn_draw: Position = "fen 8/8/3k4/4n3/8/2KB4/8/8 w - - 0 1".parse().unwrap();
-        let krvkn: Position = "fen 8/8/4k3/4n3/8/2KR4/8/8 w - - 0 1".parse().unwrap();
-
-        assert!(!kbvkn_mate.insufficient_material());
-        assert!(kbvkn_draw.insufficient_material());
-        assert!(!krvkn.insufficient_material());
+    fn test_consecutive_null() {
+        let mut position = Position::default();
+        let mut t = Thread::fixed_depth(1);
+        assert!(!position.null_allowed(&t)); // root
+
+        position.make_move(position.board.find_move("e2e4").unwrap(), &mut t);
+        assert!(position.null_allowed(&t));
+
+        position.make_null(&mut t);
+        assert_eq!(t.ply_from_null, 0);
+        assert!(!position.null_allowed(&t));
+
+        position.make_move(position.board.find_move("d2d4").unwrap(), &mut t);
+        assert!(position.null_allowed(&t));
+
+        // Unmaking restores the counter from before the null move
+        position.undo_move(&mut t);
+        position.undo_move(&mut t);
+        assert_eq!(t.ply_from_null, 1);
+        assert!(position.null_allowed(&t));
+    }
+
+    #[test]
+    fn test_null_zugzwang() {
+        // White has a lone knight against Black's bare pawns
+        let mut position: Position = "fen 8/5pk1/6p1/8/8/6P1/4NPK1/8 w - - 0 1".parse().unwrap();
+        let mut t = Thread::fixed_depth(1);
+        assert!(position.board.has_non_pawn_material(Color::White));
+        assert!(!position.board.has_non_pawn_material(Color::Black));
+
+        position.make_move(position.board.find_move("e2f4").unwrap(), &mut t);
+        assert!(!position.null_allowed(&t));
+
+        position.make_move(position.board.find_move("g7h6").unwrap(), &mut t);
+        assert!(position.null_allowed(&t));
+    }
+
+    #[test]
+    fn test_position_moves() {
+        let moves = "e2e4 g8f6 e4e5 d7d5 e5d6 f6e4 d6c7 e4c3 c7d8q";
+        let position: Position = format!("startpos moves {moves}").parse().unwrap();
+        assert_eq!(
+            position.board.to_fen(),
+            "rnbQkb1r/pp2pppp/8/8/8/2n5/PPPP1PPP/RNBQKBNR b KQkq - 0 5"
+        );
+        assert_eq!(position.history.len(), 9);
+
+        // Move counters are optional before the move list
+        let short_fen = "fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - moves e2e4";
+        let position: Position = short_fen.parse().unwrap();
+        assert_eq!(
+            position.board.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn test_position_errors() {
+        let moves = ["e2e4", "e7e5", "g1f3"].map(String::from);
+        let position = Position::from_start_and_moves(Board::default(), &moves).unwrap();
+        let parsed: Position = "startpos moves e2e4 e7e5 g1f3".parse().unwrap();
+        assert_eq!(position.board, parsed.board);
+        assert_eq!(position.history.len(), 3);
+
+        // The king can not step into the queen's attack
+        let illegal = ["e2e4", "e7e5", "e1e2", "d8g5", "e2e3"].map(String::from);
+        assert_eq!(
+            Position::from_start_and_moves(Board::default(), &illegal).unwrap_err(),
+            PositionError::IllegalMove {
+                index: 4,
+                text: "e2e3".to_owned()
+            }
+        );
+        assert_eq!(
+            "startpos moves e2e4 e7e4".parse::<Position>().unwrap_err(),
+            PositionError::IllegalMove {
+                index: 1,
+                text: "e7e4".to_owned()
+            }
+        );
+
+        for (line, index, text) in [
+            ("startpos moves e2e4 e7e5 Nf3", 2, "Nf3"),
+            ("startpos moves e2e4 e7e5x", 1, "e7e5x"),
+            ("startpos moves e2", 0, "e2"),
+        ] {
+            assert_eq!(
+                line.parse::<Position>().unwrap_err(),
+                PositionError::MalformedMove {
+                    index,
+                    text: text.to_owned()
+                }
+            );
+        }
+
+        assert_eq!(
+            "kiwipete".parse::<Position>().unwrap_err(),
+            PositionError::Invalid("Invalid position")
+        );
+    }
+
+    #[test]
+    fn test_game_cycle() {
+        // White, a queen down, can go back to the starting position with Ng1
+        let cycle: Position =
+            "fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR b KQkq - 0 1 moves g8f6 g1f3 f6g8"
+                .parse()
+                .unwrap();
+        assert!(cycle.has_game_cycle(4, 4));
+
+        // Before the root, the starting position only occurred once, so it is not a draw yet
+        assert!(!cycle.has_game_cycle(0, 0));
+        assert!(!cycle.has_game_cycle(3, 3));
+
+        // Once it has been repeated, going back to it would be a threefold
+        let repeated: Position = "fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR b KQkq - 0 1 \
+            moves g8f6 g1f3 f6g8 f3g1 g8f6 g1f3 f6g8"
+            .parse()
+            .unwrap();
+        assert!(repeated.has_game_cycle(0, 0));
+
+        // Too few reversible plies for any cycle
+        let short: Position = "startpos moves g1f3 g8f6".parse().unwrap();
+        assert!(!short.has_game_cycle(0, 0));
+
+        // A pawn push resets the cycle
+        let reset: Position = "startpos moves g1f3 g8f6 e2e4 f6g8".parse().unwrap();
+        assert!(!reset.has_game_cycle(0, 0));
+
+        // Black can go back to the starting position with Ng8
+        let black: Position = "startpos moves g1f3 g8f6 f3g1".parse().unwrap();
+        assert!(black.has_game_cycle(4, 4));
+        assert!(!black.has_game_cycle(0, 0));
+
+        // No earlier position can be reached in one move
+        let blocked: Position = "startpos moves g1f3 b8c6 f3g1 c6e5 b1c3".parse().unwrap();
+        assert!(!blocked.has_game_cycle(0, 0));
+    }
+
+    #[test]
+    fn test_repetition() {
+        const SHUFFLE: &str = "g1f3 g8f6 f3g1 f6g8";
+
+        // Startpos is repeated once, which is only a draw if it happened within the search
+        let once: Position = format!("startpos moves {SHUFFLE}").parse().unwrap();
+        assert!(!once.is_threefold());
+        assert!(!once.is_draw(0, once.board.halfmoves));
+        assert!(once.is_repetition(4, 4));
+        assert!(!once.is_repetition(3, 4));
+        assert!(!once.is_repetition(4, 2));
+
+        let twice: Position = format!("startpos moves {SHUFFLE} {SHUFFLE}")
+            .parse()
+            .unwrap();
+        assert!(twice.is_threefold());
+        assert!(twice.is_draw(0, twice.board.halfmoves));
+
+        // In search, the null move counter starts at the root, and the lookback still reaches
+        // the earlier occurrences before it
+        assert!(twice.is_repetition(2, 2));
+        assert!(!once.is_repetition(2, 2));
+
+        // A null move in the current line stops the lookback
+        assert!(!twice.is_repetition(2, 1));
+
+        // Repetitions do not cross irreversible moves
+        let reset: Position =
+            format!("startpos moves {SHUFFLE} e2e4 e7e5 {SHUFFLE} {SHUFFLE} {SHUFFLE}")
+                .parse()
+                .unwrap();
+        assert!(reset.is_threefold());
+        let pushed: Position = format!("startpos moves {SHUFFLE} e2e4 e7e5 {SHUFFLE}")
+            .parse()
+            .unwrap();
+        assert!(!pushed.is_threefold());
+        assert!(!Position::default().is_repetition(0, 0));
+    }
+
+    #[test]
+    fn test_halfmove_scaling() {
+        let pawn_table = &mut PawnTable::default();
+        let fresh: Position = "fen 4k3/8/8/8/8/8/PPPP4/4K3 w - - 0 60".parse().unwrap();
+        let stale: Position = "fen 4k3/8/8/8/8/8/PPPP4/4K3 w - - 90 60".parse().unwrap();
+
+        let raw = fresh.raw_evaluate(pawn_table, -INFINITY, INFINITY);
+        assert!(raw > 200);
+        assert_eq!(stale.raw_evaluate(pawn_table, -INFINITY, INFINITY), raw);
+        assert_eq!(fresh.evaluate(pawn_table, -INFINITY, INFINITY), raw);
+        assert_eq!(
+            stale.evaluate(pawn_table, -INFINITY, INFINITY),
+            raw * 110 / 200
+        );
+
+        // Mate scores are left alone
+        assert_eq!(stale.scale_to_halfmoves(MATE - 3), MATE - 3);
+        assert_eq!(stale.scale_to_halfmoves(-MATE + 8), -MATE + 8);
     }
 }
@@ -1,27 +1,59 @@
 /// The Search module implements Carp's Alpha-Beta algorithm for single-threaded tree search.
 use crate::chess::{board::*, moves::*, tables::*};
 use crate::engine::{
-    move_picker::*, position::*, search_params::*, search_tables::*, thread::*, tt::*,
+    hce::is_known_draw, move_picker::*, position::*, search_params::*, search_tables::*, thread::*,
+    tt::*,
 };
 
+/// Effective branching factor from the nodes of the last two iterations, 0 when unknown
+fn branching_factor(iteration_nodes: [u64; 2]) -> f64 {
+    match iteration_nodes {
+        [0, _] => 0.0,
+        [previous, last] => last as f64 / previous as f64,
+    }
+}
+
 impl Position {
     /// Iteratively searches the position at increasing depth
     /// Search results remain stored within the thread.
     pub fn iterative_search<const INFO: bool>(&mut self, t: &mut Thread, tt: &TT) {
-        while t.depth < MAX_DEPTH && t.clock.start_search(t.depth + 1, t.nodes, t.best_move()) {
+        // Nodes of the last two iterations, giving the effective branching factor
+        let mut iteration_nodes = [0; 2];
+        let mut stability = 0;
+
+        while t.depth < MAX_DEPTH
+            && t.clock.start_search(t.depth + 1, t.nodes, t.best_move())
+            && t.clock
+                .should_start_iteration(t.depth + 1, branching_factor(iteration_nodes))
+        {
+            let (start_nodes, best_move) = (t.nodes, t.best_move());
             let eval = self.aspiration_window(t, tt);
 
             if t.stop {
                 break;
             }
 
+            // Think longer when the best move keeps changing or the score drops
+            stability = if t.best_move() == best_move {
+                stability + 1
+            } else {
+                0
+            };
+            if t.depth > 0 {
+                t.clock
+                    .scale_soft_limit(soft_limit_factor(stability, t.eval - eval));
+            }
+
             // Update thread data after a search finishes.
+            iteration_nodes = [iteration_nodes[1], t.nodes - start_nodes];
             t.eval = eval;
             t.depth += 1;
             if INFO {
                 println!("{t}");
             }
         }
+
+        t.clock.flush_nodes(t.nodes);
     }
 
     /// Aspiration Window loop
@@ -35,7 +67,8 @@ impl Position {
         let mut delta = ASPIRATION_WINDOW;
 
         // Setup aspiration windows when searching a sufficient depth
-        if new_depth >= ASPIRATION_LOWER_LIMIT {
+        // Root moves are all searched on a full window when scoring them, so skip them then
+        if new_depth >= ASPIRATION_LOWER_LIMIT && t.root_scores.is_none() {
             alpha = (-INFINITY).max(t.eval - delta);
             beta = (INFINITY).min(t.eval + delta);
         }
@@ -106,6 +139,8 @@ impl Position {
 
         let pv_node = alpha != beta - 1;
         let in_check = self.king_in_check();
+        let scoring_root = ROOT && t.root_scores.is_some();
+        let mut root_scores = Vec::new();
 
         let mut old_pv = PVTable::default();
         let opv = &mut old_pv;
@@ -118,8 +153,25 @@ impl Position {
         }
 
         // Check extension
-        if in_check {
+        // Extensions along the current line are capped by the root depth, to avoid explosions.
+        // Checks losing material are not extended, as they are rarely more than spite checks.
+        if ROOT {
+            t.extensions[0] = 0;
+        }
+
+        let mut extensions = t.extensions[t.ply];
+        if in_check && extensions <= t.depth && !self.losing_check(t.last_move()) {
             depth += 1;
+            extensions += 1;
+        }
+
+        // Upcoming repetition detection
+        // If we can force a repetition, the position is worth at least a draw.
+        if !ROOT && alpha < 0 && self.has_game_cycle(t.ply, t.ply_from_null) {
+            alpha = 0;
+            if alpha >= beta {
+                return alpha;
+            }
         }
 
         // Quiescence search
@@ -127,6 +179,11 @@ impl Position {
             return self.quiescence(t, tt, alpha, beta);
         }
 
+        // Killers of the next ply were found in sibling subtrees, which rarely share refutations
+        if t.ply + 1 < MAX_DEPTH {
+            t.killer_moves[t.ply + 1] = [NULL_MOVE; 2];
+        }
+
         if !ROOT {
             // Mate distance pruning
             // Shrink the window based on the best/worst possible outcomes, which are being mated
@@ -138,8 +195,8 @@ impl Position {
                 return alpha;
             }
 
-            // Stop searching if the position is a rule-based draw
-            if self.is_draw(t.ply_from_null) {
+            // Stop searching if the position is a rule-based or a trivially known draw
+            if self.is_draw(t.ply, t.ply_from_null) || is_known_draw(&self.board) {
                 return 0;
             }
         }
@@ -172,8 +229,10 @@ impl Position {
                     }
                 }
 
-                tt_move = entry.get_move();
+                // Hash collisions may hand us a move from another position
+                tt_move = entry.get_move().filter(|&m| self.board.is_legal(m));
                 possible_singularity = !ROOT
+                    && tt_move.is_some()
                     && depth >= SE_LOWER_LIMIT
                     && tt_eval.abs() < MATE_IN_PLY
                     && (tt_flag == TTFlag::Lower || tt_flag == TTFlag::Exact)
@@ -183,10 +242,13 @@ impl Position {
 
         // Compute the static eval. Try to avoid re-computing it if we already have it in some form.
         // When in check, we keep -INFINITY.
+        // The tt stores the raw static eval, which is scaled by the halfmove clock on use.
         let mut stand_pat = -INFINITY;
+        let mut raw_eval = -INFINITY;
 
         if in_singular_search {
             stand_pat = t.eval_stack[t.ply];
+            raw_eval = tt_entry.map_or(-INFINITY, |entry| entry.get_static_eval());
         } else if !in_check {
             // If we have a tt entry, use the static eval from there
             if let Some(entry) = tt_entry {
@@ -195,12 +257,14 @@ impl Position {
                 let tt_static_eval = entry.get_static_eval();
 
                 if tt_static_eval == -INFINITY {
-                    stand_pat = self.evaluate();
+                    raw_eval = self.raw_evaluate(&mut t.pawn_table, alpha, beta);
                 } else {
-                    stand_pat = tt_static_eval;
+                    raw_eval = tt_static_eval;
                 }
+                stand_pat = self.scale_to_halfmoves(raw_eval);
 
                 // If the tt eval is a tigher bound than the static eval, use it as stand pat
+                let tt_static_eval = self.scale_to_halfmoves(tt_static_eval);
                 if (tt_flag == TTFlag::Lower && tt_eval > tt_static_eval)
                     || (tt_flag == TTFlag::Upper && tt_eval <= tt_static_eval)
                 {
@@ -208,7 +272,8 @@ impl Position {
                 }
             } else {
                 // Without a tt entry (and not in check), we have to compute the static eval
-                stand_pat = self.evaluate();
+                raw_eval = self.raw_evaluate(&mut t.pawn_table, alpha, beta);
+                stand_pat = self.scale_to_halfmoves(raw_eval);
 
                 // Chuck the static eval into the tt. This won't overwrite any relevant entry
                 tt.insert(
@@ -216,7 +281,7 @@ impl Position {
                     TTFlag::None,
                     NULL_MOVE,
                     -INFINITY,
-                    stand_pat,
+                    raw_eval,
                     0,
                     t.ply,
                     false,
@@ -229,7 +294,7 @@ impl Position {
         // Improving is true when the current static eval is better than that of a move ago
         // Assuming this trend continues down this branch, we can prune high more aggressively,
         // while we should be less aggressive when pruning low.
-        let improving = !in_check && t.ply > 1 && stand_pat > t.eval_stack[t.ply - 2];
+        let improving = t.is_improving(in_check);
 
         // Static pruning techniques:
         // these heuristics are trying to prove that the position is statically good enough to not
@@ -238,9 +303,7 @@ impl Position {
             // Reverse Futility Pruning (static eval pruning)
             // If the static eval is above beta by a certain margin at shallow depth, we can prune
             // assuming a beta cutoff. If the static eval is improving, we reduce the margin.
-            let rfp_margin =
-                RFP_MARGIN * (depth as Eval) - RFP_IMPROVING_MARGIN * (improving as Eval);
-            if depth <= RFP_THRESHOLD && stand_pat - rfp_margin >= beta {
+            if depth <= RFP_THRESHOLD && stand_pat - rfp_margin(depth, improving) >= beta {
                 return beta;
             }
 
@@ -248,12 +311,12 @@ impl Position {
             // Give the opponent a "free shot" and see if that improves beta.
             if depth > NMP_LOWER_LIMIT
                 && stand_pat + NMP_IMPROVING_MARGIN * (improving as Eval) >= beta
-                && t.ply_from_null > 0
-                && !self.only_king_pawns_left()
+                && self.null_allowed(t)
             {
                 let r = (NMP_BASE + depth / NMP_FACTOR).min(depth);
 
                 self.make_null(t);
+                t.extensions[t.ply] = extensions;
                 let eval = -self.zw_search(t, tt, opv, -(beta - 1), depth - r, !cutnode);
                 self.undo_move(t);
 
@@ -293,7 +356,7 @@ impl Position {
         let mut move_count = 0;
 
         #[cfg(not(feature = "datagen"))]
-        let lmp_count = LMP_BASE + (depth * depth);
+        let lmp_count = lmp_limit(depth, improving);
 
         #[cfg(not(feature = "datagen"))]
         let see_margins = [
@@ -324,12 +387,12 @@ impl Position {
 
                 // Extended Futility pruning
                 // Below a certain depth, prune moves which will most likely not improve alpha
-                let efp_margin = EFP_BASE + EFP_MARGIN * (lmr_depth as Eval);
+                let efp_margin = efp_margin(lmr_depth, improving);
                 if lmr_depth <= EFP_THRESHOLD && stand_pat + efp_margin < alpha {
                     picker.skip_quiets = true;
                 }
 
-                // Late move pruning
+                // Late move pruning (more lenient when improving)
                 if depth <= LMP_THRESHOLD && move_count >= lmp_count {
                     picker.skip_quiets = true;
                 }
@@ -337,7 +400,8 @@ impl Position {
 
             // SEE pruning for captures and quiets
             #[cfg(not(feature = "datagen"))]
-            if best_eval > -MATE_IN_PLY
+            if !scoring_root
+                && best_eval > -MATE_IN_PLY
                 && depth <= SEE_PRUNING_THRESHOLD
                 && picker.stage > Stage::GoodTacticals
                 && !self.board.see(m, see_margins[is_quiet as usize])
@@ -359,12 +423,14 @@ impl Position {
                 let eval = self.zw_search(t, tt, opv, se_beta, se_depth, cutnode);
                 t.excluded[t.ply] = None;
 
-                if eval < se_beta {
+                if eval < se_beta && extensions <= t.depth {
                     ext_depth += 1;
                 }
             }
 
             self.make_move(m, t);
+            t.extensions[t.ply] = extensions + ext_depth - depth;
+            debug_assert!(t.extensions[t.ply] <= t.depth + 1);
             tt.prefetch(self.board.hash); // prefetch next hash
 
             // Principal Variation Search + Late Move Reductions
@@ -372,37 +438,38 @@ impl Position {
             // fails high on alpha. If it doesn't, it's likely a cutnode.
             // We reduce the depth of these searches the further in the move list we go.
             let mut eval = -INFINITY;
-            let full_depth_search =
-                if depth >= LMR_LOWER_LIMIT && move_count >= LMR_THRESHOLD + pv_node as usize {
-                    let r = if is_quiet {
-                        let mut r = lmr_reduction(depth, move_count) as i32;
-                        let is_check = self.king_in_check();
-
-                        r += !pv_node as i32; // reduce more in non-pv nodes
-                        r += cutnode as i32; // reduce more for cutnodes
-
-                        r -= in_check as i32; // reduce less when in check
-                        r -= is_check as i32; // reduce less when giving check
-
-                        if s > HISTORY_MAX / 2 {
-                            r -= 1; // Reduce less high history moves/killers
-                        } else if s < -HISTORY_MAX / 2 {
-                            r += 1; // Reduce more low history moves
-                        }
-
-                        r.clamp(1, (depth - 1) as i32) as usize
-                    } else {
-                        1
-                    };
+            let full_depth_search = if scoring_root {
+                false
+            } else if depth >= LMR_LOWER_LIMIT && move_count >= LMR_THRESHOLD + pv_node as usize {
+                let r = if is_quiet {
+                    let mut r = lmr_reduction(depth, move_count) as i32;
+                    let is_check = self.king_in_check();
+
+                    r += !pv_node as i32; // reduce more in non-pv nodes
+                    r += cutnode as i32; // reduce more for cutnodes
+
+                    r -= in_check as i32; // reduce less when in check
+                    r -= is_check as i32; // reduce less when giving check
+
+                    if s > HISTORY_MAX / 2 {
+                        r -= 1; // Reduce less high history moves/killers
+                    } else if s < -HISTORY_MAX / 2 {
+                        r += 1; // Reduce more low history moves
+                    }
 
-                    // Reduced depth null window search
-                    // Since we are speculating being an allnode, expect the child to be a cutnode
-                    eval = -self.zw_search(t, tt, opv, -alpha, ext_depth - r, true);
-                    eval > alpha && r > 1
+                    r.clamp(1, (depth - 1) as i32) as usize
                 } else {
-                    !pv_node || move_count > 0
+                    1
                 };
 
+                // Reduced depth null window search
+                // Since we are speculating being an allnode, expect the child to be a cutnode
+                eval = -self.zw_search(t, tt, opv, -alpha, ext_depth - r, true);
+                eval > alpha && r > 1
+            } else {
+                !pv_node || move_count > 0
+            };
+
             // Full depth null window search when lmr fails or when using pvs
             // Allnodes/Cutnodes alternate
             if full_depth_search {
@@ -411,7 +478,11 @@ impl Position {
 
             // Full depth full window search for the first move of all PV nodes and when pvs fails
             // We expect the child node to be a PV node
-            if pv_node && (move_count == 0 || eval > alpha) {
+            // When scoring root moves, every move gets an exact score on a full window
+            if scoring_root {
+                eval =
+                    -self.negamax::<false>(t, tt, opv, -INFINITY, INFINITY, ext_depth - 1, false);
+            } else if pv_node && (move_count == 0 || eval > alpha) {
                 eval = -self.negamax::<false>(t, tt, opv, -beta, -alpha, ext_depth - 1, false);
             }
 
@@ -426,6 +497,10 @@ impl Position {
                 t.clock.update_node_counts(m, t.nodes - start_nodes);
             }
 
+            if scoring_root {
+                root_scores.push((m, eval));
+            }
+
             if eval > best_eval {
                 best_eval = eval;
 
@@ -453,6 +528,11 @@ impl Position {
             move_count += 1;
         }
 
+        // Only keep the root scores of completed iterations
+        if scoring_root && !t.stop {
+            t.root_scores = Some(root_scores);
+        }
+
         if !t.stop {
             let tt_flag = if best_eval >= beta {
                 TTFlag::Lower
@@ -467,7 +547,7 @@ impl Position {
                 tt_flag,
                 best_move,
                 alpha,
-                stand_pat,
+                raw_eval,
                 depth,
                 t.ply,
                 pv_node,
@@ -488,7 +568,7 @@ impl Position {
 
         // Return early when reaching max depth
         if t.ply >= MAX_DEPTH {
-            return self.evaluate();
+            return self.evaluate(&mut t.pawn_table, alpha, beta);
         }
 
         let in_check = self.king_in_check();
@@ -513,6 +593,7 @@ impl Position {
 
         // Compute the static eval when not in check
         let mut stand_pat = -INFINITY;
+        let mut raw_eval = -INFINITY;
 
         if !in_check {
             if let Some(entry) = tt_entry {
@@ -521,19 +602,22 @@ impl Position {
                 let tt_static_eval = entry.get_static_eval();
 
                 if tt_static_eval == -INFINITY {
-                    stand_pat = self.evaluate();
+                    raw_eval = self.raw_evaluate(&mut t.pawn_table, alpha, beta);
                 } else {
-                    stand_pat = tt_static_eval;
+                    raw_eval = tt_static_eval;
                 }
+                stand_pat = self.scale_to_halfmoves(raw_eval);
 
                 // If the tt eval is a tigher bound than the static eval, use it as stand pat
+                let tt_static_eval = self.scale_to_halfmoves(tt_static_eval);
                 if (tt_flag == TTFlag::Lower && tt_eval > tt_static_eval)
                     || (tt_flag == TTFlag::Upper && tt_eval <= tt_static_eval)
                 {
                     stand_pat = tt_eval;
                 }
             } else {
-                stand_pat = self.evaluate();
+                raw_eval = self.raw_evaluate(&mut t.pawn_table, alpha, beta);
+                stand_pat = self.scale_to_halfmoves(raw_eval);
             }
         };
 
@@ -595,7 +679,7 @@ impl Position {
                 tt_flag,
                 best_move,
                 alpha,
-                stand_pat,
+                raw_eval,
                 0,
                 t.ply,
                 false,
@@ -611,6 +695,7 @@ impl Position {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::engine::hce::PawnTable;
     use std::time::Instant;
 
     #[test]
@@ -618,13 +703,11 @@ mod tests {
         #[rustfmt::skip]
         const SEARCH_SUITE: [(&str, &str, usize); 4] = [
             ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "Kiwipete", 15),
-            ("rnbqkb1r/pp1p1pPp/8/2p1pP2/1P1P4/3P3P/P1P1P3/RNBQKBNR w KQkq e6 0 1", "Killer", 15),
+            ("rnbqkb1r/pp1p1pPp/8/2p1pP2/1P1P4/3P4/P1P1P3/RNBQKBNR w KQkq e6 0 1", "Killer", 15),
             ("8/8/8/2K5/5Q2/8/4k3/8 w - - 0 1", "Mate in 4", 20),
             ("1b2k3/3rP3/2B1K3/8/5P2/8/1p6/8 b - - 4 57", "All moves lead to mate", 5),
         ];
 
-        init_all_tables();
-
         for (fen, name, depth) in SEARCH_SUITE {
             println!("Searching: {}", name);
 
@@ -643,4 +726,124 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn bounded_check_extensions() {
+        #[rustfmt::skip]
+        const CHECK_SUITE: [&str; 3] = [
+            "3k4/8/8/8/8/8/8/Q2K3q w - - 0 1",
+            "4k3/pp6/8/8/8/8/PP6/Q3K2q w - - 0 1",
+            "6k1/6p1/6Q1/8/8/8/5q2/7K w - - 0 1",
+        ];
+
+        // Exposed kings and queens on both sides, so most lines are long check sequences.
+        // Every node checks the cap in debug builds, and the stack keeps the last line searched.
+        for fen in CHECK_SUITE {
+            let position: Position = format!("fen {fen}").parse().unwrap();
+
+            for depth in 1..=9 {
+                let mut t = Thread::fixed_depth(depth);
+                position
+                    .clone()
+                    .iterative_search::<false>(&mut t, &TT::default());
+
+                assert!(t.extensions.iter().all(|&e| e <= depth), "{fen} {depth}");
+            }
+        }
+    }
+
+    #[test]
+    fn sibling_killers_cleared() {
+        let mut position: Position = "startpos".parse().unwrap();
+        let mut t = Thread::fixed_depth(1);
+        let stale = position.board.find_move("a2a3").unwrap();
+        t.killer_moves[1] = [stale; 2];
+
+        // At depth 1 the root clears the next ply, whose nodes drop into quiescence right away
+        position.iterative_search::<false>(&mut t, &TT::default());
+        assert_eq!(t.killer_moves[1], [NULL_MOVE; 2]);
+    }
+
+    #[test]
+    fn root_scores_recorded() {
+        // Ra8 mates through Qd8, and every other root move still gets its own exact score
+        let mut position: Position = "fen 6k1/5ppp/8/8/8/8/3q1PPP/R5K1 w - - 0 1"
+            .parse()
+            .unwrap();
+        let mut t = Thread::fixed_depth(3);
+        t.root_scores = Some(Vec::new());
+        position.iterative_search::<false>(&mut t, &TT::default());
+
+        let scores = t.root_scores.unwrap();
+        assert_eq!(scores.len(), position.board.gen_moves::<QUIETS>().len());
+
+        let best = scores.iter().max_by_key(|&&(_, s)| s).unwrap();
+        assert_eq!(best.0.to_string(), "a1a8");
+        assert_eq!(best.1, t.eval);
+        assert_eq!(scores.iter().filter(|&&(_, s)| s == best.1).count(), 1);
+    }
+
+    #[test]
+    fn upcoming_repetition() {
+        // White is three pawns up, but Kg1 is forced and lets black repeat a third time with Ka8
+        let mut position: Position = "fen k7/8/8/8/p1p1p3/P1P1P2p/P1P1P2P/6K1 w - - 0 1 \
+            moves g1h1 a8b8 h1g1 b8a8 g1h1 a8b8"
+            .parse()
+            .unwrap();
+        let mut t = Thread::fixed_depth(1);
+        position.iterative_search::<false>(&mut t, &TT::default());
+
+        assert_eq!(t.eval, 0);
+    }
+
+    #[test]
+    fn single_occurrence_before_root() {
+        // Ka8 only goes back to a position which occurred once, which is not a draw
+        let mut position: Position =
+            "fen k7/8/8/8/p1p1p3/P1P1P2p/P1P1P2P/6K1 w - - 0 1 moves g1h1 a8b8"
+                .parse()
+                .unwrap();
+        let mut t = Thread::fixed_depth(1);
+        position.iterative_search::<false>(&mut t, &TT::default());
+
+        assert!(t.eval > 0);
+    }
+
+    #[test]
+    fn threefold_before_root() {
+        // White, a queen down, has already been back to the start twice and takes the third time
+        let mut position: Position =
+            "fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR b KQkq - 0 1 \
+            moves g8f6 g1f3 f6g8 f3g1 g8f6 g1f3 f6g8"
+                .parse()
+                .unwrap();
+        let mut t = Thread::fixed_depth(4);
+        position.iterative_search::<false>(&mut t, &TT::default());
+
+        assert_eq!(t.eval, 0);
+        assert_eq!(t.best_move().to_string(), "f3g1");
+    }
+
+    #[test]
+    fn unscaled_tt_static_eval() {
+        // Same position and hash, only the halfmove clock differs
+        let fresh: Position = "fen 4k3/8/8/8/8/8/PPPP4/4K3 w - - 0 60".parse().unwrap();
+        let mut stale: Position = "fen 4k3/8/8/8/8/8/PPPP4/4K3 w - - 90 60".parse().unwrap();
+        assert_eq!(fresh.board.hash, stale.board.hash);
+
+        let pawn_table = &mut PawnTable::default();
+        let tt = TT::new(1);
+        stale.iterative_search::<false>(&mut Thread::fixed_depth(3), &tt);
+
+        // The tt entry is good for the position regardless of the clock
+        let entry = tt.probe(stale.board.hash).unwrap();
+        let fresh_eval = fresh.evaluate(pawn_table, -INFINITY, INFINITY);
+        let stale_eval = stale.evaluate(pawn_table, -INFINITY, INFINITY);
+        assert_eq!(entry.get_static_eval(), fresh_eval);
+        assert_eq!(
+            stale.scale_to_halfmoves(entry.get_static_eval()),
+            stale_eval
+        );
+        assert!(stale_eval < fresh_eval);
+    }
 }
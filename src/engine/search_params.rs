@@ -5,7 +5,6 @@
 ///     BASE: flat bonus in a formula
 ///     MARGIN: multiplicative (usually depth) coefficient in a formula
 ///     FACTOR: dividing coefficient in a formula
-
 pub type Eval = i32;
 pub const MAX_DEPTH: usize = 127; // max depth to search at
 pub const INFINITY: Eval = 32001; // score upper bound
@@ -18,6 +17,10 @@ pub const ASPIRATION_LOWER_LIMIT: usize = 5;
 pub const ASPIRATION_WINDOW: Eval = 25;
 pub const BIG_DELTA: Eval = 1100;
 
+// Static evals are scaled by (HALFMOVE_SCALE_BASE - halfmoves) / HALFMOVE_SCALE_BASE
+pub const HALFMOVE_SCALE_BASE: Eval = 200;
+pub const HALFMOVE_SCALE_FLOOR: Eval = 100;
+
 pub const LMR_THRESHOLD: usize = 2;
 pub const LMR_LOWER_LIMIT: usize = 2;
 pub const LMR_BASE: f32 = 0.75;
@@ -29,6 +32,11 @@ pub const RFP_THRESHOLD: usize = 8;
 pub const RFP_MARGIN: Eval = 80;
 pub const RFP_IMPROVING_MARGIN: Eval = 55;
 
+/// Static eval margin for reverse futility pruning, smaller when improving
+pub const fn rfp_margin(depth: usize, improving: bool) -> Eval {
+    RFP_MARGIN * depth as Eval - RFP_IMPROVING_MARGIN * improving as Eval
+}
+
 pub const NMP_LOWER_LIMIT: usize = 3;
 pub const NMP_IMPROVING_MARGIN: Eval = 70;
 pub const NMP_BASE: usize = 4;
@@ -42,12 +50,79 @@ pub const HLP_BASE: i32 = -5000;
 pub const EFP_THRESHOLD: usize = 5;
 pub const EFP_BASE: Eval = 80;
 pub const EFP_MARGIN: Eval = 90;
+pub const EFP_IMPROVING_MARGIN: Eval = 30;
+
+/// Static eval margin for extended futility pruning, smaller when improving
+pub const fn efp_margin(lmr_depth: usize, improving: bool) -> Eval {
+    EFP_BASE + EFP_MARGIN * lmr_depth as Eval - EFP_IMPROVING_MARGIN * improving as Eval
+}
 
 pub const LMP_THRESHOLD: usize = 8;
 pub const LMP_BASE: usize = 4;
 
+/// Late move pruning move counts, indexed by [improving][depth]
+/// Improving nodes are allowed twice as many moves before pruning.
+pub const LMP_TABLE: [[usize; LMP_THRESHOLD + 1]; 2] = {
+    let mut table = [[0; LMP_THRESHOLD + 1]; 2];
+    let mut depth = 0;
+
+    while depth <= LMP_THRESHOLD {
+        table[0][depth] = (LMP_BASE + depth * depth) / 2;
+        table[1][depth] = LMP_BASE + depth * depth;
+        depth += 1;
+    }
+
+    table
+};
+
+/// Move count after which quiets are pruned (only meaningful up to LMP_THRESHOLD)
+pub const fn lmp_limit(depth: usize, improving: bool) -> usize {
+    let depth = if depth > LMP_THRESHOLD {
+        LMP_THRESHOLD
+    } else {
+        depth
+    };
+    LMP_TABLE[improving as usize][depth]
+}
+
 pub const SEE_PRUNING_THRESHOLD: usize = 9;
 pub const SEE_CAPTURE_MARGIN: Eval = -20;
 pub const SEE_QUIET_MARGIN: Eval = -65;
 
+// Sudden death allocation: base = time / TIME_FACTOR + inc * TIME_INC_MARGIN, the soft and hard
+// budgets being scaled from it and capped at TIME_MAX_FRACTION of the remaining time
+pub const TIME_FACTOR: u64 = 20;
+pub const TIME_INC_MARGIN: f64 = 0.75;
+pub const TIME_SOFT_MARGIN: f64 = 0.6;
+pub const TIME_HARD_MARGIN: f64 = 2.0;
+pub const TIME_MAX_FRACTION: f64 = 0.8;
+pub const LOW_TIME_THRESHOLD: u64 = 100;
+
+// The soft limit is scaled by best move stability and score drops (up to TIME_SCORE_DROP_LIMIT),
+// within TIME_SOFT_SCALE_MIN and TIME_SOFT_SCALE_MAX
+pub const TIME_STABILITY_SCALES: [f64; 5] = [1.6, 1.3, 1.1, 0.95, 0.85];
+pub const TIME_SCORE_DROP_LIMIT: Eval = 100;
+pub const TIME_SCORE_DROP_MARGIN: f64 = 0.006;
+pub const TIME_SOFT_SCALE_MIN: f64 = 0.5;
+pub const TIME_SOFT_SCALE_MAX: f64 = 2.5;
+pub const TIME_PREDICTION_LOWER_LIMIT: usize = 6;
+
+/// Soft limit scale, given how many iterations in a row kept the best move and the score drop
+/// from the previous iteration
+pub fn soft_limit_factor(stability: usize, score_drop: Eval) -> f64 {
+    let drop = score_drop.clamp(0, TIME_SCORE_DROP_LIMIT) as f64;
+    TIME_STABILITY_SCALES[stability.min(TIME_STABILITY_SCALES.len() - 1)]
+        * (1.0 + drop * TIME_SCORE_DROP_MARGIN)
+}
+
 pub const PIECE_VALUES: [Eval; 12] = [161, 161, 446, 446, 464, 464, 705, 705, 1322, 1322, 0, 0];
+
+/// Pruning margins as uci info strings, printed under "debug on"
+pub fn pruning_info() -> String {
+    format!(
+        "info string RFP_MARGIN {RFP_MARGIN} RFP_IMPROVING_MARGIN {RFP_IMPROVING_MARGIN}\n\
+         info string EFP_MARGIN {EFP_MARGIN} EFP_IMPROVING_MARGIN {EFP_IMPROVING_MARGIN}\n\
+         info string LMP_TABLE {:?} {:?}",
+        LMP_TABLE[0], LMP_TABLE[1],
+    )
+}
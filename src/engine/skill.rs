@@ -0,0 +1,172 @@
+/// Implement strength limitation for UCI_LimitStrength.
+///
+/// When limiting strength, the search scores every root move on a full window, and keeps the
+/// scores of the last completed iteration. Moves scoring within a margin of the best are then
+/// sampled through a softmax, with a temperature which decreases as the requested elo increases.
+/// Sampling uses the crate's own seeded Prng, as the engine does not depend on the rand crate.
+use crate::chess::{bitboard::*, board::*, moves::*, tables::Prng};
+use crate::engine::search_params::*;
+
+pub const MIN_ELO: u32 = 1000;
+pub const MAX_ELO: u32 = 3000;
+
+/// Strength limiter, holding the target elo and its own rng.
+#[derive(Clone, Debug)]
+pub struct Skill {
+    elo: u32,
+    rng: Prng,
+}
+
+impl Skill {
+    /// Create a skill limiter for the given elo (clamped to the supported range).
+    pub fn new(elo: u32, seed: u64) -> Self {
+        Self {
+            elo: elo.clamp(MIN_ELO, MAX_ELO),
+            rng: Prng::new(seed),
+        }
+    }
+
+    /// Position of the elo within the supported range, from 0.0 to 1.0
+    fn level(&self) -> f64 {
+        (self.elo - MIN_ELO) as f64 / (MAX_ELO - MIN_ELO) as f64
+    }
+
+    /// Moves scoring further than this from the best move are never played
+    pub fn margin(&self) -> Eval {
+        (300.0 - 280.0 * self.level()) as Eval
+    }
+
+    /// Softmax temperature in centipawns
+    pub fn temperature(&self) -> f64 {
+        150.0 - 140.0 * self.level()
+    }
+
+    /// Pick a move out of the scored root moves, returning it alongside its own score, or None
+    /// without any moves. Moves losing to a forced mate are only played when nothing else is
+    /// available.
+    pub fn pick_move(&mut self, scored: &[(Move, Eval)]) -> Option<(Move, Eval)> {
+        let best = scored.iter().map(|&(_, s)| s).max()?;
+        let candidates: Vec<(Move, Eval)> = scored
+            .iter()
+            .copied()
+            .filter(|&(_, s)| s == best || (s > -MATE_IN_PLY && best - s <= self.margin()))
+            .collect();
+
+        let temperature = self.temperature();
+        let weights: Vec<f64> = candidates
+            .iter()
+            .map(|&(_, s)| ((s - best) as f64 / temperature).exp())
+            .collect();
+
+        let mut target = self.rng.next_f64() * weights.iter().sum::<f64>();
+        for (&choice, w) in candidates.iter().zip(weights) {
+            if target < w {
+                return Some(choice);
+            }
+            target -= w;
+        }
+
+        candidates.last().copied() // only reached through rounding errors
+    }
+}
+
+/// Whether the opponent has a mate in one after the move
+fn allows_mate_in_one(board: &Board, m: Move) -> bool {
+    let board = board.make_move(m);
+    let move_list = board.gen_moves::<QUIETS>();
+
+    move_list.moves[..move_list.len()].iter().any(|&reply| {
+        let reply = board.make_move(reply);
+        reply.checkers != EMPTY_BB && reply.gen_moves::<QUIETS>().is_empty()
+    })
+}
+
+/// Drop the root moves allowing a mate in one, which a shallow search may not have seen, unless
+/// every move allows one
+pub fn discard_mated(board: &Board, scored: Vec<(Move, Eval)>) -> Vec<(Move, Eval)> {
+    let safe: Vec<(Move, Eval)> = scored
+        .iter()
+        .copied()
+        .filter(|&(m, _)| !allows_mate_in_one(board, m))
+        .collect();
+
+    if safe.is_empty() {
+        scored
+    } else {
+        safe
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::square::*;
+
+    fn sample(elo: u32) -> Vec<usize> {
+        let moves = [
+            Move::new(Square::E2, Square::E4, MoveType::Quiet),
+            Move::new(Square::D2, Square::D4, MoveType::Quiet),
+            Move::new(Square::G1, Square::F3, MoveType::Quiet),
+            Move::new(Square::A2, Square::A3, MoveType::Quiet),
+        ];
+        let scored = [
+            (moves[0], 50),
+            (moves[1], 30),
+            (moves[2], 0),
+            (moves[3], -200),
+        ];
+
+        let mut skill = Skill::new(elo, 0xC0FFEE);
+        let mut counts = vec![0; moves.len()];
+        for _ in 0..1000 {
+            let (m, s) = skill.pick_move(&scored).unwrap();
+            let i = moves.iter().position(|&x| x == m).unwrap();
+            assert_eq!(scored[i].1, s);
+            counts[i] += 1;
+        }
+
+        counts
+    }
+
+    #[test]
+    fn test_skill_distribution() {
+        let low = sample(MIN_ELO);
+        assert!(low[0] > low[1] && low[1] > low[2] && low[2] > low[3]);
+        assert!(low[3] > 0);
+        assert!(low[0] < 600);
+
+        let high = sample(MAX_ELO);
+        assert!(high[0] > 800);
+        assert_eq!(high[2] + high[3], 0);
+    }
+
+    #[test]
+    fn test_skill_avoids_mate() {
+        let safe = Move::new(Square::E2, Square::E4, MoveType::Quiet);
+        let mated = Move::new(Square::F2, Square::F3, MoveType::Quiet);
+        let scored = [(safe, -250), (mated, -MATE + 2)];
+
+        let mut skill = Skill::new(MIN_ELO, 1);
+        for _ in 0..100 {
+            assert_eq!(skill.pick_move(&scored).unwrap().0, safe);
+        }
+        assert_eq!(skill.pick_move(&[]), None);
+    }
+
+    #[test]
+    fn test_discard_mated() {
+        // Any move but Kg8 or a king move lets Qd8 mate on the back rank
+        let board: Board = "6k1/5ppp/8/8/8/8/5PPP/3Q2K1 b - - 0 1".parse().unwrap();
+        let move_list = board.gen_moves::<QUIETS>();
+        let scored: Vec<(Move, Eval)> = move_list.moves[..move_list.len()]
+            .iter()
+            .map(|&m| (m, 0))
+            .collect();
+
+        let safe = discard_mated(&board, scored.clone());
+        assert!(!safe.is_empty() && safe.len() < scored.len());
+        for (m, _) in safe {
+            assert!(!allows_mate_in_one(&board, m), "{m}");
+        }
+    }
+}
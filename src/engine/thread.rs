@@ -12,7 +12,27 @@ use std::sync::{
 use std::thread;
 
 use crate::chess::{board::*, moves::*, piece::*};
-use crate::engine::{clock::*, position::*, search_params::*, search_tables::*, tt::*};
+use crate::engine::{
+    clock::*, hce::PawnTable, position::*, search_params::*, search_tables::*, skill::*, tt::*,
+    wdl::*,
+};
+
+/// Outcome of a search, with the figures of the whole thread pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub depth: usize,
+    pub nodes: u64,
+    pub time: u64,
+}
+
+impl SearchResult {
+    /// Nodes searched per second, the time being in microseconds
+    pub fn nps(&self) -> u64 {
+        self.nodes * 1_000_000 / self.time.max(1)
+    }
+}
 
 /// Information only relevant within the search tree (thread local)
 pub struct Thread {
@@ -21,6 +41,7 @@ pub struct Thread {
     search_stack: [(Piece, Move, usize); MAX_DEPTH],
     pub eval_stack: [Eval; MAX_DEPTH],
     pub excluded: [Option<Move>; MAX_DEPTH],
+    pub extensions: [usize; MAX_DEPTH + 1],
 
     // Move ordering
     pub killer_moves: [[Move; 2]; MAX_DEPTH],
@@ -28,6 +49,9 @@ pub struct Thread {
     counter_moves: DoubleHistoryTable,
     followup_moves: DoubleHistoryTable,
 
+    // Classical evaluation, used when no network is loaded
+    pub pawn_table: PawnTable,
+
     // Search stats
     pub nodes: u64,
     pub seldepth: usize,
@@ -39,34 +63,68 @@ pub struct Thread {
     pub eval: Eval,
     pub depth: usize,
     pub stop: bool,
+
+    // Full-window scores of every root move, kept when limiting strength
+    pub root_scores: Option<Vec<(Move, Eval)>>,
+
+    // Score reporting
+    pub root_material: u32,
+    pub show_wdl: bool,
+    pub normalize_score: bool,
+}
+
+/// Format an evaluation as a UCI score
+/// Being mated on the board is "mate 0", as there is no "-0"
+pub fn uci_score(eval: Eval) -> String {
+    if eval == -MATE {
+        "mate 0".to_string()
+    } else if eval.abs() >= MATE_IN_PLY {
+        let moves_to_mate = (MATE - eval.abs() + 1) / 2;
+        if eval > 0 {
+            format!("mate {}", moves_to_mate)
+        } else {
+            format!("mate -{}", moves_to_mate)
+        }
+    } else {
+        format!("cp {}", eval)
+    }
+}
+
+/// UCI info for a root without legal moves, either checkmated or stalemated
+fn no_moves_info(pos: &Position) -> String {
+    let score = if pos.king_in_check() { -MATE } else { 0 };
+    format!("info depth 0 score {}", uci_score(score))
 }
 
 /// Display UCI info
+/// Scores are optionally normalized and followed by the win-draw-loss estimate
 impl std::fmt::Display for Thread {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let score = if self.eval.abs() >= MATE_IN_PLY {
-            let moves_to_mate = (MATE - self.eval.abs() + 1) / 2;
-            if self.eval > 0 {
-                format!("mate {}", moves_to_mate)
-            } else {
-                format!("mate -{}", moves_to_mate)
-            }
+        let score = if self.normalize_score {
+            uci_score(normalize(self.eval, self.root_material))
         } else {
-            format!("cp {}", self.eval)
+            uci_score(self.eval)
         };
-
-        let time = self.clock.elapsed().as_millis().max(1);
-        let nodes = self.clock.global_nodes();
+        let wdl = if self.show_wdl {
+            let (w, d, l) = win_probability(self.eval, self.root_material);
+            format!(" wdl {w} {d} {l}")
+        } else {
+            String::new()
+        };
+        let micros = self.clock.elapsed_micros();
+        // Nodes from the other threads, along with the ones not yet added by this thread
+        let nodes = self.clock.global_nodes() + self.nodes - self.clock.last_nodes;
 
         write!(
             f,
-            "info time {} score {} depth {} seldepth {} nodes {} nps {} {}",
-            time,
+            "info time {} score {}{} depth {} seldepth {} nodes {} nps {} {}",
+            micros / 1000,
             score,
+            wdl,
             self.depth,
             self.seldepth,
             nodes,
-            (nodes as u128 * 1000) / time,
+            nodes * 1_000_000 / micros.max(1),
             self.pv
         )
     }
@@ -81,12 +139,15 @@ impl Thread {
             search_stack: [(Piece::WP, NULL_MOVE, 0); MAX_DEPTH],
             eval_stack: [0; MAX_DEPTH],
             excluded: [None; MAX_DEPTH],
+            extensions: [0; MAX_DEPTH + 1],
 
             killer_moves: [[NULL_MOVE; 2]; MAX_DEPTH],
             history: HistoryTable::default(),
             counter_moves: DoubleHistoryTable::default(),
             followup_moves: DoubleHistoryTable::default(),
 
+            pawn_table: PawnTable::default(),
+
             nodes: 0,
             seldepth: 0,
             ply: 0,
@@ -96,6 +157,12 @@ impl Thread {
             eval: -INFINITY,
             depth: 0,
             stop: false,
+
+            root_scores: None,
+
+            root_material: 0,
+            show_wdl: false,
+            normalize_score: false,
         }
     }
 
@@ -115,6 +182,12 @@ impl Thread {
         ))
     }
 
+    /// Whether the static eval in the current node is better than the one from a move ago.
+    /// Must be called after the current static eval is pushed onto the eval stack.
+    pub fn is_improving(&self, in_check: bool) -> bool {
+        !in_check && self.ply > 1 && self.eval_stack[self.ply] > self.eval_stack[self.ply - 2]
+    }
+
     /// Get the current best move for the searching thread.
     pub fn best_move(&self) -> Move {
         self.pv.moves[0]
@@ -208,6 +281,11 @@ impl Thread {
         }
     }
 
+    /// Get the move leading to the current node, if it was not a null move
+    pub fn last_move(&self) -> Option<Move> {
+        self.get_previous_entry(1).map(|(_, m, _)| m)
+    }
+
     /// Get the stack entry from 'rollback' ply ago
     fn get_previous_entry(&self, rollback: usize) -> Option<(Piece, Move, usize)> {
         if self.ply >= rollback && self.search_stack[self.ply - rollback].1 != NULL_MOVE {
@@ -224,6 +302,10 @@ pub struct ThreadPool {
     workers: Vec<Thread>,
     global_stop: Arc<AtomicBool>,
     global_nodes: Arc<AtomicU64>,
+    pub skill: Option<Skill>,
+    pub show_wdl: bool,
+    pub normalize_score: bool,
+    pub debug: bool,
 }
 
 impl ThreadPool {
@@ -236,6 +318,10 @@ impl ThreadPool {
             workers: Vec::new(),
             global_stop,
             global_nodes,
+            skill: None,
+            show_wdl: false,
+            normalize_score: false,
+            debug: false,
         }
     }
 
@@ -252,14 +338,43 @@ impl ThreadPool {
         self.resize(self.workers.len());
     }
 
-    /// Deploy a parallel search using LazySMP, returning the agreed-upon best move.
+    /// Deploy a parallel search using LazySMP, returning the agreed-upon best move along with
+    /// the final search figures.
     pub fn deploy_search(
         &mut self,
         pos: &mut Position,
         tt: &TT,
         time_control: TimeControl,
-    ) -> Move {
+    ) -> SearchResult {
+        let best_move = self.search_best_move(pos, tt, time_control);
+        let clock = &self.main_thread.clock;
+
+        if let (true, Some(soft), Some(hard)) =
+            (self.debug, clock.soft_remaining(), clock.hard_remaining())
+        {
+            println!(
+                "info string time remaining soft {} hard {}",
+                soft.as_millis(),
+                hard.as_millis()
+            );
+        }
+
+        SearchResult {
+            best_move,
+            depth: self.main_thread.depth,
+            nodes: clock.global_nodes(),
+            time: clock.elapsed_micros(),
+        }
+    }
+
+    /// Run the search on all threads and agree on the best move.
+    fn search_best_move(&mut self, pos: &mut Position, tt: &TT, time_control: TimeControl) -> Move {
         // Setup all threads to start the search.
+        // Workers only share the node limit, time management is left to the main thread.
+        let worker_control = match time_control {
+            TimeControl::FixedNodes(nodes) => TimeControl::FixedNodes(nodes),
+            _ => TimeControl::Infinite,
+        };
         self.main_thread.clock = Clock::new(
             self.global_stop.clone(),
             self.global_nodes.clone(),
@@ -267,7 +382,28 @@ impl ThreadPool {
             pos.white_to_move(),
         );
         self.main_thread.advance_ply(2);
-        self.workers.iter_mut().for_each(|t| t.advance_ply(2));
+        self.main_thread.root_material = material_count(&pos.board);
+        self.main_thread.show_wdl = self.show_wdl;
+        self.main_thread.normalize_score = self.normalize_score;
+        self.main_thread.root_scores = self.skill.as_ref().map(|_| Vec::new());
+        for t in self.workers.iter_mut() {
+            t.clock = Clock::new(
+                self.global_stop.clone(),
+                self.global_nodes.clone(),
+                worker_control.clone(),
+                pos.white_to_move(),
+            );
+            t.advance_ply(2);
+        }
+
+        if self.debug && self.main_thread.clock.is_timed() {
+            let (soft, hard) = self.main_thread.clock.budgets();
+            println!(
+                "info string time budget soft {} hard {}",
+                soft.as_millis(),
+                hard.as_millis()
+            );
+        }
 
         self.global_stop.store(false, Ordering::SeqCst);
         self.global_nodes.store(0, Ordering::SeqCst);
@@ -276,7 +412,9 @@ impl ThreadPool {
         let move_list = pos.board.gen_moves::<QUIETS>();
         let move_count = move_list.len();
 
+        // Without legal moves, report the mate/stalemate score and play the null move
         if move_count == 0 {
+            println!("{}", no_moves_info(pos));
             return NULL_MOVE;
         } else if move_count == 1 || self.main_thread.clock.no_search_time() {
             return move_list.moves[0];
@@ -297,13 +435,23 @@ impl ThreadPool {
             self.global_stop.store(true, Ordering::SeqCst);
         });
 
+        // When limiting strength, sample one of the root moves scored by the last full iteration
+        if let Some(skill) = &mut self.skill {
+            let scored = self.main_thread.root_scores.take().unwrap_or_default();
+            if let Some((m, eval)) = skill.pick_move(&discard_mated(&pos.board, scored)) {
+                let depth = self.main_thread.depth;
+                println!("info depth {depth} score {} pv {m}", uci_score(eval));
+                return m;
+            }
+        }
+
         // Take the moves at highest depth, and from those the ones which occur the most
         let results = iter::once(&self.main_thread).chain(self.workers.iter());
         let highest_depth = results.clone().max_by_key(|t| t.depth).unwrap().depth;
 
-        results
+        let best = results
             .filter_map(|t| {
-                if t.depth == highest_depth {
+                if t.depth == highest_depth && t.best_move() != NULL_MOVE {
                     Some(t.best_move())
                 } else {
                     None
@@ -318,7 +466,284 @@ impl ThreadPool {
             )
             .into_iter()
             .max_by_key(|(_, value)| *value)
-            .unwrap()
-            .0 // always at least one search, impossible panic
+            .map(|(m, _)| m);
+
+        // If the search was stopped before finding any move, play the first legal one
+        best.unwrap_or(move_list.moves[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::square::*;
+    use std::time::{Duration, Instant};
+
+    fn search_position(fen: &str, time_control: TimeControl) -> Move {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut pool = ThreadPool::new(stop);
+        let mut position: Position = format!("fen {fen}").parse().unwrap();
+
+        pool.deploy_search(&mut position, &TT::new(1), time_control)
+            .best_move
+    }
+
+    #[test]
+    fn test_no_root_moves() {
+        let fen = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+        let mated = search_position(fen, TimeControl::FixedDepth(5));
+        assert_eq!(mated, NULL_MOVE);
+        assert_eq!(mated.to_string(), "0000");
+        let position: Position = format!("fen {fen}").parse().unwrap();
+        assert_eq!(no_moves_info(&position), "info depth 0 score mate 0");
+
+        let fen = "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1";
+        let stalemate = search_position(fen, TimeControl::FixedDepth(5));
+        assert_eq!(stalemate, NULL_MOVE);
+        let position: Position = format!("fen {fen}").parse().unwrap();
+        assert_eq!(no_moves_info(&position), "info depth 0 score cp 0");
+
+        assert_eq!(uci_score(MATE - 1), "mate 1");
+        assert_eq!(uci_score(-MATE + 2), "mate -1");
+    }
+
+    #[test]
+    fn test_instant_stop() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut pool = ThreadPool::new(stop.clone());
+        let mut position = Position::default();
+        let searching = Arc::new(AtomicBool::new(true));
+
+        // Keep tripping the stop flag, so that the search may not even complete depth 1
+        let stopper_searching = searching.clone();
+        let stopper = thread::spawn(move || {
+            while stopper_searching.load(Ordering::SeqCst) {
+                stop.store(true, Ordering::SeqCst);
+                thread::yield_now();
+            }
+        });
+
+        let m = pool
+            .deploy_search(&mut position, &TT::new(1), TimeControl::Infinite)
+            .best_move;
+        searching.store(false, Ordering::SeqCst);
+        stopper.join().unwrap();
+
+        assert_ne!(m, NULL_MOVE);
+        assert!(position.board.find_move(&m.to_string()).is_some());
+    }
+
+    #[test]
+    fn test_time_ceiling() {
+        let fen = "r1b2rk1/2q1b1pp/p2ppn2/1p6/3QP3/1BN1B3/PPP3PP/R4RK1 w - - 0 1";
+        let tc = TimeControl::Variable {
+            wtime: 300,
+            btime: 300,
+            winc: None,
+            binc: None,
+            movestogo: None,
+        };
+        let tt = TT::new(16);
+
+        for _ in 0..20 {
+            let mut pool = ThreadPool::new(Arc::new(AtomicBool::new(false)));
+            let mut position: Position = format!("fen {fen}").parse().unwrap();
+
+            let start = Instant::now();
+            let m = pool.deploy_search(&mut position, &tt, tc.clone()).best_move;
+            let elapsed = start.elapsed();
+
+            assert!(position.board.find_move(&m.to_string()).is_some());
+            assert!(elapsed < Duration::from_millis(300), "{elapsed:?}");
+        }
+    }
+
+    #[test]
+    fn test_movetime() {
+        let fen = "r1b2rk1/2q1b1pp/p2ppn2/1p6/3QP3/1BN1B3/PPP3PP/R4RK1 w - - 0 1";
+        let mut position: Position = format!("fen {fen}").parse().unwrap();
+        let mut pool = ThreadPool::new(Arc::new(AtomicBool::new(false)));
+        let tt = TT::new(16);
+
+        let start = Instant::now();
+        let result = pool.deploy_search(&mut position, &tt, TimeControl::FixedTime(200));
+        let elapsed = start.elapsed();
+        let m = result.best_move;
+
+        assert!(position.board.find_move(&m.to_string()).is_some());
+        assert!(pool.main_thread.depth > 0);
+        // The clock is only read every CHECK_FREQUENCY nodes, which takes a while in debug builds
+        let check_time = Duration::from_micros(CHECK_FREQUENCY * 1_000_000 / result.nps().max(1));
+        assert!(
+            elapsed < Duration::from_millis(200) + check_time,
+            "{elapsed:?}"
+        );
+    }
+
+    #[test]
+    fn test_search_figures() {
+        let mut pool = ThreadPool::new(Arc::new(AtomicBool::new(false)));
+        let mut position = Position::default();
+        let tc = TimeControl::FixedTime(2000);
+        let result = pool.deploy_search(&mut position, &TT::new(16), tc);
+
+        // Info line printed now, with all the nodes counted
+        let info = pool.main_thread.to_string();
+        let field = |name: &str| -> u64 {
+            let mut tokens = info.split_whitespace().skip_while(|&token| token != name);
+            tokens.nth(1).unwrap().parse().unwrap()
+        };
+
+        assert!((1_000..100_000_000).contains(&field("nps")), "{info}");
+        assert_eq!(field("nodes"), result.nodes);
+        assert!(field("time") >= result.time / 1000);
+        // The clock is only read every CHECK_FREQUENCY nodes, which takes a while in debug builds
+        let check_time = CHECK_FREQUENCY * 1_000_000 / result.nps().max(1);
+        assert!(result.time < 2_000_000 + check_time, "{result:?}");
+        assert_eq!(result.depth, pool.main_thread.depth);
+        assert!((1_000..100_000_000).contains(&result.nps()), "{result:?}");
+    }
+
+    #[test]
+    fn test_smp_node_limit() {
+        let mut pool = ThreadPool::new(Arc::new(AtomicBool::new(false)));
+        pool.resize(3);
+
+        let limit = 100_000;
+        let mut position = Position::default();
+        let m = pool
+            .deploy_search(&mut position, &TT::new(16), TimeControl::FixedNodes(limit))
+            .best_move;
+        assert!(position.board.find_move(&m.to_string()).is_some());
+
+        // Every thread may finish at most its current batch after the limit is hit
+        let nodes = pool.global_nodes.load(Ordering::SeqCst);
+        let searched: u64 = iter::once(&pool.main_thread)
+            .chain(&pool.workers)
+            .map(|t| t.nodes)
+            .sum();
+        assert_eq!(nodes, searched);
+        assert!(nodes >= limit);
+        assert!(nodes < limit + 4 * CHECK_FREQUENCY, "{nodes}");
+    }
+
+    #[test]
+    fn test_smp_score() {
+        // Short forced mate, which a single thread finds well within the node budget
+        let fen = "k7/8/2K5/8/8/8/8/7R w - - 0 1";
+        let score = |threads: usize| {
+            let mut pool = ThreadPool::new(Arc::new(AtomicBool::new(false)));
+            pool.resize(threads - 1);
+            let mut position: Position = format!("fen {fen}").parse().unwrap();
+            let tc = TimeControl::FixedNodes(200_000);
+            pool.deploy_search(&mut position, &TT::new(16), tc);
+            pool.main_thread.eval
+        };
+
+        let single = score(1);
+        assert!(single > MATE_IN_PLY, "{single}");
+        assert!(score(4) >= single);
+    }
+
+    #[test]
+    fn test_improving_margins() {
+        let mut t = Thread::fixed_depth(1);
+        t.ply = 4;
+        t.eval_stack[2] = 10;
+
+        t.eval_stack[4] = 50;
+        assert!(t.is_improving(false));
+        assert!(!t.is_improving(true));
+        let improving = t.is_improving(false);
+        assert_eq!(lmp_limit(3, improving), LMP_TABLE[1][3]);
+        assert_eq!(
+            rfp_margin(3, improving),
+            3 * RFP_MARGIN - RFP_IMPROVING_MARGIN
+        );
+        assert_eq!(
+            efp_margin(3, improving),
+            EFP_BASE + 3 * EFP_MARGIN - EFP_IMPROVING_MARGIN
+        );
+
+        t.eval_stack[4] = -50;
+        assert!(!t.is_improving(false));
+        let improving = t.is_improving(false);
+        assert_eq!(lmp_limit(3, improving), LMP_TABLE[0][3]);
+        assert_eq!(rfp_margin(3, improving), 3 * RFP_MARGIN);
+        assert_eq!(efp_margin(3, improving), EFP_BASE + 3 * EFP_MARGIN);
+        assert_eq!(lmp_limit(3, true), 2 * lmp_limit(3, false) + 1);
+
+        // Not enough history at the first ply
+        t.ply = 1;
+        assert!(!t.is_improving(false));
+    }
+
+    #[test]
+    fn test_counter_moves() {
+        let mut t = Thread::fixed_depth(1);
+        let board = Board::default();
+        let e4 = board.find_move("e2e4").unwrap();
+        let board = board.make_move(e4);
+        let (nf6, nc6) = (
+            board.find_move("g8f6").unwrap(),
+            board.find_move("b8c6").unwrap(),
+        );
+
+        // The cutoff answering e4 is rewarded for that previous move only
+        t.push_move(Piece::WP, e4);
+        t.update_tables(nf6, 8, Color::Black, vec![nc6]);
+        assert!(t.counter_moves.get_score(nf6, Piece::WP, Square::E4) > 0);
+        assert!(t.counter_moves.get_score(nc6, Piece::WP, Square::E4) < 0);
+        assert_eq!(t.counter_moves.get_score(nf6, Piece::WP, Square::D4), 0);
+
+        let mut scores = [0; 2];
+        t.assign_history_scores(Color::Black, &[nf6, nc6], &mut scores);
+        let history = t.history.get_score(nf6, Color::Black);
+        assert_eq!(
+            scores[0],
+            history + t.counter_moves.get_score(nf6, Piece::WP, Square::E4)
+        );
+
+        // Null moves leave no previous move to answer
+        t.pop_move();
+        t.push_null();
+        assert!(t.get_previous_entry(1).is_none());
+        t.update_tables(nf6, 8, Color::Black, vec![nc6]);
+        t.assign_history_scores(Color::Black, &[nf6, nc6], &mut scores);
+        assert_eq!(scores[0], t.history.get_score(nf6, Color::Black));
+    }
+
+    #[test]
+    fn test_score_report() {
+        let mut t = Thread::fixed_depth(1);
+        t.eval = 300;
+        t.root_material = 58;
+        assert!(t.to_string().contains(" score cp 300 depth"));
+
+        t.show_wdl = true;
+        let (w, d, l) = win_probability(300, 58);
+        assert!(t
+            .to_string()
+            .contains(&format!(" score cp 300 wdl {w} {d} {l} depth")));
+
+        t.normalize_score = true;
+        let score = format!(" score cp {} wdl", normalize(300, 58));
+        assert!(t.to_string().contains(&score));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let result = SearchResult {
+            best_move: Board::default().find_move("g1f3").unwrap(),
+            depth: 12,
+            nodes: 123_456,
+            time: 78_910,
+        };
+        let json = serde_json::to_string(&result).unwrap();
+
+        assert_eq!(serde_json::from_str::<SearchResult>(&json).unwrap(), result);
+        let invalid = json.replace(&result.best_move.0.to_string(), "12288");
+        assert!(serde_json::from_str::<SearchResult>(&invalid).is_err());
     }
 }
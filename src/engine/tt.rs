@@ -143,7 +143,7 @@ impl From<(u64, u64)> for TTEntry {
             key: key ^ data,
             age: (data & AGE_MASK) as u8,
             depth: ((data & DEPTH_MASK) >> DEPTH_OFFSET) as u8,
-            flag: unsafe { transmute(((data & FLAG_MASK) >> FLAG_OFFSET) as u8) },
+            flag: unsafe { transmute::<u8, TTFlag>(((data & FLAG_MASK) >> FLAG_OFFSET) as u8) },
             best_move: Move(((data & MOVE_MASK) >> MOVE_OFFSET) as u16),
             eval: ((data & SEARCH_MASK) >> SEARCH_OFFSET) as i16,
             static_eval: (data >> EVAL_OFFSET) as i16,
@@ -188,21 +188,27 @@ pub struct TT {
     age: u8,
 }
 pub const DEFAULT_SIZE: usize = 16;
+pub const MAX_SIZE: usize = 1048576;
 
 // Default to 16 MiB size
 impl Default for TT {
     fn default() -> Self {
+        Self::new(DEFAULT_SIZE)
+    }
+}
+
+impl TT {
+    /// Create an empty tt of the given size in MiB
+    pub fn new(mb_size: usize) -> Self {
         let mut tt = TT {
             table: Vec::new(),
             age: 0,
         };
-        tt.resize(DEFAULT_SIZE);
+        tt.resize(mb_size);
 
         tt
     }
-}
 
-impl TT {
     /// Get a key that wraps around the table size, avoiding using Modulo.
     /// https://lemire.me/blog/2016/06/27/a-fast-alternative-to-the-modulo-reduction/
     fn get_key(&self, hash: ZHash) -> usize {
@@ -212,9 +218,12 @@ impl TT {
         ((key * len) >> 64) as usize
     }
 
-    /// Resize the tt to the given size in MiB
+    /// Resize the tt to the given size in MiB. Old entries would be indexed by the old size, so the
+    /// table starts out empty.
     pub fn resize(&mut self, mb_size: usize) {
         let new_size = (mb_size << 20) / size_of::<AtomicField>();
+        self.age = 0;
+        self.table = Vec::new(); // free the old table before allocating the new one
         self.table.resize_with(new_size, AtomicField::default);
     }
 
@@ -345,4 +354,24 @@ mod tests {
         let new = tt.probe(ZHash(0)); // check no match on first hash
         assert!(new.is_none());
     }
+
+    #[test]
+    fn test_tt_resize() {
+        let mut tt = TT::new(1);
+        tt.insert(
+            ZHash(0xC0FFEE),
+            TTFlag::Exact,
+            NULL_MOVE,
+            100,
+            100,
+            1,
+            0,
+            false,
+        );
+        assert!(tt.probe(ZHash(0xC0FFEE)).is_some());
+
+        tt.resize(2);
+        assert_eq!(131072, tt.table.len());
+        assert!(tt.probe(ZHash(0xC0FFEE)).is_none());
+    }
 }
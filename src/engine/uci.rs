@@ -7,17 +7,39 @@ use std::{
     sync::atomic::{AtomicBool, Ordering},
     sync::{mpsc, Arc},
     thread,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::engine::{clock::*, position::*, thread::*, tt::*};
+use crate::chess::moves::NULL_MOVE;
+use crate::engine::{
+    clock::*, hce, nnue, position::*, search_params::*, skill::*, thread::*, tt::*,
+};
 
 const NAME: &str = "Carp";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const AUTHOR: &str = env!("CARGO_PKG_AUTHORS");
 
-const ENGINE_OPTIONS: &str = "
-option name Hash type spin default 16 min 1 max 1048576 
-option name Threads type spin default 1 min 1 max 512";
+/// Most search threads allowed, one per available core
+fn max_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Options advertised on "uci", with the thread limit depending on the machine
+fn engine_options() -> String {
+    format!(
+        "
+option name Hash type spin default 16 min 1 max 1048576
+option name Threads type spin default 1 min 1 max {}
+option name UCI_LimitStrength type check default false
+option name UCI_Elo type spin default 3000 min 1000 max 3000
+option name EvalFile type string default <embedded>
+option name UseNNUE type check default true
+option name UCI_ShowWDL type check default false
+option name UCI_Chess960 type check default false
+option name NormalizeScore type check default false",
+        max_threads()
+    )
+}
 
 /// Enum to represent UCI commands (and extra debug commands)
 enum UCICommand {
@@ -25,8 +47,10 @@ enum UCICommand {
     UciNewGame,
     Uci,
     IsReady,
+    Debug(bool),
     Option(String, String),
     Position(Box<Position>),
+    InvalidPosition(PositionError),
     Go(TimeControl),
     Quit,
     Stop,
@@ -48,16 +72,24 @@ impl FromStr for UCICommand {
             Some("ucinewgame") => Ok(Self::UciNewGame),
             Some("uci") => Ok(Self::Uci),
             Some("isready") => Ok(Self::IsReady),
+            Some("debug") => match tokens.next() {
+                Some("on") => Ok(Self::Debug(true)),
+                Some("off") => Ok(Self::Debug(false)),
+                _ => Err("Invalid debug command"),
+            },
             Some("setoption") => {
                 let opt_name: String = match tokens.next() {
                     Some("name") => tokens.next().ok_or("No option name!")?.to_owned(),
                     _ => return Err("Invalid option command"),
                 };
                 let opt_value: String = match tokens.next() {
-                    Some("value") => tokens.next().ok_or("No option value!")?.to_owned(),
+                    Some("value") => tokens.collect::<Vec<&str>>().join(" "),
                     _ => return Err("Invalid option command"),
                 };
 
+                if opt_value.is_empty() {
+                    return Err("No option value!");
+                }
                 Ok(Self::Option(opt_name, opt_value))
             }
             Some("perft") => match tokens.next().ok_or("No option value!")?.parse() {
@@ -66,9 +98,10 @@ impl FromStr for UCICommand {
             },
             Some("print") => Ok(Self::Print),
             Some("eval") => Ok(Self::Eval),
-            Some("position") => Ok(Self::Position(Box::new(
-                tokens.collect::<Vec<&str>>().join(" ").parse()?,
-            ))),
+            Some("position") => match tokens.collect::<Vec<&str>>().join(" ").parse() {
+                Ok(position) => Ok(Self::Position(Box::new(position))),
+                Err(e) => Ok(Self::InvalidPosition(e)),
+            },
             Some("go") => Ok(Self::Go(tokens.collect::<Vec<&str>>().join(" ").parse()?)),
             Some("stop") => Ok(Self::Stop),
             Some("quit") => Ok(Self::Quit),
@@ -115,12 +148,18 @@ impl UCIReader {
                         UCICommand::Uci => {
                             println!("id name {NAME} {VERSION}");
                             println!("id author {AUTHOR}");
-                            println!("{ENGINE_OPTIONS}");
+                            println!("{}", engine_options());
                             println!("uciok");
                         }
                         UCICommand::IsReady => {
                             println!("readyok");
                         }
+                        UCICommand::Debug(on) => {
+                            if on {
+                                println!("{}", pruning_info());
+                            }
+                            self.controller_tx.send(command).unwrap();
+                        }
                         UCICommand::Stop => self.stop.store(true, Ordering::SeqCst), // strict ordering
                         UCICommand::Quit => return,
                         _ => self.controller_tx.send(command).unwrap(),
@@ -142,48 +181,119 @@ impl UCIController {
         let mut position = Position::default();
         let mut tt = TT::default();
         let mut thread_pool = ThreadPool::new(stop);
+        let mut limit_strength = false;
+        let mut elo = MAX_ELO;
+        let mut chess960 = false;
+        let mut position_error = None;
 
         for command in &rx {
             match command {
                 UCICommand::UciNewGame => {
                     position = Position::default();
+                    position_error = None;
                     tt.clear();
                     thread_pool.reset();
                 }
 
                 UCICommand::Option(name, value) => match &name[..] {
                     "Hash" => match value.parse::<usize>() {
-                        Ok(size) if size > 0 => tt.resize(size),
+                        Ok(size) if (1..=MAX_SIZE).contains(&size) => tt.resize(size),
                         _ => eprintln!("Could not parse hash option value!"),
                     },
                     "Threads" => match value.parse::<usize>() {
-                        Ok(size) if size > 0 => thread_pool.resize(size - 1),
+                        Ok(size) if size > 0 => thread_pool.resize(size.min(max_threads()) - 1),
                         _ => eprintln!("Could not parse threads option value!"),
                     },
+                    "UCI_LimitStrength" => match value.parse::<bool>() {
+                        Ok(limit) => limit_strength = limit,
+                        _ => eprintln!("Could not parse limit strength option value!"),
+                    },
+                    "UCI_Elo" => match value.parse::<u32>() {
+                        Ok(e) => elo = e,
+                        _ => eprintln!("Could not parse elo option value!"),
+                    },
+                    "EvalFile" => {
+                        let loaded = match &value[..] {
+                            "<embedded>" => nnue::load_embedded_network(),
+                            path => nnue::load_network(path),
+                        };
+
+                        match loaded {
+                            Ok(()) => println!("info string Loaded network {value}"),
+                            Err(e) => println!("info string {e}"),
+                        }
+                    }
+                    "UseNNUE" => match value.parse::<bool>() {
+                        Ok(enabled) => nnue::set_nnue_enabled(enabled),
+                        _ => eprintln!("Could not parse use nnue option value!"),
+                    },
+                    "UCI_ShowWDL" => match value.parse::<bool>() {
+                        Ok(show) => thread_pool.show_wdl = show,
+                        _ => eprintln!("Could not parse show wdl option value!"),
+                    },
+                    "UCI_Chess960" => match value.parse::<bool>() {
+                        Ok(enabled) => chess960 = enabled,
+                        _ => eprintln!("Could not parse chess960 option value!"),
+                    },
+                    "NormalizeScore" => match value.parse::<bool>() {
+                        Ok(normalize) => thread_pool.normalize_score = normalize,
+                        _ => eprintln!("Could not parse normalize score option value!"),
+                    },
                     _ => eprintln!("Unsupported option command!"),
                 },
 
                 UCICommand::Perft(d) => {
-                    position.board.perft(d);
+                    position.board.perft(d, false);
                 }
 
                 UCICommand::Print => {
                     println!("{}", position.board);
                 }
 
+                UCICommand::Debug(on) => thread_pool.debug = on,
+
                 UCICommand::Eval => {
-                    println!("Static evaluation: {}", position.evaluate());
+                    position.refresh_network();
+                    let pawn_table = &mut hce::PawnTable::new(1);
+                    println!(
+                        "Static evaluation: {}\n",
+                        position.evaluate(pawn_table, -INFINITY, INFINITY)
+                    );
+                    print!("{}", hce::trace(&position.board, pawn_table));
                 }
 
+                // Castling is parsed in both notations, but only printed as king takes rook in
+                // Chess960 mode. Chess960 positions which are not standard ones enable it anyway.
                 UCICommand::Position(pos) => {
                     position = *pos;
+                    position.board.chess960 |= chess960;
+                    position_error = None;
+                }
+
+                // Searching the last valid position would play a move for the wrong board, so no
+                // search is started until a valid position comes in
+                UCICommand::InvalidPosition(e) => {
+                    eprintln!("{e}");
+                    position_error = Some(e);
+                }
+
+                UCICommand::Go(_) if position_error.is_some() => {
+                    eprintln!("Invalid position: {}", position_error.as_ref().unwrap());
+                    println!("bestmove {NULL_MOVE}");
                 }
 
                 UCICommand::Go(tc) => {
+                    thread_pool.skill = limit_strength.then(|| {
+                        let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                        Skill::new(elo, seed.as_nanos() as u64)
+                    });
+
+                    // Positions are parsed as they come in, possibly before a network change
+                    position.refresh_network();
                     tt.increment_age();
                     println!(
                         "bestmove {}",
-                        thread_pool.deploy_search(&mut position, &tt, tc),
+                        thread_pool.deploy_search(&mut position, &tt, tc).best_move,
                     );
                 }
 
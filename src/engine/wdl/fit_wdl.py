@@ -0,0 +1,111 @@
+#!/usr/bin/env python
+# Fit the win-draw-loss model coefficients used in mod.rs from datagen output.
+#
+# Input: files of "<fen> | <score> | <result>" lines, as written by the datagen tool, with the
+# score in centipawns and the result as 1, 0.5 or 0 (both from White's point of view).
+# Output: the A_COEFFS and B_COEFFS constants, ready to be pasted into mod.rs.
+#
+# For every material count, a and b are fitted by maximum likelihood of the game results, then a
+# cubic polynomial in material / 58 is fitted through them, weighted by the number of positions.
+import math
+import sys
+
+PIECE_VALUES = {'p': 1, 'n': 3, 'b': 3, 'r': 5, 'q': 9}
+MIN_MATERIAL, MAX_MATERIAL = 17, 78
+MATERIAL_SCALE = 58
+MIN_POSITIONS = 1000
+
+
+def material(fen):
+    return sum(PIECE_VALUES.get(c, 0) for c in fen.split()[0].lower())
+
+
+def logistic(t):
+    return 1 / (1 + math.exp(-max(min(t, 500), -500)))
+
+
+def neg_log_likelihood(data, a, b):
+    total = 0
+    for score, result in data:
+        win = logistic((score - a) / b)
+        loss = logistic((-score - a) / b)
+        prob = win if result == 1 else loss if result == 0 else 1 - win - loss
+        total -= math.log(max(prob, 1e-12))
+
+    return total
+
+
+def fit_bucket(data):
+    """Maximum likelihood a and b, by coordinate descent with shrinking steps"""
+    a, b, step = 200.0, 50.0, 64.0
+    best = neg_log_likelihood(data, a, b)
+
+    while step > 0.01:
+        improved = False
+        for da, db in ((step, 0), (-step, 0), (0, step), (0, -step)):
+            if b + db <= 1 or a + da <= 0:
+                continue
+            error = neg_log_likelihood(data, a + da, b + db)
+            if error < best:
+                a, b, best, improved = a + da, b + db, error, True
+                break
+        if not improved:
+            step /= 2
+
+    return a, b
+
+
+def polyfit(xs, ys, weights, degree=3):
+    """Weighted least squares polynomial, highest degree first"""
+    n = degree + 1
+    matrix = [[sum(w * x ** (i + j) for x, w in zip(xs, weights)) for j in range(n)] for i in range(n)]
+    vector = [sum(w * y * x ** i for x, y, w in zip(xs, ys, weights)) for i in range(n)]
+
+    # Gaussian elimination with partial pivoting
+    for col in range(n):
+        pivot = max(range(col, n), key=lambda row: abs(matrix[row][col]))
+        matrix[col], matrix[pivot] = matrix[pivot], matrix[col]
+        vector[col], vector[pivot] = vector[pivot], vector[col]
+        for row in range(col + 1, n):
+            factor = matrix[row][col] / matrix[col][col]
+            matrix[row] = [r - factor * c for r, c in zip(matrix[row], matrix[col])]
+            vector[row] -= factor * vector[col]
+
+    coeffs = [0.0] * n
+    for row in reversed(range(n)):
+        rest = sum(matrix[row][j] * coeffs[j] for j in range(row + 1, n))
+        coeffs[row] = (vector[row] - rest) / matrix[row][row]
+
+    return coeffs[::-1]
+
+
+if len(sys.argv) < 2:
+    print("Usage: python fit_wdl.py <datagen files>")
+    sys.exit(1)
+
+buckets = {}
+for path in sys.argv[1:]:
+    with open(path) as file:
+        for line in file:
+            fen, score, result = (field.strip() for field in line.split('|'))
+            m = min(max(material(fen), MIN_MATERIAL), MAX_MATERIAL)
+            buckets.setdefault(m, []).append((float(score), float(result)))
+
+ms, as_, bs, weights = [], [], [], []
+for m, data in sorted(buckets.items()):
+    if len(data) < MIN_POSITIONS:
+        continue
+
+    a, b = fit_bucket(data)
+    ms.append(m / MATERIAL_SCALE)
+    as_.append(a)
+    bs.append(b)
+    weights.append(math.sqrt(len(data)))
+
+if len(ms) < 4:
+    print("Not enough material buckets with at least", MIN_POSITIONS, "positions")
+    sys.exit(1)
+
+for name, values in (("A_COEFFS", as_), ("B_COEFFS", bs)):
+    coeffs = polyfit(ms, values, weights)
+    print(f"const {name}: [f64; 4] = [" + ", ".join(f"{c:.2f}" for c in coeffs) + "];")
@@ -0,0 +1,137 @@
+/// Win-draw-loss model.
+///
+/// The expected outcome of a score is modelled with two logistic curves, one for the win and one
+/// for the loss probability, centered at +a and -a and with spread b. Both parameters depend on
+/// the material left on the board, as the same score converts more reliably in simpler positions.
+/// a and b are cubic polynomials in the material, whose coefficients are produced by the
+/// fit_wdl.py script in this directory from datagen output.
+///
+/// The same model backs the UCI_ShowWDL output, score normalization and adjudication, so that
+/// they always agree on what a score means.
+use crate::chess::{board::*, piece::*};
+use crate::engine::search_params::*;
+
+/// Polynomial coefficients for a and b, highest degree first, in terms of material / 58.
+/// The current values are the Stockfish 16 model rescaled to centipawns, to be replaced by a fit
+/// on Carp's own games: paste the two lines printed by fit_wdl.py here.
+const A_COEFFS: [f64; 4] = [-89.29, 242.72, -210.86, 227.91];
+const B_COEFFS: [f64; 4] = [42.90, -65.88, 35.23, 22.85];
+
+// Material range used by the model, counting pawns as 1, minors as 3, rooks as 5 and queens as 9
+const MIN_MATERIAL: u32 = 17;
+const MAX_MATERIAL: u32 = 78;
+const MATERIAL_SCALE: f64 = 58.0;
+
+/// Material on the board with the model's piece values
+pub fn material_count(board: &Board) -> u32 {
+    let count = |piece: Piece| board.piece_bb[piece as usize].count_bits();
+
+    [
+        (Piece::WP, 1),
+        (Piece::WN, 3),
+        (Piece::WB, 3),
+        (Piece::WR, 5),
+        (Piece::WQ, 9),
+    ]
+    .into_iter()
+    .map(|(piece, value)| value * (count(piece) + count(piece.opposite_color())))
+    .sum()
+}
+
+/// Parameters of the logistic curves at the given material
+fn model_params(material: u32) -> (f64, f64) {
+    let m = material.clamp(MIN_MATERIAL, MAX_MATERIAL) as f64 / MATERIAL_SCALE;
+    let poly = |coeffs: [f64; 4]| coeffs.iter().fold(0.0, |acc, &c| acc * m + c);
+
+    (poly(A_COEFFS), poly(B_COEFFS))
+}
+
+/// Win, draw and loss permille for the side the score is given for
+pub fn win_probability(score: Eval, material: u32) -> (u16, u16, u16) {
+    if score.abs() >= MATE_IN_PLY {
+        return if score > 0 {
+            (1000, 0, 0)
+        } else {
+            (0, 0, 1000)
+        };
+    }
+
+    let (a, b) = model_params(material);
+    let x = score as f64;
+    let logistic = |t: f64| 1000.0 / (1.0 + (-t / b).exp());
+
+    // With a > 0 the two curves sum to less than 1000, so rounding leaves draw non-negative
+    let win = logistic(x - a).round() as u16;
+    let loss = logistic(-x - a).round() as u16;
+
+    (win, 1000 - win - loss, loss)
+}
+
+/// Score rescaled so that 100 means a 50% win probability, keeping mate scores unchanged
+pub fn normalize(score: Eval, material: u32) -> Eval {
+    if score.abs() >= MATE_IN_PLY {
+        return score;
+    }
+
+    let (a, _) = model_params(material);
+    (score as f64 * 100.0 / a).round() as Eval
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MATERIALS: [u32; 6] = [0, 17, 30, 45, 58, 78];
+
+    #[test]
+    fn test_wdl_sum_and_symmetry() {
+        for material in MATERIALS {
+            for score in (-3000..=3000).step_by(7) {
+                let (w, d, l) = win_probability(score, material);
+                assert_eq!(w + d + l, 1000);
+                assert_eq!(win_probability(-score, material), (l, d, w));
+            }
+        }
+
+        let (w, d, l) = win_probability(0, 58);
+        assert_eq!(w, l);
+        assert!(d > 900);
+    }
+
+    #[test]
+    fn test_wdl_monotonic() {
+        for material in MATERIALS {
+            let mut previous = win_probability(-3000, material);
+            for score in -2999..=3000 {
+                let current = win_probability(score, material);
+                assert!(current.0 >= previous.0 && current.2 <= previous.2);
+                previous = current;
+            }
+        }
+
+        assert!(win_probability(1500, 58).0 > 990);
+    }
+
+    #[test]
+    fn test_wdl_mates() {
+        for material in MATERIALS {
+            assert_eq!(win_probability(MATE - 5, material), (1000, 0, 0));
+            assert_eq!(win_probability(MATE_IN_PLY, material), (1000, 0, 0));
+            assert_eq!(win_probability(-MATE + 8, material), (0, 0, 1000));
+            assert_eq!(normalize(MATE - 5, material), MATE - 5);
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(material_count(&Board::default()), 78);
+
+        for material in MATERIALS {
+            let (a, _) = model_params(material);
+            let (w, _, _) = win_probability(a.round() as Eval, material);
+
+            assert_eq!(normalize(a.round() as Eval, material), 100);
+            assert!((495..=505).contains(&w));
+        }
+    }
+}
@@ -3,21 +3,30 @@
 /// This is a didactic chess engine for both approaching chess programming and learning to code
 /// in Rust.
 pub mod chess;
+pub mod cli;
 pub mod engine;
 
 #[cfg(feature = "tools")]
 pub mod tools;
 
-fn main() {
-    chess::init_all_tables();
+use std::process::ExitCode;
 
-    if std::env::args().nth(1).as_deref() == Some("bench") {
-        engine::bench::run_benchmark();
-        return;
-    }
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    #[cfg(feature = "tools")]
-    tools::parse_cli();
+    match cli::CliCommand::parse(&args) {
+        Ok(cli::CliCommand::Uci) => engine::UCIReader::default().run(),
+        Ok(command) => {
+            if let Err(err) = command.run() {
+                cli::report(err);
+                return ExitCode::FAILURE;
+            }
+        }
+        Err(err) => {
+            cli::report(err);
+            return ExitCode::FAILURE;
+        }
+    }
 
-    engine::UCIReader::default().run();
+    ExitCode::SUCCESS
 }
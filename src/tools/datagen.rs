@@ -9,8 +9,12 @@ use std::{
     time::Instant,
 };
 
-use super::*;
-use crate::engine::{clock::*, position::*, search_params::*, thread::*, tt::*};
+use super::{packed::*, *};
+use crate::chess::{board::*, game::*, piece::*};
+use crate::engine::{
+    book::*, clock::*, hce::TEMPO, nnue::network, position::*, search_params::*, thread::*, tt::*,
+    wdl::*,
+};
 use clap::Args;
 
 /// Generate training data through self-play, defaulting to depth 8 searches.
@@ -31,8 +35,49 @@ pub struct DatagenOptions {
     /// Limit searches to 'x' plies.
     #[arg(long, short = 'd')]
     depth: Option<usize>,
+
+    /// Number of random plies starting each game, after any book moves.
+    #[arg(long, default_value_t = 8)]
+    random_plies: usize,
+
+    /// Polyglot opening book, whose moves are followed before the random plies.
+    #[arg(long)]
+    book: Option<PathBuf>,
+
+    /// Write packed binary records instead of text lines.
+    #[arg(long)]
+    binary: bool,
+}
+
+/// Search depth used to discard unbalanced openings, and the largest score accepted
+const VERIFICATION_DEPTH: usize = 10;
+const MAX_OPENING_EVAL: Eval = 1000;
+
+/// Games are adjudicated as won after a few plies with a win probability (permille) above the
+/// threshold, and as drawn after more plies with a score close to zero
+const WIN_ADJ_PERMILLE: u16 = 990;
+const WIN_ADJ_PLIES: usize = 4;
+const DRAW_ADJ_EVAL: Eval = 5;
+const DRAW_ADJ_PLIES: usize = 12;
+
+/// Games reaching this length are adjudicated by the win probability of the last score
+pub(super) const MAX_GAME_PLY: usize = 400;
+
+/// Positions recorded before this ply are not written
+const MIN_RECORD_PLY: usize = 16;
+
+/// Everything needed to play a datagen game
+struct GameSettings<'a> {
+    tc: TimeControl,
+    random_plies: usize,
+    book: Option<&'a Book>,
+    verification_depth: usize,
+    max_ply: usize,
 }
 
+/// A recorded position, with its White relative score
+type Record = (Board, Eval);
+
 static STOP_FLAG: AtomicBool = AtomicBool::new(false);
 static FENS: AtomicU64 = AtomicU64::new(0);
 static WHITE_WINS: AtomicU64 = AtomicU64::new(0);
@@ -56,10 +101,18 @@ pub fn run_datagen(options: &DatagenOptions) {
     let data_dir = PathBuf::from("data").join(run_id);
     std::fs::create_dir_all(&data_dir).unwrap();
 
-    if options.games % options.threads != 0 {
+    if !options.games.is_multiple_of(options.threads) {
         println!("{ORANGE}WARNING: {DEFAULT}The number of games is not divisible by the number of threads!");
     }
 
+    let book = options.book.as_ref().map(|path| match Book::load(path) {
+        Ok(book) => book,
+        Err(err) => {
+            eprintln!("{ORANGE}{err}");
+            std::process::exit(1)
+        }
+    });
+
     let games_per_thread = (options.games / options.threads).max(1);
     std::thread::scope(|s| {
         for id in 0..options.threads {
@@ -72,30 +125,36 @@ pub fn run_datagen(options: &DatagenOptions) {
                 tc = TimeControl::FixedDepth(depth);
             }
 
+            let settings = GameSettings {
+                tc,
+                random_plies: options.random_plies,
+                book: book.as_ref(),
+                verification_depth: VERIFICATION_DEPTH,
+                max_ply: MAX_GAME_PLY,
+            };
+            let binary = options.binary;
+
             s.spawn(move || {
-                datagen_thread(id, games_per_thread, tc, path);
+                datagen_thread(id, games_per_thread, &settings, binary, path);
             });
         }
     });
 }
 
 /// Run a single datagen thread
-/// Each thread will play the given number of games at the given time control, and save the results
-/// to a file named after its id.
-/// Each game starts with 12 random moves.
-fn datagen_thread(id: usize, games: usize, tc: TimeControl, path: &Path) {
+/// Each thread will play the given number of games with the given settings, and save the results
+/// to a file named after its id: one line per position, or packed records in binary mode.
+fn datagen_thread(id: usize, games: usize, settings: &GameSettings, binary: bool, path: &Path) {
     let rng = fastrand::Rng::new();
-
-    let mut position;
-    let mut game_buffer: Vec<(Eval, String)> = Vec::new();
     let mut tt = TT::default();
 
-    let mut output_file = File::create(path.join(format!("thread_{id}.txt"))).unwrap();
+    let extension = if binary { "bin" } else { "txt" };
+    let mut output_file = File::create(path.join(format!("thread_{id}.{extension}"))).unwrap();
     let mut output_buffer = BufWriter::new(&mut output_file);
 
     let timer = Instant::now();
 
-    'main: for games_played in 0..games {
+    for games_played in 0..games {
         // Main thread logging
         if id == 0 && games_played != 0 && games_played % 64 == 0 {
             let fens = FENS.load(Ordering::Relaxed);
@@ -129,137 +188,277 @@ fn datagen_thread(id: usize, games: usize, tc: TimeControl, path: &Path) {
             stdout().flush().unwrap();
         }
 
-        // Reset everything from previous game
+        let Some((records, game_result)) = play_game(settings, &rng, &mut tt) else {
+            continue;
+        };
+
+        let counter = match game_result {
+            GameResult::WhiteWin(false) => &WHITE_WINS,
+            GameResult::WhiteWin(true) => &WHITE_WIN_ADJ,
+            GameResult::BlackWin(false) => &BLACK_WINS,
+            GameResult::BlackWin(true) => &BLACK_WIN_ADJ,
+            GameResult::Draw(false) => &DRAWS,
+            GameResult::Draw(true) => &DRAW_ADJ,
+            GameResult::Ongoing => unreachable!(),
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        // Write the positions, always reporting results from white's perspective
+        FENS.fetch_add(records.len() as u64, Ordering::Relaxed);
+        let wdl = white_wdl(game_result);
+        for (board, score) in records {
+            if binary {
+                let record = PackedBoard::pack(&board, score, wdl);
+                output_buffer.write_all(&record.to_bytes()).unwrap();
+            } else {
+                let result = ["0", "0.5", "1"][wdl as usize];
+                writeln!(output_buffer, "{} | {score} | {result}", board.to_fen()).unwrap();
+            }
+        }
         output_buffer.flush().unwrap();
-        position = Position::default();
 
-        // Start each game at a random position, skip if randomly stumble into a game over
-        // We randomize the starting side to avoid biasing the data
-        for _ in 0..rng.usize(8..=9) {
-            let move_list = position.board.gen_moves::<true>();
+        // Safely abort with CTRLC handler since otherwise
+        // our files could get truncated and the data get lost.
+        if STOP_FLAG.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+}
 
-            if move_list.is_empty() || position.is_draw(position.board.halfmoves) {
-                continue 'main;
-            }
+/// Game result for White: 0 loss, 1 draw, 2 win
+fn white_wdl(result: GameResult) -> u8 {
+    match result {
+        GameResult::WhiteWin(_) => 2,
+        GameResult::BlackWin(_) => 0,
+        _ => 1,
+    }
+}
+
+/// Result adjudicated from the score of the side to move, if its win or loss probability is
+/// above the given permille
+fn adjudicate(board: &Board, eval: Eval, permille: u16) -> Option<GameResult> {
+    let (win, _, loss) = win_probability(eval, material_count(board));
+    let (winner, loser) = match board.side {
+        Color::White => (GameResult::WhiteWin(ADJ), GameResult::BlackWin(ADJ)),
+        Color::Black => (GameResult::BlackWin(ADJ), GameResult::WhiteWin(ADJ)),
+    };
+
+    if win >= permille {
+        Some(winner)
+    } else if loss >= permille {
+        Some(loser)
+    } else {
+        None
+    }
+}
+
+/// Score compared against DRAW_ADJ_EVAL. The classical eval's tempo bonus only makes up for
+/// stand pat undervaluing the side to move, so it is taken out when no network is loaded.
+fn drawish_score(board: &Board, eval: Eval, classical: bool) -> Eval {
+    if classical {
+        eval - TEMPO.taper(board.phase())
+    } else {
+        eval
+    }
+}
 
-            let m = move_list.moves[rng.usize(..move_list.len())];
-            position.push_move(m);
+/// Adjudicates games from the scores found at every ply
+#[derive(Default)]
+pub(super) struct Adjudicator {
+    win_plies: usize,
+    draw_plies: usize,
+}
+
+impl Adjudicator {
+    /// Update with the score of the side to move in the board reached at the given ply,
+    /// returning the result once the game can be adjudicated
+    pub(super) fn update(
+        &mut self,
+        board: &Board,
+        eval: Eval,
+        ply: usize,
+        max_ply: usize,
+    ) -> Option<GameResult> {
+        let lopsided = adjudicate(board, eval, WIN_ADJ_PERMILLE);
+        if lopsided.is_some() {
+            self.win_plies += 1;
+            self.draw_plies = 0;
+        } else if drawish_score(board, eval, network().is_none()).abs() <= DRAW_ADJ_EVAL {
+            self.draw_plies += 1;
+            self.win_plies = 0;
+        } else {
+            self.win_plies = 0;
+            self.draw_plies = 0;
         }
 
-        // Avoid positions that are too unbalanced
-        tt.clear();
-        let mut thread = Thread::fixed_depth(10);
-        position.iterative_search::<false>(&mut thread, &tt);
-        if thread.eval.abs() >= 1000 {
-            continue 'main;
+        if self.win_plies >= WIN_ADJ_PLIES {
+            lopsided
+        } else if self.draw_plies >= DRAW_ADJ_PLIES {
+            Some(GameResult::Draw(ADJ))
+        } else if ply >= max_ply {
+            // Long games go to whoever is more likely to win, if anyone
+            adjudicate(board, eval, 500).or(Some(GameResult::Draw(ADJ)))
+        } else {
+            None
         }
+    }
+}
 
-        // Play out the game
-        let mut win_adj_counter = 0;
-        let mut draw_adj_counter = 0;
+/// Play out a game from a random opening, returning the quiet positions with their scores and
+/// the game result. Returns None when the opening ends the game or is too unbalanced.
+fn play_game(
+    settings: &GameSettings,
+    rng: &fastrand::Rng,
+    tt: &mut TT,
+) -> Option<(Vec<Record>, GameResult)> {
+    let mut position = Position::default();
+    let mut records = Vec::new();
+
+    // Follow the book, picking moves proportionally to their weight
+    while let Some(book) = settings.book {
+        let moves = book.probe(&position.board);
+        if moves.is_empty() || position.ply() >= settings.max_ply {
+            break;
+        }
 
-        let game_result = loop {
-            let result = position.check_result();
-            if result != GameResult::Ongoing {
-                break result;
-            }
+        let total: u32 = moves.iter().map(|&(_, weight)| weight as u32).sum();
+        let mut pick = rng.u32(..total.max(1));
+        let &(m, _) = moves
+            .iter()
+            .find(|&&(_, weight)| match pick.checked_sub(weight as u32) {
+                Some(rest) => {
+                    pick = rest;
+                    false
+                }
+                None => true,
+            })
+            .unwrap_or(&moves[0]);
+        position.push_move(m);
+    }
 
-            tt.increment_age();
-            thread.advance_ply(1);
-            thread.clock = Clock::new(
-                Arc::new(AtomicBool::new(false)),
-                Arc::new(AtomicU64::new(0)),
-                tc.clone(),
-                position.white_to_move(),
-            );
-
-            position.iterative_search::<false>(&mut thread, &tt);
-
-            // filter noisy positions
-            if !position.king_in_check()
-                && thread.eval.abs() < MATE_IN_PLY
-                && thread.best_move().get_type().is_quiet()
-                && position.ply() > 16
-            {
-                // Always report scores from white's perspective
-                let eval = if position.white_to_move() {
-                    thread.eval
-                } else {
-                    -thread.eval
-                };
-
-                game_buffer.push((eval, position.board.to_fen()));
-            }
+    // Then play random moves, skip if randomly stumble into a game over
+    // We randomize the starting side to avoid biasing the data
+    for _ in 0..rng.usize(settings.random_plies..=settings.random_plies + 1) {
+        let move_list = position.board.gen_moves::<true>();
+
+        if move_list.is_empty() || position.is_draw(0, position.board.halfmoves) {
+            return None;
+        }
+
+        let m = move_list.moves[rng.usize(..move_list.len())];
+        position.push_move(m);
+    }
 
-            // Increment adjudication counters
-            let abs_eval = thread.eval.abs();
-            if abs_eval >= 2000 {
-                win_adj_counter += 1;
-                draw_adj_counter = 0;
-            } else if abs_eval <= 5 {
-                draw_adj_counter += 1;
-                win_adj_counter = 0;
+    // Avoid positions that are too unbalanced
+    tt.clear();
+    let mut thread = Thread::fixed_depth(settings.verification_depth);
+    position.iterative_search::<false>(&mut thread, tt);
+    if thread.eval.abs() >= MAX_OPENING_EVAL {
+        return None;
+    }
+
+    // Play out the game
+    let mut adjudicator = Adjudicator::default();
+
+    let game_result = loop {
+        let result = position.check_result();
+        if result != GameResult::Ongoing {
+            break result;
+        }
+
+        tt.increment_age();
+        thread.advance_ply(1);
+        thread.clock = Clock::new(
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicU64::new(0)),
+            settings.tc.clone(),
+            position.white_to_move(),
+        );
+
+        position.iterative_search::<false>(&mut thread, tt);
+
+        // filter noisy positions
+        if !position.king_in_check()
+            && thread.eval.abs() < MATE_IN_PLY
+            && thread.best_move().get_type().is_quiet()
+            && position.ply() > MIN_RECORD_PLY
+        {
+            // Always report scores from white's perspective
+            let eval = if position.white_to_move() {
+                thread.eval
             } else {
-                win_adj_counter = 0;
-                draw_adj_counter = 0;
-            }
+                -thread.eval
+            };
+            records.push((position.board.clone(), eval));
+        }
 
-            // Win adjudication after 4 moves at +-2000 centipawns
-            if win_adj_counter >= 4 {
-                let result = if position.white_to_move() {
-                    GameResult::WhiteWin(ADJ)
-                } else {
-                    GameResult::BlackWin(ADJ)
-                };
-                break result;
-            }
-            // Draw adjudication after 12 moves at +-5 centipawns
-            if draw_adj_counter >= 12 {
-                break GameResult::Draw(ADJ);
-            }
+        let adjudicated = adjudicator.update(
+            &position.board,
+            thread.eval,
+            position.ply(),
+            settings.max_ply,
+        );
+        if let Some(result) = adjudicated {
+            break result;
+        }
+
+        position.push_move(thread.best_move());
+    };
 
-            position.push_move(thread.best_move());
+    Some((records, game_result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::bitboard::*;
+
+    #[test]
+    fn test_short_run() {
+        let rng = fastrand::Rng::with_seed(0xDA7A);
+        let mut tt = TT::new(1);
+        let settings = GameSettings {
+            tc: TimeControl::FixedDepth(2),
+            random_plies: 8,
+            book: None,
+            verification_depth: 2,
+            max_ply: 80,
         };
 
-        // Always report wins from white's perspective
-        let result_output = match game_result {
-            GameResult::Draw(adj) => {
-                if adj {
-                    DRAW_ADJ.fetch_add(1, Ordering::Relaxed);
-                } else {
-                    DRAWS.fetch_add(1, Ordering::Relaxed);
+        let mut bytes = Vec::new();
+        let mut results = Vec::new();
+        while results.len() < 2 {
+            if let Some((records, result)) = play_game(&settings, &rng, &mut tt) {
+                for (board, eval) in &records {
+                    let packed = PackedBoard::pack(board, *eval, white_wdl(result));
+                    bytes.extend(packed.to_bytes());
                 }
-                "0.5".to_string()
+                results.push((records.len(), white_wdl(result)));
             }
-            GameResult::WhiteWin(adj) => {
-                if adj {
-                    WHITE_WIN_ADJ.fetch_add(1, Ordering::Relaxed);
-                } else {
-                    WHITE_WINS.fetch_add(1, Ordering::Relaxed);
-                }
-                "1".to_string()
-            }
-            GameResult::BlackWin(adj) => {
-                if adj {
-                    BLACK_WIN_ADJ.fetch_add(1, Ordering::Relaxed);
-                } else {
-                    BLACK_WINS.fetch_add(1, Ordering::Relaxed);
-                }
-                "0".to_string()
-            }
-            _ => unreachable!(),
-        };
-
-        // Write the result
-        FENS.fetch_add(game_buffer.len() as u64, Ordering::Relaxed);
-        for (score, fen) in game_buffer.drain(..) {
-            writeln!(output_buffer, "{fen} | {score} | {result_output}").unwrap();
         }
 
-        // Safely abort with CTRLC handler since otherwise
-        // our files could get truncated and the data get lost.
-        if STOP_FLAG.load(Ordering::SeqCst) {
-            break 'main;
+        assert!(!bytes.is_empty());
+        let mut records = read_records(&bytes).unwrap().into_iter();
+        for (count, wdl) in results {
+            for record in records.by_ref().take(count) {
+                let (board, eval, record_wdl) = record.unpack().unwrap();
+
+                assert_eq!(record_wdl, wdl);
+                assert!(eval.abs() < MATE_IN_PLY);
+                assert_eq!(board.checkers, EMPTY_BB);
+                assert!(!board.gen_moves::<QUIETS>().is_empty());
+            }
         }
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn test_drawish_score() {
+        let board = Board::default();
+        let tempo = TEMPO.taper(board.phase());
+
+        assert_eq!(drawish_score(&board, tempo, true), 0);
+        assert_eq!(drawish_score(&board, tempo, false), tempo);
+        assert!(tempo > DRAW_ADJ_EVAL);
     }
 }
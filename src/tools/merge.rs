@@ -1,6 +1,7 @@
 /// Utilities to merge and deduplicate data generation files.
 /// Code is completely taken from Viri, and adapted to merge an entire directory.
-use super::*;
+/// Packed binary files are converted to text lines before merging.
+use super::{packed::*, *};
 use std::{
     collections::{hash_map::DefaultHasher, BinaryHeap},
     error::Error,
@@ -110,6 +111,18 @@ pub fn merge<P1: AsRef<Path>>(input_dir: &P1) -> Result<(), Box<dyn Error>> {
             );
 
             File::open(path)?.read_to_end(&mut buffer)?;
+        } else if path.is_file() && path.extension().and_then(OsStr::to_str) == Some("bin") {
+            let mb = path.metadata()?.len() as f64 / 1_000_000.0;
+            println!(
+                "Found {WHITE}{}{DEFAULT}: converting {mb:.0}MB",
+                path.display(),
+            );
+
+            for record in read_records(&fs::read(path)?)? {
+                let (board, score, wdl) = record.unpack()?;
+                let result = ["0", "0.5", "1"][wdl as usize];
+                writeln!(buffer, "{} | {score} | {result}", board.to_fen())?;
+            }
         }
     }
     let buffer = String::from_utf8(buffer)?;
@@ -151,7 +164,7 @@ pub fn merge<P1: AsRef<Path>>(input_dir: &P1) -> Result<(), Box<dyn Error>> {
 
         // Merge and dedup the two chunks
         let mut res: Vec<QuicklySortableString<'_>> =
-            Itertools::merge(f1.into_iter(), f2.into_iter()).collect();
+            Itertools::merge(f1.into_iter(), f2).collect();
         res.dedup();
 
         let new_size = res.len() as i64;
@@ -2,6 +2,9 @@
 /// Will get expanded as more functionality is introduced
 mod datagen;
 mod merge;
+mod packed;
+mod selfplay;
+mod tune;
 
 use clap::{Parser, Subcommand};
 
@@ -26,6 +29,8 @@ struct Cli {
 enum Command {
     Datagen(datagen::DatagenOptions),
     Merge(merge::MergeOptions),
+    Selfplay(selfplay::SelfplayOptions),
+    Tune(tune::TuneOptions),
 }
 
 /// Parse command line arguments. Any subcommand will terminate the program after execution.
@@ -41,6 +46,18 @@ pub fn parse_cli() {
                     std::process::exit(1)
                 }
             }
+            Command::Selfplay(opts) => {
+                if let Err(err) = selfplay::run_selfplay(opts) {
+                    eprintln!("{ORANGE}{err}");
+                    std::process::exit(1)
+                }
+            }
+            Command::Tune(opts) => {
+                if let Err(err) = tune::run_tune(opts) {
+                    eprintln!("{ORANGE}{err}");
+                    std::process::exit(1)
+                }
+            }
         }
         std::process::exit(0);
     }
@@ -0,0 +1,236 @@
+/// Compact binary training records, in the marlinformat layout.
+///
+/// Every record takes 32 little-endian bytes:
+///
+///     occupancy    u64        occupied squares, from A1 = bit 0 to H8 = bit 63
+///     pieces       [u8; 16]   a nibble per occupied square in occupancy order, low nibble first:
+///                             pawn 0 to king 5, 6 for a rook which can still castle, +8 if Black
+///     stm_ep       u8         bit 7 set with Black to move, en passant square (or 64) below
+///     halfmove     u8         halfmove clock
+///     fullmove     u16        fullmove number
+///     eval         i16        search score from White's point of view
+///     wdl          u8         game result for White: 0 loss, 1 draw, 2 win
+///     extra        u8         unused
+use crate::chess::{board::*, castle::*, piece::*, square::*};
+use crate::engine::search_params::Eval;
+
+pub const RECORD_SIZE: usize = 32;
+
+const UNMOVED_ROOK: u8 = 6;
+const NO_SQUARE: u8 = 64;
+
+/// Rooks which can still castle, which may be on any file in Chess960
+fn is_castling_rook(rights: CastlingRights, square: Square) -> bool {
+    [Color::White, Color::Black].into_iter().any(|side| {
+        [true, false].into_iter().any(|kingside| {
+            rights.has_right(side, kingside) && rights.rook_square(side, kingside) == square
+        })
+    })
+}
+
+/// Square indices are counted from A8, records count them from A1
+fn flip(index: usize) -> usize {
+    index ^ 56
+}
+
+/// A position with its score and game result
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedBoard {
+    occupancy: u64,
+    pieces: [u8; 16],
+    stm_ep: u8,
+    halfmove: u8,
+    fullmove: u16,
+    eval: i16,
+    wdl: u8,
+    extra: u8,
+}
+
+impl PackedBoard {
+    /// Pack the board, with a White relative score and result
+    pub fn pack(board: &Board, eval: Eval, wdl: u8) -> Self {
+        let occupancy = board.occupancy.0.swap_bytes();
+        let mut pieces = [0; 16];
+
+        let mut occupied = occupancy;
+        let mut i = 0;
+        while occupied != 0 {
+            let square = Square::from(flip(occupied.trailing_zeros() as usize));
+            let piece = board.piece_at(square);
+            let castling_rook = piece.is_rook() && is_castling_rook(board.castling_rights, square);
+
+            let code = match castling_rook {
+                true => UNMOVED_ROOK,
+                false => piece as u8 >> 1,
+            } | (piece as u8 & 1) << 3;
+            pieces[i / 2] |= code << (4 * (i % 2));
+
+            occupied &= occupied - 1;
+            i += 1;
+        }
+
+        let ep = board
+            .en_passant
+            .map_or(NO_SQUARE, |sq| flip(sq as usize) as u8);
+        Self {
+            occupancy,
+            pieces,
+            stm_ep: (board.side as u8) << 7 | ep,
+            halfmove: board.halfmoves.min(u8::MAX as usize) as u8,
+            fullmove: board.fullmoves.min(u16::MAX as usize) as u16,
+            eval: eval.clamp(i16::MIN as Eval, i16::MAX as Eval) as i16,
+            wdl,
+            extra: 0,
+        }
+    }
+
+    /// Unpack the board, its White relative score and result
+    pub fn unpack(&self) -> Result<(Board, Eval, u8), &'static str> {
+        if self.wdl > 2 {
+            return Err("Invalid game result!");
+        }
+
+        let mut squares = [None; SQUARE_COUNT];
+        let mut rights = String::new();
+
+        let mut occupied = self.occupancy;
+        let mut i = 0;
+        while occupied != 0 {
+            let square = Square::from(flip(occupied.trailing_zeros() as usize));
+            let code = self.pieces[i / 2] >> (4 * (i % 2)) & 0xF;
+            let piece_type = match code & 7 {
+                UNMOVED_ROOK => 3,
+                t if t < UNMOVED_ROOK => t as usize,
+                _ => return Err("Invalid piece code!"),
+            };
+            let piece = PIECES[(code >> 3) as usize][piece_type];
+
+            // Rights are given by rook file, as in Shredder-FEN
+            if code & 7 == UNMOVED_ROOK {
+                if square.relative_rank(piece.color()) != 0 {
+                    return Err("Castling rook out of place!");
+                }
+                rights.push(match piece.color() {
+                    Color::White => square.file().to_char().to_ascii_uppercase(),
+                    Color::Black => square.file().to_char(),
+                });
+            }
+            squares[square as usize] = Some(piece);
+
+            occupied &= occupied - 1;
+            i += 1;
+        }
+
+        let mut fen = String::new();
+        for (rank, row) in squares.chunks(8).enumerate() {
+            let mut empty = 0;
+            for square in row {
+                match square {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen += &empty.to_string();
+                            empty = 0;
+                        }
+                        fen.push(piece.to_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen += &empty.to_string();
+            }
+            if rank < 7 {
+                fen.push('/');
+            }
+        }
+
+        // Rooks are unpacked from A1, so White rights come first as FEN requires
+        let side = if self.stm_ep >> 7 == 0 { 'w' } else { 'b' };
+        let ep = match self.stm_ep & 0x7F {
+            NO_SQUARE => "-".to_string(),
+            ep if ep < NO_SQUARE => Square::from(flip(ep as usize)).to_string(),
+            _ => return Err("Invalid en passant square!"),
+        };
+        let rights = if rights.is_empty() { "-" } else { &rights };
+        let fen = format!(
+            "{fen} {side} {rights} {ep} {} {}",
+            self.halfmove, self.fullmove
+        );
+
+        Ok((fen.parse()?, self.eval as Eval, self.wdl))
+    }
+
+    pub fn to_bytes(self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0; RECORD_SIZE];
+        bytes[0..8].copy_from_slice(&self.occupancy.to_le_bytes());
+        bytes[8..24].copy_from_slice(&self.pieces);
+        bytes[24] = self.stm_ep;
+        bytes[25] = self.halfmove;
+        bytes[26..28].copy_from_slice(&self.fullmove.to_le_bytes());
+        bytes[28..30].copy_from_slice(&self.eval.to_le_bytes());
+        bytes[30] = self.wdl;
+        bytes[31] = self.extra;
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            occupancy: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            pieces: bytes[8..24].try_into().unwrap(),
+            stm_ep: bytes[24],
+            halfmove: bytes[25],
+            fullmove: u16::from_le_bytes(bytes[26..28].try_into().unwrap()),
+            eval: i16::from_le_bytes(bytes[28..30].try_into().unwrap()),
+            wdl: bytes[30],
+            extra: bytes[31],
+        }
+    }
+}
+
+/// Read every record of a datagen file
+pub fn read_records(bytes: &[u8]) -> Result<Vec<PackedBoard>, &'static str> {
+    if !bytes.len().is_multiple_of(RECORD_SIZE) {
+        return Err("File size is not a multiple of the record size!");
+    }
+
+    Ok(bytes
+        .chunks_exact(RECORD_SIZE)
+        .map(PackedBoard::from_bytes)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 3 7",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2k5/8/8/3K4/8/5p2/8 b - - 41 80",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        ];
+
+        for fen in fens {
+            let board: Board = fen.parse().unwrap();
+            let packed = PackedBoard::pack(&board, -123, 1);
+            let read = read_records(&packed.to_bytes()).unwrap();
+
+            let (unpacked, eval, wdl) = read[0].unpack().unwrap();
+            assert_eq!(unpacked.to_fen(), fen);
+            assert_eq!(unpacked.hash, board.hash, "{fen}");
+            assert_eq!((eval, wdl), (-123, 1));
+        }
+
+        // From A1: a rook without rights, the king, a rook which can castle, then Black's a8 rook
+        let board: Board = "r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1".parse().unwrap();
+        let packed = PackedBoard::pack(&board, 0, 2);
+        assert_eq!(packed.pieces[0], 3 | 5 << 4);
+        assert_eq!(packed.pieces[1], UNMOVED_ROOK | (8 | UNMOVED_ROOK) << 4);
+
+        assert!(read_records(&[0; RECORD_SIZE + 1]).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+/// Engine against engine matches, for quick regression tests without a match runner.
+///
+/// Two players play pairs of games from each opening, swapping colors, at a fixed node count.
+/// Games are adjudicated like in datagen, and the match ends with a W/D/L summary from the point
+/// of view of the first player along with an Elo estimate. Search parameters are compile-time
+/// constants, so the players can only differ in their node budget.
+use std::{
+    error::Error,
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    sync::{Arc, Mutex},
+};
+
+use super::{datagen::*, *};
+use crate::chess::{board::*, game::*, moves::*};
+use crate::engine::{clock::*, position::*, search_params::*, thread::*, tt::*};
+use clap::Args;
+
+/// Play a match between two instances of the engine.
+#[derive(Args)]
+pub struct SelfplayOptions {
+    /// Number of games to play, rounded up to an even number.
+    #[arg(long, short = 'g', default_value_t = 100)]
+    games: usize,
+
+    /// Nodes searched by the first player for each move.
+    #[arg(long, short = 'n', default_value_t = 20000)]
+    nodes: u64,
+
+    /// Nodes searched by the second player, the same as the first one by default.
+    #[arg(long)]
+    opponent_nodes: Option<u64>,
+
+    /// EPD or FEN file with an opening per line, random openings are played otherwise.
+    #[arg(long, short = 'b')]
+    book: Option<PathBuf>,
+
+    /// Number of games played at the same time.
+    #[arg(long, short = 'c', default_value_t = 1)]
+    concurrency: usize,
+}
+
+/// Random plies played from startpos when no book is given
+const RANDOM_PLIES: usize = 8;
+
+/// Hash size of each player, in MB
+const HASH_MB: usize = 16;
+
+/// Everything that can differ between the two players
+#[derive(Clone, Copy, Debug)]
+pub struct PlayerOptions {
+    pub nodes: u64,
+}
+
+/// Games won, drawn and lost by the first player
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchResult {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl MatchResult {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Score of the first player, between 0 and 1
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    /// Elo difference for the score, with its 95% confidence margin
+    pub fn elo(&self) -> (f64, f64) {
+        let games = self.games().max(1) as f64;
+        let score = self.score();
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games;
+        let margin = 1.96 * (variance / games).sqrt();
+
+        // Bounds past a perfect score give an infinite margin
+        let elo = |score: f64| -400.0 * (1.0 / score.clamp(0.0, 1.0) - 1.0).log10();
+        let (low, high) = (elo(score - margin), elo(score + margin));
+
+        (elo(score), (high - low) / 2.0)
+    }
+
+    /// Count a game from the point of view of the first player
+    fn add(&mut self, result: GameResult, first_is_white: bool) {
+        match (result, first_is_white) {
+            (GameResult::WhiteWin(_), true) | (GameResult::BlackWin(_), false) => self.wins += 1,
+            (GameResult::WhiteWin(_), false) | (GameResult::BlackWin(_), true) => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+}
+
+impl std::fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (elo, margin) = self.elo();
+        write!(
+            f,
+            "W: {} D: {} L: {} [{:.3}] Elo: {elo:.1} +/- {margin:.1}",
+            self.wins,
+            self.draws,
+            self.losses,
+            self.score()
+        )
+    }
+}
+
+/// Random opening from startpos, which does not end the game
+fn random_opening(rng: &fastrand::Rng) -> Board {
+    loop {
+        let mut game = Game::default();
+        for _ in 0..RANDOM_PLIES {
+            let move_list = game.current().gen_moves::<QUIETS>();
+            if move_list.is_empty() {
+                break;
+            }
+
+            let m = move_list.moves[rng.usize(..move_list.len())];
+            game.push(m).unwrap();
+        }
+
+        if game.result() == GameResult::Ongoing {
+            return game.current().clone();
+        }
+    }
+}
+
+/// Engine instance playing one side of a game
+struct Player {
+    options: PlayerOptions,
+    thread: Thread,
+    tt: TT,
+}
+
+impl Player {
+    fn new(options: PlayerOptions) -> Self {
+        Self {
+            options,
+            thread: Thread::fixed_depth(1),
+            tt: TT::new(HASH_MB),
+        }
+    }
+
+    /// Search the current position of the game, returning the best move and its score
+    fn search(&mut self, game: &Game) -> (Move, Eval) {
+        let mut position = Position::from(game.clone());
+
+        self.tt.increment_age();
+        self.thread.advance_ply(2);
+        self.thread.clock = Clock::new(
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicU64::new(0)),
+            TimeControl::FixedNodes(self.options.nodes),
+            position.white_to_move(),
+        );
+        position.iterative_search::<false>(&mut self.thread, &self.tt);
+
+        (self.thread.best_move(), self.thread.eval)
+    }
+}
+
+/// Play a game from the opening, with the first player as White if requested
+fn play_game(
+    opening: &Board,
+    first: PlayerOptions,
+    second: PlayerOptions,
+    first_is_white: bool,
+) -> Result<(Game, GameResult), &'static str> {
+    let (white, black) = if first_is_white {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    let mut players = [Player::new(white), Player::new(black)];
+    let mut game = Game::from(opening.clone());
+    let mut adjudicator = Adjudicator::default();
+
+    let result = loop {
+        let result = game.result();
+        if result != GameResult::Ongoing {
+            break result;
+        }
+
+        let (m, eval) = players[game.current().side as usize].search(&game);
+        if let Some(result) = adjudicator.update(game.current(), eval, game.len(), MAX_GAME_PLY) {
+            break result;
+        }
+
+        game.push(m).map_err(|_| "Engine played an illegal move!")?;
+    };
+
+    Ok((game, result))
+}
+
+/// Play the match, each opening twice with swapped colors. Games are returned with their results
+/// and whether the first player was White.
+pub fn play_match(
+    openings: &[Board],
+    games: usize,
+    first: PlayerOptions,
+    second: PlayerOptions,
+    concurrency: usize,
+) -> Result<Vec<(Game, GameResult, bool)>, &'static str> {
+    let next_game = AtomicUsize::new(0);
+    let played = Mutex::new(Vec::new());
+    let summary = Mutex::new(MatchResult::default());
+    let games = games.next_multiple_of(2);
+
+    std::thread::scope(|s| {
+        for _ in 0..concurrency.max(1) {
+            s.spawn(|| -> Result<(), &'static str> {
+                loop {
+                    let index = next_game.fetch_add(1, Ordering::Relaxed);
+                    if index >= games {
+                        return Ok(());
+                    }
+
+                    let first_is_white = index.is_multiple_of(2);
+                    let opening = &openings[index / 2 % openings.len()];
+                    let (game, result) = play_game(opening, first, second, first_is_white)?;
+
+                    let mut summary = summary.lock().unwrap();
+                    summary.add(result, first_is_white);
+                    println!("Game {} of {games}: {result:?}, {summary}", summary.games());
+
+                    played
+                        .lock()
+                        .unwrap()
+                        .push((index, game, result, first_is_white));
+                }
+            });
+        }
+    });
+
+    let mut played = played.into_inner().unwrap();
+    if played.len() < games {
+        return Err("Engine played an illegal move!");
+    }
+
+    played.sort_by_key(|&(index, ..)| index);
+    Ok(played
+        .into_iter()
+        .map(|(_, game, result, first_is_white)| (game, result, first_is_white))
+        .collect())
+}
+
+/// Run a match with the given options, printing the summary
+pub fn run_selfplay(options: &SelfplayOptions) -> Result<(), Box<dyn Error>> {
+    let openings = match &options.book {
+        Some(path) => fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Board>, _>>()?,
+        None => {
+            let rng = fastrand::Rng::new();
+            (0..options.games.div_ceil(2))
+                .map(|_| random_opening(&rng))
+                .collect()
+        }
+    };
+    if openings.is_empty() {
+        return Err("No openings in the book!".into());
+    }
+
+    let first = PlayerOptions {
+        nodes: options.nodes,
+    };
+    let second = PlayerOptions {
+        nodes: options.opponent_nodes.unwrap_or(options.nodes),
+    };
+    println!(
+        "Playing {} games at {} vs {} nodes",
+        options.games.next_multiple_of(2),
+        first.nodes,
+        second.nodes
+    );
+
+    let played = play_match(&openings, options.games, first, second, options.concurrency)?;
+    let mut summary = MatchResult::default();
+    for &(_, result, first_is_white) in &played {
+        summary.add(result, first_is_white);
+    }
+    println!("\n{GREEN}Final result{DEFAULT} {summary}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_match() {
+        let rng = fastrand::Rng::with_seed(0x5E1F);
+        let openings = [random_opening(&rng), random_opening(&rng)];
+        let player = PlayerOptions { nodes: 300 };
+
+        let played = play_match(&openings, 4, player, player, 2).unwrap();
+        assert_eq!(played.len(), 4);
+
+        let mut summary = MatchResult::default();
+        for (game, result, first_is_white) in &played {
+            assert_ne!(*result, GameResult::Ongoing);
+            assert!(game.len() <= MAX_GAME_PLY);
+            summary.add(*result, *first_is_white);
+
+            // Every move is legal in the position it was played in
+            let mut replay = Game::from(game.start().clone());
+            for (board, m) in game.iter() {
+                let move_list = board.gen_moves::<QUIETS>();
+                assert!(move_list.moves[..move_list.len()].contains(&m));
+                replay.push(m).unwrap();
+            }
+            assert_eq!(replay.current().hash, game.current().hash);
+        }
+
+        assert_eq!(summary.games(), 4);
+        let score = summary.score();
+        assert!((0.0..=1.0).contains(&score));
+        let (elo, _) = summary.elo();
+        assert_eq!(elo > 0.0, summary.wins > summary.losses);
+    }
+
+    #[test]
+    fn test_elo() {
+        let even = MatchResult {
+            wins: 10,
+            draws: 20,
+            losses: 10,
+        };
+        assert_eq!(even.elo().0, 0.0);
+        assert!(even.elo().1 > 0.0);
+
+        let ahead = MatchResult {
+            wins: 30,
+            draws: 40,
+            losses: 10,
+        };
+        let (elo, margin) = ahead.elo();
+        assert!((elo - 88.7).abs() < 0.1);
+        assert!(margin > 0.0 && margin < elo);
+
+        let lopsided = MatchResult {
+            wins: 3,
+            draws: 0,
+            losses: 1,
+        };
+        assert!(lopsided.elo().1.is_infinite());
+    }
+}
@@ -0,0 +1,56 @@
+/// Texel tuning of the classical evaluation parameters
+use std::{fs, path::PathBuf, time::Instant};
+
+use super::*;
+use crate::engine::hce::*;
+use clap::Args;
+
+/// Tune the classical evaluation on a file of "FEN; result" lines.
+#[derive(Args)]
+pub struct TuneOptions {
+    /// Path to the dataset.
+    pub path: PathBuf,
+
+    /// Maximum number of passes over all the parameters.
+    #[arg(long, short = 'p', default_value_t = 100)]
+    passes: usize,
+
+    /// File the tuned parameters are written to, as Rust source.
+    #[arg(long, short = 'o', default_value = "tuned_params.rs")]
+    output: PathBuf,
+}
+
+/// Fit K, then tune the parameters, saving them after every pass
+pub fn run_tune(options: &TuneOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(&options.path)?;
+    let entries = parse_dataset(&text)?;
+    println!("Loaded {WHITE}{}{DEFAULT} positions", entries.len());
+
+    let k = fit_k(&entries, &DEFAULT_PARAMS);
+    let initial = tuning_error(&entries, &DEFAULT_PARAMS, k);
+    println!("Fitted K = {WHITE}{k:.5}{DEFAULT}, initial error {initial:.8}");
+
+    let start = Instant::now();
+    tune(
+        &entries,
+        &DEFAULT_PARAMS,
+        k,
+        options.passes,
+        |pass, error, params| {
+            println!(
+                "{GREEN}[pass {pass}]{DEFAULT} error {error:.8} after {}s",
+                start.elapsed().as_secs()
+            );
+
+            if let Err(err) = fs::write(&options.output, params.to_source()) {
+                eprintln!("{ORANGE}Could not save parameters: {err}");
+            }
+        },
+    );
+    println!(
+        "Tuned parameters saved to {WHITE}{}{DEFAULT}",
+        options.output.display()
+    );
+
+    Ok(())
+}